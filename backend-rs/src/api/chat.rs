@@ -3,45 +3,83 @@
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        Path, State,
     },
     response::IntoResponse,
     routing::{get, post},
     Json, Router,
 };
-use futures::{SinkExt, StreamExt};
+use futures::{Sink, SinkExt, StreamExt};
 use std::sync::Arc;
 use tracing::{error, info, warn};
 
+use crate::config::get_config;
+use crate::error::AppError;
+use crate::llm::ChatStream;
 use crate::models::{
     ChatContext, SuggestQuestionsRequest, SuggestQuestionsResponse, WsInbound, WsOutbound,
 };
-use crate::services::{LlmService, PromptService};
+use crate::services::{ChatOverrides, LlmService, PromptService};
 use crate::state::AppState;
+use crate::utils::{current_request_id, StoredConversation};
 
 /// 获取建议问题
 async fn suggest_questions(
+    State(state): State<Arc<AppState>>,
     Json(req): Json<SuggestQuestionsRequest>,
 ) -> Json<SuggestQuestionsResponse> {
     let prompt_service = PromptService::new();
-    let questions = prompt_service.generate_suggested_questions(
-        req.project_path.as_deref(),
-        req.current_file.as_deref(),
-        req.file_tree_summary.as_deref(),
-    );
+    let questions = if get_config().llm_suggested_questions {
+        prompt_service
+            .suggest_questions_with_llm(
+                &LlmService::new()
+                    .with_request_logger(state.request_logger.clone())
+                    .with_request_id(current_request_id()),
+                req.project_path.as_deref(),
+                req.current_file.as_deref(),
+                req.file_tree_summary.as_deref(),
+            )
+            .await
+    } else {
+        prompt_service.generate_suggested_questions(
+            req.project_path.as_deref(),
+            req.current_file.as_deref(),
+            req.file_tree_summary.as_deref(),
+        )
+    };
     Json(SuggestQuestionsResponse { questions })
 }
 
 /// WebSocket 升级处理
+///
+/// 连接建立后已离开升级请求的作用域，请求 ID 需在此处取出并传给连接处理
 async fn websocket_upgrade(
     ws: WebSocketUpgrade,
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(handle_websocket)
+    let request_id = current_request_id();
+    ws.on_upgrade(move |socket| handle_websocket(socket, state, request_id))
+}
+
+/// 获取会话历史
+async fn get_conversation_history(
+    State(state): State<Arc<AppState>>,
+    Path(conversation_id): Path<String>,
+) -> Result<Json<StoredConversation>, AppError> {
+    let store = state
+        .conversation_store
+        .as_ref()
+        .ok_or_else(|| AppError::NotFound("会话持久化未启用".to_string()))?;
+
+    store
+        .get(&conversation_id)
+        .await
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound(format!("会话不存在: {}", conversation_id)))
 }
 
 /// WebSocket 连接处理
-async fn handle_websocket(socket: WebSocket) {
+async fn handle_websocket(socket: WebSocket, state: Arc<AppState>, request_id: Option<String>) {
     let (mut sender, mut receiver) = socket.split();
 
     info!("WebSocket connected");
@@ -81,6 +119,8 @@ async fn handle_websocket(socket: WebSocket) {
                 conversation_id,
                 content,
                 context,
+                temperature,
+                max_tokens,
             } => {
                 info!("Received chat message: conversation_id={}", conversation_id);
 
@@ -90,6 +130,9 @@ async fn handle_websocket(socket: WebSocket) {
                     &conversation_id,
                     &content,
                     context.as_ref(),
+                    ChatOverrides { temperature, max_tokens },
+                    &state,
+                    request_id.as_deref(),
                 )
                 .await
                 {
@@ -108,9 +151,14 @@ async fn handle_chat_message(
     conversation_id: &str,
     content: &str,
     context: Option<&ChatContext>,
+    overrides: ChatOverrides,
+    state: &AppState,
+    request_id: Option<&str>,
 ) -> Result<(), String> {
-    let prompt_service = PromptService::new();
-    let llm_service = LlmService::new();
+    let prompt_service = PromptService::new().with_system_prompt(get_config().system_prompt);
+    let llm_service = LlmService::new()
+        .with_request_logger(state.request_logger.clone())
+        .with_request_id(request_id.map(str::to_string));
 
     // 构建消息
     let messages = prompt_service.build_chat_messages(
@@ -123,7 +171,7 @@ async fn handle_chat_message(
     );
 
     // 流式调用 LLM
-    let stream = match llm_service.stream_chat(messages, None) {
+    let stream = match llm_service.stream_chat(messages, None, overrides) {
         Ok(s) => s,
         Err(e) => {
             // 配置错误
@@ -136,12 +184,38 @@ async fn handle_chat_message(
         }
     };
 
-    // 流式发送响应
-    let mut stream = std::pin::pin!(stream);
+    if let Some(answer) = forward_chat_stream(sender, conversation_id, stream).await? {
+        // 只保存完整的回答
+        if let Some(store) = &state.conversation_store {
+            store.append_turn(conversation_id, content, &answer).await;
+        }
+        info!("Chat completed: conversation_id={}", conversation_id);
+    }
+    Ok(())
+}
+
+/// 将 LLM 流转发为 WebSocket 消息
+///
+/// 正常结束发送 `chat_done` 并返回完整回答；已输出部分内容后出错发送 `chat_aborted`，
+/// 以便前端保留并标记不完整的回答；尚未输出内容时出错发送 `chat_error`。出错时返回 `None`。
+async fn forward_chat_stream<S>(
+    sender: &mut S,
+    conversation_id: &str,
+    mut stream: ChatStream,
+) -> Result<Option<String>, String>
+where
+    S: Sink<Message> + Unpin,
+    S::Error: std::fmt::Display,
+{
+    let mut answer = String::new();
+    let mut partial_length = 0;
+
     while let Some(result) = stream.next().await {
         match result {
             Ok(chunk) => {
                 if let Some(text) = chunk.content {
+                    partial_length += text.chars().count();
+                    answer.push_str(&text);
                     let msg = WsOutbound::chat_chunk(conversation_id, text).to_json();
                     if let Err(e) = sender.send(Message::Text(msg)).await {
                         return Err(format!("Failed to send message: {}", e));
@@ -149,14 +223,21 @@ async fn handle_chat_message(
                 }
             }
             Err(e) => {
-                let error_msg =
-                    WsOutbound::chat_error(conversation_id, format!("AI service error: {}", e))
-                        .to_json();
+                let error = format!("AI service error: {}", e);
+                let msg = if partial_length > 0 {
+                    warn!(
+                        "Chat stream aborted after {} chars: conversation_id={}",
+                        partial_length, conversation_id
+                    );
+                    WsOutbound::chat_aborted(conversation_id, error, partial_length)
+                } else {
+                    WsOutbound::chat_error(conversation_id, error)
+                };
                 sender
-                    .send(Message::Text(error_msg))
+                    .send(Message::Text(msg.to_json()))
                     .await
                     .map_err(|e| e.to_string())?;
-                return Ok(());
+                return Ok(None);
             }
         }
     }
@@ -168,13 +249,112 @@ async fn handle_chat_message(
         .await
         .map_err(|e| e.to_string())?;
 
-    info!("Chat completed: conversation_id={}", conversation_id);
-    Ok(())
+    Ok(Some(answer))
 }
 
 /// 创建聊天路由
 pub fn chat_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/api/chat/suggest", post(suggest_questions))
+        .route("/api/chat/:id/history", get(get_conversation_history))
         .route("/ws/chat", get(websocket_upgrade))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{ChatChunk, LlmError};
+    use crate::utils::ConversationStore;
+
+    fn chunk(text: &str) -> Result<ChatChunk, LlmError> {
+        Ok(ChatChunk {
+            content: Some(text.to_string()),
+            ..Default::default()
+        })
+    }
+
+    async fn forward(items: Vec<Result<ChatChunk, LlmError>>) -> Vec<serde_json::Value> {
+        forward_with_answer(items).await.0
+    }
+
+    async fn forward_with_answer(items: Vec<Result<ChatChunk, LlmError>>) -> (Vec<serde_json::Value>, Option<String>) {
+        let (mut tx, rx) = futures::channel::mpsc::unbounded::<Message>();
+        let stream: ChatStream = Box::pin(futures::stream::iter(items));
+        let answer = forward_chat_stream(&mut tx, "conv-1", stream).await.unwrap();
+        drop(tx);
+
+        let messages = rx
+            .map(|msg| match msg {
+                Message::Text(text) => serde_json::from_str(&text).unwrap(),
+                other => panic!("unexpected message: {:?}", other),
+            })
+            .collect()
+            .await;
+        (messages, answer)
+    }
+
+    #[tokio::test]
+    async fn test_mid_stream_error_sends_abort_marker() {
+        let messages = forward(vec![
+            chunk("Hello, "),
+            chunk("wor"),
+            Err(LlmError::StreamError("connection reset".to_string())),
+        ])
+        .await;
+
+        let types: Vec<&str> = messages.iter().map(|m| m["type"].as_str().unwrap()).collect();
+        assert_eq!(types, vec!["chat_chunk", "chat_chunk", "chat_aborted"]);
+        assert_eq!(messages[2]["conversationId"], "conv-1");
+        assert_eq!(messages[2]["partialLength"], 10);
+        assert!(messages[2]["error"].as_str().unwrap().contains("connection reset"));
+    }
+
+    #[tokio::test]
+    async fn test_error_before_content_sends_chat_error() {
+        let messages = forward(vec![Err(LlmError::StreamError("boom".to_string()))]).await;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["type"], "chat_error");
+
+        let (messages, answer) = forward_with_answer(vec![chunk("o"), chunk("k")]).await;
+        let types: Vec<&str> = messages.iter().map(|m| m["type"].as_str().unwrap()).collect();
+        assert_eq!(types, vec!["chat_chunk", "chat_chunk", "chat_done"]);
+        assert_eq!(answer.as_deref(), Some("ok"));
+    }
+
+    #[tokio::test]
+    async fn test_get_conversation_history() {
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use tower::Service;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = Arc::new(ConversationStore::new(Some(dir.path().to_path_buf()), 10, 100));
+        store.append_turn("conv-1", "What is this?", "A code reader.").await;
+        store.append_turn("conv-1", "Which language?", "Rust.").await;
+
+        let state = Arc::new(AppState {
+            conversation_store: Some(store),
+            ..AppState::new()
+        });
+        let mut app = chat_routes().with_state(state);
+
+        let response = app
+            .call(Request::builder().uri("/api/chat/conv-1/history").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["conversationId"], "conv-1");
+        let messages = json["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[3]["role"], "assistant");
+        assert_eq!(messages[3]["content"], "Rust.");
+
+        let response = app
+            .call(Request::builder().uri("/api/chat/unknown/history").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,17 +1,24 @@
 //! 配置管理端点
 
 use axum::{
+    extract::State,
     routing::{get, post, put},
     Json, Router,
 };
-use futures::StreamExt;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::config::{get_config, update_config, AppConfig};
 use crate::error::{AppError, AppResult};
-use crate::llm::{ChatMessage, ChatOptions, LlmClient};
+use crate::llm::LlmClient;
 use crate::state::AppState;
+use crate::utils::current_request_id;
 
 /// 配置响应（隐藏 api_key 的实际值）
 #[derive(Serialize)]
@@ -26,6 +33,12 @@ pub struct ConfigResponse {
     pub temperature: f64,
     /// 最大 token 数
     pub max_tokens: u32,
+    /// LLM 请求日志最多保留的条目数
+    pub log_max_entries: usize,
+    /// 同时运行的文档生成任务上限
+    pub max_concurrent_tasks: usize,
+    /// 自定义聊天系统提示词
+    pub system_prompt: Option<String>,
 }
 
 impl From<AppConfig> for ConfigResponse {
@@ -36,6 +49,9 @@ impl From<AppConfig> for ConfigResponse {
             model: config.model,
             temperature: config.temperature,
             max_tokens: config.max_tokens,
+            log_max_entries: config.log_max_entries,
+            max_concurrent_tasks: config.max_concurrent_tasks,
+            system_prompt: config.system_prompt,
         }
     }
 }
@@ -48,6 +64,9 @@ pub struct ConfigUpdateRequest {
     pub model: Option<String>,
     pub temperature: Option<f64>,
     pub max_tokens: Option<u32>,
+    pub log_max_entries: Option<usize>,
+    pub max_concurrent_tasks: Option<usize>,
+    pub system_prompt: Option<String>,
 }
 
 /// 配置更新响应
@@ -71,6 +90,10 @@ pub struct TestConnectionResponse {
     pub success: bool,
     pub message: String,
     pub model: String,
+    /// 首个 token 的延迟（毫秒）
+    pub latency_ms: u64,
+    /// 完整响应的总耗时（毫秒）
+    pub total_ms: u64,
 }
 
 /// 获取当前配置
@@ -81,8 +104,26 @@ async fn get_config_handler() -> Json<ConfigResponse> {
 
 /// 更新配置
 async fn update_config_handler(
+    State(state): State<Arc<AppState>>,
     Json(req): Json<ConfigUpdateRequest>,
 ) -> AppResult<Json<ConfigUpdateResponse>> {
+    if req.log_max_entries == Some(0) {
+        return Err(AppError::BadRequest(
+            "log_max_entries must be at least 1".to_string(),
+        ));
+    }
+    if req.max_concurrent_tasks == Some(0) {
+        return Err(AppError::BadRequest(
+            "max_concurrent_tasks must be at least 1".to_string(),
+        ));
+    }
+    if req.system_prompt.as_deref().is_some_and(|p| p.trim().is_empty()) {
+        return Err(AppError::BadRequest(
+            "system_prompt must not be empty".to_string(),
+        ));
+    }
+
+    let log_max_entries = req.log_max_entries;
     update_config(|config| {
         if let Some(api_key) = req.api_key {
             config.api_key = api_key;
@@ -99,7 +140,20 @@ async fn update_config_handler(
         if let Some(max_tokens) = req.max_tokens {
             config.max_tokens = max_tokens;
         }
+        if let Some(log_max_entries) = req.log_max_entries {
+            config.log_max_entries = log_max_entries;
+        }
+        if let Some(max_concurrent_tasks) = req.max_concurrent_tasks {
+            config.max_concurrent_tasks = max_concurrent_tasks;
+        }
+        if let Some(system_prompt) = req.system_prompt {
+            config.system_prompt = Some(system_prompt);
+        }
     })?;
+    // 请求日志记录器在启动时创建，需同步新的上限
+    if let Some(log_max_entries) = log_max_entries {
+        state.request_logger.set_max_entries(log_max_entries);
+    }
 
     Ok(Json(ConfigUpdateResponse {
         success: true,
@@ -109,9 +163,12 @@ async fn update_config_handler(
 
 /// 测试 LLM 连接
 async fn test_connection_handler(
+    State(state): State<Arc<AppState>>,
     Json(req): Json<TestConnectionRequest>,
 ) -> AppResult<Json<TestConnectionResponse>> {
     let config = get_config();
+    let settings = config.http_client_settings();
+    let browser_headers = config.browser_header_settings();
 
     // 确定使用的参数
     let api_key = req.api_key.unwrap_or(config.api_key);
@@ -124,43 +181,78 @@ async fn test_connection_handler(
     }
 
     // 创建 LLM 客户端
-    let client = LlmClient::new(&api_key, &base_url, true)
-        .map_err(|e| AppError::BadRequest(format!("创建客户端失败: {}", e)))?;
-
-    // 发送测试消息
-    let messages = vec![ChatMessage::user("Hi")];
-    let options = ChatOptions {
-        max_tokens: Some(10),
-        ..Default::default()
-    };
+    let client = LlmClient::with_settings(&api_key, &base_url, true, settings)
+        .map_err(|e| AppError::BadRequest(format!("创建客户端失败: {}", e)))?
+        .with_browser_headers(&browser_headers)
+        .with_request_logger(state.request_logger.clone())
+        .with_request_id(current_request_id());
 
-    let mut stream = client.stream_chat(messages, &model, options);
-
-    // 等待至少一个有效响应
-    let mut got_response = false;
-    while let Some(result) = stream.next().await {
-        match result {
-            Ok(chunk) => {
-                if chunk.content.is_some() {
-                    got_response = true;
-                    break;
-                }
-            }
-            Err(e) => {
-                return Err(AppError::BadRequest(format!("Connection failed: {}", e)));
-            }
-        }
+    // 发送测试消息（上游状态码透传，401/429 等问题一目了然）
+    let timing = client.ping(&model).await?;
+
+    Ok(Json(TestConnectionResponse {
+        success: true,
+        message: "Connection successful".to_string(),
+        model,
+        latency_ms: timing.first_token.as_millis() as u64,
+        total_ms: timing.total.as_millis() as u64,
+    }))
+}
+
+/// 模型列表响应
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelListResponse {
+    /// 可用的模型 ID
+    pub models: Vec<String>,
+    /// 列表来源：`api`（提供方的模型接口）或 `static`（内置列表）
+    pub source: String,
+}
+
+/// 模型列表缓存有效期
+const MODEL_LIST_TTL: Duration = Duration::from_secs(300);
+
+/// 按 (base_url, 当前模型, API Key 指纹) 缓存的模型列表及获取时间
+type ModelListCache = HashMap<(String, String, u64), (Instant, ModelListResponse)>;
+
+static MODEL_LIST_CACHE: Lazy<Mutex<ModelListCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// 获取当前提供方可用的模型列表（缓存 5 分钟）
+async fn list_models_handler() -> AppResult<Json<ModelListResponse>> {
+    let config = get_config();
+    if config.api_key.is_empty() {
+        return Err(AppError::BadRequest("API Key is required".to_string()));
     }
 
-    if got_response {
-        Ok(Json(TestConnectionResponse {
-            success: true,
-            message: "Connection successful".to_string(),
-            model,
-        }))
-    } else {
-        Err(AppError::BadRequest("No response from API".to_string()))
+    let client = LlmClient::with_settings(&config.api_key, &config.base_url, true, config.http_client_settings())
+        .map_err(|e| AppError::BadRequest(format!("创建客户端失败: {}", e)))?
+        .with_browser_headers(&config.browser_header_settings());
+
+    Ok(Json(list_models_cached(&client, &config.base_url, &config.api_key, &config.model).await?))
+}
+
+/// 更换 API Key 后不会复用其他 Key 获取的列表；缓存中只保存 Key 的哈希
+async fn list_models_cached(
+    client: &LlmClient,
+    base_url: &str,
+    api_key: &str,
+    model: &str,
+) -> AppResult<ModelListResponse> {
+    let mut hasher = DefaultHasher::new();
+    api_key.hash(&mut hasher);
+    let key = (base_url.to_string(), model.to_string(), hasher.finish());
+    if let Some((fetched_at, cached)) = MODEL_LIST_CACHE.lock().get(&key) {
+        if fetched_at.elapsed() < MODEL_LIST_TTL {
+            return Ok(cached.clone());
+        }
     }
+
+    let (models, from_api) = client.list_models(model).await?;
+    let response = ModelListResponse {
+        models,
+        source: if from_api { "api" } else { "static" }.to_string(),
+    };
+    MODEL_LIST_CACHE.lock().insert(key, (Instant::now(), response.clone()));
+    Ok(response)
 }
 
 /// 创建配置路由
@@ -169,4 +261,51 @@ pub fn config_routes() -> Router<Arc<AppState>> {
         .route("/api/config", get(get_config_handler))
         .route("/api/config", put(update_config_handler))
         .route("/api/config/test", post(test_connection_handler))
+        .route("/api/config/models", get(list_models_handler))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{spawn_mock_llm, spawn_mock_llm_with_delay, MOCK_MODELS};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_connection_reports_latency() {
+        let mock = spawn_mock_llm_with_delay(vec!["pong".to_string()], Duration::from_millis(20)).await;
+
+        let Json(response) = test_connection_handler(State(Arc::new(AppState::new())), Json(TestConnectionRequest {
+            api_key: Some("sk-test".to_string()),
+            base_url: Some(mock.base_url.clone()),
+            model: Some("gpt-4o".to_string()),
+        }))
+        .await
+        .unwrap();
+
+        assert!(response.success);
+        assert_eq!(response.model, "gpt-4o");
+        assert!(response.latency_ms >= 20, "latency_ms = {}", response.latency_ms);
+        assert!(response.total_ms >= response.latency_ms);
+    }
+
+    #[tokio::test]
+    async fn test_list_models_cached() {
+        let mock = spawn_mock_llm(vec![]).await;
+        let client = LlmClient::new("sk-test", &mock.base_url, false).unwrap();
+
+        let response = list_models_cached(&client, &mock.base_url, "sk-test", "gpt-4o").await.unwrap();
+        assert_eq!(response.source, "api");
+        let mut expected: Vec<String> = MOCK_MODELS.iter().map(|m| m.to_string()).collect();
+        expected.sort();
+        assert_eq!(response.models, expected);
+
+        // 缓存期内不再请求上游：换一个无法连接的客户端仍返回缓存结果
+        let offline = LlmClient::new("sk-test", "http://127.0.0.1:1", false).unwrap();
+        let cached = list_models_cached(&offline, &mock.base_url, "sk-test", "gpt-4o").await.unwrap();
+        assert_eq!(cached.models, expected);
+
+        // 换了 API Key 则不复用缓存
+        let other_key = LlmClient::new("sk-other", "http://127.0.0.1:1", false).unwrap();
+        assert!(list_models_cached(&other_key, &mock.base_url, "sk-other", "gpt-4o").await.is_err());
+    }
 }
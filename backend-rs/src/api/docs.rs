@@ -3,7 +3,7 @@
 //! 提供文档生成任务的 REST API 和 WebSocket 接口
 
 use axum::{
-    extract::{Path, State, WebSocketUpgrade},
+    extract::{Path, Query, State, WebSocketUpgrade},
     response::IntoResponse,
     routing::{get, post},
     Json, Router,
@@ -12,25 +12,41 @@ use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::config::get_config;
+use crate::config::{get_config, AppConfig};
 use crate::error::AppError;
 use crate::llm::LlmClient;
-use crate::services::doc_generator::{DocGenService, ProjectGraphData, TaskStats, WsDocMessage};
-use crate::services::doc_generator::types::{DirGraphData, FileGraphData};
-use crate::state::{AppState, CompletedPathType, InProgressPathType, TaskState};
+use crate::services::doc_generator::{
+    relative_path_segments, resolve_docs_path, DocGenService, DocumentGenerator, GenerationReport, GraphInvalidateSummary, ProcessorError, ProjectGraphData,
+    TaskStats, WsDocMessage, REPORT_FILE_NAME,
+};
+use crate::services::doc_generator::types::{DirGraphData, DocGenConfig, FailureMode, FileGraphData, FileNode, FileOrder, NodeStatus, ReadmeMode, SkippedFile};
+use crate::services::doc_generator::api_inventory::{ApiInventory, API_INVENTORY_FILE_NAME};
+use crate::services::doc_generator::git::{self, GitError};
+use crate::state::{AppState, TaskState};
+use crate::utils::current_request_id;
 
 /// 创建文档生成路由
 pub fn docs_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/api/docs/generate", post(generate_docs))
+        .route("/api/docs/tree", post(preview_tree))
+        .route("/api/docs/regenerate-file", post(regenerate_file))
+        .route("/api/docs/reanalyze-file", post(reanalyze_file))
         .route("/api/docs/tasks/:id", get(get_task_status))
+        .route("/api/docs/tasks/:id/nodes", get(get_task_nodes))
         .route("/api/docs/tasks/:id/cancel", post(cancel_task))
         .route("/api/docs/graph", post(get_project_graph))
+        .route("/api/docs/graph/invalidate", post(invalidate_graph))
         .route("/api/docs/file-graph", post(get_file_graph))
         .route("/api/docs/dir-graph", post(get_dir_graph))
+        .route("/api/docs/file-doc", post(get_file_doc))
+        .route("/api/docs/report", get(get_report))
+        .route("/api/docs/checkpoint/clear", post(clear_checkpoint))
+        .route("/api/docs/api-inventory", get(get_api_inventory))
         .route("/ws/docs/:id", get(ws_handler))
 }
 
@@ -39,10 +55,32 @@ pub fn docs_routes() -> Router<Arc<AppState>> {
 pub struct GenerateDocsRequest {
     /// 源码路径
     pub source_path: String,
-    /// 文档输出路径（可选，默认为 {source}_docs）
+    /// 文档输出路径（可选，默认为 {docs_output_root}/{项目目录名} 或 {source}/.docs）
     pub docs_path: Option<String>,
     /// 是否启用断点续传（默认 true）
     pub resume: Option<bool>,
+    /// 只生成该子目录（相对于 `source_path`，如 `src`）的文档，默认整个项目
+    pub subtree: Option<String>,
+    /// 节点失败策略：`fast_fail`（默认）或 `best_effort`
+    pub failure_mode: Option<FailureMode>,
+    /// 只重新生成相对该 git 引用（如 `origin/main`、`HEAD~1`）变化的文件，最终文档仍会重新生成；
+    /// 源码目录不是 git 仓库或查询失败时退回全量生成
+    pub since: Option<String>,
+    /// 只为匹配的文件生成文档（相对路径 glob 或目录前缀），默认不限制
+    #[serde(default)]
+    pub include_patterns: Vec<String>,
+    /// README 已存在时的写入方式：`overwrite`（默认）、`append_section` 或 `skip_if_exists`
+    pub readme_mode: Option<ReadmeMode>,
+    /// 同一深度内文件的处理顺序：`name`（默认）、`size_asc` 或 `size_desc`
+    pub order: Option<FileOrder>,
+    /// 优先处理的文件（相对路径），在所在深度内排到最前
+    #[serde(default)]
+    pub priority_paths: Vec<String>,
+    /// 是否提取知识图谱，未指定时使用全局配置
+    pub extract_graph: Option<bool>,
+    /// 只基于已有文档重新生成 README、阅读指南和项目图谱（默认 false）
+    #[serde(default)]
+    pub final_only: bool,
 }
 
 /// 生成文档响应
@@ -71,6 +109,25 @@ pub struct TaskStatusResponse {
     pub error: Option<String>,
 }
 
+/// 根据全局配置构建文档生成配置
+fn doc_gen_config(config: &AppConfig) -> DocGenConfig {
+    let mut doc_config = DocGenConfig {
+        task_log: config.doc_task_log,
+        progress_channel_capacity: config.doc_progress_channel_capacity,
+        max_files: config.max_analyzed_files,
+        front_matter: config.doc_front_matter,
+        normalize_markdown: config.doc_normalize_markdown,
+        line_annotations: config.doc_line_annotations,
+        extract_graph: config.doc_extract_graph,
+        keep_api_artifacts: config.doc_keep_api_artifacts,
+        ..DocGenConfig::default()
+    };
+    if let Some(template) = config.doc_front_matter_template.as_ref().filter(|t| !t.trim().is_empty()) {
+        doc_config.front_matter_template = template.clone();
+    }
+    doc_config
+}
+
 /// 启动文档生成任务
 async fn generate_docs(
     State(state): State<Arc<AppState>>,
@@ -96,41 +153,102 @@ async fn generate_docs(
     // 获取配置
     let config = get_config();
 
+    // 计算文档路径：显式指定 > 配置的统一输出根目录 > 项目根目录下的 .docs
+    let docs_path = resolve_docs_path(
+        &source_path,
+        req.docs_path.map(PathBuf::from),
+        config.docs_output_root.as_deref().map(std::path::Path::new),
+    );
+
+    // 同一文档目录的检查与启动串行执行；已有进行中的任务时直接返回该任务（重复提交幂等）
+    let start_lock = state.docs_path_lock(&docs_path);
+    let _start_guard = start_lock.lock().await;
+    if let Some(task_id) = state.active_task_for_docs_path(&docs_path).await {
+        info!("Reusing active task {} for docs path {}", task_id, docs_path.display());
+        return Ok(Json(GenerateDocsResponse {
+            task_id,
+            docs_path: docs_path.to_string_lossy().to_string(),
+        }));
+    }
+
+    // 检查并占用并发任务名额（每次读取配置，运行时修改立即生效），任务注册后释放
+    let max_concurrent_tasks = config.max_concurrent_tasks.max(1);
+    let _slot = state.reserve_task_slot(max_concurrent_tasks).await.map_err(|active| {
+        AppError::BadRequest(format!(
+            "正在运行的文档生成任务已达上限 ({}/{})，请等待已有任务完成后再试",
+            active, max_concurrent_tasks
+        ))
+    })?;
+
     // 创建 LLM 客户端
     let llm_client = Arc::new(
-        LlmClient::new(&config.api_key, &config.base_url, false)
-            .map_err(|e| AppError::Internal(format!("创建 LLM 客户端失败: {}", e)))?,
+        LlmClient::with_settings(&config.api_key, &config.base_url, false, config.http_client_settings())
+            .map_err(|e| AppError::Internal(format!("创建 LLM 客户端失败: {}", e)))?
+            .with_default_max_tokens(config.max_tokens)
+            .with_request_logger(state.request_logger.clone())
+            .with_request_id(current_request_id()),
     );
 
-    // 计算文档路径：默认放在项目根目录下的 .docs 目录
-    let docs_path = req.docs_path.map(PathBuf::from).unwrap_or_else(|| {
-        source_path.join(".docs")
-    });
-
     // 创建文档生成服务
-    let service = DocGenService::with_default_config();
+    let mut doc_config = doc_gen_config(&config);
+    if let Some(failure_mode) = req.failure_mode {
+        doc_config.failure_mode = failure_mode;
+    }
+    if let Some(extract_graph) = req.extract_graph {
+        doc_config.extract_graph = extract_graph;
+    }
+    doc_config.include_patterns = req.include_patterns;
+    if let Some(since) = req.since.as_deref().filter(|s| !s.trim().is_empty()) {
+        match git::changed_files_since(&source_path, since).await {
+            Ok(files) => {
+                info!("{} files changed since {}", files.len(), since);
+                doc_config.changed_files = Some(files);
+            }
+            Err(GitError::InvalidRef(r)) => {
+                return Err(AppError::BadRequest(format!("无效的 git 引用: {}", r)));
+            }
+            Err(e) => warn!("Cannot list changes since {}, falling back to full generation: {}", since, e),
+        }
+    }
+    if let Some(readme_mode) = req.readme_mode {
+        doc_config.readme_mode = readme_mode;
+    }
+    if let Some(order) = req.order {
+        doc_config.order = order;
+    }
+    doc_config.priority_paths = req.priority_paths;
+    let service = DocGenService::new(doc_config).with_analysis_cache(state.analysis_cache.clone());
 
     // 启动生成任务
-    let (task, progress_rx) = service
+    let (task, progress_rx, tree) = service
         .start_generation(
             source_path,
             Some(docs_path.clone()),
+            req.subtree.as_deref(),
             llm_client,
             config.model.clone(),
             req.resume.unwrap_or(true),
+            req.final_only,
         )
         .await
-        .map_err(|e| AppError::Internal(format!("启动文档生成失败: {}", e)))?;
+        .map_err(|e| match e {
+            ProcessorError::InvalidSubtree(msg) => AppError::BadRequest(format!("无效的子目录: {}", msg)),
+            ProcessorError::NoSupportedFiles(msg) | ProcessorError::TooManyFiles(msg) => AppError::BadRequest(msg),
+            ProcessorError::MissingDocs(msg) => {
+                AppError::BadRequest(format!("无法只生成最终文档，{}。请先完整生成文档。", msg))
+            }
+            other => AppError::Internal(format!("启动文档生成失败: {}", other)),
+        })?;
 
     // 获取任务 ID
     let task_id = task.read().await.id.clone();
 
     // 创建广播通道（用于 WebSocket）
     // 保留一个接收器以防止在 WebSocket 客户端连接前 send 失败
-    let (tx, _keep_alive_rx) = broadcast::channel(100);
+    let (tx, _keep_alive_rx) = broadcast::channel(config.doc_progress_channel_capacity.max(1));
 
     // 创建任务状态
-    let task_state = Arc::new(TaskState::new(task, tx.clone()));
+    let task_state = Arc::new(TaskState::new(task, tx.clone()).with_tree(tree));
 
     // 注册任务
     state.doc_tasks.insert(task_id.clone(), task_state.clone());
@@ -143,7 +261,16 @@ async fn generate_docs(
         // 保持接收器存活，防止在 WebSocket 客户端连接前 tx.send 因无接收器而失败
         let _rx_guard = _keep_alive_rx;
         let mut rx = progress_rx;
-        while let Ok(msg) = rx.recv().await {
+        loop {
+            let msg = match rx.recv().await {
+                Ok(msg) => msg,
+                // 丢失的消息由 WebSocket 端从任务状态重新同步，这里继续转发
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Task {} progress forwarding lagged by {} messages", task_id_clone, skipped);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
             // 记录路径状态，用于 WebSocket 连接时重放
             match &msg {
                 WsDocMessage::FileStarted { path } => {
@@ -164,11 +291,12 @@ async fn generate_docs(
             // 即使当前没有 WebSocket 订阅者，也继续转发（不因 send 失败退出）
             let _ = tx_clone.send(msg.clone());
 
-            // 如果任务完成或失败，退出循环
+            // 如果任务完成或失败，记录结束时间并退出循环
             match &msg {
                 WsDocMessage::Completed { .. }
                 | WsDocMessage::Error { .. }
                 | WsDocMessage::Cancelled => {
+                    task_state_clone.mark_finished();
                     break;
                 }
                 _ => {}
@@ -183,6 +311,204 @@ async fn generate_docs(
     }))
 }
 
+/// 文件树预览请求
+#[derive(Debug, Deserialize)]
+pub struct PreviewTreeRequest {
+    /// 源码路径
+    pub source_path: String,
+    /// 文档输出路径（可选，位于源码树内时会被排除）
+    pub docs_path: Option<String>,
+    /// 只扫描该子目录（相对于 `source_path`）
+    pub subtree: Option<String>,
+    /// 只包含匹配的文件（相对路径 glob 或目录前缀），默认不限制
+    #[serde(default)]
+    pub include_patterns: Vec<String>,
+}
+
+/// 文件树预览响应
+#[derive(Debug, Serialize)]
+pub struct PreviewTreeResponse {
+    /// 文件树根节点
+    pub root: FileNode,
+    /// 将被处理的文件数
+    pub file_count: usize,
+    /// 将被处理的目录数
+    pub dir_count: usize,
+    /// 因超过大小上限被跳过的文件
+    pub oversized: Vec<SkippedFile>,
+}
+
+/// 预览文档生成将处理的文件树
+///
+/// 只扫描目录，不调用 LLM、不写任何文件
+async fn preview_tree(
+    Json(req): Json<PreviewTreeRequest>,
+) -> Result<Json<PreviewTreeResponse>, AppError> {
+    let source_path = PathBuf::from(&req.source_path);
+    if !source_path.is_dir() {
+        return Err(AppError::BadRequest(format!(
+            "源码路径不存在或不是目录: {}",
+            req.source_path
+        )));
+    }
+
+    let config = get_config();
+    let docs_path = resolve_docs_path(
+        &source_path,
+        req.docs_path.map(PathBuf::from),
+        config.docs_output_root.as_deref().map(std::path::Path::new),
+    );
+    let doc_config = DocGenConfig {
+        include_patterns: req.include_patterns,
+        ..doc_gen_config(&config)
+    };
+
+    let result = DocGenService::new(doc_config)
+        .scan_source(&source_path, &docs_path, req.subtree.as_deref())
+        .map_err(|e| match e {
+            ProcessorError::InvalidSubtree(msg) => AppError::BadRequest(format!("无效的子目录: {}", msg)),
+            ProcessorError::TooManyFiles(msg) => AppError::BadRequest(msg),
+            other => AppError::Internal(format!("扫描源码目录失败: {}", other)),
+        })?;
+
+    Ok(Json(PreviewTreeResponse {
+        file_count: result.root.file_count(),
+        dir_count: result.root.get_all_dirs().len(),
+        root: result.root,
+        oversized: result.oversized,
+    }))
+}
+
+/// 单文件重新生成请求
+#[derive(Debug, Deserialize)]
+pub struct RegenerateFileRequest {
+    /// 源码路径
+    pub source_path: String,
+    /// 文档输出路径（可选，默认为 {docs_output_root}/{项目目录名} 或 {source}/.docs）
+    pub docs_path: Option<String>,
+    /// 文件相对路径（相对于源码根目录）
+    pub file_path: String,
+    /// 模型名称（可选，默认使用配置中的模型）
+    pub model: Option<String>,
+}
+
+/// 单文件重新生成响应
+#[derive(Debug, Serialize)]
+pub struct RegenerateFileResponse {
+    /// 文件相对路径
+    pub file_path: String,
+    /// 生成的文档路径
+    pub doc_path: String,
+}
+
+/// 带反馈的单文件重新分析请求
+#[derive(Debug, Deserialize)]
+pub struct ReanalyzeFileRequest {
+    #[serde(flatten)]
+    pub file: RegenerateFileRequest,
+    /// 追加到分析 Prompt 的补充要求（如"重点关注安全问题"）
+    pub instructions: String,
+}
+
+/// 重新生成单个文件的文档
+///
+/// 只分析指定文件，更新其文档、图谱和断点，并重新聚合项目图谱
+async fn regenerate_file(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<RegenerateFileRequest>,
+) -> Result<Json<RegenerateFileResponse>, AppError> {
+    info!(
+        "Received file regeneration request: source_path={}, file_path={}",
+        req.source_path, req.file_path
+    );
+
+    run_file_regeneration(&state, req, None).await
+}
+
+/// 按用户反馈重新分析单个文件
+///
+/// 与 `regenerate_file` 相同，但会把 `instructions` 追加到分析 Prompt 中
+async fn reanalyze_file(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ReanalyzeFileRequest>,
+) -> Result<Json<RegenerateFileResponse>, AppError> {
+    info!(
+        "Received file reanalysis request: source_path={}, file_path={}",
+        req.file.source_path, req.file.file_path
+    );
+
+    let instructions = req.instructions.trim();
+    if instructions.is_empty() {
+        return Err(AppError::BadRequest("instructions 不能为空".to_string()));
+    }
+
+    run_file_regeneration(&state, req.file, Some(instructions)).await
+}
+
+/// 执行单文件重新生成，`instructions` 为可选的 Prompt 补充要求
+async fn run_file_regeneration(
+    state: &AppState,
+    req: RegenerateFileRequest,
+    instructions: Option<&str>,
+) -> Result<Json<RegenerateFileResponse>, AppError> {
+    let source_path = PathBuf::from(&req.source_path);
+    if !source_path.is_dir() {
+        return Err(AppError::BadRequest(format!(
+            "源码路径不存在或不是目录: {}",
+            req.source_path
+        )));
+    }
+
+    let config = get_config();
+    let docs_path = resolve_docs_path(
+        &source_path,
+        req.docs_path.map(PathBuf::from),
+        config.docs_output_root.as_deref().map(std::path::Path::new),
+    );
+
+    // 与启动任务、清除断点共用同一把锁；整个目录正在生成时不允许并发写入断点和图谱
+    let start_lock = state.docs_path_lock(&docs_path);
+    let _start_guard = start_lock.lock().await;
+    if let Some(task_id) = state.active_task_for_docs_path(&docs_path).await {
+        return Err(AppError::Conflict(format!(
+            "文档目录正在被任务 {} 使用，请等待任务结束或取消后再重新生成",
+            task_id
+        )));
+    }
+
+    let llm_client = Arc::new(
+        LlmClient::with_settings(&config.api_key, &config.base_url, false, config.http_client_settings())
+            .map_err(|e| AppError::Internal(format!("创建 LLM 客户端失败: {}", e)))?
+            .with_default_max_tokens(config.max_tokens)
+            .with_request_logger(state.request_logger.clone())
+            .with_request_id(current_request_id()),
+    );
+    let model = req.model.unwrap_or_else(|| config.model.clone());
+
+    let service = DocGenService::new(doc_gen_config(&config));
+    let doc_path = service
+        .regenerate_file(
+            source_path,
+            Some(docs_path),
+            &req.file_path,
+            instructions,
+            llm_client,
+            model,
+        )
+        .await
+        .map_err(|e| match e {
+            ProcessorError::NotFound(path) => {
+                AppError::NotFound(format!("源码中不存在该文件: {}", path))
+            }
+            other => AppError::Internal(format!("重新生成文档失败: {}", other)),
+        })?;
+
+    Ok(Json(RegenerateFileResponse {
+        file_path: req.file_path,
+        doc_path: doc_path.to_string_lossy().to_string(),
+    }))
+}
+
 /// 获取任务状态
 async fn get_task_status(
     State(state): State<Arc<AppState>>,
@@ -206,6 +532,60 @@ async fn get_task_status(
     }))
 }
 
+/// 任务中单个节点的状态
+#[derive(Debug, Serialize)]
+pub struct TaskNodeStatus {
+    /// 相对于源码根目录的路径（根目录为空字符串）
+    pub path: String,
+    /// 是否为文件
+    pub is_file: bool,
+    /// 处理状态
+    pub status: NodeStatus,
+    /// 生成的文档路径
+    pub doc_path: Option<String>,
+    /// 处理失败时的错误信息
+    pub error: Option<String>,
+}
+
+/// 任务节点状态响应
+#[derive(Debug, Serialize)]
+pub struct TaskNodesResponse {
+    pub task_id: String,
+    /// 按树的先序遍历排列的节点
+    pub nodes: Vec<TaskNodeStatus>,
+}
+
+fn collect_node_statuses(node: &FileNode, out: &mut Vec<TaskNodeStatus>) {
+    out.push(TaskNodeStatus {
+        path: node.relative_path.clone(),
+        is_file: node.is_file,
+        status: node.status,
+        doc_path: node.doc_path.clone(),
+        error: node.error.clone(),
+    });
+    for child in &node.children {
+        collect_node_statuses(child, out);
+    }
+}
+
+/// 获取任务各节点的处理状态
+async fn get_task_nodes(
+    State(state): State<Arc<AppState>>,
+    Path(task_id): Path<String>,
+) -> Result<Json<TaskNodesResponse>, AppError> {
+    let tree = state
+        .doc_tasks
+        .get(&task_id)
+        .ok_or_else(|| AppError::NotFound(format!("Task not found: {}", task_id)))?
+        .tree
+        .clone()
+        .ok_or_else(|| AppError::NotFound(format!("Node tree not available for task: {}", task_id)))?;
+
+    let mut nodes = Vec::new();
+    collect_node_statuses(&*tree.read().await, &mut nodes);
+    Ok(Json(TaskNodesResponse { task_id, nodes }))
+}
+
 /// 取消任务
 async fn cancel_task(
     State(state): State<Arc<AppState>>,
@@ -221,6 +601,7 @@ async fn cancel_task(
         let mut task = task_state.task.write().await;
         task.cancel();
     }
+    task_state.mark_finished();
 
     // 发送取消消息
     let _ = task_state.tx.send(WsDocMessage::Cancelled);
@@ -248,7 +629,24 @@ async fn handle_ws_connection(
     state: Arc<AppState>,
     task_id: String,
 ) {
-    let (mut sender, mut receiver) = socket.split();
+    let (sender, receiver) = socket.split();
+    let ping_interval = Duration::from_secs(get_config().ws_ping_interval_secs.max(1));
+    relay_task_progress(sender, receiver, state, task_id, ping_interval).await;
+}
+
+/// 向客户端推送任务进度，直到任务结束、任务被清理或客户端断开
+///
+/// 没有进度消息时每隔 `ping_interval` 主动发送 Ping，避免中间代理因空闲关闭连接。
+async fn relay_task_progress<S, R>(
+    mut sender: S,
+    mut receiver: R,
+    state: Arc<AppState>,
+    task_id: String,
+    ping_interval: Duration,
+) where
+    S: futures::Sink<axum::extract::ws::Message> + Unpin,
+    R: futures::Stream<Item = Result<axum::extract::ws::Message, axum::Error>> + Unpin,
+{
 
     // 获取任务状态
     let task_state = match state.doc_tasks.get(&task_id) {
@@ -268,89 +666,65 @@ async fn handle_ws_connection(
 
     info!("WebSocket connection established: task_id={}", task_id);
 
-    // 发送当前状态
-    {
-        let task = task_state.task.read().await;
-        let msg = WsDocMessage::Progress {
-            progress: task.progress,
-            current_file: task.current_file.clone(),
-            stats: task.stats.clone(),
-        };
-        let _ = sender
-            .send(axum::extract::ws::Message::Text(
-                serde_json::to_string(&msg).unwrap(),
-            ))
-            .await;
-    }
-
-    // 重放已完成的文件/目录消息
-    // 这样前端可以正确显示在 WebSocket 连接前已处理完成的文件状态
-    let completed_paths = task_state.get_completed_paths();
-    info!("Replaying {} completed paths for task {}", completed_paths.len(), task_id);
-    for path_type in completed_paths {
-        let msg = match path_type {
-            CompletedPathType::File(path) => WsDocMessage::FileCompleted { path },
-            CompletedPathType::Dir(path) => WsDocMessage::DirCompleted { path },
-        };
-        if sender
-            .send(axum::extract::ws::Message::Text(
-                serde_json::to_string(&msg).unwrap(),
-            ))
-            .await
-            .is_err()
-        {
-            return;
-        }
-    }
-
-    // 重放正在处理中的文件/目录状态（FileStarted/DirStarted）
-    // 因为 Started 消息可能在 WebSocket 连接前就已发送，前端未收到
-    let in_progress_paths = task_state.get_in_progress_paths();
-    info!("Replaying {} in-progress paths for task {}", in_progress_paths.len(), task_id);
-    for path_type in in_progress_paths {
-        let msg = match path_type {
-            InProgressPathType::File(path) => WsDocMessage::FileStarted { path },
-            InProgressPathType::Dir(path) => WsDocMessage::DirStarted { path },
-        };
-        if sender
-            .send(axum::extract::ws::Message::Text(
-                serde_json::to_string(&msg).unwrap(),
-            ))
-            .await
-            .is_err()
-        {
-            return;
-        }
+    // 发送当前进度，并重放已完成/处理中的文件和目录
+    // 这样前端可以正确显示在 WebSocket 连接前已发送的消息对应的状态
+    if send_snapshot(&mut sender, &task_state).await.is_err() {
+        return;
     }
 
     // 订阅广播通道以接收后续消息
     let mut rx = task_state.tx.subscribe();
+    // 任务被清理时关闭连接
+    let mut closed_rx = task_state.closed.subscribe();
+    if *closed_rx.borrow_and_update() {
+        let _ = sender.send(axum::extract::ws::Message::Close(None)).await;
+        return;
+    }
+
+    // 心跳：首次在一个间隔后触发，发送进度消息后重新计时
+    let mut heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + ping_interval, ping_interval);
+    heartbeat.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
 
     // 监听进度消息
-    loop {
+    'relay: loop {
         tokio::select! {
             // 接收进度消息并发送给客户端
             result = rx.recv() => {
                 match result {
                     Ok(msg) => {
-                        let json = serde_json::to_string(&msg).unwrap();
-                        if sender.send(axum::extract::ws::Message::Text(json)).await.is_err() {
-                            break;
+                        // 慢客户端发送期间积压的消息一并取出，连续的进度更新只发送最新一条
+                        let (batch, lagged) = drain_coalesced(msg, &mut rx);
+                        for msg in batch {
+                            let json = serde_json::to_string(&msg).unwrap();
+                            if sender.send(axum::extract::ws::Message::Text(json)).await.is_err() {
+                                break 'relay;
+                            }
+
+                            // 如果任务完成，关闭连接
+                            match msg {
+                                WsDocMessage::Completed { .. }
+                                | WsDocMessage::Error { .. }
+                                | WsDocMessage::Cancelled => {
+                                    break 'relay;
+                                }
+                                _ => {}
+                            }
                         }
+                        heartbeat.reset();
 
-                        // 如果任务完成，关闭连接
-                        match msg {
-                            WsDocMessage::Completed { .. }
-                            | WsDocMessage::Error { .. }
-                            | WsDocMessage::Cancelled => {
+                        if lagged {
+                            info!("WebSocket for task {} lagged while draining, resyncing", task_id);
+                            if send_snapshot(&mut sender, &task_state).await.is_err() {
                                 break;
                             }
-                            _ => {}
                         }
                     }
-                    Err(broadcast::error::RecvError::Lagged(_)) => {
-                        // 跳过延迟的消息
-                        continue;
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        // 丢失的消息无法找回，改为从任务状态重新同步客户端
+                        info!("WebSocket for task {} lagged by {} messages, resyncing", task_id, skipped);
+                        if send_snapshot(&mut sender, &task_state).await.is_err() {
+                            break;
+                        }
                     }
                     Err(broadcast::error::RecvError::Closed) => {
                         break;
@@ -358,6 +732,19 @@ async fn handle_ws_connection(
                 }
             }
 
+            // 空闲期间发送心跳
+            _ = heartbeat.tick() => {
+                if sender.send(axum::extract::ws::Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+
+            // 任务已从注册表移除
+            _ = closed_rx.changed() => {
+                let _ = sender.send(axum::extract::ws::Message::Close(None)).await;
+                break;
+            }
+
             // 处理客户端消息（主要是 ping/pong）
             result = receiver.next() => {
                 match result {
@@ -376,11 +763,56 @@ async fn handle_ws_connection(
     info!("WebSocket connection closed: task_id={}", task_id);
 }
 
+/// 取出 `first` 之后已在通道中积压的消息，合并连续的 `Progress` 为最新一条
+///
+/// 文件/目录事件和终止事件总是原样保留。第二个返回值表示取出过程中发生了滞后（有消息丢失）。
+fn drain_coalesced(
+    first: WsDocMessage,
+    rx: &mut broadcast::Receiver<WsDocMessage>,
+) -> (Vec<WsDocMessage>, bool) {
+    let mut batch = vec![first];
+    loop {
+        match rx.try_recv() {
+            Ok(msg) => {
+                if matches!(msg, WsDocMessage::Progress { .. })
+                    && matches!(batch.last(), Some(WsDocMessage::Progress { .. }))
+                {
+                    batch.pop();
+                }
+                batch.push(msg);
+            }
+            Err(broadcast::error::TryRecvError::Lagged(_)) => return (batch, true),
+            Err(_) => return (batch, false),
+        }
+    }
+}
+
+/// 向 WebSocket 客户端发送任务状态快照
+async fn send_snapshot<S>(sender: &mut S, task_state: &TaskState) -> Result<(), S::Error>
+where
+    S: futures::Sink<axum::extract::ws::Message> + Unpin,
+{
+    let messages = task_state.snapshot_messages().await;
+    info!("Sending {} snapshot messages", messages.len());
+    for msg in messages {
+        sender
+            .send(axum::extract::ws::Message::Text(serde_json::to_string(&msg).unwrap()))
+            .await?;
+    }
+    Ok(())
+}
+
 /// 获取项目图谱请求
 #[derive(Debug, Deserialize)]
 pub struct GetProjectGraphRequest {
     /// 文档路径（.docs 目录的路径）
     pub docs_path: String,
+    /// 只返回这些类型的节点（可选）
+    #[serde(default)]
+    pub node_types: Option<Vec<String>>,
+    /// 只返回这些类型的边（可选）
+    #[serde(default)]
+    pub edge_types: Option<Vec<String>>,
 }
 
 /// 获取项目级知识图谱
@@ -414,8 +846,9 @@ async fn get_project_graph(
         .await
         .map_err(|e| AppError::Internal(format!("读取项目图谱文件失败: {}", e)))?;
 
-    let graph_data: ProjectGraphData = serde_json::from_str(&content)
+    let mut graph_data: ProjectGraphData = serde_json::from_str(&content)
         .map_err(|e| AppError::Internal(format!("解析项目图谱数据失败: {}", e)))?;
+    graph_data.retain_types(req.node_types.as_deref(), req.edge_types.as_deref());
 
     info!(
         "返回项目图谱: {} 节点, {} 边",
@@ -426,6 +859,119 @@ async fn get_project_graph(
     Ok(Json(graph_data))
 }
 
+/// 图谱增量更新请求
+#[derive(Debug, Deserialize)]
+pub struct InvalidateGraphRequest {
+    /// 文档路径（.docs 目录的路径）
+    pub docs_path: String,
+    /// 发生变化的文件相对路径
+    pub file_paths: Vec<String>,
+}
+
+/// 根据文件变更增量更新项目图谱
+///
+/// 只重新读取变更文件的 .graph.json，避免全量重新聚合。该目录有进行中的生成任务时拒绝执行
+async fn invalidate_graph(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<InvalidateGraphRequest>,
+) -> Result<Json<GraphInvalidateSummary>, AppError> {
+    let docs_path = resolve_docs_dir(&req.docs_path)?;
+    if req.file_paths.is_empty() {
+        return Err(AppError::BadRequest("file_paths 不能为空".to_string()));
+    }
+    for path in &req.file_paths {
+        validate_relative_path(path)?;
+    }
+
+    // 与启动任务、单文件重新生成共用同一把锁，避免并发改写 _project_graph.json
+    let start_lock = state.docs_path_lock(&docs_path);
+    let _start_guard = start_lock.lock().await;
+    if let Some(task_id) = state.active_task_for_docs_path(&docs_path).await {
+        return Err(AppError::Conflict(format!(
+            "文档目录正在被任务 {} 使用，请等待任务结束或取消后再更新图谱",
+            task_id
+        )));
+    }
+
+    let summary = DocGenService::with_default_config()
+        .invalidate_graph_files(docs_path, &req.file_paths)
+        .await
+        .map_err(|e| match e {
+            ProcessorError::NotFound(path) => {
+                AppError::NotFound(format!("项目图谱文件不存在: {}。请先生成文档。", path))
+            }
+            other => AppError::Internal(format!("更新项目图谱失败: {}", other)),
+        })?;
+
+    Ok(Json(summary))
+}
+
+/// 清除断点请求
+#[derive(Debug, Deserialize)]
+pub struct ClearCheckpointRequest {
+    /// 文档路径（.docs 目录的路径）
+    pub docs_path: String,
+    /// 是否同时删除已生成的文档（默认 false）
+    #[serde(default)]
+    pub delete_docs: bool,
+}
+
+/// 清除断点响应
+#[derive(Debug, Serialize)]
+pub struct ClearCheckpointResponse {
+    /// 是否存在并删除了断点文件
+    pub checkpoint_removed: bool,
+    /// 是否删除了文档目录
+    pub docs_deleted: bool,
+}
+
+/// 清除文档目录的断点（可选删除已生成的文档）
+///
+/// 该目录有进行中的生成任务时拒绝执行
+async fn clear_checkpoint(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ClearCheckpointRequest>,
+) -> Result<Json<ClearCheckpointResponse>, AppError> {
+    let docs_path = resolve_docs_dir(&req.docs_path)?;
+
+    // 与启动任务共用同一把锁，避免清除过程中有新任务写入该目录
+    let start_lock = state.docs_path_lock(&docs_path);
+    let _start_guard = start_lock.lock().await;
+    if let Some(task_id) = state.active_task_for_docs_path(&docs_path).await {
+        return Err(AppError::Conflict(format!(
+            "文档目录正在被任务 {} 使用，请等待任务结束或取消后再清除断点",
+            task_id
+        )));
+    }
+
+    // 所有已知任务（含已结束任务）的源码目录都不能被删除
+    let source_roots: Vec<PathBuf> = {
+        let tasks: Vec<_> = state.doc_tasks.iter().map(|entry| entry.value().task.clone()).collect();
+        let mut roots = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            roots.push(task.read().await.source_path.clone());
+        }
+        roots
+    };
+
+    let checkpoint_removed = DocGenService::with_default_config()
+        .clear_checkpoint(docs_path.clone(), req.delete_docs, &source_roots)
+        .await
+        .map_err(|e| match e {
+            ProcessorError::NotDocsDir(path) => {
+                AppError::BadRequest(format!("目录中没有生成标记，拒绝删除: {}", path))
+            }
+            ProcessorError::UnsafeDelete(reason) => AppError::BadRequest(format!("拒绝删除文档目录: {}", reason)),
+            other => AppError::Internal(format!("清除断点失败: {}", other)),
+        })?;
+    info!("Cleared checkpoint for {} (delete_docs={})", docs_path.display(), req.delete_docs);
+
+    Ok(Json(ClearCheckpointResponse {
+        checkpoint_removed,
+        docs_deleted: req.delete_docs,
+    }))
+}
+
 /// 获取单文件图谱请求
 #[derive(Debug, Deserialize)]
 pub struct GetFileGraphRequest {
@@ -441,30 +987,12 @@ pub struct GetFileGraphRequest {
 async fn get_file_graph(
     Json(req): Json<GetFileGraphRequest>,
 ) -> Result<Json<FileGraphData>, AppError> {
-    let docs_path = PathBuf::from(&req.docs_path);
-
-    // 验证 docs 路径存在
-    if !docs_path.exists() {
-        return Err(AppError::NotFound(format!(
-            "文档目录不存在: {}",
-            req.docs_path
-        )));
-    }
+    let docs_path = resolve_docs_dir(&req.docs_path)?;
 
     // 构建文件图谱路径
-    // 例如: file_path = "src/utils/helper.py" -> docs_path/src/utils/helper.py.graph.json
-    let file_path = std::path::Path::new(&req.file_path);
-    let file_name = file_path.file_name()
-        .and_then(|n| n.to_str())
-        .ok_or_else(|| AppError::BadRequest("无效的文件路径".to_string()))?;
-
-    let graph_name = format!("{}.graph.json", file_name);
-    let graph_path = match file_path.parent() {
-        Some(parent) if !parent.as_os_str().is_empty() => {
-            docs_path.join(parent).join(graph_name)
-        }
-        _ => docs_path.join(graph_name),
-    };
+    // 例如: file_path = "src/utils/helper.py" 或 "src\\utils\\helper.py" -> docs_path/src/utils/helper.py.graph.json
+    let node = relative_node(&req.file_path, true)?;
+    let graph_path = DocumentGenerator::new(docs_path, DocGenConfig::default()).get_file_graph_path(&node);
 
     if !graph_path.exists() {
         return Err(AppError::NotFound(format!(
@@ -506,24 +1034,13 @@ pub struct GetDirGraphRequest {
 async fn get_dir_graph(
     Json(req): Json<GetDirGraphRequest>,
 ) -> Result<Json<DirGraphData>, AppError> {
-    let docs_path = PathBuf::from(&req.docs_path);
-
-    // 验证 docs 路径存在
-    if !docs_path.exists() {
-        return Err(AppError::NotFound(format!(
-            "文档目录不存在: {}",
-            req.docs_path
-        )));
-    }
+    let docs_path = resolve_docs_dir(&req.docs_path)?;
 
     // 构建目录图谱路径
     // 例如: dir_path = "src/utils" -> docs_path/src/utils/_dir.graph.json
     // 根目录: dir_path = "" -> docs_path/_dir.graph.json
-    let graph_path = if req.dir_path.is_empty() {
-        docs_path.join("_dir.graph.json")
-    } else {
-        docs_path.join(&req.dir_path).join("_dir.graph.json")
-    };
+    let node = relative_node(&req.dir_path, false)?;
+    let graph_path = DocumentGenerator::new(docs_path, DocGenConfig::default()).get_dir_graph_path(&node);
 
     if !graph_path.exists() {
         return Err(AppError::NotFound(format!(
@@ -549,3 +1066,684 @@ async fn get_dir_graph(
 
     Ok(Json(graph_data))
 }
+
+/// 校验文档目录存在
+fn resolve_docs_dir(docs_path: &str) -> Result<PathBuf, AppError> {
+    let docs_path = PathBuf::from(docs_path);
+    if !docs_path.is_dir() {
+        return Err(AppError::NotFound(format!(
+            "文档目录不存在: {}",
+            docs_path.display()
+        )));
+    }
+    Ok(docs_path)
+}
+
+/// 校验相对路径：不允许绝对路径和 `..`，防止读取文档目录以外的文件
+///
+/// `/` 和 `\` 都视为分隔符，避免在 Unix 上通过 `..\` 绕过检查
+fn validate_relative_path(path: &str) -> Result<(), AppError> {
+    let escapes = path.starts_with(['/', '\\'])
+        || relative_path_segments(path).any(|s| s == ".." || s.contains(':'));
+    if escapes {
+        return Err(AppError::BadRequest(format!("无效的相对路径: {}", path)));
+    }
+    Ok(())
+}
+
+/// 由请求中的相对路径构造文件/目录节点，供 `DocumentGenerator` 计算文档路径
+fn relative_node(path: &str, is_file: bool) -> Result<FileNode, AppError> {
+    validate_relative_path(path)?;
+    let name = relative_path_segments(path).last().unwrap_or_default().to_string();
+    let full_path: PathBuf = relative_path_segments(path).collect();
+    if is_file {
+        if name.is_empty() {
+            return Err(AppError::BadRequest("无效的文件路径".to_string()));
+        }
+        Ok(FileNode::new_file(name, full_path, path.to_string(), 0))
+    } else {
+        Ok(FileNode::new_dir(name, full_path, path.to_string(), 0))
+    }
+}
+
+/// 获取文档内容请求
+#[derive(Debug, Deserialize)]
+pub struct GetFileDocRequest {
+    /// 文档路径（.docs 目录的路径）
+    pub docs_path: String,
+    /// 文件相对路径（与 `dir_path` 二选一）
+    pub file_path: Option<String>,
+    /// 目录相对路径（根目录传空字符串）
+    pub dir_path: Option<String>,
+}
+
+/// 文档内容响应
+#[derive(Debug, Serialize)]
+pub struct FileDocResponse {
+    /// 文档文件的完整路径
+    pub doc_path: String,
+    /// Markdown 内容
+    pub content: String,
+}
+
+/// 获取文件或目录的 Markdown 文档
+///
+/// 路径规则与 `DocumentGenerator::get_file_doc_path` / `get_dir_doc_path` 一致
+async fn get_file_doc(
+    Json(req): Json<GetFileDocRequest>,
+) -> Result<Json<FileDocResponse>, AppError> {
+    let docs_path = resolve_docs_dir(&req.docs_path)?;
+    let generator = DocumentGenerator::new(docs_path, DocGenConfig::default());
+
+    let doc_path = match (&req.file_path, &req.dir_path) {
+        (Some(file_path), None) => generator.get_file_doc_path(&relative_node(file_path, true)?),
+        (None, Some(dir_path)) => generator.get_dir_doc_path(&relative_node(dir_path, false)?),
+        _ => {
+            return Err(AppError::BadRequest(
+                "file_path 和 dir_path 必须且只能提供一个".to_string(),
+            ))
+        }
+    };
+
+    if !doc_path.is_file() {
+        return Err(AppError::NotFound(format!(
+            "文档不存在: {}",
+            doc_path.display()
+        )));
+    }
+
+    let content = tokio::fs::read_to_string(&doc_path)
+        .await
+        .map_err(|e| AppError::Internal(format!("读取文档失败: {}", e)))?;
+
+    Ok(Json(FileDocResponse {
+        doc_path: doc_path.to_string_lossy().to_string(),
+        content,
+    }))
+}
+
+/// 获取生成报告请求
+#[derive(Debug, Deserialize)]
+pub struct GetReportQuery {
+    /// 文档路径（.docs 目录的路径）
+    pub docs_path: String,
+}
+
+/// 获取最近一次生成任务的报告
+///
+/// 读取 .docs/_report.json 文件并返回
+async fn get_report(
+    Query(query): Query<GetReportQuery>,
+) -> Result<Json<GenerationReport>, AppError> {
+    let docs_path = resolve_docs_dir(&query.docs_path)?;
+    let report_path = docs_path.join(REPORT_FILE_NAME);
+
+    if !report_path.is_file() {
+        return Err(AppError::NotFound(format!(
+            "生成报告不存在: {}。请先生成文档。",
+            report_path.display()
+        )));
+    }
+
+    let content = tokio::fs::read_to_string(&report_path)
+        .await
+        .map_err(|e| AppError::Internal(format!("读取生成报告失败: {}", e)))?;
+
+    let report: GenerationReport = serde_json::from_str(&content)
+        .map_err(|e| AppError::Internal(format!("解析生成报告失败: {}", e)))?;
+
+    Ok(Json(report))
+}
+
+/// 获取 API 清单请求
+#[derive(Debug, Deserialize)]
+pub struct GetApiInventoryQuery {
+    /// 文档路径（.docs 目录的路径）
+    pub docs_path: String,
+}
+
+/// 获取 API 清单
+///
+/// 读取 .docs/_api.json 文件并返回
+async fn get_api_inventory(
+    Query(query): Query<GetApiInventoryQuery>,
+) -> Result<Json<ApiInventory>, AppError> {
+    let docs_path = resolve_docs_dir(&query.docs_path)?;
+    let inventory_path = docs_path.join(API_INVENTORY_FILE_NAME);
+
+    if !inventory_path.is_file() {
+        return Err(AppError::NotFound(format!(
+            "API 清单不存在: {}。请先生成文档。",
+            inventory_path.display()
+        )));
+    }
+
+    let content = tokio::fs::read_to_string(&inventory_path)
+        .await
+        .map_err(|e| AppError::Internal(format!("读取 API 清单失败: {}", e)))?;
+
+    let inventory: ApiInventory = serde_json::from_str(&content)
+        .map_err(|e| AppError::Internal(format!("解析 API 清单失败: {}", e)))?;
+
+    Ok(Json(inventory))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::doc_generator::types::DocTask;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tokio::sync::RwLock;
+    use tower::Service;
+
+    /// 注册一个指定状态的任务
+    fn register_task(state: &AppState, id: &str, start: bool, finish: bool) {
+        let mut task = DocTask::new(id.to_string(), PathBuf::from("src"), PathBuf::from("docs"));
+        if start {
+            task.start();
+        }
+        if finish {
+            task.complete();
+        }
+        let (tx, _) = broadcast::channel(1);
+        let task_state = TaskState::new(Arc::new(RwLock::new(task)), tx);
+        state.doc_tasks.insert(id.to_string(), Arc::new(task_state));
+    }
+
+    #[tokio::test]
+    async fn test_generate_docs_rejected_when_limit_reached() {
+        let limit = get_config().max_concurrent_tasks.max(1);
+        let state = AppState::new();
+        register_task(&state, "pending", false, false);
+        for i in 1..limit {
+            register_task(&state, &format!("running-{}", i), true, false);
+        }
+        register_task(&state, "done", true, true);
+        assert_eq!(state.active_task_count().await, limit);
+
+        let source = tempfile::TempDir::new().unwrap();
+        let body = serde_json::json!({
+            "source_path": source.path().to_string_lossy(),
+        });
+
+        let mut app = docs_routes().with_state(Arc::new(state));
+        let response = app
+            .call(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/docs/generate")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(json["error"].as_str().unwrap().contains(&format!("{}/{}", limit, limit)));
+    }
+
+    #[tokio::test]
+    async fn test_generate_docs_reuses_active_task_for_same_docs_path() {
+        let source = tempfile::TempDir::new().unwrap();
+        let docs_path = source.path().join(".docs");
+
+        let state = AppState::new();
+        let mut task = DocTask::new("first".to_string(), source.path().to_path_buf(), docs_path.clone());
+        task.start();
+        let (tx, _) = broadcast::channel(1);
+        state
+            .doc_tasks
+            .insert("first".to_string(), Arc::new(TaskState::new(Arc::new(RwLock::new(task)), tx)));
+
+        let body = serde_json::json!({
+            "source_path": source.path().to_string_lossy(),
+            "docs_path": docs_path.to_string_lossy(),
+        });
+        let request = || {
+            Request::builder()
+                .method("POST")
+                .uri("/api/docs/generate")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let app = docs_routes().with_state(Arc::new(state));
+        let (first, second) = tokio::join!(app.clone().call(request()), app.clone().call(request()));
+        for response in [first.unwrap(), second.unwrap()] {
+            assert_eq!(response.status(), StatusCode::OK);
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(json["task_id"], "first");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clear_checkpoint() {
+        let docs = tempfile::TempDir::new().unwrap();
+        let checkpoint = docs.path().join(".checkpoint.json");
+        std::fs::write(&checkpoint, "{}").unwrap();
+
+        let state = AppState::new();
+        let mut task = DocTask::new("running".to_string(), PathBuf::from("src"), docs.path().to_path_buf());
+        task.start();
+        let (tx, _) = broadcast::channel(1);
+        let task = Arc::new(RwLock::new(task));
+        state
+            .doc_tasks
+            .insert("running".to_string(), Arc::new(TaskState::new(task.clone(), tx)));
+
+        let body = serde_json::json!({ "docs_path": docs.path().to_string_lossy() });
+        let request = || {
+            Request::builder()
+                .method("POST")
+                .uri("/api/docs/checkpoint/clear")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let mut app = docs_routes().with_state(Arc::new(state));
+
+        // 任务进行中：拒绝清除
+        let response = app.call(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert!(checkpoint.is_file());
+
+        task.write().await.complete();
+        let response = app.call(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["checkpoint_removed"], true);
+        assert!(!checkpoint.exists());
+        assert!(docs.path().is_dir());
+    }
+
+    #[tokio::test]
+    async fn test_get_task_nodes_reports_failed_node() {
+        let mut root = FileNode::new_dir("proj".to_string(), PathBuf::from("proj"), String::new(), 0);
+        let mut ok = FileNode::new_file("a.py".to_string(), PathBuf::from("proj/a.py"), "a.py".to_string(), 1);
+        ok.status = NodeStatus::Completed;
+        ok.doc_path = Some("a.py.md".to_string());
+        let mut bad = FileNode::new_file("b.py".to_string(), PathBuf::from("proj/b.py"), "b.py".to_string(), 1);
+        bad.status = NodeStatus::Failed;
+        bad.error = Some("LLM error".to_string());
+        root.children = vec![ok, bad];
+
+        let state = AppState::new();
+        register_task(&state, "plain", true, false);
+        let task = DocTask::new("t1".to_string(), PathBuf::from("proj"), PathBuf::from("docs"));
+        let (tx, _) = broadcast::channel(1);
+        let task_state =
+            TaskState::new(Arc::new(RwLock::new(task)), tx).with_tree(Arc::new(RwLock::new(root)));
+        state.doc_tasks.insert("t1".to_string(), Arc::new(task_state));
+        let mut app = docs_routes().with_state(Arc::new(state));
+
+        let request = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = app.call(request("/api/docs/tasks/t1/nodes")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let nodes = json["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[1]["path"], "a.py");
+        assert_eq!(nodes[1]["status"], "completed");
+        assert_eq!(nodes[1]["doc_path"], "a.py.md");
+        assert_eq!(nodes[2]["path"], "b.py");
+        assert_eq!(nodes[2]["status"], "failed");
+        assert_eq!(nodes[2]["error"], "LLM error");
+
+        // 未关联文件树的任务和不存在的任务
+        let response = app.call(request("/api/docs/tasks/plain/nodes")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app.call(request("/api/docs/tasks/missing/nodes")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_ws_sends_pings_while_idle() {
+        use axum::extract::ws::Message;
+        use futures::channel::mpsc;
+
+        let state = AppState::new();
+        register_task(&state, "quiet", true, false);
+
+        let (server_tx, mut client_rx) = mpsc::unbounded::<Message>();
+        let (client_tx, server_rx) = mpsc::unbounded::<Result<Message, axum::Error>>();
+        let relay = tokio::spawn(relay_task_progress(
+            server_tx,
+            server_rx,
+            Arc::new(state),
+            "quiet".to_string(),
+            Duration::from_millis(20),
+        ));
+
+        // 先收到进度快照，随后在没有任何进度消息的情况下收到心跳
+        assert!(matches!(client_rx.next().await, Some(Message::Text(_))));
+        for _ in 0..2 {
+            let msg = tokio::time::timeout(Duration::from_secs(1), client_rx.next()).await.unwrap();
+            assert!(matches!(msg, Some(Message::Ping(_))));
+        }
+
+        // 客户端断开后推送结束
+        drop(client_tx);
+        tokio::time::timeout(Duration::from_secs(1), relay).await.unwrap().unwrap();
+    }
+
+    async fn post_file_doc(body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let mut app = docs_routes().with_state(Arc::new(AppState::new()));
+        let response = app
+            .call(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/docs/file-doc")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_get_file_doc() {
+        let docs = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(docs.path().join("src")).unwrap();
+        std::fs::write(docs.path().join("src/main.py.md"), "# main.py").unwrap();
+        std::fs::write(docs.path().join("src/_dir_summary.md"), "# src").unwrap();
+        let docs_path = docs.path().to_string_lossy().to_string();
+
+        let (status, json) =
+            post_file_doc(serde_json::json!({ "docs_path": docs_path, "file_path": "src/main.py" })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["content"], "# main.py");
+
+        let (status, json) =
+            post_file_doc(serde_json::json!({ "docs_path": docs_path, "dir_path": "src" })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["content"], "# src");
+
+        // 文档不存在
+        let (status, _) =
+            post_file_doc(serde_json::json!({ "docs_path": docs_path, "file_path": "src/missing.py" })).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // 文档目录不存在
+        let (status, _) = post_file_doc(
+            serde_json::json!({ "docs_path": docs.path().join("nope"), "file_path": "src/main.py" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // 路径越界
+        let (status, _) =
+            post_file_doc(serde_json::json!({ "docs_path": docs_path, "file_path": "../secret.py" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_get_graphs_with_either_separator() {
+        let docs = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(docs.path().join("src/utils")).unwrap();
+        let file_graph = FileGraphData::new("src/utils/helper.py".to_string(), Default::default());
+        std::fs::write(
+            docs.path().join("src/utils/helper.py.graph.json"),
+            serde_json::to_string(&file_graph).unwrap(),
+        )
+        .unwrap();
+        let dir_graph = DirGraphData::new("src/utils".to_string(), Default::default());
+        std::fs::write(
+            docs.path().join("src/utils/_dir.graph.json"),
+            serde_json::to_string(&dir_graph).unwrap(),
+        )
+        .unwrap();
+        let docs_path = docs.path().to_string_lossy().to_string();
+
+        let post = |uri: &'static str, body: serde_json::Value| async move {
+            let mut app = docs_routes().with_state(Arc::new(AppState::new()));
+            app.call(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+        };
+
+        for file_path in ["src/utils/helper.py", "src\\utils\\helper.py"] {
+            let body = serde_json::json!({ "docs_path": docs_path, "file_path": file_path });
+            assert_eq!(post("/api/docs/file-graph", body).await, StatusCode::OK, "{}", file_path);
+        }
+        for dir_path in ["src/utils", "src\\utils\\"] {
+            let body = serde_json::json!({ "docs_path": docs_path, "dir_path": dir_path });
+            assert_eq!(post("/api/docs/dir-graph", body).await, StatusCode::OK, "{}", dir_path);
+        }
+
+        // 反斜杠形式的越界路径同样被拒绝
+        let body = serde_json::json!({ "docs_path": docs_path, "file_path": "..\\secret.py" });
+        assert_eq!(post("/api/docs/file-graph", body).await, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_reanalyze_file_requires_instructions() {
+        let source = tempfile::TempDir::new().unwrap();
+        let body = serde_json::json!({
+            "source_path": source.path().to_string_lossy(),
+            "file_path": "a.py",
+            "instructions": "   ",
+        });
+
+        let mut app = docs_routes().with_state(Arc::new(AppState::new()));
+        let response = app
+            .call(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/docs/reanalyze-file")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    /// 在文档目录上登记一个运行中的生成任务
+    fn register_active_task(state: &AppState, id: &str, source: &std::path::Path, docs_path: &std::path::Path) {
+        let mut task = DocTask::new(id.to_string(), source.to_path_buf(), docs_path.to_path_buf());
+        task.start();
+        let (tx, _) = broadcast::channel(1);
+        state
+            .doc_tasks
+            .insert(id.to_string(), Arc::new(TaskState::new(Arc::new(RwLock::new(task)), tx)));
+    }
+
+    #[tokio::test]
+    async fn test_regenerate_file_rejected_while_task_owns_docs_path() {
+        let source = tempfile::TempDir::new().unwrap();
+        std::fs::write(source.path().join("a.py"), "x = 1\n").unwrap();
+        let docs_path = source.path().join(".docs");
+
+        let state = AppState::new();
+        register_active_task(&state, "running", source.path(), &docs_path);
+
+        let body = serde_json::json!({
+            "source_path": source.path().to_string_lossy(),
+            "docs_path": docs_path.to_string_lossy(),
+            "file_path": "a.py",
+        });
+        let mut app = docs_routes().with_state(Arc::new(state));
+        let response = app
+            .call(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/docs/regenerate-file")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(json["error"].as_str().unwrap().contains("running"));
+        assert!(!docs_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_graph_rejected_while_task_owns_docs_path() {
+        let source = tempfile::TempDir::new().unwrap();
+        let docs_path = source.path().join(".docs");
+        std::fs::create_dir(&docs_path).unwrap();
+        let graph = r#"{"nodes":[],"edges":[]}"#;
+        std::fs::write(docs_path.join("_project_graph.json"), graph).unwrap();
+
+        let state = AppState::new();
+        register_active_task(&state, "running", source.path(), &docs_path);
+
+        let body = serde_json::json!({
+            "docs_path": docs_path.to_string_lossy(),
+            "file_paths": ["a.py"],
+        });
+        let mut app = docs_routes().with_state(Arc::new(state));
+        let response = app
+            .call(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/docs/graph/invalidate")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(std::fs::read_to_string(docs_path.join("_project_graph.json")).unwrap(), graph);
+    }
+
+    #[tokio::test]
+    async fn test_reanalyze_file_rejected_while_task_owns_docs_path() {
+        let source = tempfile::TempDir::new().unwrap();
+        std::fs::write(source.path().join("a.py"), "x = 1\n").unwrap();
+        let docs_path = source.path().join(".docs");
+
+        let state = AppState::new();
+        register_active_task(&state, "running", source.path(), &docs_path);
+
+        let body = serde_json::json!({
+            "source_path": source.path().to_string_lossy(),
+            "docs_path": docs_path.to_string_lossy(),
+            "file_path": "a.py",
+            "instructions": "重点说明全局变量",
+        });
+        let mut app = docs_routes().with_state(Arc::new(state));
+        let response = app
+            .call(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/docs/reanalyze-file")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert!(!docs_path.exists());
+    }
+
+    #[test]
+    fn test_drain_coalesces_progress_burst() {
+        let progress = |n: usize| WsDocMessage::Progress {
+            progress: n as f32,
+            current_file: Some(format!("f{}.py", n)),
+            stats: TaskStats::default(),
+        };
+
+        let (tx, mut rx) = broadcast::channel(256);
+        for n in 0..50 {
+            tx.send(progress(n)).unwrap();
+        }
+        tx.send(WsDocMessage::FileCompleted { path: "a.py".to_string() }).unwrap();
+        for n in 50..100 {
+            tx.send(progress(n)).unwrap();
+        }
+        tx.send(WsDocMessage::FileCompleted { path: "b.py".to_string() }).unwrap();
+        tx.send(progress(100)).unwrap();
+        tx.send(WsDocMessage::Completed { stats: TaskStats::default() }).unwrap();
+
+        // 模拟慢客户端：整批消息在下一次接收前已全部积压
+        let first = rx.try_recv().unwrap();
+        let (batch, lagged) = drain_coalesced(first, &mut rx);
+        assert!(!lagged);
+
+        let summary: Vec<String> = batch
+            .iter()
+            .map(|msg| match msg {
+                WsDocMessage::Progress { progress, .. } => format!("progress:{}", progress),
+                WsDocMessage::FileCompleted { path } => format!("file:{}", path),
+                WsDocMessage::Completed { .. } => "completed".to_string(),
+                other => panic!("unexpected message: {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            ["progress:49", "file:a.py", "progress:99", "file:b.py", "progress:100", "completed"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_preview_tree() {
+        let source = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(source.path().join("src/utils")).unwrap();
+        std::fs::write(source.path().join("src/main.py"), "print('hi')").unwrap();
+        std::fs::write(source.path().join("src/utils/helper.py"), "def helper(): pass").unwrap();
+        std::fs::create_dir_all(source.path().join("node_modules/pkg")).unwrap();
+        std::fs::write(source.path().join("node_modules/pkg/index.js"), "module.exports = 1").unwrap();
+        std::fs::create_dir_all(source.path().join(".docs")).unwrap();
+        std::fs::write(source.path().join(".docs/old.py"), "x = 1").unwrap();
+
+        let body = serde_json::json!({ "source_path": source.path().to_string_lossy() });
+        let mut app = docs_routes().with_state(Arc::new(AppState::new()));
+        let response = app
+            .call(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/docs/tree")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+        assert_eq!(json["file_count"], 2);
+        let root = &json["root"];
+        let top: Vec<&str> = root["children"].as_array().unwrap().iter().map(|c| c["name"].as_str().unwrap()).collect();
+        assert_eq!(top, vec!["src"]);
+        let src: Vec<&str> = root["children"][0]["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["relative_path"].as_str().unwrap())
+            .collect();
+        assert_eq!(src, vec!["src/utils", "src/main.py"]);
+        assert_eq!(root["children"][0]["children"][0]["children"][0]["relative_path"], "src/utils/helper.py");
+    }
+}
@@ -1,15 +1,20 @@
 //! 知识图谱 API 端点
 
 use axum::{
+    extract::State,
     routing::post,
     Json, Router,
 };
+use futures::stream::{self, StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tracing::info;
 
-use crate::error::AppResult;
-use crate::services::code_analyzer::types::GraphData;
+use crate::config::get_config;
+use crate::error::{AppError, AppResult};
+use crate::services::code_analyzer::types::{FileImports, GraphData};
+use crate::services::code_analyzer::AnalyzerError;
 use crate::services::CodeAnalyzer;
 use crate::state::AppState;
 
@@ -37,6 +42,18 @@ impl From<GraphData> for GraphResponse {
 #[derive(Deserialize)]
 pub struct ProjectGraphRequest {
     pub project_path: String,
+    /// 是否排除测试文件（默认 false，测试文件节点带 `is_test` 元数据）
+    #[serde(default)]
+    pub exclude_tests: bool,
+    /// 是否按命名约定为相关文件（如源文件与其测试文件）添加 `related` 边（默认 true）
+    #[serde(default)]
+    pub related_files: Option<bool>,
+    /// 只返回这些类型的节点（可选）
+    #[serde(default)]
+    pub node_types: Option<Vec<String>>,
+    /// 只返回这些类型的边（可选）
+    #[serde(default)]
+    pub edge_types: Option<Vec<String>>,
 }
 
 /// 模块图谱请求
@@ -44,15 +61,79 @@ pub struct ProjectGraphRequest {
 pub struct ModuleGraphRequest {
     pub project_path: String,
     pub file_path: String,
+    /// 只返回这些类型的节点（可选）
+    #[serde(default)]
+    pub node_types: Option<Vec<String>>,
+    /// 只返回这些类型的边（可选）
+    #[serde(default)]
+    pub edge_types: Option<Vec<String>>,
+    /// 强制使用的分析器语言（如 `python`、`typescript`），不填时按扩展名选择
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+/// 批量模块图谱请求
+#[derive(Deserialize)]
+pub struct ModuleGraphsRequest {
+    pub project_path: String,
+    pub file_paths: Vec<String>,
+}
+
+/// 批量请求最多包含的文件数
+const MAX_BATCH_FILES: usize = 100;
+
+/// 批量分析的并发数
+const BATCH_CONCURRENCY: usize = 8;
+
+/// 单文件导入解析请求
+#[derive(Deserialize)]
+pub struct FileImportsRequest {
+    pub project_path: String,
+    pub file_path: String,
+}
+
+/// 源码内容分析请求
+#[derive(Deserialize)]
+pub struct AnalyzeContentRequest {
+    /// 文件路径（用于选择语言分析器和生成节点 ID）
+    pub file_path: String,
+    /// 源码内容
+    pub content: String,
+    /// 强制使用的分析器语言，不填时按扩展名选择
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+/// 将分析错误映射为 HTTP 错误
+fn map_analyzer_error(err: AnalyzerError) -> AppError {
+    match err {
+        AnalyzerError::ProjectNotFound(_) | AnalyzerError::FileNotFound(_) => {
+            AppError::NotFound(err.to_string())
+        }
+        AnalyzerError::NotADirectory(_)
+        | AnalyzerError::PermissionDenied(_)
+        | AnalyzerError::TooManyFiles { .. }
+        | AnalyzerError::UnknownLanguage(..) => {
+            AppError::BadRequest(err.to_string())
+        }
+        AnalyzerError::IoError(..) => AppError::Analyzer(err.to_string()),
+    }
 }
 
 /// 获取项目级知识图谱
 async fn get_project_graph(
+    State(state): State<Arc<AppState>>,
     Json(req): Json<ProjectGraphRequest>,
 ) -> AppResult<Json<GraphResponse>> {
-    let analyzer = CodeAnalyzer::new(&req.project_path);
+    let analyzer = CodeAnalyzer::new(&req.project_path)
+        .with_max_files(get_config().max_analyzed_files)
+        .with_exclude_tests(req.exclude_tests)
+        .with_related_files(req.related_files.unwrap_or(true));
 
-    let graph = analyzer.analyze_project();
+    let mut graph = analyzer
+        .analyze_project_cached(&state.graph_cache)
+        .map_err(map_analyzer_error)?;
+    graph.retain_types(req.node_types.as_deref(), req.edge_types.as_deref());
     info!(
         "项目图谱生成完成: {} 节点, {} 边",
         graph.nodes.len(),
@@ -68,7 +149,11 @@ async fn get_module_graph(
 ) -> AppResult<Json<GraphResponse>> {
     let analyzer = CodeAnalyzer::new(&req.project_path);
 
-    let graph = analyzer.analyze_module(&req.file_path);
+    let language = req.language.as_deref().filter(|l| !l.trim().is_empty());
+    let mut graph = analyzer
+        .analyze_module_with_language(&req.file_path, language)
+        .map_err(map_analyzer_error)?;
+    graph.retain_types(req.node_types.as_deref(), req.edge_types.as_deref());
     info!(
         "模块图谱生成完成 {}: {} 节点, {} 边",
         req.file_path,
@@ -79,9 +164,285 @@ async fn get_module_graph(
     Ok(Json(GraphResponse::from(graph)))
 }
 
+/// 批量获取多个文件的模块级知识图谱
+///
+/// 返回 `file_path → 图谱` 的映射；任一文件分析失败则整个请求失败（错误码与单文件接口一致）
+async fn get_module_graphs(
+    Json(req): Json<ModuleGraphsRequest>,
+) -> AppResult<Json<BTreeMap<String, GraphResponse>>> {
+    if req.file_paths.is_empty() {
+        return Err(AppError::BadRequest("file_paths 不能为空".to_string()));
+    }
+    if req.file_paths.len() > MAX_BATCH_FILES {
+        return Err(AppError::BadRequest(format!(
+            "file_paths 最多 {} 个，实际 {} 个",
+            MAX_BATCH_FILES,
+            req.file_paths.len()
+        )));
+    }
+
+    let project_path = std::path::PathBuf::from(&req.project_path);
+    if !project_path.is_dir() {
+        return Err(AppError::NotFound(format!("项目路径不存在: {}", req.project_path)));
+    }
+
+    let mut file_paths = req.file_paths;
+    file_paths.sort();
+    file_paths.dedup();
+
+    // 同一请求内的所有文件共用一个分析器
+    let analyzer = Arc::new(CodeAnalyzer::new(project_path));
+    let graphs: BTreeMap<String, GraphResponse> = stream::iter(file_paths)
+        .map(|file_path| {
+            let analyzer = analyzer.clone();
+            async move {
+                let graph = tokio::task::spawn_blocking({
+                    let file_path = file_path.clone();
+                    move || analyzer.analyze_module(&file_path)
+                })
+                .await
+                .map_err(|e| AppError::Internal(format!("分析任务失败: {}", e)))?
+                .map_err(map_analyzer_error)?;
+                Ok::<_, AppError>((file_path, GraphResponse::from(graph)))
+            }
+        })
+        .buffer_unordered(BATCH_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    info!("批量模块图谱生成完成: {} 个文件", graphs.len());
+    Ok(Json(graphs))
+}
+
+/// 解析单个文件的导入，返回能解析和无法解析的导入
+///
+/// 用于排查项目图谱中缺少的跨文件依赖边
+async fn get_file_imports(
+    Json(req): Json<FileImportsRequest>,
+) -> AppResult<Json<FileImports>> {
+    let analyzer = CodeAnalyzer::new(&req.project_path)
+        .with_max_files(get_config().max_analyzed_files);
+
+    let imports = analyzer
+        .analyze_imports(&req.file_path)
+        .map_err(map_analyzer_error)?;
+    info!(
+        "导入解析完成 {}: {} 个已解析, {} 个未解析",
+        req.file_path,
+        imports.resolved.len(),
+        imports.unresolved.len()
+    );
+
+    Ok(Json(imports))
+}
+
+/// 分析未保存的源码内容
+async fn analyze_content(
+    Json(req): Json<AnalyzeContentRequest>,
+) -> AppResult<Json<GraphResponse>> {
+    if req.file_path.trim().is_empty() {
+        return Err(AppError::BadRequest("file_path 不能为空".to_string()));
+    }
+
+    let language = req.language.as_deref().filter(|l| !l.trim().is_empty());
+    let graph = CodeAnalyzer::analyze_content_with_language(&req.file_path, &req.content, language)
+        .map_err(map_analyzer_error)?;
+    info!(
+        "内容图谱生成完成 {}: {} 节点, {} 边",
+        req.file_path,
+        graph.nodes.len(),
+        graph.edges.len()
+    );
+
+    Ok(Json(GraphResponse::from(graph)))
+}
+
 /// 创建图谱路由
 pub fn graph_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/api/graph/project", post(get_project_graph))
         .route("/api/graph/module", post(get_module_graph))
+        .route("/api/graph/modules", post(get_module_graphs))
+        .route("/api/graph/analyze-content", post(analyze_content))
+        .route("/api/graph/imports", post(get_file_imports))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::Service;
+
+    async fn post_json(uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let mut app = graph_routes().with_state(Arc::new(AppState::new()));
+        let response = app
+            .call(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    async fn post_module(project_path: &str, file_path: &str) -> (StatusCode, serde_json::Value) {
+        let body = serde_json::json!({ "project_path": project_path, "file_path": file_path });
+        post_json("/api/graph/module", body).await
+    }
+
+    #[tokio::test]
+    async fn test_module_graph_filtered_by_node_type() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let content = "class Base:\n    pass\n\nclass Foo(Base):\n    def bar(self):\n        pass\n\ndef baz():\n    pass\n";
+        std::fs::write(dir.path().join("a.py"), content).unwrap();
+
+        let body = serde_json::json!({
+            "project_path": dir.path().to_string_lossy(),
+            "file_path": "a.py",
+            "node_types": ["class"],
+        });
+        let (status, json) = post_json("/api/graph/module", body).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let nodes = json["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert!(nodes.iter().all(|n| n["type"] == "class"));
+        // 只剩类之间的继承边，file→class 的 contains 边和方法相关的边都被移除
+        let edges = json["edges"].as_array().unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0]["type"], "inherits");
+    }
+
+    #[tokio::test]
+    async fn test_module_graph_missing_vs_empty() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("empty.py"), "").unwrap();
+        let project = dir.path().to_string_lossy().to_string();
+
+        let (status, _) = post_module(&project, "missing.py").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, json) = post_module(&project, "empty.py").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["nodes"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_project_graph_invalid_path() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("main.py"), "def main():\n    pass\n").unwrap();
+
+        // 不存在的目录不能返回空图谱
+        let missing = dir.path().join("missing").to_string_lossy().to_string();
+        let (status, json) = post_json("/api/graph/project", serde_json::json!({ "project_path": missing })).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(json["success"], false);
+
+        // 文件不是目录
+        let file = dir.path().join("main.py").to_string_lossy().to_string();
+        let (status, _) = post_json("/api/graph/project", serde_json::json!({ "project_path": file })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let project = dir.path().to_string_lossy().to_string();
+        let (status, _) = post_json("/api/graph/project", serde_json::json!({ "project_path": project })).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_module_graphs_batch() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("pkg")).unwrap();
+        std::fs::write(dir.path().join("a.py"), "def alpha():\n    pass\n").unwrap();
+        std::fs::write(dir.path().join("b.go"), "package main\n\nfunc Beta() {}\n").unwrap();
+        std::fs::write(dir.path().join("pkg/c.js"), "function gamma() {}\n").unwrap();
+        let project = dir.path().to_string_lossy().to_string();
+
+        let body = serde_json::json!({ "project_path": project, "file_paths": ["a.py", "b.go", "pkg/c.js"] });
+        let (status, json) = post_json("/api/graph/modules", body).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let graphs = json.as_object().unwrap();
+        assert_eq!(graphs.len(), 3);
+        for (path, label) in [("a.py", "alpha"), ("b.go", "Beta"), ("pkg/c.js", "gamma")] {
+            let nodes = graphs[path]["nodes"].as_array().unwrap();
+            assert!(nodes.iter().any(|n| n["label"] == label), "{} missing {}", path, label);
+            assert!(nodes.iter().any(|n| n["type"] == "file" && n["file_path"] == path), "{} has no file node", path);
+        }
+
+        // 任一文件不存在则返回 404
+        let body = serde_json::json!({ "project_path": project, "file_paths": ["a.py", "missing.py"] });
+        let (status, _) = post_json("/api/graph/modules", body).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_content_python() {
+        let body = serde_json::json!({
+            "file_path": "app/service.py",
+            "content": "class UserService(Base):\n    def get(self, id):\n        pass\n\ndef main():\n    pass\n",
+        });
+        let (status, json) = post_json("/api/graph/analyze-content", body).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let nodes = json["nodes"].as_array().unwrap();
+        let has = |node_type: &str, label: &str| {
+            nodes.iter().any(|n| n["type"] == node_type && n["label"] == label)
+        };
+        assert!(has("class", "UserService"));
+        assert!(has("method", "get"));
+        assert!(has("function", "main"));
+    }
+
+    #[tokio::test]
+    async fn test_analyze_content_language_override() {
+        let body = serde_json::json!({
+            "file_path": "snippet.txt",
+            "content": "def main():\n    pass\n",
+            "language": "python",
+        });
+        let (status, json) = post_json("/api/graph/analyze-content", body).await;
+        assert_eq!(status, StatusCode::OK);
+        let nodes = json["nodes"].as_array().unwrap();
+        assert!(nodes.iter().any(|n| n["type"] == "function" && n["label"] == "main"));
+
+        let body = serde_json::json!({
+            "file_path": "snippet.txt",
+            "content": "def main():\n    pass\n",
+            "language": "cobol",
+        });
+        let (status, _) = post_json("/api/graph/analyze-content", body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_file_imports() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/util.ts"), "export const x = 1;\n").unwrap();
+        std::fs::write(
+            dir.path().join("src/main.ts"),
+            "import { x } from './util';\nimport { y } from './missing';\n",
+        )
+        .unwrap();
+        let project_path = dir.path().to_string_lossy().to_string();
+
+        let body = serde_json::json!({ "project_path": project_path, "file_path": "src/main.ts" });
+        let (status, json) = post_json("/api/graph/imports", body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["resolved"][0]["path"], "./util");
+        assert_eq!(json["resolved"][0]["target"], "src/util.ts");
+        assert_eq!(json["unresolved"][0]["path"], "./missing");
+
+        let body = serde_json::json!({ "project_path": project_path, "file_path": "src/nope.ts" });
+        let (status, _) = post_json("/api/graph/imports", body).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
 }
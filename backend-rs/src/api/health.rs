@@ -1,19 +1,208 @@
 //! 健康检查端点
 
-use axum::{routing::get, Json, Router};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
 use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
 
-use crate::state::AppState;
+use crate::config::{get_config, AppConfig};
+use crate::llm::LlmClient;
+use crate::state::{AppState, ReadinessProbe};
 use std::sync::Arc;
 
+/// 模型连通性探测结果的缓存时长
+const READINESS_CACHE_TTL: Duration = Duration::from_secs(30);
+
 /// 健康检查处理器
-async fn health_check() -> Json<Value> {
+///
+/// 返回服务版本、运行时长（秒）和活跃的文档生成任务数。
+async fn health_check(State(state): State<Arc<AppState>>) -> Json<Value> {
     Json(json!({
-        "status": "ok"
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION"),
+        "uptime_secs": state.started_at.elapsed().as_secs(),
+        "active_tasks": state.active_task_count().await,
     }))
 }
 
+/// 就绪检查参数
+#[derive(Debug, Deserialize)]
+pub struct ReadyQuery {
+    /// 是否实际请求模型验证连通性（默认 false）
+    pub check_connection: Option<bool>,
+}
+
+/// 就绪检查处理器
+///
+/// 未配置 API 密钥或模型探测失败时返回 503。
+async fn readiness_check(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ReadyQuery>,
+) -> (StatusCode, Json<Value>) {
+    let config = get_config();
+    check_readiness(&state, &config, query.check_connection.unwrap_or(false)).await
+}
+
+/// 根据配置判断服务是否可以处理 LLM 请求
+async fn check_readiness(
+    state: &AppState,
+    config: &AppConfig,
+    check_connection: bool,
+) -> (StatusCode, Json<Value>) {
+    if config.api_key.is_empty() {
+        return not_ready("API Key is not configured");
+    }
+
+    if check_connection {
+        if let Err(reason) = probe_model(state, config).await {
+            return not_ready(&reason);
+        }
+    }
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "status": "ready",
+            "model": config.model,
+        })),
+    )
+}
+
+/// 探测结果对应的配置指纹；API Key 只保存哈希，不留存在 `AppState` 中
+fn config_key(config: &AppConfig) -> String {
+    let mut hasher = DefaultHasher::new();
+    config.api_key.hash(&mut hasher);
+    format!("{}|{}|{:x}", config.base_url, config.model, hasher.finish())
+}
+
+/// 探测模型连通性（结果缓存 `READINESS_CACHE_TTL`，配置变化时失效）
+async fn probe_model(state: &AppState, config: &AppConfig) -> Result<(), String> {
+    let config_key = config_key(config);
+
+    if let Some(probe) = state.readiness_cache.read().as_ref() {
+        if probe.config_key == config_key && probe.checked_at.elapsed() < READINESS_CACHE_TTL {
+            return probe.result.clone();
+        }
+    }
+
+    let result = match LlmClient::with_settings(&config.api_key, &config.base_url, true, config.http_client_settings()) {
+        Ok(client) => client
+            .with_browser_headers(&config.browser_header_settings())
+            .ping(&config.model)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+
+    *state.readiness_cache.write() = Some(ReadinessProbe {
+        checked_at: Instant::now(),
+        config_key,
+        result: result.clone(),
+    });
+
+    result
+}
+
+/// 构造未就绪响应
+fn not_ready(reason: &str) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(json!({
+            "status": "not_ready",
+            "reason": reason,
+        })),
+    )
+}
+
 /// 创建健康检查路由
 pub fn health_routes() -> Router<Arc<AppState>> {
-    Router::new().route("/api/health", get(health_check))
+    Router::new()
+        .route("/api/health", get(health_check))
+        .route("/api/ready", get(readiness_check))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::Service;
+
+    #[tokio::test]
+    async fn test_health_includes_version() {
+        let mut app = health_routes().with_state(Arc::new(AppState::new()));
+        let response = app
+            .call(Request::builder().uri("/api/health").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["status"], "ok");
+        assert!(!json["version"].as_str().unwrap().is_empty());
+        assert!(json["uptime_secs"].is_u64());
+        assert_eq!(json["active_tasks"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_ready_without_api_key() {
+        let state = AppState::new();
+        let config = AppConfig::default();
+
+        let (status, Json(body)) = check_readiness(&state, &config, false).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "not_ready");
+    }
+
+    #[tokio::test]
+    async fn test_ready_with_api_key() {
+        let state = AppState::new();
+        let config = AppConfig {
+            api_key: "sk-test".to_string(),
+            ..AppConfig::default()
+        };
+
+        let (status, Json(body)) = check_readiness(&state, &config, false).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ready");
+    }
+
+    #[tokio::test]
+    async fn test_ready_uses_cached_probe() {
+        let state = AppState::new();
+        let config = AppConfig {
+            api_key: "sk-test".to_string(),
+            base_url: "http://127.0.0.1:1".to_string(),
+            ..AppConfig::default()
+        };
+        let config_key = config_key(&config);
+        assert!(!config_key.contains(&config.api_key));
+
+        // 缓存的失败结果在有效期内直接返回，不会发起请求
+        *state.readiness_cache.write() = Some(ReadinessProbe {
+            checked_at: Instant::now(),
+            config_key: config_key.clone(),
+            result: Err("cached failure".to_string()),
+        });
+        let (status, Json(body)) = check_readiness(&state, &config, true).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["reason"], "cached failure");
+
+        *state.readiness_cache.write() = Some(ReadinessProbe {
+            checked_at: Instant::now(),
+            config_key,
+            result: Ok(()),
+        });
+        let (status, _) = check_readiness(&state, &config, true).await;
+        assert_eq!(status, StatusCode::OK);
+    }
 }
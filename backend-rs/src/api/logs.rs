@@ -0,0 +1,200 @@
+//! LLM 请求日志查询端点
+
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::state::AppState;
+use crate::utils::LogEntry;
+
+/// 默认返回条数
+const DEFAULT_LOG_LIMIT: usize = 50;
+
+/// 单次最多返回条数
+const MAX_LOG_LIMIT: usize = 500;
+
+/// 日志查询参数
+#[derive(Debug, Deserialize)]
+pub struct RequestLogsQuery {
+    /// 返回条数（默认 50，最大 500）
+    pub limit: Option<usize>,
+    /// 按状态过滤（pending / success / error）
+    pub status: Option<String>,
+}
+
+/// 日志查询响应
+#[derive(Serialize)]
+pub struct RequestLogsResponse {
+    /// 日志条目（最新的在前，只包含脱敏后的 API 密钥）
+    pub entries: Vec<LogEntry>,
+}
+
+/// 查询最近的 LLM 请求日志
+async fn get_request_logs(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RequestLogsQuery>,
+) -> Json<RequestLogsResponse> {
+    let limit = query.limit.unwrap_or(DEFAULT_LOG_LIMIT).min(MAX_LOG_LIMIT);
+    let status = query.status.as_deref().filter(|s| !s.is_empty());
+
+    let entries = state.request_logger.read_recent(limit, status);
+
+    Json(RequestLogsResponse { entries })
+}
+
+/// 创建日志路由
+pub fn logs_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/api/logs/requests", get(get_request_logs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::RequestLogger;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use std::time::Instant;
+    use tempfile::TempDir;
+    use tower::Service;
+
+    fn seed_logger(dir: &TempDir) -> RequestLogger {
+        let logger = RequestLogger::new(Some(dir.path().to_path_buf()), 1000);
+        let messages = vec![("user".to_string(), "hello".to_string())];
+
+        for i in 0..3 {
+            let entry = logger.log_request(
+                &format!("ok-{}", i), "openai", "/v1/chat/completions", "gpt-4o",
+                &messages, None, None, 120, "https://api.openai.com", "sk-test-secret-key",
+            );
+            logger.log_success(entry, Instant::now(), 5, 1, "hello");
+        }
+        for i in 0..2 {
+            let entry = logger.log_request(
+                &format!("err-{}", i), "openai", "/v1/chat/completions", "gpt-4o",
+                &messages, None, None, 120, "https://api.openai.com", "sk-test-secret-key",
+            );
+            logger.log_error(entry, Instant::now(), "ApiError", "unauthorized", Some(401));
+        }
+
+        logger
+    }
+
+    async fn query_logs(state: Arc<AppState>, uri: &str) -> serde_json::Value {
+        let mut app = logs_routes().with_state(state);
+        let response = app
+            .call(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_request_logs_filter_and_limit() {
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(AppState {
+            request_logger: Arc::new(seed_logger(&dir)),
+            ..AppState::new()
+        });
+
+        // 按状态过滤
+        let json = query_logs(state.clone(), "/api/logs/requests?status=error").await;
+        let entries = json["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|e| e["status"] == "error"));
+        // 最新的在前
+        assert_eq!(entries[0]["request_id"], "err-1");
+
+        // 限制条数
+        let json = query_logs(state.clone(), "/api/logs/requests?limit=2").await;
+        let entries = json["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+
+        // 不返回原始密钥
+        let json = query_logs(state, "/api/logs/requests").await;
+        let raw = json.to_string();
+        assert_eq!(json["entries"].as_array().unwrap().len(), 5);
+        assert!(!raw.contains("sk-test-secret-key"));
+        assert!(raw.contains("sk-t...-key"));
+    }
+
+    #[tokio::test]
+    async fn test_llm_calls_are_logged() {
+        use crate::llm::{ChatMessage, ChatOptions, CollectMode, LlmClient};
+
+        let dir = TempDir::new().unwrap();
+        let state = Arc::new(AppState {
+            request_logger: Arc::new(RequestLogger::new(Some(dir.path().to_path_buf()), 1000)),
+            ..AppState::new()
+        });
+        let mock = crate::test_utils::spawn_mock_llm(vec!["pong".to_string()]).await;
+
+        let client = LlmClient::new("sk-test-secret-key", &mock.base_url, false)
+            .unwrap()
+            .with_request_logger(state.request_logger.clone());
+        client
+            .stream_and_collect(vec![ChatMessage::user("ping")], "gpt-4o", ChatOptions::default(), CollectMode::ContentOnly)
+            .await
+            .unwrap();
+
+        // 无法连接的上游记录为失败
+        let offline = LlmClient::new("sk-test-secret-key", "http://127.0.0.1:9", false)
+            .unwrap()
+            .with_request_logger(state.request_logger.clone());
+        assert!(offline
+            .stream_and_collect(vec![ChatMessage::user("ping")], "gpt-4o", ChatOptions::default(), CollectMode::ContentOnly)
+            .await
+            .is_err());
+
+        let json = query_logs(state, "/api/logs/requests").await;
+        let entries = json["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0]["status"], "error");
+        assert_eq!(entries[0]["error_type"], "HttpError");
+
+        assert_eq!(entries[1]["status"], "success");
+        assert_eq!(entries[1]["model"], "gpt-4o");
+        assert_eq!(entries[1]["base_url"], mock.base_url.as_str());
+        assert_eq!(entries[1]["response_preview"], "pong");
+        assert_eq!(entries[1]["messages_preview"][0]["content_preview"], "ping");
+        assert!(!json.to_string().contains("sk-test-secret-key"));
+    }
+
+    #[tokio::test]
+    async fn test_request_id_survives_spawned_task() {
+        use crate::llm::{ChatMessage, ChatOptions, CollectMode, LlmClient};
+        use crate::utils::{current_request_id, scope_request_id};
+
+        let dir = TempDir::new().unwrap();
+        let logger = Arc::new(RequestLogger::new(Some(dir.path().to_path_buf()), 1000));
+        let mock = crate::test_utils::spawn_mock_llm(vec!["pong".to_string()]).await;
+
+        // 与处理器相同：在请求作用域内取出 ID 交给客户端，再在后台任务中调用
+        let client = scope_request_id("req-spawned".to_string(), async {
+            LlmClient::new("sk-test", &mock.base_url, false)
+                .unwrap()
+                .with_request_logger(logger.clone())
+                .with_request_id(current_request_id())
+        })
+        .await;
+        tokio::spawn(async move {
+            client
+                .stream_and_collect(vec![ChatMessage::user("ping")], "gpt-4o", ChatOptions::default(), CollectMode::ContentOnly)
+                .await
+                .unwrap();
+        })
+        .await
+        .unwrap();
+
+        let entries = logger.read_recent(10, None);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].request_id, "req-spawned");
+    }
+}
@@ -0,0 +1,254 @@
+//! 全局 HTTP 中间件
+
+use axum::{
+    extract::{DefaultBodyLimit, Request, State},
+    http::{Extensions, HeaderMap, HeaderValue, Method, StatusCode, Version},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use std::time::Duration;
+use tower_http::compression::predicate::{DefaultPredicate, Predicate};
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{Any, CorsLayer};
+use tracing::{warn, Instrument};
+
+use crate::error::AppError;
+use crate::utils::{scope_request_id, RequestLogger};
+
+/// 请求 ID 请求头/响应头
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// 客户端提供的请求 ID 最大长度，超出或含非法字符时改为服务端生成
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// 为路由添加请求体大小上限（超出返回 413）和处理超时（超时返回 408）
+pub fn apply_request_limits(router: Router, max_body_bytes: usize, timeout: Duration) -> Router {
+    router
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .layer(middleware::from_fn_with_state(timeout, request_timeout))
+}
+
+/// 按 `Accept-Encoding` 对响应做 gzip / deflate 压缩
+///
+/// 沿用 tower-http 的默认判定（跳过过小的响应、图片和 SSE 流），并排除 WebSocket 升级响应（101）。
+pub fn apply_compression(router: Router) -> Router {
+    router.layer(CompressionLayer::new().compress_when(DefaultPredicate::new().and(not_upgrade)))
+}
+
+/// 协议升级响应（如 `/ws/*`）没有可压缩的响应体，不能带 `Content-Encoding`
+fn not_upgrade(status: StatusCode, _: Version, _: &HeaderMap, _: &Extensions) -> bool {
+    status != StatusCode::SWITCHING_PROTOCOLS
+}
+
+/// 为每个请求分配请求 ID：沿用客户端的 `X-Request-Id` 或生成新 ID，
+/// 记录在 tracing span 和 LLM 请求日志中，并在响应头中回传
+pub fn apply_request_id(router: Router) -> Router {
+    router.layer(middleware::from_fn(request_id))
+}
+
+/// 添加 CORS 支持（允许所有来源，与 Python 版保持一致）
+///
+/// 预检请求由 `CorsLayer` 直接应答，不会进入路由，因此只有 POST 的接口同样可以预检；
+/// 应答状态统一为 204。
+pub fn apply_cors(router: Router) -> Router {
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any);
+
+    router
+        .layer(cors)
+        .layer(middleware::from_fn(preflight_no_content))
+}
+
+/// 将 `CorsLayer` 返回的 200 预检应答改为 204 No Content
+async fn preflight_no_content(request: Request, next: Next) -> Response {
+    let is_options = request.method() == Method::OPTIONS;
+    let mut response = next.run(request).await;
+    if is_options && response.status() == StatusCode::OK {
+        *response.status_mut() = StatusCode::NO_CONTENT;
+    }
+    response
+}
+
+/// 请求 ID 中间件
+async fn request_id(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.chars().all(|c| c.is_ascii_graphic()))
+        .map(str::to_string)
+        .unwrap_or_else(RequestLogger::generate_request_id);
+
+    let span = tracing::info_span!("request", request_id = %id, method = %request.method(), uri = %request.uri());
+    let mut response = scope_request_id(id.clone(), next.run(request).instrument(span)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// 请求超时中间件
+///
+/// 只限制处理器返回响应的时间；SSE / WebSocket 在返回响应头后不受影响
+async fn request_timeout(State(timeout): State<Duration>, request: Request, next: Next) -> Response {
+    let uri = request.uri().clone();
+    match tokio::time::timeout(timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            warn!("Request timed out after {:?}: {}", timeout, uri);
+            AppError::Timeout(format!("处理超过 {} 秒", timeout.as_secs())).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use axum::Json;
+    use tower::Service;
+
+    fn app(max_body_bytes: usize, timeout: Duration) -> Router {
+        let router = Router::new()
+            .route("/echo", post(|Json(body): Json<serde_json::Value>| async move { Json(body) }))
+            .route(
+                "/slow",
+                post(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "done"
+                }),
+            );
+        apply_request_limits(router, max_body_bytes, timeout)
+    }
+
+    fn post_json(uri: &str, body: String) -> axum::http::Request<Body> {
+        axum::http::Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_body_limit_rejects_large_payload() {
+        let mut app = app(64, Duration::from_secs(5));
+
+        let small = app.call(post_json("/echo", r#"{"a":1}"#.to_string())).await.unwrap();
+        assert_eq!(small.status(), StatusCode::OK);
+
+        let large = format!(r#"{{"content":"{}"}}"#, "x".repeat(1024));
+        let response = app.call(post_json("/echo", large)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflight_for_post_route() {
+        let mut app = apply_cors(app(1024, Duration::from_secs(5)));
+
+        let request = axum::http::Request::builder()
+            .method("OPTIONS")
+            .uri("/echo")
+            .header("origin", "http://localhost:5173")
+            .header("access-control-request-method", "POST")
+            .header("access-control-request-headers", "content-type")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let headers = response.headers();
+        assert_eq!(headers["access-control-allow-origin"], "*");
+        assert_eq!(headers["access-control-allow-methods"], "*");
+        assert_eq!(headers["access-control-allow-headers"], "*");
+
+        // 普通请求同样带 CORS 头
+        let response = app.call(post_json("/echo", r#"{"a":1}"#.to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["access-control-allow-origin"], "*");
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let mut app = app(1024, Duration::from_millis(50));
+
+        let response = app.call(post_json("/slow", String::new())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn test_request_id_echoed() {
+        let router = Router::new().route(
+            "/id",
+            post(|| async { RequestLogger::generate_request_id() }),
+        );
+        let mut app = apply_request_id(router);
+
+        let request = axum::http::Request::builder()
+            .method("POST")
+            .uri("/id")
+            .header(REQUEST_ID_HEADER, "client-abc-123")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.call(request).await.unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "client-abc-123");
+        // 处理器内生成的日志 ID 与请求 ID 一致
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"client-abc-123");
+
+        // 未提供时由服务端生成
+        let response = app.call(post_json("/id", String::new())).await.unwrap();
+        let generated = response.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        assert!(!generated.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_compression_skips_sse_and_upgrade_responses() {
+        use axum::routing::get;
+
+        let large = serde_json::json!({ "items": vec!["item"; 2000] });
+        let sse = "data: x\n\n".repeat(200);
+        let mut app = apply_compression(
+            Router::new()
+                .route("/large", get(move || async move { Json(large) }))
+                .route(
+                    "/events",
+                    get(move || async move { ([("content-type", "text/event-stream")], sse) }),
+                )
+                .route(
+                    "/ws",
+                    get(|| async { (StatusCode::SWITCHING_PROTOCOLS, "x".repeat(1000)) }),
+                ),
+        );
+        let get_gzip = |uri: &str| {
+            axum::http::Request::builder()
+                .uri(uri)
+                .header("accept-encoding", "gzip")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.call(get_gzip("/large")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(body.len() < 2000 * "\"item\",".len());
+
+        let response = app
+            .call(axum::http::Request::builder().uri("/large").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert!(response.headers().get("content-encoding").is_none());
+
+        for uri in ["/events", "/ws"] {
+            let response = app.call(get_gzip(uri)).await.unwrap();
+            assert!(response.headers().get("content-encoding").is_none(), "{}", uri);
+        }
+    }
+}
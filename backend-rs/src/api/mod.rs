@@ -5,25 +5,101 @@ mod config;
 mod docs;
 mod graph;
 mod health;
+mod logs;
+mod middleware;
 
 pub use chat::chat_routes;
 pub use config::config_routes;
 pub use docs::docs_routes;
 pub use graph::graph_routes;
 pub use health::health_routes;
+pub use logs::logs_routes;
+pub use middleware::apply_cors;
 
 use axum::Router;
 
+use crate::config::get_config;
 use crate::state::AppState;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// 创建所有 API 路由
 pub fn create_api_routes(state: Arc<AppState>) -> Router {
-    Router::new()
+    let config = get_config();
+    let router = Router::new()
         .merge(health_routes())
         .merge(config_routes())
         .merge(chat_routes())
         .merge(graph_routes())
         .merge(docs_routes())
-        .with_state(state)
+        .merge(logs_routes())
+        .with_state(state);
+
+    // 请求 ID 位于最外层，超时等中间件生成的错误响应同样带回请求 ID
+    middleware::apply_request_id(middleware::apply_compression(middleware::apply_request_limits(
+        router,
+        config.max_body_bytes,
+        Duration::from_secs(config.request_timeout_secs),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::Service;
+
+    #[tokio::test]
+    async fn test_api_responses_are_compressed() {
+        let mut app = create_api_routes(Arc::new(AppState::new()));
+        let response = app
+            .call(
+                Request::builder()
+                    .uri("/api/config")
+                    .header("accept-encoding", "gzip")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+        assert!(response.headers().contains_key(middleware::REQUEST_ID_HEADER));
+    }
+
+    #[tokio::test]
+    async fn test_request_id_recorded_in_llm_request_log() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let state = Arc::new(AppState {
+            request_logger: Arc::new(crate::utils::RequestLogger::new(Some(dir.path().to_path_buf()), 100)),
+            ..AppState::new()
+        });
+        let mock = crate::test_utils::spawn_mock_llm(vec!["pong".to_string()]).await;
+        let body = serde_json::json!({
+            "api_key": "sk-test",
+            "base_url": mock.base_url,
+            "model": "gpt-4o",
+        });
+
+        let mut app = create_api_routes(state);
+        let response = app
+            .call(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/config/test")
+                    .header("content-type", "application/json")
+                    .header(middleware::REQUEST_ID_HEADER, "client-req-42")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let log = std::fs::read_to_string(dir.path().join("llm_requests.jsonl")).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(log.lines().last().unwrap()).unwrap();
+        assert_eq!(entry["request_id"], "client-req-42");
+        assert_eq!(entry["status"], "success");
+    }
 }
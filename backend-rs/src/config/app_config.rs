@@ -5,10 +5,13 @@
 use once_cell::sync::Lazy;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use crate::error::AppError;
+use crate::llm::{BrowserHeaderSettings, HttpClientSettings};
 
 /// 获取配置文件路径
 fn get_config_path() -> PathBuf {
@@ -42,6 +45,126 @@ pub struct AppConfig {
     /// 最大 token 数
     #[serde(default = "default_max_tokens")]
     pub max_tokens: u32,
+
+    /// LLM 请求日志最多保留的条目数（至少为 1）
+    #[serde(default = "default_log_max_entries")]
+    pub log_max_entries: usize,
+
+    /// 同时运行的文档生成任务上限（至少为 1）
+    #[serde(default = "default_max_concurrent_tasks")]
+    pub max_concurrent_tasks: usize,
+
+    /// 已结束任务在内存中保留的时间（秒），超时后被清理
+    #[serde(default = "default_task_ttl_secs")]
+    pub task_ttl_secs: u64,
+
+    /// 文档统一输出根目录；设置后未指定 `docs_path` 的项目文档写入 `{docs_output_root}/{项目目录名}`
+    #[serde(default)]
+    pub docs_output_root: Option<String>,
+
+    /// 是否为每个文档生成任务在文档目录下写入 `_task.log` 生命周期日志
+    #[serde(default)]
+    pub doc_task_log: bool,
+
+    /// 生成的文件/目录文档是否以 YAML front matter 开头（供 Docusaurus/Hugo 等使用）
+    #[serde(default)]
+    pub doc_front_matter: bool,
+
+    /// 保存文档前是否规范化 Markdown 空白（行尾空格、多余空行、结尾换行），便于通过 markdownlint
+    #[serde(default)]
+    pub doc_normalize_markdown: bool,
+
+    /// 文件文档末尾是否附加组件位置表（依赖图谱提取得到的行号）
+    #[serde(default)]
+    pub doc_line_annotations: bool,
+
+    /// 自定义 front matter 模板（支持 `{title}`、`{source}`、`{date}`），为空时使用默认模板
+    #[serde(default)]
+    pub doc_front_matter_template: Option<String>,
+
+    /// 调试选项：为每个文件保留 API 提取中间结果（`*.api.md`），用于排查 API 清单中丢失的接口
+    #[serde(default)]
+    pub doc_keep_api_artifacts: bool,
+
+    /// 文档生成时是否同时提取知识图谱；只需要文档时关闭可节省 token
+    #[serde(default = "default_doc_extract_graph")]
+    pub doc_extract_graph: bool,
+
+    /// 项目图谱分析和文档生成最多处理的源文件数，超出时直接报错（防止误选主目录等巨大目录）
+    #[serde(default = "default_max_analyzed_files")]
+    pub max_analyzed_files: usize,
+
+    /// 文档进度 WebSocket 空闲时服务端发送 Ping 的间隔（秒）
+    #[serde(default = "default_ws_ping_interval_secs")]
+    pub ws_ping_interval_secs: u64,
+
+    /// 文档生成进度广播通道容量，WebSocket 客户端较慢时可适当调大
+    #[serde(default = "default_doc_progress_channel_capacity")]
+    pub doc_progress_channel_capacity: usize,
+
+    /// 是否将聊天会话历史保存到磁盘
+    #[serde(default = "default_persist_conversations")]
+    pub persist_conversations: bool,
+
+    /// 最多保存的聊天会话数（至少为 1），超出时淘汰最久未更新的会话
+    #[serde(default = "default_max_stored_conversations")]
+    pub max_stored_conversations: usize,
+
+    /// 每个会话最多保存的消息数（至少为 2），超出时丢弃最早的消息
+    #[serde(default = "default_max_conversation_messages")]
+    pub max_conversation_messages: usize,
+
+    /// LLM HTTP 客户端每个主机保留的最大空闲连接数
+    #[serde(default = "default_llm_pool_max_idle_per_host")]
+    pub llm_pool_max_idle_per_host: usize,
+
+    /// LLM HTTP 客户端连接超时（秒）
+    #[serde(default = "default_llm_connect_timeout_secs")]
+    pub llm_connect_timeout_secs: u64,
+
+    /// LLM HTTP 请求总超时（秒）
+    #[serde(default = "default_llm_timeout_secs")]
+    pub llm_timeout_secs: u64,
+
+    /// 同一 base_url 连续失败多少次后熔断（0 表示不熔断）
+    #[serde(default = "default_llm_circuit_failure_threshold")]
+    pub llm_circuit_failure_threshold: u32,
+
+    /// 熔断后的冷却时间（秒），期间请求直接失败
+    #[serde(default = "default_llm_circuit_cooldown_secs")]
+    pub llm_circuit_cooldown_secs: u64,
+
+    /// 浏览器模拟：自定义 User-Agent（为空时使用默认值）
+    #[serde(default)]
+    pub browser_user_agent: Option<String>,
+
+    /// 浏览器模拟：额外请求头，与默认请求头同名时覆盖默认值
+    #[serde(default)]
+    pub browser_extra_headers: BTreeMap<String, String>,
+
+    /// 浏览器模拟：禁用的默认请求头名称（如 `Accept-Language`）
+    #[serde(default)]
+    pub browser_disabled_headers: Vec<String>,
+
+    /// 请求体大小上限（字节），超出返回 413
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+
+    /// 单个 HTTP 请求的处理超时（秒），超时返回 408；流式响应只计算到响应头返回为止
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+
+    /// 是否使用 LLM 生成与上下文相关的建议问题（失败时回退到静态列表）
+    #[serde(default)]
+    pub llm_suggested_questions: bool,
+
+    /// 自定义聊天系统提示词（如团队审查规范、回答语言）；未设置时使用内置的代码审查提示词
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+}
+
+fn default_doc_extract_graph() -> bool {
+    true
 }
 
 fn default_base_url() -> String {
@@ -60,6 +183,70 @@ fn default_max_tokens() -> u32 {
     4096
 }
 
+fn default_log_max_entries() -> usize {
+    1000
+}
+
+fn default_max_analyzed_files() -> usize {
+    50_000
+}
+
+fn default_ws_ping_interval_secs() -> u64 {
+    30
+}
+
+fn default_doc_progress_channel_capacity() -> usize {
+    1024
+}
+
+fn default_max_concurrent_tasks() -> usize {
+    4
+}
+
+fn default_task_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_persist_conversations() -> bool {
+    true
+}
+
+fn default_max_stored_conversations() -> usize {
+    100
+}
+
+fn default_max_conversation_messages() -> usize {
+    200
+}
+
+fn default_llm_pool_max_idle_per_host() -> usize {
+    5
+}
+
+fn default_llm_connect_timeout_secs() -> u64 {
+    30
+}
+
+fn default_llm_timeout_secs() -> u64 {
+    120
+}
+
+fn default_llm_circuit_failure_threshold() -> u32 {
+    5
+}
+
+fn default_llm_circuit_cooldown_secs() -> u64 {
+    30
+}
+
+fn default_max_body_bytes() -> usize {
+    10 * 1024 * 1024
+}
+
+fn default_request_timeout_secs() -> u64 {
+    300
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
@@ -68,6 +255,61 @@ impl Default for AppConfig {
             model: default_model(),
             temperature: default_temperature(),
             max_tokens: default_max_tokens(),
+            log_max_entries: default_log_max_entries(),
+            max_concurrent_tasks: default_max_concurrent_tasks(),
+            task_ttl_secs: default_task_ttl_secs(),
+            docs_output_root: None,
+            doc_task_log: false,
+            doc_front_matter: false,
+            doc_normalize_markdown: false,
+            doc_line_annotations: false,
+            doc_front_matter_template: None,
+            doc_extract_graph: default_doc_extract_graph(),
+            doc_keep_api_artifacts: false,
+            max_analyzed_files: default_max_analyzed_files(),
+            ws_ping_interval_secs: default_ws_ping_interval_secs(),
+            doc_progress_channel_capacity: default_doc_progress_channel_capacity(),
+            persist_conversations: default_persist_conversations(),
+            max_stored_conversations: default_max_stored_conversations(),
+            max_conversation_messages: default_max_conversation_messages(),
+            llm_pool_max_idle_per_host: default_llm_pool_max_idle_per_host(),
+            llm_connect_timeout_secs: default_llm_connect_timeout_secs(),
+            llm_timeout_secs: default_llm_timeout_secs(),
+            llm_circuit_failure_threshold: default_llm_circuit_failure_threshold(),
+            llm_circuit_cooldown_secs: default_llm_circuit_cooldown_secs(),
+            browser_user_agent: None,
+            browser_extra_headers: BTreeMap::new(),
+            browser_disabled_headers: Vec::new(),
+            max_body_bytes: default_max_body_bytes(),
+            request_timeout_secs: default_request_timeout_secs(),
+            llm_suggested_questions: false,
+            system_prompt: None,
+        }
+    }
+}
+
+impl AppConfig {
+    /// LLM HTTP 客户端的连接池与超时设置
+    pub fn http_client_settings(&self) -> HttpClientSettings {
+        HttpClientSettings {
+            pool_max_idle_per_host: self.llm_pool_max_idle_per_host,
+            connect_timeout: Duration::from_secs(self.llm_connect_timeout_secs),
+            timeout: Duration::from_secs(self.llm_timeout_secs),
+            circuit_failure_threshold: self.llm_circuit_failure_threshold,
+            circuit_cooldown: Duration::from_secs(self.llm_circuit_cooldown_secs),
+        }
+    }
+
+    /// 浏览器模拟请求头设置
+    pub fn browser_header_settings(&self) -> BrowserHeaderSettings {
+        BrowserHeaderSettings {
+            user_agent: self.browser_user_agent.clone().filter(|ua| !ua.is_empty()),
+            extra_headers: self
+                .browser_extra_headers
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            disabled_headers: self.browser_disabled_headers.clone(),
         }
     }
 }
@@ -117,6 +359,7 @@ where
 }
 
 /// 替换整个配置
+#[allow(dead_code)] // 配置接口目前只做局部更新（update_config）
 pub fn set_config(new_config: AppConfig) -> Result<(), AppError> {
     save_config_to_file(&new_config)?;
     *CONFIG.write() = new_config;
@@ -124,6 +367,7 @@ pub fn set_config(new_config: AppConfig) -> Result<(), AppError> {
 }
 
 /// 重新从文件加载配置
+#[allow(dead_code)] // 尚无手动编辑配置文件后的重载入口
 pub fn reload_config() {
     if let Some(config) = load_config_from_file() {
         *CONFIG.write() = config;
@@ -141,5 +385,11 @@ mod tests {
         assert_eq!(config.model, "gpt-4o");
         assert!((config.temperature - 0.7).abs() < f64::EPSILON);
         assert_eq!(config.max_tokens, 4096);
+        assert_eq!(config.log_max_entries, 1000);
+        assert_eq!(config.max_concurrent_tasks, 4);
+        assert_eq!(config.task_ttl_secs, 3600);
+        assert_eq!(config.http_client_settings(), HttpClientSettings::default());
+        assert_eq!(config.max_body_bytes, 10 * 1024 * 1024);
+        assert_eq!(config.request_timeout_secs, 300);
     }
 }
@@ -3,13 +3,15 @@
 //! 定义应用级错误类型，并实现 axum 的 IntoResponse trait 以便自动转换为 HTTP 响应。
 
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde_json::json;
 use thiserror::Error;
 
+use crate::llm::LlmError;
+
 /// 应用错误枚举
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -21,6 +23,18 @@ pub enum AppError {
     #[error("LLM 错误: {0}")]
     Llm(String),
 
+    /// LLM 上游返回的 HTTP 错误（保留上游状态码）
+    #[error("LLM 上游错误 ({status}): {message}")]
+    LlmUpstream {
+        status: u16,
+        message: String,
+        retry_after: Option<u64>,
+    },
+
+    /// LLM 调用超时
+    #[error("LLM 超时: {0}")]
+    LlmTimeout(String),
+
     /// 代码分析错误
     #[error("分析错误: {0}")]
     Analyzer(String),
@@ -33,30 +47,218 @@ pub enum AppError {
     #[error("未找到: {0}")]
     NotFound(String),
 
+    /// 资源正被占用（如文档目录正在被生成任务写入）
+    #[error("冲突: {0}")]
+    Conflict(String),
+
+    /// 请求处理超时
+    #[error("请求超时: {0}")]
+    Timeout(String),
+
     /// 内部错误
     #[error("内部错误: {0}")]
     Internal(String),
 }
 
+impl AppError {
+    /// 对应的 HTTP 状态码
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Config(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Llm(_) => StatusCode::BAD_GATEWAY,
+            // 鉴权与限流原样透传，便于定位密钥配置问题；其余上游错误统一为 502
+            AppError::LlmUpstream { status, .. } => match *status {
+                401 => StatusCode::UNAUTHORIZED,
+                403 => StatusCode::FORBIDDEN,
+                429 => StatusCode::TOO_MANY_REQUESTS,
+                _ => StatusCode::BAD_GATEWAY,
+            },
+            AppError::LlmTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            AppError::Analyzer(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Timeout(_) => StatusCode::REQUEST_TIMEOUT,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// 稳定的机器可读错误码，供前端/IDE 客户端程序化处理
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Config(_) => "config",
+            AppError::Llm(_) => "llm_upstream",
+            AppError::LlmUpstream { status, .. } => match *status {
+                401 | 403 => "llm_auth",
+                429 => "llm_rate_limited",
+                _ => "llm_upstream",
+            },
+            AppError::LlmTimeout(_) => "llm_timeout",
+            AppError::Analyzer(_) => "analyzer",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::Timeout(_) => "timeout",
+            AppError::Internal(_) => "internal",
+        }
+    }
+
+    /// 面向用户的错误消息（不含变体前缀）
+    fn message(&self) -> &str {
+        match self {
+            AppError::Config(msg)
+            | AppError::Llm(msg)
+            | AppError::LlmUpstream { message: msg, .. }
+            | AppError::LlmTimeout(msg)
+            | AppError::Analyzer(msg)
+            | AppError::BadRequest(msg)
+            | AppError::NotFound(msg)
+            | AppError::Conflict(msg)
+            | AppError::Timeout(msg)
+            | AppError::Internal(msg) => msg,
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match &self {
-            AppError::Config(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
-            AppError::Llm(msg) => (StatusCode::BAD_GATEWAY, msg.clone()),
-            AppError::Analyzer(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
-            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
-            AppError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
-        };
-
-        let body = Json(json!({
+        let mut body = json!({
             "success": false,
-            "error": error_message
-        }));
+            "code": self.code(),
+            "error": self.message()
+        });
+
+        match &self {
+            AppError::LlmUpstream { status, retry_after, .. } => {
+                body["upstream_status"] = json!(status);
+                let mut response = (self.status_code(), Json(body)).into_response();
+                if let Some(secs) = retry_after {
+                    response
+                        .headers_mut()
+                        .insert(header::RETRY_AFTER, header::HeaderValue::from(*secs));
+                }
+                response
+            }
+            _ => (self.status_code(), Json(body)).into_response(),
+        }
+    }
+}
 
-        (status, body).into_response()
+impl From<LlmError> for AppError {
+    fn from(err: LlmError) -> Self {
+        match err {
+            LlmError::ApiError { status, message, retry_after } => AppError::LlmUpstream {
+                status,
+                message,
+                retry_after,
+            },
+            LlmError::Timeout => AppError::LlmTimeout(err.to_string()),
+            LlmError::HttpError(e) if e.is_timeout() => AppError::LlmTimeout(e.to_string()),
+            LlmError::ConfigError(msg) => AppError::Config(msg),
+            LlmError::CircuitOpen { retry_after, .. } => AppError::LlmUpstream {
+                status: 503,
+                message: err.to_string(),
+                retry_after: Some(retry_after),
+            },
+            other => AppError::Llm(other.to_string()),
+        }
     }
 }
 
 /// 便捷类型别名
 pub type AppResult<T> = Result<T, AppError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_error_codes_and_status() {
+        let upstream = |status: u16, retry_after: Option<u64>| AppError::LlmUpstream {
+            status,
+            message: "u".into(),
+            retry_after,
+        };
+        // (错误, 错误码, HTTP 状态, Retry-After 头)
+        let cases = [
+            (AppError::Config("c".into()), "config", StatusCode::INTERNAL_SERVER_ERROR, None),
+            (AppError::Llm("l".into()), "llm_upstream", StatusCode::BAD_GATEWAY, None),
+            (upstream(401, None), "llm_auth", StatusCode::UNAUTHORIZED, None),
+            (upstream(403, None), "llm_auth", StatusCode::FORBIDDEN, None),
+            (upstream(429, Some(7)), "llm_rate_limited", StatusCode::TOO_MANY_REQUESTS, Some("7")),
+            (upstream(503, Some(30)), "llm_upstream", StatusCode::BAD_GATEWAY, Some("30")),
+            (upstream(500, None), "llm_upstream", StatusCode::BAD_GATEWAY, None),
+            (AppError::LlmTimeout("lt".into()), "llm_timeout", StatusCode::GATEWAY_TIMEOUT, None),
+            (AppError::Analyzer("a".into()), "analyzer", StatusCode::INTERNAL_SERVER_ERROR, None),
+            (AppError::BadRequest("b".into()), "bad_request", StatusCode::BAD_REQUEST, None),
+            (AppError::NotFound("n".into()), "not_found", StatusCode::NOT_FOUND, None),
+            (AppError::Conflict("cf".into()), "conflict", StatusCode::CONFLICT, None),
+            (AppError::Timeout("t".into()), "timeout", StatusCode::REQUEST_TIMEOUT, None),
+            (AppError::Internal("i".into()), "internal", StatusCode::INTERNAL_SERVER_ERROR, None),
+        ];
+
+        for (error, code, status, retry_after) in cases {
+            let message = error.message().to_string();
+            let response = error.into_response();
+            assert_eq!(response.status(), status, "{}", code);
+            assert_eq!(
+                response.headers().get(header::RETRY_AFTER).map(|v| v.to_str().unwrap()),
+                retry_after,
+                "{}",
+                code
+            );
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json["success"], false);
+            assert_eq!(json["code"], code);
+            assert_eq!(json["error"], message);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_llm_error_conversion() {
+        let unauthorized = AppError::from(LlmError::ApiError {
+            status: 401,
+            message: "invalid api key".into(),
+            retry_after: None,
+        });
+        assert_eq!(unauthorized.status_code(), StatusCode::UNAUTHORIZED);
+        assert_eq!(unauthorized.code(), "llm_auth");
+
+        let rate_limited = AppError::from(LlmError::ApiError {
+            status: 429,
+            message: "slow down".into(),
+            retry_after: Some(12),
+        });
+        assert_eq!(rate_limited.status_code(), StatusCode::TOO_MANY_REQUESTS);
+        let response = rate_limited.into_response();
+        assert_eq!(response.headers()[header::RETRY_AFTER], "12");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["upstream_status"], 429);
+        assert_eq!(json["code"], "llm_rate_limited");
+
+        let server_error = AppError::from(LlmError::ApiError {
+            status: 500,
+            message: "boom".into(),
+            retry_after: None,
+        });
+        assert_eq!(server_error.status_code(), StatusCode::BAD_GATEWAY);
+
+        let timeout = AppError::from(LlmError::Timeout);
+        assert_eq!(timeout.status_code(), StatusCode::GATEWAY_TIMEOUT);
+
+        let config = AppError::from(LlmError::ConfigError("API Key is required".into()));
+        assert!(matches!(config, AppError::Config(_)));
+
+        let http = reqwest::Client::new().get("not a url").build().unwrap_err();
+        assert!(matches!(AppError::from(LlmError::HttpError(http)), AppError::Llm(_)));
+
+        let stream = AppError::from(LlmError::StreamError("broken".into()));
+        assert!(matches!(stream, AppError::Llm(_)));
+
+        let json_err = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        assert!(matches!(AppError::from(LlmError::JsonError(json_err)), AppError::Llm(_)));
+    }
+}
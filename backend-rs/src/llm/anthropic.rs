@@ -1,17 +1,16 @@
 //! Anthropic Messages API 流式实现
 
 use async_stream::try_stream;
-use futures::Stream;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::pin::Pin;
-use tracing::{debug, error};
+use tracing::{debug, error, info};
 
-use super::format::{build_anthropic_endpoint, get_browser_headers};
-use super::types::{ChatChunk, ChatMessage, ChatOptions, LlmError};
+use super::format::{build_anthropic_endpoint, apply_browser_headers, parse_retry_after};
+use super::sse::sse_data;
+use super::types::{ChatChunk, ChatMessage, ChatOptions, ChatStream, LlmError, TokenUsage};
 
 /// Anthropic 请求载荷
-#[derive(Serialize)]
+#[derive(Serialize, Debug)]
 struct AnthropicRequest {
     model: String,
     messages: Vec<AnthropicMessage>,
@@ -23,7 +22,7 @@ struct AnthropicRequest {
     temperature: Option<f64>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Debug)]
 struct AnthropicMessage {
     role: String,
     content: String,
@@ -36,6 +35,25 @@ struct AnthropicEvent {
     event_type: String,
     #[serde(default)]
     delta: Option<AnthropicDelta>,
+    /// `message_start` 事件携带的消息体（含输入 token 数）
+    #[serde(default)]
+    message: Option<AnthropicMessageStart>,
+    /// `message_delta` 事件携带的输出 token 数
+    #[serde(default)]
+    usage: Option<AnthropicUsage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AnthropicMessageStart {
+    #[serde(default)]
+    usage: Option<AnthropicUsage>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct AnthropicUsage {
+    input_tokens: u64,
+    output_tokens: u64,
 }
 
 #[derive(Deserialize, Debug)]
@@ -46,6 +64,72 @@ struct AnthropicDelta {
     stop_reason: Option<String>,
 }
 
+/// 调用方未指定 `max_tokens` 时的兜底值（Anthropic 要求必须提供该参数）
+const FALLBACK_MAX_TOKENS: u32 = 4096;
+
+/// JSON 模式下预填充的助手消息
+const JSON_PREFILL: &str = "{";
+
+/// JSON 模式下追加到系统提示词的说明
+const JSON_MODE_INSTRUCTION: &str =
+    "Respond with a single valid JSON object only. Do not include any text, explanation or markdown code fences outside the JSON.";
+
+/// 是否请求了 JSON 输出
+fn is_json_mode(options: &ChatOptions) -> bool {
+    options.response_format.as_deref() == Some("json_object")
+}
+
+/// 构建 Anthropic 请求载荷
+///
+/// Anthropic Messages API 没有 `response_format` 参数。当请求 `json_object` 时，
+/// 通过系统提示词要求只输出 JSON，并预填充一条内容为 `{` 的助手消息来模拟 JSON 模式。
+/// 这只是尽力而为：模型仍可能输出非法 JSON，调用方需要在收集后校验。
+fn build_anthropic_request(
+    messages: Vec<ChatMessage>,
+    model: &str,
+    options: &ChatOptions,
+) -> AnthropicRequest {
+    // 分离系统消息
+    let mut system_content: Option<String> = None;
+    let mut anthropic_messages: Vec<AnthropicMessage> = Vec::new();
+
+    for msg in messages {
+        if msg.role == "system" {
+            system_content = Some(msg.content);
+        } else {
+            anthropic_messages.push(AnthropicMessage {
+                role: msg.role,
+                content: msg.content,
+            });
+        }
+    }
+
+    if is_json_mode(options) {
+        system_content = Some(match system_content {
+            Some(system) => format!("{}\n\n{}", system, JSON_MODE_INSTRUCTION),
+            None => JSON_MODE_INSTRUCTION.to_string(),
+        });
+        anthropic_messages.push(AnthropicMessage {
+            role: "assistant".to_string(),
+            content: JSON_PREFILL.to_string(),
+        });
+    }
+
+    let max_tokens = options.max_tokens.unwrap_or_else(|| {
+        info!("Anthropic request without max_tokens, defaulting to {}", FALLBACK_MAX_TOKENS);
+        FALLBACK_MAX_TOKENS
+    });
+
+    AnthropicRequest {
+        model: model.to_string(),
+        messages: anthropic_messages,
+        system: system_content,
+        stream: true,
+        max_tokens,
+        temperature: options.temperature,
+    }
+}
+
 /// 流式调用 Anthropic API
 pub fn stream_anthropic(
     client: &Client,
@@ -54,54 +138,28 @@ pub fn stream_anthropic(
     messages: Vec<ChatMessage>,
     model: &str,
     options: &ChatOptions,
-    simulate_browser: bool,
-) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, LlmError>> + Send>> {
+    browser_headers: &[(String, String)],
+) -> ChatStream {
     let endpoint = build_anthropic_endpoint(base_url);
     let api_key = api_key.to_string();
     let model = model.to_string();
     let options = options.clone();
     let client = client.clone();
-    let simulate_browser = simulate_browser;
+    let browser_headers = browser_headers.to_vec();
 
     Box::pin(try_stream! {
-        // 分离系统消息
-        let mut system_content: Option<String> = None;
-        let mut anthropic_messages: Vec<AnthropicMessage> = Vec::new();
-
-        for msg in messages {
-            if msg.role == "system" {
-                system_content = Some(msg.content);
-            } else {
-                anthropic_messages.push(AnthropicMessage {
-                    role: msg.role,
-                    content: msg.content,
-                });
-            }
-        }
-
-        // 构建请求体
-        let payload = AnthropicRequest {
-            model: model.clone(),
-            messages: anthropic_messages,
-            system: system_content,
-            stream: true,
-            max_tokens: options.max_tokens.unwrap_or(4096),
-            temperature: options.temperature,
-        };
+        let json_mode = is_json_mode(&options);
+        let payload = build_anthropic_request(messages, &model, &options);
 
         // 构建请求头
-        let mut request = client
+        let request = client
             .post(&endpoint)
             .header("Content-Type", "application/json")
             .header("Authorization", format!("Bearer {}", api_key))
             .header("anthropic-version", "2023-06-01");
 
         // 添加浏览器模拟头
-        if simulate_browser {
-            for (key, value) in get_browser_headers() {
-                request = request.header(key, value);
-            }
-        }
+        let request = apply_browser_headers(request, &browser_headers);
 
         debug!("Anthropic API request: endpoint={}, model={}", endpoint, model);
 
@@ -115,18 +173,33 @@ pub fn stream_anthropic(
         let status = response.status();
         if !status.is_success() {
             let status_code = status.as_u16();
+            let retry_after = parse_retry_after(response.headers());
             let error_text = response.text().await.unwrap_or_default();
             error!("Anthropic API error: status={}, body={}", status_code, &error_text[..error_text.len().min(500)]);
             Err(LlmError::ApiError {
                 status: status_code,
                 message: error_text,
+                retry_after,
             })?;
             // 不会执行到这里
             unreachable!();
         }
 
+        // JSON 模式下响应从预填充的 `{` 之后开始，先补回这个字符。
+        // 放在上游成功响应之后产出：调用方（如熔断器）据此把它视为真实响应的一部分
+        if json_mode {
+            yield ChatChunk {
+                content: Some(JSON_PREFILL.to_string()),
+                finish_reason: None,
+                reasoning_content: None,
+                usage: None,
+            };
+        }
+
         // 处理 SSE 流
         let mut buffer = String::new();
+        // 输入 token 数只在 `message_start` 中给出，需要暂存到 `message_delta` 时合并
+        let mut input_tokens = 0u64;
         let mut stream = response.bytes_stream();
 
         use futures::StreamExt;
@@ -136,15 +209,11 @@ pub fn stream_anthropic(
 
             // 按行处理
             while let Some(newline_pos) = buffer.find('\n') {
-                let line = buffer[..newline_pos].trim().to_string();
+                let line = buffer[..newline_pos].to_string();
                 buffer = buffer[newline_pos + 1..].to_string();
 
-                if line.is_empty() {
-                    continue;
-                }
-
-                // 解析 SSE 数据
-                if let Some(data) = line.strip_prefix("data: ") {
+                // 解析 SSE 数据（兼容缺少 `data:` 前缀的网关）
+                if let Some(data) = sse_data(&line) {
                     if data == "[DONE]" {
                         return;
                     }
@@ -160,20 +229,31 @@ pub fn stream_anthropic(
                                                     content: Some(text.clone()),
                                                     finish_reason: None,
                                                     reasoning_content: None,
+                                                    usage: None,
                                                 };
                                             }
                                         }
                                     }
                                 }
+                                "message_start" => {
+                                    if let Some(usage) = event.message.and_then(|m| m.usage) {
+                                        input_tokens = usage.input_tokens;
+                                    }
+                                }
                                 "message_delta" => {
-                                    if let Some(delta) = &event.delta {
-                                        if let Some(stop_reason) = &delta.stop_reason {
-                                            yield ChatChunk {
-                                                content: None,
-                                                finish_reason: Some(stop_reason.clone()),
-                                                reasoning_content: None,
-                                            };
-                                        }
+                                    let usage = event.usage.map(|u| TokenUsage {
+                                        prompt_tokens: input_tokens,
+                                        completion_tokens: u.output_tokens,
+                                        total_tokens: input_tokens + u.output_tokens,
+                                    });
+                                    let stop_reason = event.delta.and_then(|d| d.stop_reason);
+                                    if stop_reason.is_some() || usage.is_some() {
+                                        yield ChatChunk {
+                                            content: None,
+                                            finish_reason: stop_reason,
+                                            reasoning_content: None,
+                                            usage,
+                                        };
                                     }
                                 }
                                 "message_stop" => {
@@ -181,6 +261,7 @@ pub fn stream_anthropic(
                                         content: None,
                                         finish_reason: Some("stop".to_string()),
                                         reasoning_content: None,
+                                        usage: None,
                                     };
                                 }
                                 _ => {
@@ -198,3 +279,53 @@ pub fn stream_anthropic(
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_mode_prefills_assistant_turn() {
+        let messages = vec![ChatMessage::system("You are helpful."), ChatMessage::user("List items")];
+        let options = ChatOptions {
+            response_format: Some("json_object".to_string()),
+            ..Default::default()
+        };
+
+        let request = build_anthropic_request(messages, "claude-3-5-sonnet", &options);
+
+        let last = request.messages.last().unwrap();
+        assert_eq!(last.role, "assistant");
+        assert_eq!(last.content, "{");
+        assert_eq!(request.messages.len(), 2);
+
+        let system = request.system.unwrap();
+        assert!(system.starts_with("You are helpful."));
+        assert!(system.contains(JSON_MODE_INSTRUCTION));
+    }
+
+    #[test]
+    fn test_no_prefill_without_json_mode() {
+        let messages = vec![ChatMessage::user("Hi")];
+        let request = build_anthropic_request(messages, "claude-3-5-sonnet", &ChatOptions::default());
+
+        assert_eq!(request.messages.len(), 1);
+        assert_eq!(request.messages[0].role, "user");
+        assert!(request.system.is_none());
+    }
+
+    #[test]
+    fn test_max_tokens_from_options() {
+        // 文档生成器的最终文档阶段使用 16384，不应被截断为 4096
+        let config = crate::services::doc_generator::types::DocGenConfig::default();
+        let options = ChatOptions {
+            max_tokens: Some(config.synthesis_max_tokens),
+            ..Default::default()
+        };
+        let request = build_anthropic_request(vec![ChatMessage::user("Hi")], "claude-3-5-sonnet", &options);
+        assert_eq!(request.max_tokens, 16384);
+
+        let request = build_anthropic_request(vec![ChatMessage::user("Hi")], "claude-3-5-sonnet", &ChatOptions::default());
+        assert_eq!(request.max_tokens, FALLBACK_MAX_TOKENS);
+    }
+}
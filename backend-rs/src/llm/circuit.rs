@@ -0,0 +1,171 @@
+//! 按 base_url 区分的熔断器
+//!
+//! 同一上游连续失败达到阈值后熔断，冷却期内新请求直接失败；冷却结束后放行一个探测请求，
+//! 探测成功则恢复，失败则重新熔断。熔断状态在所有使用同一 base_url 的客户端之间共享。
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::types::LlmError;
+
+/// 全局熔断器注册表（base_url -> 熔断器）
+static BREAKERS: Lazy<DashMap<String, Arc<CircuitBreaker>>> = Lazy::new(DashMap::new);
+
+/// 获取指定 base_url 的熔断器
+///
+/// 首次创建时使用传入的阈值和冷却时间；之后沿用已有实例，但会更新为最新设置。
+pub fn breaker_for(base_url: &str, failure_threshold: u32, cooldown: Duration) -> Arc<CircuitBreaker> {
+    let breaker = BREAKERS
+        .entry(base_url.trim_end_matches('/').to_string())
+        .or_insert_with(|| Arc::new(CircuitBreaker::new(failure_threshold, cooldown)))
+        .clone();
+    breaker.configure(failure_threshold, cooldown);
+    breaker
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    failure_threshold: u32,
+    cooldown: Duration,
+    /// 连续失败次数
+    consecutive_failures: u32,
+    /// 熔断截止时间；为 None 表示闭合
+    open_until: Option<Instant>,
+    /// 冷却结束后正在探测
+    half_open: bool,
+}
+
+/// 熔断器
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    /// 创建熔断器，`failure_threshold` 为 0 时不启用
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            state: Mutex::new(BreakerState {
+                failure_threshold,
+                cooldown,
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn configure(&self, failure_threshold: u32, cooldown: Duration) {
+        let mut state = self.state.lock();
+        state.failure_threshold = failure_threshold;
+        state.cooldown = cooldown;
+    }
+
+    /// 请求前检查，熔断中返回剩余冷却时间
+    ///
+    /// 冷却结束后的第一个请求作为探测放行，同时重新计时，其余请求在探测期间继续被拒绝。
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let mut state = self.state.lock();
+        let Some(until) = state.open_until else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+        if now < until {
+            return Err(until - now);
+        }
+        state.open_until = Some(now + state.cooldown);
+        state.half_open = true;
+        Ok(())
+    }
+
+    /// 记录请求结果
+    ///
+    /// 只有连接失败、超时和 5xx 视为上游故障；其他错误说明上游可达，按成功处理。
+    pub fn record<T>(&self, result: &Result<T, LlmError>) {
+        match result {
+            Err(e) if is_outage(e) => self.record_failure(),
+            _ => self.record_success(),
+        }
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock();
+        state.consecutive_failures = 0;
+        state.open_until = None;
+        state.half_open = false;
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock();
+        if state.failure_threshold == 0 {
+            return;
+        }
+        state.consecutive_failures += 1;
+        if state.half_open || state.consecutive_failures >= state.failure_threshold {
+            state.open_until = Some(Instant::now() + state.cooldown);
+            state.half_open = false;
+        }
+    }
+}
+
+/// 错误是否表示上游不可用
+fn is_outage(err: &LlmError) -> bool {
+    match err {
+        LlmError::HttpError(_) | LlmError::Timeout => true,
+        LlmError::ApiError { status, .. } => *status >= 500,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_error() -> Result<(), LlmError> {
+        Err(LlmError::ApiError {
+            status: 503,
+            message: "down".to_string(),
+            retry_after: None,
+        })
+    }
+
+    #[test]
+    fn test_opens_after_threshold_and_probes_after_cooldown() {
+        let breaker = CircuitBreaker::new(3, Duration::from_millis(50));
+
+        for _ in 0..3 {
+            assert!(breaker.try_acquire().is_ok());
+            breaker.record(&server_error());
+        }
+        assert!(breaker.try_acquire().is_err());
+
+        // 冷却结束：放行一个探测请求，其余请求仍被拒绝
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.try_acquire().is_ok());
+        assert!(breaker.try_acquire().is_err());
+
+        // 探测失败立即重新熔断
+        breaker.record(&server_error());
+        assert!(breaker.try_acquire().is_err());
+
+        // 再次探测成功后恢复
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.try_acquire().is_ok());
+        breaker.record(&Ok::<(), LlmError>(()));
+        assert!(breaker.try_acquire().is_ok());
+        assert!(breaker.try_acquire().is_ok());
+    }
+
+    #[test]
+    fn test_client_errors_do_not_trip() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        breaker.record(&Err::<(), _>(LlmError::ApiError {
+            status: 400,
+            message: "bad request".to_string(),
+            retry_after: None,
+        }));
+        assert!(breaker.try_acquire().is_ok());
+    }
+}
@@ -1,17 +1,44 @@
 //! 统一 LLM 客户端
 
-use futures::{Stream, StreamExt};
+use futures::{stream, StreamExt};
 use reqwest::Client;
-use std::pin::Pin;
-use std::time::Duration;
-use tracing::info;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, warn};
 
 use super::anthropic::stream_anthropic;
-use super::format::{detect_api_format, ApiFormat};
+use super::circuit::{breaker_for, CircuitBreaker};
+use super::format::{
+    apply_browser_headers, build_anthropic_endpoint, build_models_endpoint, build_openai_endpoint,
+    resolve_browser_headers,
+};
+use super::{detect_api_format, ApiFormat};
 use super::openai::stream_openai;
 use super::types::{
-    ChatChunk, ChatMessage, ChatOptions, CollectMode, LlmError, StreamCollectResult,
+    BrowserHeaderSettings, ChatMessage, ChatOptions, ChatStream, CollectMode, HttpClientSettings, LlmError,
+    PingTiming, StreamCollectResult,
 };
+use crate::utils::{LogEntry, RequestLogger};
+
+/// Anthropic 没有公开的模型列表接口，使用内置列表
+const ANTHROPIC_MODELS: &[&str] = &[
+    "claude-opus-4-1",
+    "claude-opus-4-0",
+    "claude-sonnet-4-0",
+    "claude-3-7-sonnet-latest",
+    "claude-3-5-haiku-latest",
+];
+
+/// OpenAI 兼容 `/v1/models` 的响应
+#[derive(serde::Deserialize)]
+struct ModelList {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(serde::Deserialize)]
+struct ModelEntry {
+    id: String,
+}
 
 /// 统一 LLM 客户端
 ///
@@ -21,11 +48,34 @@ pub struct LlmClient {
     api_key: String,
     base_url: String,
     simulate_browser: bool,
+    /// 实际发送的浏览器模拟头（未启用模拟时为空）
+    browser_headers: Vec<(String, String)>,
+    /// 调用方未指定 `max_tokens` 时使用的默认值（Anthropic 要求必须提供）
+    default_max_tokens: Option<u32>,
+    /// 按 base_url 共享的熔断器
+    breaker: Arc<CircuitBreaker>,
+    /// 请求日志记录器（未设置时不记录）
+    request_logger: Option<Arc<RequestLogger>>,
+    /// 单次请求的总超时（秒），记录到请求日志
+    timeout_secs: u64,
+    /// 发起调用的 HTTP 请求 ID，记录到请求日志（未设置时沿用当前请求作用域的 ID 或随机生成）
+    request_id: Option<String>,
 }
 
 impl LlmClient {
-    /// 创建新的 LLM 客户端
+    /// 创建新的 LLM 客户端（使用默认连接池设置）
+    #[cfg_attr(not(test), allow(dead_code))] // 服务端按配置调用 with_settings，目前只有测试使用默认设置
     pub fn new(api_key: impl Into<String>, base_url: impl Into<String>, simulate_browser: bool) -> Result<Self, LlmError> {
+        Self::with_settings(api_key, base_url, simulate_browser, HttpClientSettings::default())
+    }
+
+    /// 使用自定义连接池与超时设置创建 LLM 客户端
+    pub fn with_settings(
+        api_key: impl Into<String>,
+        base_url: impl Into<String>,
+        simulate_browser: bool,
+        settings: HttpClientSettings,
+    ) -> Result<Self, LlmError> {
         let api_key = api_key.into();
         if api_key.is_empty() {
             return Err(LlmError::ConfigError("API Key is required".to_string()));
@@ -33,31 +83,98 @@ impl LlmClient {
 
         // 构建 HTTP 客户端
         let client = Client::builder()
-            .timeout(Duration::from_secs(120))
-            .connect_timeout(Duration::from_secs(30))
-            .pool_max_idle_per_host(5)
+            .timeout(settings.timeout)
+            .connect_timeout(settings.connect_timeout)
+            .pool_max_idle_per_host(settings.pool_max_idle_per_host)
             .build()
             .map_err(LlmError::HttpError)?;
 
+        let base_url = base_url.into();
+        let breaker = breaker_for(&base_url, settings.circuit_failure_threshold, settings.circuit_cooldown);
+
         Ok(Self {
             client,
             api_key,
-            base_url: base_url.into(),
+            base_url,
             simulate_browser,
+            browser_headers: if simulate_browser {
+                resolve_browser_headers(&BrowserHeaderSettings::default())
+            } else {
+                Vec::new()
+            },
+            default_max_tokens: None,
+            breaker,
+            request_logger: None,
+            timeout_secs: settings.timeout.as_secs(),
+            request_id: None,
         })
     }
 
+    /// 将每次请求的结果写入请求日志（`llm_requests.jsonl`）
+    pub fn with_request_logger(mut self, logger: Arc<RequestLogger>) -> Self {
+        self.request_logger = Some(logger);
+        self
+    }
+
+    /// 指定写入请求日志的请求 ID
+    ///
+    /// 客户端在后台任务或 WebSocket 连接中使用时已离开 HTTP 请求作用域，需由处理器显式传入
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    /// API 基础 URL
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// 自定义浏览器模拟头（仅在启用 `simulate_browser` 时生效）
+    pub fn with_browser_headers(mut self, settings: &BrowserHeaderSettings) -> Self {
+        if self.simulate_browser {
+            self.browser_headers = resolve_browser_headers(settings);
+        }
+        self
+    }
+
+    /// 设置默认 `max_tokens`（通常为配置中的 `max_tokens`）
+    pub fn with_default_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.default_max_tokens = Some(max_tokens);
+        self
+    }
+
     /// 流式聊天（自动检测 API 格式）
+    ///
+    /// 上游已熔断时直接返回 `CircuitOpen` 错误，不发送请求。
     pub fn stream_chat(
         &self,
         messages: Vec<ChatMessage>,
         model: &str,
         options: ChatOptions,
-    ) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, LlmError>> + Send>> {
+    ) -> ChatStream {
+        if let Err(remaining) = self.breaker.try_acquire() {
+            warn!("Circuit open for {}, rejecting request", self.base_url);
+            let err = LlmError::CircuitOpen {
+                base_url: self.base_url.clone(),
+                retry_after: remaining.as_secs().max(1),
+            };
+            return Box::pin(stream::once(async move { Err(err) }));
+        }
+
         let api_format = detect_api_format(model);
         info!("LLM request: model={}, api_format={:?}", model, api_format);
 
-        match api_format {
+        let options = match api_format {
+            ApiFormat::OpenAi => options,
+            ApiFormat::Anthropic => self.with_default_options(options),
+        };
+        let log_entry = self
+            .request_logger
+            .as_ref()
+            .map(|logger| (logger.clone(), self.log_entry(logger, api_format, &messages, model, &options)));
+
+        let breaker = self.breaker.clone();
+        let stream = match api_format {
             ApiFormat::OpenAi => stream_openai(
                 &self.client,
                 &self.api_key,
@@ -65,7 +182,7 @@ impl LlmClient {
                 messages,
                 model,
                 &options,
-                self.simulate_browser,
+                &self.browser_headers,
             ),
             ApiFormat::Anthropic => stream_anthropic(
                 &self.client,
@@ -74,11 +191,121 @@ impl LlmClient {
                 messages,
                 model,
                 &options,
-                self.simulate_browser,
+                &self.browser_headers,
             ),
+        };
+        let stream: ChatStream = Box::pin(stream.inspect(move |result| breaker.record(result)));
+        match log_entry {
+            Some((logger, entry)) => log_stream(stream, logger, entry),
+            None => stream,
         }
     }
 
+    /// 创建一条待完成的请求日志
+    fn log_entry(
+        &self,
+        logger: &RequestLogger,
+        api_format: ApiFormat,
+        messages: &[ChatMessage],
+        model: &str,
+        options: &ChatOptions,
+    ) -> LogEntry {
+        let (format_name, endpoint) = match api_format {
+            ApiFormat::OpenAi => ("openai", build_openai_endpoint(&self.base_url)),
+            ApiFormat::Anthropic => ("anthropic", build_anthropic_endpoint(&self.base_url)),
+        };
+        let messages: Vec<(String, String)> =
+            messages.iter().map(|m| (m.role.clone(), m.content.clone())).collect();
+        let request_id = self.request_id.clone().unwrap_or_else(RequestLogger::generate_request_id);
+        logger.log_request(
+            &request_id,
+            format_name,
+            &endpoint,
+            model,
+            &messages,
+            options.temperature,
+            options.max_tokens,
+            self.timeout_secs,
+            &self.base_url,
+            &self.api_key,
+        )
+    }
+
+    /// 未指定 `max_tokens` 时填入客户端的默认值
+    fn with_default_options(&self, mut options: ChatOptions) -> ChatOptions {
+        if options.max_tokens.is_none() {
+            options.max_tokens = self.default_max_tokens;
+        }
+        options
+    }
+
+    /// 发送一条极短的测试消息，确认模型可用
+    ///
+    /// 收到第一段有效内容即视为成功，之后读完剩余响应以测量总耗时（后续错误不影响结果）。
+    pub async fn ping(&self, model: &str) -> Result<PingTiming, LlmError> {
+        let messages = vec![ChatMessage::user("Hi")];
+        let options = ChatOptions {
+            max_tokens: Some(10),
+            ..Default::default()
+        };
+
+        let started = Instant::now();
+        let mut first_token = None;
+        let mut stream = self.stream_chat(messages, model, options);
+        while let Some(result) = stream.next().await {
+            match result {
+                Ok(chunk) if first_token.is_none() && chunk.content.is_some() => {
+                    first_token = Some(started.elapsed());
+                }
+                Ok(_) => {}
+                Err(e) if first_token.is_none() => return Err(e),
+                Err(e) => {
+                    warn!("Ping stream ended with error after first token: {}", e);
+                    break;
+                }
+            }
+        }
+
+        match first_token {
+            Some(first_token) => Ok(PingTiming {
+                first_token,
+                total: started.elapsed(),
+            }),
+            None => Err(LlmError::StreamError("No response from API".to_string())),
+        }
+    }
+
+    /// 列出可用的模型 ID
+    ///
+    /// `model` 为当前配置的模型，用于判断提供方：Anthropic 格式返回内置列表（`from_api` 为 `false`），
+    /// 其余调用 OpenAI 兼容的 `/v1/models` 接口，结果按名称排序。
+    pub async fn list_models(&self, model: &str) -> Result<(Vec<String>, bool), LlmError> {
+        if detect_api_format(model) == ApiFormat::Anthropic {
+            return Ok((ANTHROPIC_MODELS.iter().map(|m| m.to_string()).collect(), false));
+        }
+
+        let request = self
+            .client
+            .get(build_models_endpoint(&self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key));
+        let response = apply_browser_headers(request, &self.browser_headers).send().await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(LlmError::ApiError {
+                status: status.as_u16(),
+                message: response.text().await.unwrap_or_default(),
+                retry_after: None,
+            });
+        }
+
+        let list: ModelList = serde_json::from_str(&response.text().await?)?;
+        let mut models: Vec<String> = list.data.into_iter().map(|m| m.id).collect();
+        models.sort();
+        models.dedup();
+        Ok((models, true))
+    }
+
     /// 流式请求并收集完整响应
     pub async fn stream_and_collect(
         &self,
@@ -87,6 +314,27 @@ impl LlmClient {
         options: ChatOptions,
         collect_mode: CollectMode,
     ) -> Result<StreamCollectResult, LlmError> {
+        self.stream_and_collect_with(messages, model, options, collect_mode, |_| {}, |_| {})
+            .await
+    }
+
+    /// 流式请求并收集完整响应，同时按增量回调内容和推理
+    ///
+    /// 只有当前收集模式包含的子流才会触发对应回调；返回值与 `stream_and_collect` 相同。
+    pub async fn stream_and_collect_with<C, R>(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: &str,
+        options: ChatOptions,
+        collect_mode: CollectMode,
+        mut on_content: C,
+        mut on_reasoning: R,
+    ) -> Result<StreamCollectResult, LlmError>
+    where
+        C: FnMut(&str),
+        R: FnMut(&str),
+    {
+        let json_mode = options.response_format.as_deref() == Some("json_object");
         let mut stream = self.stream_chat(messages, model, options);
         let mut result = StreamCollectResult::default();
 
@@ -98,6 +346,7 @@ impl LlmClient {
             match collect_mode {
                 CollectMode::ContentOnly | CollectMode::WithReasoning => {
                     if let Some(content) = chunk.content {
+                        on_content(&content);
                         result.content.push_str(&content);
                     }
                 }
@@ -107,6 +356,7 @@ impl LlmClient {
             match collect_mode {
                 CollectMode::WithReasoning | CollectMode::ReasoningOnly => {
                     if let Some(reasoning) = chunk.reasoning_content {
+                        on_reasoning(&reasoning);
                         result.reasoning.push_str(&reasoning);
                     }
                 }
@@ -116,8 +366,275 @@ impl LlmClient {
             if chunk.finish_reason.is_some() {
                 result.finish_reason = chunk.finish_reason;
             }
+            if chunk.usage.is_some() {
+                result.usage = chunk.usage;
+            }
+        }
+
+        if let Some(reason) = result.finish_reason.as_deref().filter(|r| is_blocked_finish_reason(r)) {
+            return Err(LlmError::ContentFiltered(reason.to_string()));
+        }
+
+        // JSON 模式：去掉可能的代码块包裹并校验
+        if json_mode && collect_mode != CollectMode::ReasoningOnly {
+            result.content = normalize_json_content(&result.content)?;
         }
 
         Ok(result)
     }
 }
+
+/// 包装响应流：流结束时记录成功，首个错误时记录失败
+///
+/// 调用方提前丢弃流（如客户端断开）时不写日志。
+fn log_stream(mut stream: ChatStream, logger: Arc<RequestLogger>, entry: LogEntry) -> ChatStream {
+    Box::pin(async_stream::stream! {
+        let started = Instant::now();
+        let mut entry = Some(entry);
+        let mut response = String::new();
+        let mut chunk_count = 0;
+
+        while let Some(result) = stream.next().await {
+            match &result {
+                Ok(chunk) => {
+                    chunk_count += 1;
+                    if let Some(content) = &chunk.content {
+                        response.push_str(content);
+                    }
+                }
+                Err(e) => {
+                    if let Some(entry) = entry.take() {
+                        let status_code = match e {
+                            LlmError::ApiError { status, .. } => Some(*status),
+                            _ => None,
+                        };
+                        logger.log_error(entry, started, e.kind(), &e.to_string(), status_code);
+                    }
+                }
+            }
+            yield result;
+        }
+
+        if let Some(entry) = entry {
+            logger.log_success(entry, started, response.len(), chunk_count, &response);
+        }
+    })
+}
+
+/// 表示输出被拦截或异常中止的结束原因（OpenAI / Anthropic / Gemini 兼容网关）
+///
+/// `length`/`max_tokens` 只是截断，内容仍然可用，不在此列。
+fn is_blocked_finish_reason(reason: &str) -> bool {
+    matches!(
+        reason.to_ascii_lowercase().as_str(),
+        "content_filter" | "refusal" | "safety" | "recitation" | "prohibited_content" | "blocklist" | "error"
+    )
+}
+
+/// 规范化 JSON 模式下的响应内容
+///
+/// 去掉 markdown 代码块包裹，并校验内容是合法的 JSON。
+fn normalize_json_content(content: &str) -> Result<String, LlmError> {
+    let trimmed = content.trim();
+    let unfenced = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.trim_end().strip_suffix("```"))
+        .map(str::trim)
+        .unwrap_or(trimmed);
+
+    serde_json::from_str::<serde_json::Value>(unfenced)?;
+    Ok(unfenced.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::TokenUsage;
+    use crate::test_utils::{spawn_mock_llm, spawn_mock_sse, sse_body, sse_delta};
+    use std::time::Duration;
+
+    #[test]
+    fn test_client_with_custom_pool_settings() {
+        let settings = HttpClientSettings {
+            pool_max_idle_per_host: 32,
+            connect_timeout: Duration::from_secs(5),
+            timeout: Duration::from_secs(600),
+            ..HttpClientSettings::default()
+        };
+        let client = LlmClient::with_settings("sk-test", "https://api.openai.com", false, settings);
+        assert!(client.is_ok());
+
+        let missing_key = LlmClient::with_settings("", "https://api.openai.com", false, settings);
+        assert!(matches!(missing_key, Err(LlmError::ConfigError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_circuit_opens_after_consecutive_failures() {
+        let settings = HttpClientSettings {
+            circuit_failure_threshold: 2,
+            circuit_cooldown: Duration::from_secs(60),
+            ..HttpClientSettings::default()
+        };
+        // 无服务监听的端口：连接失败；路径保证熔断器不与其他测试共享
+        let client = LlmClient::with_settings("sk-test", "http://127.0.0.1:9/circuit-test", false, settings).unwrap();
+
+        for _ in 0..2 {
+            assert!(matches!(client.ping("gpt-4o").await, Err(LlmError::HttpError(_))));
+        }
+        let started = std::time::Instant::now();
+        match client.ping("gpt-4o").await {
+            Err(LlmError::CircuitOpen { retry_after, .. }) => assert!(retry_after > 0),
+            other => panic!("expected CircuitOpen, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_json_prefill_does_not_reset_circuit() {
+        let settings = HttpClientSettings {
+            circuit_failure_threshold: 2,
+            circuit_cooldown: Duration::from_secs(60),
+            ..HttpClientSettings::default()
+        };
+        let client =
+            LlmClient::with_settings("sk-test", "http://127.0.0.1:9/circuit-prefill-test", false, settings).unwrap();
+        let options = || ChatOptions {
+            response_format: Some("json_object".to_string()),
+            ..ChatOptions::default()
+        };
+
+        // 连接失败时不应产出预填充的 `{`，否则会被当作成功重置失败计数
+        for _ in 0..2 {
+            let results: Vec<_> = client
+                .stream_chat(vec![ChatMessage::user("hi")], "claude-3-5-sonnet", options())
+                .collect()
+                .await;
+            assert!(matches!(results.as_slice(), [Err(LlmError::HttpError(_))]));
+        }
+        let results: Vec<_> = client
+            .stream_chat(vec![ChatMessage::user("hi")], "claude-3-5-sonnet", options())
+            .collect()
+            .await;
+        assert!(matches!(results.as_slice(), [Err(LlmError::CircuitOpen { .. })]));
+    }
+
+    #[test]
+    fn test_default_max_tokens_only_when_unset() {
+        let client = LlmClient::new("sk-test", "https://api.anthropic.com", false)
+            .unwrap()
+            .with_default_max_tokens(16384);
+
+        let options = client.with_default_options(ChatOptions::default());
+        assert_eq!(options.max_tokens, Some(16384));
+
+        let explicit = ChatOptions {
+            max_tokens: Some(8192),
+            ..Default::default()
+        };
+        assert_eq!(client.with_default_options(explicit).max_tokens, Some(8192));
+    }
+
+    #[tokio::test]
+    async fn test_stream_and_collect_with_callbacks() {
+        let body = [
+            sse_delta(serde_json::json!({ "reasoning_content": "think " })),
+            sse_delta(serde_json::json!({ "reasoning_content": "hard" })),
+            sse_body("answer", "stop"),
+        ]
+        .concat();
+        let mock = spawn_mock_sse(vec![body]).await;
+        let client = LlmClient::new("sk-test", &mock.base_url, false).unwrap();
+
+        let mut contents = Vec::new();
+        let mut reasonings = Vec::new();
+        let result = client
+            .stream_and_collect_with(
+                vec![ChatMessage::user("Hi")],
+                "gpt-4o",
+                ChatOptions::default(),
+                CollectMode::WithReasoning,
+                |c| contents.push(c.to_string()),
+                |r| reasonings.push(r.to_string()),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(contents, vec!["answer"]);
+        assert_eq!(reasonings, vec!["think ", "hard"]);
+        assert_eq!(result.content, "answer");
+        assert_eq!(result.reasoning, "think hard");
+        assert_eq!(result.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(
+            result.usage,
+            Some(TokenUsage { prompt_tokens: 10, completion_tokens: 20, total_tokens: 30 })
+        );
+        assert_eq!(mock.requests.lock()[0]["stream_options"]["include_usage"], true);
+    }
+
+    #[tokio::test]
+    async fn test_list_models() {
+        let mock = spawn_mock_llm(vec![]).await;
+        let client = LlmClient::new("sk-test", &mock.base_url, false).unwrap();
+
+        let (models, from_api) = client.list_models("gpt-4o").await.unwrap();
+        assert!(from_api);
+        assert_eq!(models, vec!["gpt-4o", "gpt-4o-mini"]);
+
+        let (models, from_api) = client.list_models("claude-sonnet-4-0").await.unwrap();
+        assert!(!from_api);
+        assert!(models.iter().all(|m| m.starts_with("claude")));
+    }
+
+    #[tokio::test]
+    async fn test_content_filter_finish_reason_is_error() {
+        let mock = spawn_mock_sse(vec![sse_body("partial", "content_filter")]).await;
+        let client = LlmClient::new("sk-test", &mock.base_url, false).unwrap();
+
+        let result = client
+            .stream_and_collect(vec![ChatMessage::user("Hi")], "gpt-4o", ChatOptions::default(), CollectMode::ContentOnly)
+            .await;
+        assert!(matches!(result, Err(LlmError::ContentFiltered(reason)) if reason == "content_filter"));
+
+        // 截断不算拦截
+        let mock = spawn_mock_sse(vec![sse_body("partial", "length")]).await;
+        let client = LlmClient::new("sk-test", &mock.base_url, false).unwrap();
+        let result = client
+            .stream_and_collect(vec![ChatMessage::user("Hi")], "gpt-4o", ChatOptions::default(), CollectMode::ContentOnly)
+            .await
+            .unwrap();
+        assert_eq!(result.content, "partial");
+    }
+
+    #[tokio::test]
+    async fn test_gateway_stream_without_data_prefix() {
+        let chunk = |content: &str| serde_json::json!({ "choices": [{ "delta": { "content": content }, "finish_reason": null }] });
+        let body = format!(
+            "event: message\r\nid: 1\r\n{}\r\n\r\nretry: 3000\r\ndata:{}\r\n\r\n: keep-alive\r\n[DONE]\r\n",
+            chunk("Hello, "),
+            chunk("world"),
+        );
+        let mock = spawn_mock_sse(vec![body]).await;
+        let client = LlmClient::new("sk-test", &mock.base_url, false).unwrap();
+
+        let result = client
+            .stream_and_collect(vec![ChatMessage::user("Hi")], "gpt-4o", ChatOptions::default(), CollectMode::ContentOnly)
+            .await
+            .unwrap();
+
+        assert_eq!(result.content, "Hello, world");
+    }
+
+    #[test]
+    fn test_normalize_json_content() {
+        assert_eq!(normalize_json_content(" {\"a\": 1} ").unwrap(), "{\"a\": 1}");
+        assert_eq!(
+            normalize_json_content("```json\n{\"a\": 1}\n```").unwrap(),
+            "{\"a\": 1}"
+        );
+        assert!(matches!(
+            normalize_json_content("not json"),
+            Err(LlmError::JsonError(_))
+        ));
+    }
+}
@@ -2,6 +2,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use super::types::BrowserHeaderSettings;
+
 /// API 格式枚举
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ApiFormat {
@@ -22,6 +24,18 @@ pub fn detect_api_format(model: &str) -> ApiFormat {
     }
 }
 
+/// 判断是否为 OpenAI o 系列推理模型（o1、o3-mini、openai/o4-mini 等）
+///
+/// 推理模型不接受 `temperature`/`top_p`，且使用 `max_completion_tokens` 代替 `max_tokens`
+pub fn is_reasoning_model(model: &str) -> bool {
+    let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+    let Some(rest) = name.strip_prefix('o') else {
+        return false;
+    };
+    let version_len = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    version_len > 0 && (rest.len() == version_len || rest[version_len..].starts_with('-'))
+}
+
 /// 修复 base_url
 ///
 /// - 移除末尾斜杠
@@ -52,6 +66,18 @@ pub fn build_openai_endpoint(base_url: &str) -> String {
     }
 }
 
+/// 构建 OpenAI 兼容的模型列表端点
+pub fn build_models_endpoint(base_url: &str) -> String {
+    let url = fix_base_url(base_url);
+    let url = url.strip_suffix("/chat/completions").unwrap_or(&url);
+
+    if url.ends_with("/v1") {
+        format!("{}/models", url)
+    } else {
+        format!("{}/v1/models", url)
+    }
+}
+
 /// 构建 Anthropic Messages 端点
 pub fn build_anthropic_endpoint(base_url: &str) -> String {
     let url = fix_base_url(base_url);
@@ -74,6 +100,53 @@ pub fn get_browser_headers() -> Vec<(&'static str, &'static str)> {
     ]
 }
 
+/// 根据设置生成最终的浏览器模拟请求头
+///
+/// 顺序：默认请求头 → 自定义 User-Agent → 额外请求头（同名覆盖）→ 移除禁用的请求头
+pub fn resolve_browser_headers(settings: &BrowserHeaderSettings) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = get_browser_headers()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+    let overrides = settings
+        .user_agent
+        .iter()
+        .map(|ua| ("User-Agent".to_string(), ua.clone()))
+        .chain(settings.extra_headers.iter().cloned());
+    for (key, value) in overrides {
+        match headers.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(&key)) {
+            Some(existing) => existing.1 = value,
+            None => headers.push((key, value)),
+        }
+    }
+
+    headers.retain(|(k, _)| !settings.disabled_headers.iter().any(|d| d.eq_ignore_ascii_case(k)));
+    headers
+}
+
+/// 为请求添加浏览器模拟头
+pub fn apply_browser_headers(
+    mut request: reqwest::RequestBuilder,
+    headers: &[(String, String)],
+) -> reqwest::RequestBuilder {
+    for (key, value) in headers {
+        request = request.header(key.as_str(), value.as_str());
+    }
+    request
+}
+
+/// 解析 Retry-After 响应头（仅支持秒数形式）
+pub fn parse_retry_after(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -86,6 +159,16 @@ mod tests {
         assert_eq!(detect_api_format("Claude-3-Sonnet"), ApiFormat::Anthropic);
     }
 
+    #[test]
+    fn test_is_reasoning_model() {
+        assert!(is_reasoning_model("o1"));
+        assert!(is_reasoning_model("o3-mini"));
+        assert!(is_reasoning_model("openai/o4-mini"));
+        assert!(!is_reasoning_model("gpt-4o"));
+        assert!(!is_reasoning_model("gpt-4o-mini"));
+        assert!(!is_reasoning_model("ollama"));
+    }
+
     #[test]
     fn test_fix_base_url() {
         assert_eq!(fix_base_url("https://api.openai.com/"), "https://api.openai.com");
@@ -119,4 +202,30 @@ mod tests {
             "https://api.anthropic.com/v1/messages"
         );
     }
+
+    #[test]
+    fn test_custom_browser_headers_override_defaults() {
+        let settings = BrowserHeaderSettings {
+            user_agent: Some("my-gateway-client/1.0".to_string()),
+            extra_headers: vec![
+                ("accept-language".to_string(), "en-US".to_string()),
+                ("X-Team".to_string(), "docs".to_string()),
+            ],
+            disabled_headers: vec!["accept".to_string()],
+        };
+        let headers = resolve_browser_headers(&settings);
+
+        let request = apply_browser_headers(reqwest::Client::new().post("http://localhost/v1"), &headers)
+            .build()
+            .unwrap();
+        let h = request.headers();
+        assert_eq!(h["user-agent"], "my-gateway-client/1.0");
+        assert_eq!(h["accept-language"], "en-US");
+        assert_eq!(h["x-team"], "docs");
+        assert!(h.get("accept").is_none());
+        assert_eq!(h.get_all("accept-language").iter().count(), 1);
+
+        // 默认设置保持原有请求头
+        assert_eq!(resolve_browser_headers(&BrowserHeaderSettings::default()).len(), get_browser_headers().len());
+    }
 }
@@ -3,9 +3,11 @@
 //! 提供统一的 LLM 客户端，支持 OpenAI 和 Anthropic API 格式。
 
 mod anthropic;
+mod circuit;
 mod client;
 mod format;
 mod openai;
+mod sse;
 mod types;
 
 pub use client::LlmClient;
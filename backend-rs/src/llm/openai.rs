@@ -1,14 +1,13 @@
 //! OpenAI Chat Completions API 流式实现
 
 use async_stream::try_stream;
-use futures::Stream;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::pin::Pin;
 use tracing::{debug, error};
 
-use super::format::{build_openai_endpoint, get_browser_headers};
-use super::types::{ChatChunk, ChatMessage, ChatOptions, LlmError};
+use super::format::{apply_browser_headers, build_openai_endpoint, is_reasoning_model, parse_retry_after};
+use super::sse::sse_data;
+use super::types::{ChatChunk, ChatMessage, ChatOptions, ChatStream, LlmError, TokenUsage};
 
 /// OpenAI 请求载荷
 #[derive(Serialize)]
@@ -22,8 +21,20 @@ struct OpenAiRequest {
     top_p: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     max_tokens: Option<u32>,
+    /// 推理模型使用，代替 `max_tokens`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_completion_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning_effort: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     response_format: Option<ResponseFormat>,
+    stream_options: StreamOptions,
+}
+
+/// 流式选项：请求在最后一个块中附带 token 用量
+#[derive(Serialize)]
+struct StreamOptions {
+    include_usage: bool,
 }
 
 #[derive(Serialize)]
@@ -35,7 +46,11 @@ struct ResponseFormat {
 /// OpenAI SSE 响应块
 #[derive(Deserialize, Debug)]
 struct OpenAiStreamChunk {
+    /// 附带用量的最后一个块中为空
+    #[serde(default)]
     choices: Vec<OpenAiChoice>,
+    #[serde(default)]
+    usage: Option<TokenUsage>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -50,6 +65,28 @@ struct OpenAiDelta {
     reasoning_content: Option<String>,
 }
 
+/// 构建 OpenAI 请求载荷
+///
+/// 推理模型（o 系列）省略 `temperature`/`top_p`，用 `max_completion_tokens` 代替 `max_tokens`，
+/// 并附带 `reasoning_effort`；其他模型忽略 `reasoning_effort`。
+fn build_openai_request(messages: Vec<ChatMessage>, model: &str, options: &ChatOptions) -> OpenAiRequest {
+    let reasoning = is_reasoning_model(model);
+    OpenAiRequest {
+        model: model.to_string(),
+        messages,
+        stream: true,
+        temperature: options.temperature.filter(|_| !reasoning),
+        top_p: options.top_p.filter(|_| !reasoning),
+        max_tokens: options.max_tokens.filter(|_| !reasoning),
+        max_completion_tokens: options.max_tokens.filter(|_| reasoning),
+        reasoning_effort: options.reasoning_effort.clone().filter(|_| reasoning),
+        response_format: options.response_format.as_ref().map(|t| ResponseFormat {
+            format_type: t.clone(),
+        }),
+        stream_options: StreamOptions { include_usage: true },
+    }
+}
+
 /// 流式调用 OpenAI API
 pub fn stream_openai(
     client: &Client,
@@ -58,41 +95,27 @@ pub fn stream_openai(
     messages: Vec<ChatMessage>,
     model: &str,
     options: &ChatOptions,
-    simulate_browser: bool,
-) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, LlmError>> + Send>> {
+    browser_headers: &[(String, String)],
+) -> ChatStream {
     let endpoint = build_openai_endpoint(base_url);
     let api_key = api_key.to_string();
     let model = model.to_string();
     let options = options.clone();
     let client = client.clone();
-    let simulate_browser = simulate_browser;
+    let browser_headers = browser_headers.to_vec();
 
     Box::pin(try_stream! {
         // 构建请求体
-        let payload = OpenAiRequest {
-            model: model.clone(),
-            messages,
-            stream: true,
-            temperature: options.temperature,
-            top_p: options.top_p,
-            max_tokens: options.max_tokens,
-            response_format: options.response_format.as_ref().map(|t| ResponseFormat {
-                format_type: t.clone(),
-            }),
-        };
+        let payload = build_openai_request(messages, &model, &options);
 
         // 构建请求
-        let mut request = client
+        let request = client
             .post(&endpoint)
             .header("Authorization", format!("Bearer {}", api_key))
             .header("Content-Type", "application/json");
 
         // 添加浏览器模拟头
-        if simulate_browser {
-            for (key, value) in get_browser_headers() {
-                request = request.header(key, value);
-            }
-        }
+        let request = apply_browser_headers(request, &browser_headers);
 
         debug!("OpenAI API request: endpoint={}, model={}", endpoint, model);
 
@@ -106,11 +129,13 @@ pub fn stream_openai(
         let status = response.status();
         if !status.is_success() {
             let status_code = status.as_u16();
+            let retry_after = parse_retry_after(response.headers());
             let error_text = response.text().await.unwrap_or_default();
             error!("OpenAI API error: status={}, body={}", status_code, &error_text[..error_text.len().min(500)]);
             Err(LlmError::ApiError {
                 status: status_code,
                 message: error_text,
+                retry_after,
             })?;
             // 不会执行到这里
             unreachable!();
@@ -127,15 +152,11 @@ pub fn stream_openai(
 
             // 按行处理
             while let Some(newline_pos) = buffer.find('\n') {
-                let line = buffer[..newline_pos].trim().to_string();
+                let line = buffer[..newline_pos].to_string();
                 buffer = buffer[newline_pos + 1..].to_string();
 
-                if line.is_empty() {
-                    continue;
-                }
-
-                // 解析 SSE 数据
-                if let Some(data) = line.strip_prefix("data: ") {
+                // 解析 SSE 数据（兼容缺少 `data:` 前缀的网关）
+                if let Some(data) = sse_data(&line) {
                     if data == "[DONE]" {
                         return;
                     }
@@ -147,8 +168,14 @@ pub fn stream_openai(
                                     content: choice.delta.content.clone(),
                                     finish_reason: choice.finish_reason.clone(),
                                     reasoning_content: choice.delta.reasoning_content.clone(),
+                                    usage: chunk.usage,
                                 };
                                 yield chat_chunk;
+                            } else if chunk.usage.is_some() {
+                                yield ChatChunk {
+                                    usage: chunk.usage,
+                                    ..Default::default()
+                                };
                             }
                         }
                         Err(e) => {
@@ -161,3 +188,41 @@ pub fn stream_openai(
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> ChatOptions {
+        ChatOptions {
+            temperature: Some(0.7),
+            top_p: Some(0.9),
+            max_tokens: Some(4096),
+            reasoning_effort: Some("high".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reasoning_model_payload() {
+        let request = build_openai_request(vec![ChatMessage::user("Hi")], "o3-mini", &options());
+        let payload = serde_json::to_value(&request).unwrap();
+
+        assert!(payload.get("temperature").is_none());
+        assert!(payload.get("top_p").is_none());
+        assert!(payload.get("max_tokens").is_none());
+        assert_eq!(payload["max_completion_tokens"], 4096);
+        assert_eq!(payload["reasoning_effort"], "high");
+    }
+
+    #[test]
+    fn test_regular_model_payload() {
+        let request = build_openai_request(vec![ChatMessage::user("Hi")], "gpt-4o", &options());
+        let payload = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(payload["temperature"], 0.7);
+        assert_eq!(payload["max_tokens"], 4096);
+        assert!(payload.get("max_completion_tokens").is_none());
+        assert!(payload.get("reasoning_effort").is_none());
+    }
+}
@@ -0,0 +1,45 @@
+//! SSE 行解析
+//!
+//! 兼容不严格遵循 SSE 格式的 OpenAI 兼容网关：`data:` 后可无空格、可缺少 `data:` 前缀（裸 JSON 行），
+//! 并忽略 `event:`/`id:`/`retry:` 字段和注释行。
+
+/// 需要忽略的 SSE 字段
+const IGNORED_FIELDS: &[&str] = &["event:", "id:", "retry:"];
+
+/// 提取一行 SSE 中的数据部分，非数据行返回 `None`
+///
+/// 行尾的 `\r`（`\r\n` 换行）会被去掉。
+pub(super) fn sse_data(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with(':') {
+        return None;
+    }
+    if let Some(data) = line.strip_prefix("data:") {
+        return Some(data.trim_start());
+    }
+    if IGNORED_FIELDS.iter().any(|field| line.starts_with(field)) {
+        return None;
+    }
+    // 缺少 `data:` 前缀的裸数据行
+    (line.starts_with('{') || line == "[DONE]").then_some(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sse_data() {
+        assert_eq!(sse_data("data: {\"a\":1}"), Some("{\"a\":1}"));
+        assert_eq!(sse_data("data:{\"a\":1}\r"), Some("{\"a\":1}"));
+        assert_eq!(sse_data("{\"a\":1}\r"), Some("{\"a\":1}"));
+        assert_eq!(sse_data("[DONE]"), Some("[DONE]"));
+        assert_eq!(sse_data("data: [DONE]"), Some("[DONE]"));
+        assert_eq!(sse_data("event: message"), None);
+        assert_eq!(sse_data("id: 42"), None);
+        assert_eq!(sse_data("retry: 3000"), None);
+        assert_eq!(sse_data(": keep-alive"), None);
+        assert_eq!(sse_data("\r"), None);
+        assert_eq!(sse_data("garbage"), None);
+    }
+}
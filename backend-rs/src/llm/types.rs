@@ -1,6 +1,9 @@
 //! LLM 类型定义
 
+use futures::Stream;
 use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+use std::time::Duration;
 
 /// 聊天消息
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +29,7 @@ impl ChatMessage {
         }
     }
 
+    #[allow(dead_code)] // 会话历史由存储记录直接构造，与 user/system 对称保留
     pub fn assistant(content: impl Into<String>) -> Self {
         Self {
             role: "assistant".to_string(),
@@ -43,8 +47,34 @@ pub struct ChatChunk {
     pub finish_reason: Option<String>,
     /// 推理内容（用于 o1 等模型）
     pub reasoning_content: Option<String>,
+    /// token 用量（通常只出现在最后的块中）
+    pub usage: Option<TokenUsage>,
 }
 
+/// 一次请求的 token 用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct TokenUsage {
+    /// 输入 token 数
+    pub prompt_tokens: u64,
+    /// 输出 token 数
+    pub completion_tokens: u64,
+    /// 总 token 数
+    pub total_tokens: u64,
+}
+
+/// 连接测试的耗时
+#[derive(Debug, Clone, Copy)]
+pub struct PingTiming {
+    /// 从发出请求到收到第一段内容的时间
+    pub first_token: Duration,
+    /// 从发出请求到响应结束的总时间
+    pub total: Duration,
+}
+
+/// 流式响应类型
+pub type ChatStream = Pin<Box<dyn Stream<Item = Result<ChatChunk, LlmError>> + Send>>;
+
 /// 聊天选项
 #[derive(Debug, Clone, Default)]
 pub struct ChatOptions {
@@ -55,9 +85,12 @@ pub struct ChatOptions {
     /// 最大 token 数
     pub max_tokens: Option<u32>,
     /// 超时时间（秒）
+    #[allow(dead_code)] // 尚未下发到单次请求，目前由 HTTP 客户端的全局超时控制
     pub timeout: Option<u64>,
     /// 响应格式（如 "json_object"）
     pub response_format: Option<String>,
+    /// 推理强度（"low" / "medium" / "high"），仅对推理模型生效
+    pub reasoning_effort: Option<String>,
 }
 
 /// 流式收集结果
@@ -71,6 +104,8 @@ pub struct StreamCollectResult {
     pub finish_reason: Option<String>,
     /// chunk 数量
     pub chunk_count: usize,
+    /// token 用量，上游未返回时为空
+    pub usage: Option<TokenUsage>,
 }
 
 /// 内容收集模式
@@ -80,11 +115,52 @@ pub enum CollectMode {
     #[default]
     ContentOnly,
     /// 同时收集内容和推理
+    #[cfg_attr(not(test), allow(dead_code))] // 现有调用方只取内容或只取推理
     WithReasoning,
     /// 仅收集推理
     ReasoningOnly,
 }
 
+/// HTTP 连接池与超时设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpClientSettings {
+    /// 每个主机保留的最大空闲连接数
+    pub pool_max_idle_per_host: usize,
+    /// 建立连接的超时时间
+    pub connect_timeout: Duration,
+    /// 单次请求的总超时时间
+    pub timeout: Duration,
+    /// 同一 base_url 连续失败多少次后熔断（0 表示不熔断）
+    pub circuit_failure_threshold: u32,
+    /// 熔断冷却时间
+    pub circuit_cooldown: Duration,
+}
+
+impl Default for HttpClientSettings {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: 5,
+            connect_timeout: Duration::from_secs(30),
+            timeout: Duration::from_secs(120),
+            circuit_failure_threshold: 5,
+            circuit_cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// 浏览器模拟请求头设置
+///
+/// 在默认请求头（见 `get_browser_headers`）基础上覆盖 User-Agent、追加或禁用请求头。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BrowserHeaderSettings {
+    /// 自定义 User-Agent
+    pub user_agent: Option<String>,
+    /// 额外请求头，与默认请求头同名时覆盖默认值
+    pub extra_headers: Vec<(String, String)>,
+    /// 禁用的请求头名称（不区分大小写）
+    pub disabled_headers: Vec<String>,
+}
+
 /// LLM 错误类型
 #[derive(Debug, thiserror::Error)]
 pub enum LlmError {
@@ -94,9 +170,15 @@ pub enum LlmError {
 
     /// API 返回错误
     #[error("API 错误 ({status}): {message}")]
-    ApiError { status: u16, message: String },
+    ApiError {
+        status: u16,
+        message: String,
+        /// 上游返回的 Retry-After（秒），仅限流时可能存在
+        retry_after: Option<u64>,
+    },
 
     /// 超时错误
+    #[allow(dead_code)] // reqwest 超时目前以 HttpError 返回，保留该变体供错误映射与熔断判断
     #[error("请求超时")]
     Timeout,
 
@@ -111,4 +193,28 @@ pub enum LlmError {
     /// 流解析错误
     #[error("流解析错误: {0}")]
     StreamError(String),
+
+    /// 模型因内容审核等原因中止输出（如 `finish_reason: content_filter`），已收到的内容不可用
+    #[error("模型拒绝或中止了输出 (finish_reason: {0})")]
+    ContentFiltered(String),
+
+    /// 上游连续失败已熔断，冷却期内直接拒绝
+    #[error("上游服务暂时不可用（{base_url} 连续失败已熔断），请 {retry_after} 秒后重试")]
+    CircuitOpen { base_url: String, retry_after: u64 },
+}
+
+impl LlmError {
+    /// 错误类型名称（记录到请求日志的 `error_type`）
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LlmError::HttpError(_) => "HttpError",
+            LlmError::ApiError { .. } => "ApiError",
+            LlmError::Timeout => "Timeout",
+            LlmError::ConfigError(_) => "ConfigError",
+            LlmError::JsonError(_) => "JsonError",
+            LlmError::StreamError(_) => "StreamError",
+            LlmError::ContentFiltered(_) => "ContentFiltered",
+            LlmError::CircuitOpen { .. } => "CircuitOpen",
+        }
+    }
 }
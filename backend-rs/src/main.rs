@@ -5,7 +5,6 @@
 use axum::Router;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tower_http::cors::{Any, CorsLayer};
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -18,8 +17,11 @@ mod services;
 mod state;
 mod utils;
 
-use api::create_api_routes;
-use state::create_shared_state;
+#[cfg(test)]
+mod test_utils;
+
+use api::{apply_cors, create_api_routes};
+use state::{create_shared_state, spawn_task_sweeper};
 
 /// 在 Windows 上设置控制台代码页为 UTF-8
 #[cfg(windows)]
@@ -37,7 +39,7 @@ fn setup_console_encoding() {
 
 #[cfg(not(windows))]
 fn setup_console_encoding() {
-    // 非 Windows 平台不需要特殊处理
+    // 非 Windows 平台无法在进程内修改终端编码，日志初始化后由 `warn_if_non_utf8_locale` 提示
 }
 
 #[tokio::main]
@@ -53,22 +55,18 @@ async fn main() {
         )
         .with(tracing_subscriber::fmt::layer())
         .init();
+    utils::console::warn_if_non_utf8_locale();
 
     info!("Starting AI Code Review Platform backend...");
 
     // 创建共享状态
     let state = create_shared_state();
 
-    // 配置 CORS（允许所有来源，与 Python 版保持一致）
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+    // 定期清理已结束的过期任务
+    spawn_task_sweeper(Arc::clone(&state));
 
-    // 构建路由
-    let app = Router::new()
-        .merge(create_api_routes(Arc::clone(&state)))
-        .layer(cors);
+    // 构建路由（CORS 允许所有来源，与 Python 版保持一致）
+    let app = apply_cors(Router::new().merge(create_api_routes(Arc::clone(&state))));
 
     // 绑定地址（与 Python 版相同：127.0.0.1:8765）
     let addr = SocketAddr::from(([127, 0, 0, 1], 8765));
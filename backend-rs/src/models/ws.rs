@@ -39,6 +39,12 @@ pub enum WsInbound {
         content: String,
         #[serde(default)]
         context: Option<ChatContext>,
+        /// 本条消息使用的温度（覆盖配置）
+        #[serde(default)]
+        temperature: Option<f64>,
+        /// 本条消息的最大 token 数（覆盖配置）
+        #[serde(default, rename = "maxTokens")]
+        max_tokens: Option<u32>,
     },
 }
 
@@ -68,6 +74,16 @@ pub enum WsOutbound {
         conversation_id: String,
         error: String,
     },
+    /// 回答在流式输出中途中断：已发送的内容不完整
+    #[serde(rename = "chat_aborted")]
+    ChatAborted {
+        #[serde(rename = "conversationId")]
+        conversation_id: String,
+        error: String,
+        /// 中断前已发送的内容长度（字符数）
+        #[serde(rename = "partialLength")]
+        partial_length: usize,
+    },
 }
 
 impl WsOutbound {
@@ -94,6 +110,19 @@ impl WsOutbound {
         }
     }
 
+    /// 创建回答中断消息
+    pub fn chat_aborted(
+        conversation_id: impl Into<String>,
+        error: impl Into<String>,
+        partial_length: usize,
+    ) -> Self {
+        Self::ChatAborted {
+            conversation_id: conversation_id.into(),
+            error: error.into(),
+            partial_length,
+        }
+    }
+
     /// 序列化为 JSON 字符串
     pub fn to_json(&self) -> String {
         serde_json::to_string(self).unwrap_or_default()
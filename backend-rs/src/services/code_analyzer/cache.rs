@@ -0,0 +1,122 @@
+//! 项目图谱缓存
+//!
+//! 按项目路径缓存最近的 `analyze_project` 结果，源文件的 mtime/大小变化时自动失效。
+
+use parking_lot::Mutex;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use super::types::GraphData;
+
+/// 默认缓存的项目数
+pub const DEFAULT_GRAPH_CACHE_CAPACITY: usize = 16;
+
+/// 缓存条目
+struct CacheEntry {
+    project_path: PathBuf,
+    fingerprint: u64,
+    graph: GraphData,
+}
+
+/// 项目图谱 LRU 缓存
+///
+/// 每个项目只保留一条记录，键为 `(project_path, fingerprint)`；
+/// 超出容量时淘汰最久未使用的项目。
+pub struct ProjectGraphCache {
+    /// 最近使用的条目在队尾
+    entries: Mutex<VecDeque<CacheEntry>>,
+    capacity: usize,
+}
+
+impl ProjectGraphCache {
+    /// 创建缓存（容量至少为 1）
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::new()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// 查找缓存，命中时将条目移到队尾
+    pub fn get(&self, project_path: &Path, fingerprint: u64) -> Option<GraphData> {
+        let mut entries = self.entries.lock();
+        let pos = entries
+            .iter()
+            .position(|e| e.project_path == project_path && e.fingerprint == fingerprint)?;
+        let entry = entries.remove(pos)?;
+        let graph = entry.graph.clone();
+        entries.push_back(entry);
+        Some(graph)
+    }
+
+    /// 写入缓存，替换同一项目的旧条目
+    pub fn insert(&self, project_path: &Path, fingerprint: u64, graph: GraphData) {
+        let mut entries = self.entries.lock();
+        entries.retain(|e| e.project_path != project_path);
+        entries.push_back(CacheEntry {
+            project_path: project_path.to_path_buf(),
+            fingerprint,
+            graph,
+        });
+        while entries.len() > self.capacity {
+            entries.pop_front();
+        }
+    }
+
+    /// 当前缓存的项目数
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+}
+
+impl Default for ProjectGraphCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_GRAPH_CACHE_CAPACITY)
+    }
+}
+
+/// 根据源文件路径、mtime 和大小计算指纹（只读取元数据，不读取内容）
+pub fn fingerprint_files(files: &[PathBuf]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for file in files {
+        file.hash(&mut hasher);
+        if let Ok(meta) = file.metadata() {
+            meta.len().hash(&mut hasher);
+            if let Some(mtime) = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            {
+                mtime.as_nanos().hash(&mut hasher);
+            }
+        }
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_eviction() {
+        let cache = ProjectGraphCache::new(2);
+        cache.insert(Path::new("/a"), 1, GraphData::default());
+        cache.insert(Path::new("/b"), 1, GraphData::default());
+
+        // 访问 /a 后，/b 成为最久未使用
+        assert!(cache.get(Path::new("/a"), 1).is_some());
+        cache.insert(Path::new("/c"), 1, GraphData::default());
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(Path::new("/b"), 1).is_none());
+        assert!(cache.get(Path::new("/a"), 1).is_some());
+
+        // 指纹不同视为未命中
+        assert!(cache.get(Path::new("/a"), 2).is_none());
+    }
+}
@@ -0,0 +1,203 @@
+//! 无扩展名文件的语言识别、测试文件识别与源码内容规范化
+//!
+//! 仅根据 shebang 行和少量确定性的开头标记判断，避免误判。
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use tracing::{info, warn};
+
+/// 嗅探时读取的最大字节数
+const SNIFF_BYTES: usize = 256;
+
+/// shebang 解释器到扩展名的映射
+const SHEBANG_INTERPRETERS: &[(&str, &str)] = &[
+    ("python", ".py"),
+    ("node", ".js"),
+    ("deno", ".ts"),
+    ("ruby", ".rb"),
+    ("php", ".php"),
+];
+
+/// 去掉开头的 UTF-8 BOM，并将 `\r\n` / `\r` 换行统一为 `\n`
+///
+/// 每个换行都恰好映射为一个 `\n`，因此行号与原文件一致；已规范的内容不会复制。
+pub fn normalize_source(content: &str) -> Cow<'_, str> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    if content.contains('\r') {
+        Cow::Owned(content.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(content)
+    }
+}
+
+/// 将源文件字节解码为文本
+///
+/// 依次识别 UTF-8（含 BOM）、带 BOM 的 UTF-16；都不是时由 chardetng 推断编码（GBK、windows-1252 等）
+/// 并用 encoding_rs 完整解码，非 UTF-8 编码会记录日志。
+/// 含 NUL 字节或 UTF-16 内容不完整的文件视为二进制，返回 `InvalidData`。
+pub fn decode_source(bytes: Vec<u8>, path: &Path) -> io::Result<String> {
+    let bytes = match String::from_utf8(bytes) {
+        Ok(text) => return Ok(text),
+        Err(e) => e.into_bytes(),
+    };
+
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", reason, path.display()));
+
+    // 带 UTF-8 BOM 但内容无效的文件交给下面的编码推断处理
+    if let Some((encoding, bom_len)) = Encoding::for_bom(&bytes).filter(|(encoding, _)| *encoding != UTF_8) {
+        let text = encoding
+            .decode_without_bom_handling_and_without_replacement(&bytes[bom_len..])
+            .ok_or_else(|| invalid("UTF-16 内容无效"))?;
+        info!("Decoded {} as {}", path.display(), encoding.name());
+        return Ok(text.into_owned());
+    }
+
+    if bytes.contains(&0) {
+        return Err(invalid("疑似二进制文件"));
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(&bytes, true);
+    let encoding = detector.guess(None, false);
+    let (text, had_errors) = encoding.decode_without_bom_handling(&bytes);
+    if had_errors {
+        warn!("Decoded {} as {} (lossy, invalid sequences replaced)", path.display(), encoding.name());
+    } else {
+        info!("Decoded {} as {}", path.display(), encoding.name());
+    }
+    Ok(text.into_owned())
+}
+
+/// 存放测试代码的目录名
+const TEST_DIRS: &[&str] = &["test", "tests", "spec", "specs", "__tests__", "__test__"];
+
+/// 根据路径判断是否为测试文件
+///
+/// 识别测试目录（`tests/`、`__tests__/` 等）以及常见命名：`*_test.go`、`*.test.ts`、`*.spec.js`、
+/// `test_*.py`、`*_spec.rb`、`FooTest.java` 等。`/` 和 `\` 都视为分隔符。
+pub fn is_test_path(relative_path: &str) -> bool {
+    let mut segments: Vec<&str> = relative_path.split(['/', '\\']).filter(|s| !s.is_empty()).collect();
+    let Some(file_name) = segments.pop() else {
+        return false;
+    };
+    if segments.iter().any(|dir| TEST_DIRS.contains(&dir.to_lowercase().as_str())) {
+        return true;
+    }
+
+    let (stem, _) = file_name.rsplit_once('.').unwrap_or((file_name, ""));
+    let lower = stem.to_lowercase();
+    lower.starts_with("test_")
+        || lower.ends_with("_test")
+        || lower.ends_with("_spec")
+        || lower.ends_with(".test")
+        || lower.ends_with(".spec")
+        || lower == "conftest"
+        || (stem.len() > 4 && (stem.ends_with("Test") || stem.ends_with("Tests")))
+}
+
+/// 根据文件开头内容推断扩展名（带点，如 `.py`）
+pub fn detect_extension_from_content(content: &str) -> Option<&'static str> {
+    let first_line = content.lines().next()?.trim();
+
+    if let Some(shebang) = first_line.strip_prefix("#!") {
+        return interpreter_extension(shebang);
+    }
+    if first_line.starts_with("<?php") {
+        return Some(".php");
+    }
+    None
+}
+
+/// 读取文件开头并推断扩展名；仅用于没有扩展名的文件
+pub fn sniff_file_extension(path: &Path) -> Option<&'static str> {
+    if path.extension().is_some() {
+        return None;
+    }
+    let mut buf = [0u8; SNIFF_BYTES];
+    let n = File::open(path).and_then(|mut f| f.read(&mut buf)).ok()?;
+    detect_extension_from_content(&normalize_source(&String::from_utf8_lossy(&buf[..n])))
+}
+
+/// 文件的有效扩展名：优先使用真实扩展名，否则尝试嗅探内容
+pub fn effective_extension(path: &Path) -> Option<String> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => Some(format!(".{}", ext)),
+        None => sniff_file_extension(path).map(str::to_string),
+    }
+}
+
+/// 解析 shebang：`/usr/bin/python3`、`/usr/bin/env python3`、`/usr/bin/env -S node --flag`
+fn interpreter_extension(shebang: &str) -> Option<&'static str> {
+    let mut parts = shebang.split_whitespace();
+    let mut program = parts.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = parts.find(|p| !p.starts_with('-'))?;
+    }
+
+    // 去掉版本号后缀：python3、python3.11、ruby2.7
+    let name = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    SHEBANG_INTERPRETERS
+        .iter()
+        .find(|(interp, _)| *interp == name)
+        .map(|(_, ext)| *ext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_extension_from_content() {
+        assert_eq!(detect_extension_from_content("#!/usr/bin/env python3\nprint(1)"), Some(".py"));
+        assert_eq!(detect_extension_from_content("#!/usr/bin/python3.11\n"), Some(".py"));
+        assert_eq!(detect_extension_from_content("#!/usr/bin/env -S node --no-warnings\n"), Some(".js"));
+        assert_eq!(detect_extension_from_content("<?php\necho 1;"), Some(".php"));
+
+        // 保守：shell 脚本、无 shebang 的内容不识别
+        assert_eq!(detect_extension_from_content("#!/bin/bash\necho hi"), None);
+        assert_eq!(detect_extension_from_content("import os\nprint(1)"), None);
+        assert_eq!(detect_extension_from_content(""), None);
+    }
+
+    #[test]
+    fn test_is_test_path() {
+        for path in [
+            "src/foo.test.ts",
+            "web/app.spec.js",
+            "pkg/server_test.go",
+            "tests/helpers.py",
+            "src\\__tests__\\App.tsx",
+            "test_utils.py",
+            "spec/user_spec.rb",
+            "src/UserServiceTest.java",
+        ] {
+            assert!(is_test_path(path), "{}", path);
+        }
+        for path in ["src/foo.ts", "latest/main.py", "src/contest.py", "Test.java", "src/testing.rs"] {
+            assert!(!is_test_path(path), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_decode_source_encodings() {
+        let path = Path::new("a.py");
+        assert_eq!(decode_source("x = '中'".as_bytes().to_vec(), path).unwrap(), "x = '中'");
+
+        // GBK 编码的中文注释与字符串完整还原
+        let source = "# 计算两个数的和并返回结果\ndef add(a, b):\n    return a + b  # 加法\n";
+        let (gbk, _, _) = encoding_rs::GBK.encode(source);
+        assert_eq!(decode_source(gbk.into_owned(), path).unwrap(), source);
+
+        // Latin-1 字节后紧跟 ASCII 标识符字符时不会吞掉后面的字符
+        assert_eq!(decode_source(b"# caf\xe9".to_vec(), path).unwrap(), "# café");
+        assert_eq!(decode_source(b"caf\xe9_x = 1\n".to_vec(), path).unwrap(), "café_x = 1\n");
+        assert_eq!(decode_source(b"\xff\xfex\x00=\x001\x00".to_vec(), path).unwrap(), "x=1");
+
+        assert!(decode_source(vec![0xff, 0xfe, 0x00], path).is_err());
+        assert!(decode_source(vec![0x80, 0x00, 0x01], path).is_err());
+    }
+}
@@ -15,6 +15,20 @@ static RE_FUNC: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"^func\s+(?:\(\w+\s+\*?\w+\)\s+)?(\w+)\s*\(").unwrap()
 });
 
+static RE_CONST: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^const\s+(\w+)\b").unwrap()
+});
+static RE_CONST_SPEC: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(\w+)\b").unwrap()
+});
+
+/// 添加常量节点
+fn push_constant(graph: &mut GraphData, file_id: &str, name: &str, file_path: &str, line_number: usize) {
+    let node_id = format!("{}::const::{}", file_id, name);
+    graph.nodes.push(GraphNode::constant(&node_id, name, file_path, line_number));
+    graph.edges.push(GraphEdge::contains(file_id, &node_id));
+}
+
 /// 分析 Go 模块
 pub fn analyze_go_module(
     graph: &mut GraphData,
@@ -23,9 +37,35 @@ pub fn analyze_go_module(
     lines: &[&str],
     file_path: &str,
 ) {
+    // 是否位于 const ( ... ) 块内
+    let mut in_const_block = false;
+
     for (i, line) in lines.iter().enumerate() {
         let stripped = line.trim();
 
+        // const 块
+        if in_const_block {
+            if stripped.starts_with(')') {
+                in_const_block = false;
+            } else if !stripped.starts_with("//") {
+                if let Some(caps) = RE_CONST_SPEC.captures(stripped) {
+                    push_constant(graph, file_id, caps.get(1).unwrap().as_str(), file_path, i + 1);
+                }
+            }
+            continue;
+        }
+        // 只识别包级（无缩进）常量，忽略函数体内的局部常量
+        if let Some(rest) = line.strip_prefix("const") {
+            if rest.trim_start().starts_with('(') {
+                in_const_block = true;
+                continue;
+            }
+            if let Some(caps) = RE_CONST.captures(stripped) {
+                push_constant(graph, file_id, caps.get(1).unwrap().as_str(), file_path, i + 1);
+                continue;
+            }
+        }
+
         // struct 定义
         if let Some(caps) = RE_STRUCT.captures(stripped) {
             let name = caps.get(1).unwrap().as_str();
@@ -74,3 +114,25 @@ pub fn analyze_go_module(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_go_constants() {
+        let content = "package main\n\nconst Version = \"1.0\"\n\nconst (\n    // 状态\n    StateIdle State = iota\n    StateBusy\n)\n\nfunc main() {}\n";
+        let lines: Vec<&str> = content.lines().collect();
+        let mut graph = GraphData::default();
+        analyze_go_module(&mut graph, "file::main.go", content, &lines, "main.go");
+
+        let constants: Vec<&str> = graph
+            .nodes
+            .iter()
+            .filter(|n| n.node_type == "constant")
+            .map(|n| n.label.as_str())
+            .collect();
+        assert_eq!(constants, vec!["Version", "StateIdle", "StateBusy"]);
+        assert!(graph.nodes.iter().any(|n| n.node_type == "function" && n.label == "main"));
+    }
+}
@@ -2,7 +2,9 @@
 
 use regex::Regex;
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::hash::Hash;
 use std::path::Path;
 
 use super::types::ImportInfo;
@@ -61,9 +63,9 @@ pub fn extract_imports(content: &str, ext: &str, _current_file: &str) -> Vec<Imp
                 }
             }
         }
-        ".js" | ".jsx" | ".ts" | ".tsx" | ".vue" => {
+        ".js" | ".jsx" | ".ts" | ".tsx" | ".vue" | ".svelte" => {
             for line in content.lines() {
-                // 只处理相对导入
+                // 只处理相对导入；两个正则可能命中同一条语句，每行只取首个匹配
                 for re in [&*RE_JS_IMPORT1, &*RE_JS_IMPORT2] {
                     if let Some(caps) = re.captures(line) {
                         let imp = caps.get(1).unwrap().as_str();
@@ -77,6 +79,7 @@ pub fn extract_imports(content: &str, ext: &str, _current_file: &str) -> Vec<Imp
                                 display_name: display.to_string(),
                             });
                         }
+                        break;
                     }
                 }
             }
@@ -111,10 +114,10 @@ pub fn extract_imports(content: &str, ext: &str, _current_file: &str) -> Vec<Imp
 }
 
 /// 解析导入路径到项目文件
-pub fn resolve_import(
+pub fn resolve_import<S: Borrow<str> + Hash + Eq>(
     import_path: &str,
     current_file: &str,
-    file_map: &HashMap<String, bool>,
+    project_files: &HashSet<S>,
 ) -> Option<String> {
     let current_dir = Path::new(current_file)
         .parent()
@@ -134,7 +137,7 @@ pub fn resolve_import(
             let test = format!("{}{}", candidate, ext);
             // 规范化路径
             let normalized = normalize_path(&test);
-            if file_map.contains_key(&normalized) {
+            if project_files.contains(normalized.as_str()) {
                 return Some(normalized);
             }
         }
@@ -146,7 +149,7 @@ pub fn resolve_import(
         let candidate = import_path.replace('.', "/");
         for ext in [".py", "/__init__.py"] {
             let test = format!("{}{}", candidate, ext);
-            if file_map.contains_key(&test) {
+            if project_files.contains(test.as_str()) {
                 return Some(test);
             }
         }
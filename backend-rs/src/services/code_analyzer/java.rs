@@ -12,6 +12,10 @@ static RE_METHOD: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"^\s+(?:public|private|protected)?\s*(?:static\s+)?(?:\w+\s+)(\w+)\s*\(").unwrap()
 });
 
+static RE_CONST: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:(?:public|private|protected)\s+)?(?:static\s+final|final\s+static)\s+[\w<>\[\],.?\s]+?\s+(\w+)\s*=").unwrap()
+});
+
 /// 关键字列表，不应当作方法名
 const JAVA_KEYWORDS: &[&str] = &["if", "for", "while", "switch", "catch", "return", "new"];
 
@@ -49,6 +53,15 @@ pub fn analyze_java_module(
             continue;
         }
 
+        // 常量（static final 字段）
+        if let Some(caps) = RE_CONST.captures(line) {
+            let name = caps.get(1).unwrap().as_str();
+            let const_id = format!("{}::const::{}", file_id, name);
+            graph.nodes.push(GraphNode::constant(&const_id, name, file_path, i + 1));
+            graph.edges.push(GraphEdge::contains(file_id, &const_id));
+            continue;
+        }
+
         // 方法定义
         if let Some(caps) = RE_METHOD.captures(line) {
             let method_name = caps.get(1).unwrap().as_str();
@@ -66,3 +79,25 @@ pub fn analyze_java_module(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_java_constants() {
+        let content = "public class Config {\n    public static final int MAX_SIZE = 10;\n    private static final Map<String, Integer> LOOKUP = new HashMap<>();\n    private int count = 0;\n    public static int size() { return 0; }\n}\n";
+        let lines: Vec<&str> = content.lines().collect();
+        let mut graph = GraphData::default();
+        analyze_java_module(&mut graph, "file::Config.java", content, &lines, "Config.java");
+
+        let constants: Vec<&str> = graph
+            .nodes
+            .iter()
+            .filter(|n| n.node_type == "constant")
+            .map(|n| n.label.as_str())
+            .collect();
+        assert_eq!(constants, vec!["MAX_SIZE", "LOOKUP"]);
+        assert!(graph.nodes.iter().any(|n| n.node_type == "method" && n.label == "size"));
+    }
+}
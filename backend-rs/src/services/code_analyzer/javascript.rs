@@ -12,7 +12,7 @@ static RE_FUNC1: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"^(?:export\s+)?(?:default\s+)?(?:async\s+)?function\s+(\w+)").unwrap()
 });
 static RE_FUNC2: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"^(?:export\s+)?(?:const|let|var)\s+(\w+)\s*=\s*(?:async\s+)?\(?").unwrap()
+    Regex::new(r"^(?:export\s+)?(?:const|let|var)\s+(\w+)\s*=\s*(?:async\s+)?(?:function\b|\()").unwrap()
 });
 static RE_FUNC3: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"^(?:export\s+)?(?:const|let|var)\s+(\w+)\s*=\s*(?:async\s+)?\(.*\)\s*=>").unwrap()
@@ -21,6 +21,30 @@ static RE_TYPE: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"^(?:export\s+)?(?:interface|type)\s+(\w+)").unwrap()
 });
 
+static RE_FUNC4: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:export\s+)?(?:const|let|var)\s+(\w+)\s*=\s*(?:async\s+)?\w+\s*=>").unwrap()
+});
+static RE_CONST: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^export\s+const\s+(\w+)\s*(?::[^=]+)?=").unwrap()
+});
+
+static RE_EXPORT_LIST: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^export\s*(?:type\s*)?\{([^}]*)\}").unwrap()
+});
+
+/// 根据声明行前缀生成导出可见性元数据
+///
+/// `exported` 为 `true|false`；`export default` 额外记录 `default = true`
+fn export_metadata(stripped: &str) -> std::collections::HashMap<String, String> {
+    let mut metadata = std::collections::HashMap::new();
+    let exported = stripped.starts_with("export ");
+    metadata.insert("exported".to_string(), exported.to_string());
+    if exported && stripped["export ".len()..].trim_start().starts_with("default ") {
+        metadata.insert("default".to_string(), "true".to_string());
+    }
+    metadata
+}
+
 /// 分析 JS/TS 模块
 pub fn analyze_js_module(
     graph: &mut GraphData,
@@ -29,16 +53,31 @@ pub fn analyze_js_module(
     lines: &[&str],
     file_path: &str,
 ) {
+    // `export { a, b as c }` 形式导出的本地名称
+    let mut listed_exports: Vec<String> = Vec::new();
+
     for (i, line) in lines.iter().enumerate() {
         let stripped = line.trim();
 
+        if let Some(caps) = RE_EXPORT_LIST.captures(stripped) {
+            // `export { x } from './y'` 是转导出，不涉及本地声明
+            if !stripped.contains(" from ") {
+                for item in caps.get(1).unwrap().as_str().split(',') {
+                    if let Some(local) = item.split_whitespace().next() {
+                        listed_exports.push(local.to_string());
+                    }
+                }
+            }
+            continue;
+        }
+
         // 类定义
         if let Some(caps) = RE_CLASS.captures(stripped) {
             let class_name = caps.get(1).unwrap().as_str();
             let base_class = caps.get(2).map(|m| m.as_str());
             let class_id = format!("{}::class::{}", file_id, class_name);
 
-            let mut metadata = std::collections::HashMap::new();
+            let mut metadata = export_metadata(stripped);
             if let Some(base) = base_class {
                 metadata.insert("extends".to_string(), base.to_string());
             }
@@ -64,6 +103,7 @@ pub fn analyze_js_module(
         let func_name = RE_FUNC1.captures(stripped)
             .or_else(|| RE_FUNC2.captures(stripped))
             .or_else(|| RE_FUNC3.captures(stripped))
+            .or_else(|| RE_FUNC4.captures(stripped))
             .and_then(|caps| caps.get(1).map(|m| m.as_str().to_string()));
 
         if let Some(name) = func_name {
@@ -74,12 +114,25 @@ pub fn analyze_js_module(
                 node_type: "function".to_string(),
                 file_path: Some(file_path.to_string()),
                 line_number: Some(i + 1),
-                metadata: std::collections::HashMap::new(),
+                metadata: export_metadata(stripped),
             });
             graph.edges.push(GraphEdge::contains(file_id, &func_id));
             continue;
         }
 
+        // 导出常量（右值不是函数，函数已在上面处理）
+        if !line.starts_with(char::is_whitespace) {
+            if let Some(caps) = RE_CONST.captures(stripped) {
+                let name = caps.get(1).unwrap().as_str();
+                let const_id = format!("{}::const::{}", file_id, name);
+                let mut node = GraphNode::constant(&const_id, name, file_path, i + 1);
+                node.metadata = export_metadata(stripped);
+                graph.nodes.push(node);
+                graph.edges.push(GraphEdge::contains(file_id, &const_id));
+                continue;
+            }
+        }
+
         // 接口/类型定义（TypeScript）
         if let Some(caps) = RE_TYPE.captures(stripped) {
             let type_name = caps.get(1).unwrap().as_str();
@@ -90,9 +143,64 @@ pub fn analyze_js_module(
                 node_type: "interface".to_string(),
                 file_path: Some(file_path.to_string()),
                 line_number: Some(i + 1),
-                metadata: std::collections::HashMap::new(),
+                metadata: export_metadata(stripped),
             });
             graph.edges.push(GraphEdge::contains(file_id, &type_id));
         }
     }
+
+    // 补标通过导出列表导出的声明
+    if !listed_exports.is_empty() {
+        for node in graph.nodes.iter_mut() {
+            if node.metadata.contains_key("exported") && listed_exports.contains(&node.label) {
+                node.metadata.insert("exported".to_string(), "true".to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_js_export_metadata() {
+        let content = "export function foo() {}\nfunction bar() {}\nexport default class App {}\nfunction baz() {}\nexport { baz };\n";
+        let lines: Vec<&str> = content.lines().collect();
+        let mut graph = GraphData::default();
+        analyze_js_module(&mut graph, "file::a.js", content, &lines, "a.js");
+
+        let meta = |label: &str, key: &str| -> Option<String> {
+            graph
+                .nodes
+                .iter()
+                .find(|n| n.label == label)
+                .and_then(|n| n.metadata.get(key).cloned())
+        };
+        assert_eq!(meta("foo", "exported").as_deref(), Some("true"));
+        assert_eq!(meta("bar", "exported").as_deref(), Some("false"));
+        assert_eq!(meta("App", "exported").as_deref(), Some("true"));
+        assert_eq!(meta("App", "default").as_deref(), Some("true"));
+        assert_eq!(meta("foo", "default"), None);
+        assert_eq!(meta("baz", "exported").as_deref(), Some("true"));
+    }
+
+    #[test]
+    fn test_js_constants() {
+        let content = "export const API_URL = 'https://example.com';\nexport const handler = async (req) => req;\nexport const double = x => x * 2;\nexport const make = function() {};\nexport const LIMITS: Limits = { max: 1 };\n";
+        let lines: Vec<&str> = content.lines().collect();
+        let mut graph = GraphData::default();
+        analyze_js_module(&mut graph, "file::a.ts", content, &lines, "a.ts");
+
+        let of_type = |t: &str| -> Vec<String> {
+            graph
+                .nodes
+                .iter()
+                .filter(|n| n.node_type == t)
+                .map(|n| n.label.clone())
+                .collect()
+        };
+        assert_eq!(of_type("constant"), vec!["API_URL", "LIMITS"]);
+        assert_eq!(of_type("function"), vec!["handler", "double", "make"]);
+    }
 }
@@ -2,24 +2,104 @@
 //!
 //! 分析源代码以生成知识图谱
 
+pub mod cache;
+pub(crate) mod detect;
 mod generic;
 mod go;
-mod imports;
+pub(crate) mod imports;
 mod java;
 mod javascript;
+mod php;
 mod python;
+mod related;
+mod ruby;
+mod scala;
+mod svelte;
 pub mod types;
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(test)]
+use std::sync::Arc;
+use tracing::warn;
 use walkdir::WalkDir;
 
-use types::{GraphData, GraphEdge, GraphNode, IGNORED_DIRS, SUPPORTED_EXTENSIONS};
+pub use cache::ProjectGraphCache;
+use types::{FileImports, GraphData, GraphEdge, GraphNode, ResolvedImport, IGNORED_DIRS, SUPPORTED_EXTENSIONS};
+
+/// 代码分析错误类型
+#[derive(Debug, thiserror::Error)]
+pub enum AnalyzerError {
+    #[error("项目路径不存在: {0}")]
+    ProjectNotFound(PathBuf),
+
+    #[error("项目路径不是目录: {0}")]
+    NotADirectory(PathBuf),
+
+    #[error("没有权限访问: {0}")]
+    PermissionDenied(PathBuf),
+
+    #[error("文件不存在: {0}")]
+    FileNotFound(PathBuf),
+
+    #[error("IO错误 ({0}): {1}")]
+    IoError(PathBuf, #[source] std::io::Error),
+
+    #[error("源文件数量超过上限 {limit} 个，请确认项目路径是否正确")]
+    TooManyFiles { limit: usize },
+
+    #[error("未知的分析器语言: {0}（支持: {1}）")]
+    UnknownLanguage(String, String),
+}
+
+/// 可显式指定的分析器语言及其对应的分发扩展名（`generic` 为通用分析器）
+const ANALYZER_LANGUAGES: &[(&str, &str)] = &[
+    ("python", ".py"),
+    ("javascript", ".js"),
+    ("jsx", ".jsx"),
+    ("typescript", ".ts"),
+    ("tsx", ".tsx"),
+    ("vue", ".vue"),
+    ("java", ".java"),
+    ("go", ".go"),
+    ("ruby", ".rb"),
+    ("php", ".php"),
+    ("scala", ".scala"),
+    ("svelte", ".svelte"),
+    ("generic", ""),
+];
+
+/// 将语言名（不区分大小写）转换为分发使用的扩展名
+fn language_extension(language: &str) -> Result<&'static str, AnalyzerError> {
+    let language = language.trim().to_lowercase();
+    ANALYZER_LANGUAGES
+        .iter()
+        .find(|(name, _)| *name == language)
+        .map(|(_, ext)| *ext)
+        .ok_or_else(|| {
+            let supported: Vec<&str> = ANALYZER_LANGUAGES.iter().map(|(name, _)| *name).collect();
+            AnalyzerError::UnknownLanguage(language, supported.join(", "))
+        })
+}
+
+/// 默认最多分析的源文件数
+const DEFAULT_MAX_FILES: usize = 50_000;
 
 /// 代码分析器
 pub struct CodeAnalyzer {
     project_path: PathBuf,
+    /// 读取源文件内容的计数（测试中用于观测缓存效果）
+    #[cfg(test)]
+    read_counter: Option<Arc<AtomicUsize>>,
+    /// 收集源文件的数量上限
+    max_files: usize,
+    /// 是否从项目图谱中排除测试文件
+    exclude_tests: bool,
+    /// 是否按命名约定为相关文件添加 `related` 边
+    link_related: bool,
 }
 
 impl CodeAnalyzer {
@@ -27,149 +107,406 @@ impl CodeAnalyzer {
     pub fn new(project_path: impl Into<PathBuf>) -> Self {
         Self {
             project_path: project_path.into(),
+            #[cfg(test)]
+            read_counter: None,
+            max_files: DEFAULT_MAX_FILES,
+            exclude_tests: false,
+            link_related: true,
         }
     }
 
+    /// 是否按命名约定（如 `user_service.py` 与 `user_service_test.py`）添加 `related` 边，默认开启
+    pub fn with_related_files(mut self, link_related: bool) -> Self {
+        self.link_related = link_related;
+        self
+    }
+
+    /// 收集源文件时跳过测试文件（见 [`detect::is_test_path`]）
+    pub fn with_exclude_tests(mut self, exclude_tests: bool) -> Self {
+        self.exclude_tests = exclude_tests;
+        self
+    }
+
+    /// 设置源文件数量上限，收集时超出即返回 `TooManyFiles`
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    /// 注入文件读取计数器
+    #[cfg(test)]
+    fn with_read_counter(mut self, counter: Arc<AtomicUsize>) -> Self {
+        self.read_counter = Some(counter);
+        self
+    }
+
     /// 生成项目级概览图谱（文件/模块依赖）
-    pub fn analyze_project(&self) -> GraphData {
-        let mut graph = GraphData::default();
-        let mut file_map: HashMap<String, bool> = HashMap::new();
+    #[cfg_attr(not(test), allow(dead_code))] // 图谱接口统一走 analyze_project_cached
+    pub fn analyze_project(&self) -> Result<GraphData, AnalyzerError> {
+        let source_files = self.collect_source_files()?;
+        let mut graph = self.build_project_graph(&source_files);
+        self.apply_related_files(&mut graph);
+        Ok(graph)
+    }
+
+    /// 生成项目级概览图谱，优先使用缓存
+    ///
+    /// 缓存键为项目路径 + 源文件 mtime/大小指纹，任一源文件变化（含增删）都会重新分析。
+    pub fn analyze_project_cached(&self, cache: &ProjectGraphCache) -> Result<GraphData, AnalyzerError> {
+        let source_files = self.collect_source_files()?;
+        let fingerprint = cache::fingerprint_files(&source_files);
+        // 缓存的是不含 `related` 边的基础图谱，开关不同的请求可共用
+        let mut graph = match cache.get(&self.project_path, fingerprint) {
+            Some(graph) => graph,
+            None => {
+                let graph = self.build_project_graph(&source_files);
+                cache.insert(&self.project_path, fingerprint, graph.clone());
+                graph
+            }
+        };
+        self.apply_related_files(&mut graph);
+        Ok(graph)
+    }
 
-        // 收集所有源文件
-        let source_files = self.collect_source_files();
+    fn apply_related_files(&self, graph: &mut GraphData) {
+        if self.link_related {
+            related::link_related_files(graph);
+        }
+    }
+
+    /// 根据源文件列表构建项目图谱
+    fn build_project_graph(&self, source_files: &[PathBuf]) -> GraphData {
+        let mut graph = GraphData::default();
+        let mut project_files: HashSet<String> = HashSet::new();
 
         // 创建文件节点
-        for file_path in &source_files {
+        for file_path in source_files {
             let rel_path = self.relative_path(file_path);
-            let node_id = self.path_to_id(&rel_path);
-            let ext = file_path.extension().and_then(|e| e.to_str()).unwrap_or("");
-            let ext_with_dot = format!(".{}", ext);
+            let node_id = Self::path_to_id(&rel_path);
+            let ext_with_dot = detect::effective_extension(file_path).unwrap_or_else(|| ".".to_string());
 
-            let node = GraphNode::file(&node_id, file_path.file_name().unwrap().to_string_lossy(), &rel_path)
+            let mut node = GraphNode::file(&node_id, file_path.file_name().unwrap().to_string_lossy(), &rel_path)
                 .with_metadata("extension", &ext_with_dot)
                 .with_metadata("directory", file_path.parent().map(|p| self.relative_path(p)).unwrap_or_default())
                 .with_metadata("language", Self::ext_to_language(&ext_with_dot));
+            if detect::is_test_path(&rel_path) {
+                node = node.with_metadata("is_test", "true");
+            }
 
             graph.nodes.push(node);
-            file_map.insert(rel_path.clone(), true);
+            project_files.insert(rel_path.clone());
         }
 
-        // 分析导入关系
-        for file_path in &source_files {
+        // 分析导入关系（同一源/目标之间的重复导入合并为一条带计数的边）
+        let mut import_edges: HashMap<(String, String), usize> = HashMap::new();
+        // 每个文件定义的类节点，以及基类不在本文件中定义的继承关系（子类 id、基类名）
+        let mut file_classes: HashMap<String, Vec<GraphNode>> = HashMap::new();
+        let mut external_bases: Vec<(String, String, String)> = Vec::new();
+        for file_path in source_files {
             let rel_path = self.relative_path(file_path);
-            let source_id = self.path_to_id(&rel_path);
-            let ext = format!(".{}", file_path.extension().and_then(|e| e.to_str()).unwrap_or(""));
-
-            let content = match fs::read_to_string(file_path) {
-                Ok(c) => c,
+            let source_id = Self::path_to_id(&rel_path);
+            #[cfg(test)]
+            if let Some(counter) = &self.read_counter {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+            let content = match fs::read(file_path).and_then(|bytes| detect::decode_source(bytes, file_path)) {
+                Ok(c) => detect::normalize_source(&c).into_owned(),
                 Err(_) => continue,
             };
 
+            let ext = Self::import_extension(file_path, &content);
+
+            let module = Self::analyze_content(&rel_path, &content);
+            let local_ids: HashSet<&str> = module.nodes.iter().map(|n| n.id.as_str()).collect();
+            for edge in module.edges.iter().filter(|e| e.edge_type == "inherits") {
+                if !local_ids.contains(edge.target.as_str()) {
+                    let base = edge.target.rsplit("::").next().unwrap_or_default().to_string();
+                    external_bases.push((source_id.clone(), edge.source.clone(), base));
+                }
+            }
+            file_classes.insert(
+                source_id.clone(),
+                module.nodes.into_iter().filter(|n| n.node_type == "class").collect(),
+            );
+
             let import_infos = imports::extract_imports(&content, &ext, &rel_path);
             for imp in import_infos {
-                if let Some(resolved) = imports::resolve_import(&imp.path, &rel_path, &file_map) {
-                    let target_id = self.path_to_id(&resolved);
-                    graph.edges.push(GraphEdge::imports(&source_id, &target_id, &imp.display_name));
+                if let Some(resolved) = imports::resolve_import(&imp.path, &rel_path, &project_files) {
+                    let target_id = Self::path_to_id(&resolved);
+                    match import_edges.get(&(source_id.clone(), target_id.clone())) {
+                        Some(&i) => graph.edges[i].count += 1,
+                        None => {
+                            import_edges.insert((source_id.clone(), target_id.clone()), graph.edges.len());
+                            graph.edges.push(GraphEdge::imports(&source_id, &target_id, &imp.display_name));
+                        }
+                    }
                 }
             }
         }
+        for edge in graph.edges.iter_mut().filter(|e| e.count > 1) {
+            edge.label = format!("{} ×{}", edge.label, edge.count);
+        }
+
+        Self::link_imported_bases(&mut graph, &file_classes, &external_bases);
 
         // 添加目录分组
-        self.add_directory_groups(&mut graph, &source_files);
+        self.add_directory_groups(&mut graph, source_files);
 
         graph
     }
 
+    /// 选择导入解析使用的扩展名，没有扩展名时根据内容推断
+    fn import_extension(file_path: &Path, content: &str) -> String {
+        match file_path.extension().and_then(|e| e.to_str()) {
+            Some(ext) => format!(".{}", ext),
+            None => detect::detect_extension_from_content(content).unwrap_or("").to_string(),
+        }
+    }
+
+    /// 解析单个文件的导入语句，区分能解析到项目文件的导入和无法解析的导入
+    ///
+    /// 与项目图谱使用相同的解析规则，用于排查缺少跨文件依赖边的原因。
+    pub fn analyze_imports(&self, file_path: &str) -> Result<FileImports, AnalyzerError> {
+        let full_path = self.project_path.join(file_path);
+        if !full_path.is_file() {
+            return Err(AnalyzerError::FileNotFound(full_path));
+        }
+
+        let content = fs::read(&full_path)
+            .and_then(|bytes| detect::decode_source(bytes, &full_path))
+            .map_err(|e| AnalyzerError::IoError(full_path.clone(), e))?;
+        let content = detect::normalize_source(&content).into_owned();
+
+        let project_files: HashSet<String> = self
+            .collect_source_files()?
+            .iter()
+            .map(|path| self.relative_path(path))
+            .collect();
+
+        let rel_path = self.relative_path(&full_path);
+        let ext = Self::import_extension(&full_path, &content);
+        let mut result = FileImports::default();
+        for import in imports::extract_imports(&content, &ext, &rel_path) {
+            match imports::resolve_import(&import.path, &rel_path, &project_files) {
+                Some(target) => result.resolved.push(ResolvedImport { import, target }),
+                None => result.unresolved.push(import),
+            }
+        }
+        Ok(result)
+    }
+
     /// 生成模块级详细图谱
-    pub fn analyze_module(&self, file_path: &str) -> GraphData {
-        let mut graph = GraphData::default();
+    ///
+    /// 文件不存在或无法读取时返回错误；没有任何符号的文件返回只含文件节点的图谱。
+    pub fn analyze_module(&self, file_path: &str) -> Result<GraphData, AnalyzerError> {
+        self.analyze_module_with_language(file_path, None)
+    }
+
+    /// 生成模块级详细图谱，`language` 指定时跳过按扩展名选择分析器
+    pub fn analyze_module_with_language(
+        &self,
+        file_path: &str,
+        language: Option<&str>,
+    ) -> Result<GraphData, AnalyzerError> {
+        let ext = language.map(language_extension).transpose()?;
         let full_path = self.project_path.join(file_path);
 
         if !full_path.is_file() {
-            return graph;
+            return Err(AnalyzerError::FileNotFound(full_path));
         }
 
-        let content = match fs::read_to_string(&full_path) {
-            Ok(c) => c,
-            Err(_) => return graph,
-        };
+        let content = fs::read(&full_path)
+            .and_then(|bytes| detect::decode_source(bytes, &full_path))
+            .map_err(|e| AnalyzerError::IoError(full_path.clone(), e))?;
 
-        let ext = full_path.extension().and_then(|e| e.to_str()).unwrap_or("");
-        let ext_with_dot = format!(".{}", ext);
+        Ok(Self::analyze_content_as(file_path, &content, ext))
+    }
+
+    /// 直接分析给定的源码内容（无需文件存在于磁盘）
+    ///
+    /// `file_path` 用于选择语言分析器和生成节点 ID；没有扩展名时根据 shebang 等内容推断。
+    pub fn analyze_content(file_path: &str, content: &str) -> GraphData {
+        Self::analyze_content_as(file_path, content, None)
+    }
+
+    /// 直接分析给定的源码内容，`language` 指定时跳过按扩展名选择分析器
+    pub fn analyze_content_with_language(
+        file_path: &str,
+        content: &str,
+        language: Option<&str>,
+    ) -> Result<GraphData, AnalyzerError> {
+        let ext = language.map(language_extension).transpose()?;
+        Ok(Self::analyze_content_as(file_path, content, ext))
+    }
+
+    /// 按给定的分发扩展名分析内容，`ext` 为 None 时由文件路径和内容推断
+    fn analyze_content_as(file_path: &str, content: &str, ext: Option<&str>) -> GraphData {
+        let content = &*detect::normalize_source(content);
+        let mut graph = GraphData::default();
+        let path = Path::new(file_path);
+        let ext_with_dot = match (ext, path.extension().and_then(|e| e.to_str())) {
+            (Some(ext), _) => ext.to_string(),
+            (None, Some(ext)) => format!(".{}", ext),
+            (None, None) => detect::detect_extension_from_content(content).unwrap_or("").to_string(),
+        };
         let lines: Vec<&str> = content.lines().collect();
 
         // 文件根节点
-        let file_id = self.path_to_id(file_path);
-        graph.nodes.push(GraphNode::file(
-            &file_id,
-            full_path.file_name().unwrap().to_string_lossy(),
-            file_path,
-        ));
+        let file_id = Self::path_to_id(file_path);
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| file_path.to_string());
+        graph.nodes.push(GraphNode::file(&file_id, file_name, file_path));
 
         // 根据语言分发
         match ext_with_dot.as_str() {
-            ".py" => python::analyze_python_module(&mut graph, &file_id, &content, &lines, file_path),
+            ".py" => python::analyze_python_module(&mut graph, &file_id, content, &lines, file_path),
             ".js" | ".jsx" | ".ts" | ".tsx" | ".vue" => {
-                javascript::analyze_js_module(&mut graph, &file_id, &content, &lines, file_path)
+                javascript::analyze_js_module(&mut graph, &file_id, content, &lines, file_path)
             }
-            ".java" => java::analyze_java_module(&mut graph, &file_id, &content, &lines, file_path),
-            ".go" => go::analyze_go_module(&mut graph, &file_id, &content, &lines, file_path),
-            _ => generic::analyze_generic_module(&mut graph, &file_id, &content, &lines, file_path),
+            ".java" => java::analyze_java_module(&mut graph, &file_id, content, &lines, file_path),
+            ".go" => go::analyze_go_module(&mut graph, &file_id, content, &lines, file_path),
+            ".rb" => ruby::analyze_ruby_module(&mut graph, &file_id, content, &lines, file_path),
+            ".php" => php::analyze_php_module(&mut graph, &file_id, content, &lines, file_path),
+            ".scala" => scala::analyze_scala_module(&mut graph, &file_id, content, &lines, file_path),
+            ".svelte" => svelte::analyze_svelte_module(&mut graph, &file_id, content, &lines, file_path),
+            _ => generic::analyze_generic_module(&mut graph, &file_id, content, &lines, file_path),
         }
 
+        graph.dedup();
         graph
     }
 
     /// 收集所有源文件
-    fn collect_source_files(&self) -> Vec<PathBuf> {
+    ///
+    /// 项目根目录不存在、不是目录或无法读取时返回错误；子目录读取失败只记录警告并跳过。
+    fn collect_source_files(&self) -> Result<Vec<PathBuf>, AnalyzerError> {
+        let root = &self.project_path;
+        let meta = fs::metadata(root).map_err(|e| Self::io_error(root, e))?;
+        if !meta.is_dir() {
+            return Err(AnalyzerError::NotADirectory(root.clone()));
+        }
+
         let mut files = Vec::new();
 
-        for entry in WalkDir::new(&self.project_path)
+        for entry in WalkDir::new(root)
             .into_iter()
             .filter_entry(|e| {
                 let name = e.file_name().to_string_lossy();
                 !IGNORED_DIRS.contains(&name.as_ref())
             })
-            .filter_map(|e| e.ok())
         {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) if e.depth() == 0 => {
+                    let path = e.path().unwrap_or(root).to_path_buf();
+                    return Err(match e.into_io_error() {
+                        Some(io) => Self::io_error(&path, io),
+                        None => AnalyzerError::IoError(path, std::io::Error::other("walk error")),
+                    });
+                }
+                Err(e) => {
+                    warn!("Skipping unreadable path during analysis: {}", e);
+                    continue;
+                }
+            };
+
             if entry.file_type().is_file() {
-                if let Some(ext) = entry.path().extension().and_then(|e| e.to_str()) {
-                    let ext_with_dot = format!(".{}", ext);
-                    if SUPPORTED_EXTENSIONS.contains(&ext_with_dot.as_str()) {
+                if let Some(ext) = detect::effective_extension(entry.path()) {
+                    let is_excluded_test = self.exclude_tests
+                        && detect::is_test_path(&self.relative_path(entry.path()));
+                    if SUPPORTED_EXTENSIONS.contains(&ext.as_str()) && !is_excluded_test {
                         files.push(entry.into_path());
+                        if files.len() > self.max_files {
+                            return Err(AnalyzerError::TooManyFiles { limit: self.max_files });
+                        }
                     }
                 }
             }
         }
 
         files.sort();
-        files
+        Ok(files)
+    }
+
+    /// 将 IO 错误按类型映射为分析错误
+    fn io_error(path: &Path, err: std::io::Error) -> AnalyzerError {
+        match err.kind() {
+            std::io::ErrorKind::NotFound => AnalyzerError::ProjectNotFound(path.to_path_buf()),
+            std::io::ErrorKind::PermissionDenied => AnalyzerError::PermissionDenied(path.to_path_buf()),
+            _ => AnalyzerError::IoError(path.to_path_buf(), err),
+        }
+    }
+
+    /// 将跨文件继承关系解析到被导入文件中的类节点
+    ///
+    /// 基类只在当前文件导入的文件中按类名查找；找到时把子类、基类节点及继承边加入项目图谱，
+    /// 找不到（如第三方库的基类）则丢弃，避免产生悬空边。
+    fn link_imported_bases(
+        graph: &mut GraphData,
+        file_classes: &HashMap<String, Vec<GraphNode>>,
+        external_bases: &[(String, String, String)],
+    ) {
+        let mut added: HashSet<String> = HashSet::new();
+        let mut new_edges = Vec::new();
+
+        for (file_id, class_id, base) in external_bases {
+            let base_node = graph
+                .edges
+                .iter()
+                .filter(|e| e.edge_type == "imports" && &e.source == file_id)
+                .filter_map(|e| file_classes.get(&e.target).map(|classes| (&e.target, classes)))
+                .find_map(|(target_file, classes)| {
+                    classes.iter().find(|c| &c.label == base).map(|c| (target_file, c))
+                });
+            let child_node = file_classes
+                .get(file_id)
+                .and_then(|classes| classes.iter().find(|c| &c.id == class_id));
+
+            if let (Some((base_file, base_node)), Some(child_node)) = (base_node, child_node) {
+                for (owner, node) in [(file_id, child_node), (base_file, base_node)] {
+                    if added.insert(node.id.clone()) {
+                        graph.nodes.push(node.clone());
+                        new_edges.push(GraphEdge::contains(owner, &node.id));
+                    }
+                }
+                new_edges.push(GraphEdge::inherits(class_id, &base_node.id));
+            }
+        }
+
+        graph.edges.extend(new_edges);
     }
 
     /// 添加目录分组信息
+    ///
+    /// 为源文件所在目录及其所有上级目录创建 `dir::` 节点，并生成目录到直接子文件、
+    /// 上级目录到子目录的 `contains` 边（项目根目录不建节点）。
     fn add_directory_groups(&self, graph: &mut GraphData, files: &[PathBuf]) {
-        let mut dirs: HashSet<String> = HashSet::new();
+        let mut dirs: BTreeSet<String> = BTreeSet::new();
 
         for f in files {
             let rel = self.relative_path(f);
-            if let Some(dir) = Path::new(&rel).parent() {
-                let dir_str = dir.to_string_lossy().to_string();
-                if !dir_str.is_empty() && dir_str != "." {
-                    dirs.insert(dir_str);
+            let Some(parent) = parent_dir(&rel) else {
+                continue;
+            };
+            graph.edges.push(GraphEdge::contains(format!("dir::{}", parent), Self::path_to_id(&rel)));
+
+            let mut dir = Some(parent);
+            while let Some(d) = dir {
+                if !dirs.insert(d.to_string()) {
+                    break;
                 }
+                dir = parent_dir(d);
             }
         }
 
-        for d in dirs {
-            let dir_name = Path::new(&d)
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or(&d);
-            graph.nodes.push(GraphNode::directory(
-                format!("dir::{}", d),
-                dir_name,
-                &d,
-            ));
+        for d in &dirs {
+            let dir_name = d.rsplit('/').next().unwrap_or(d);
+            graph.nodes.push(GraphNode::directory(format!("dir::{}", d), dir_name, d));
+            if let Some(parent) = parent_dir(d) {
+                graph.edges.push(GraphEdge::contains(format!("dir::{}", parent), format!("dir::{}", d)));
+            }
         }
     }
 
@@ -182,7 +519,7 @@ impl CodeAnalyzer {
     }
 
     /// 路径转节点 ID
-    fn path_to_id(&self, path: &str) -> String {
+    fn path_to_id(path: &str) -> String {
         format!("file::{}", path.replace('\\', "/").replace(' ', "_"))
     }
 
@@ -203,8 +540,316 @@ impl CodeAnalyzer {
             ".hpp" => "C++ Header",
             ".cs" => "C#",
             ".rb" => "Ruby",
+            ".php" => "PHP",
             ".vue" => "Vue",
+            ".scala" => "Scala",
+            ".svelte" => "Svelte",
             _ => "Unknown",
         }
     }
 }
+
+/// `/` 分隔的相对路径的上级目录，位于项目根目录时返回 `None`
+fn parent_dir(path: &str) -> Option<&str> {
+    path.rsplit_once('/').map(|(parent, _)| parent).filter(|p| !p.is_empty() && *p != ".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_analyze_module_missing_file() {
+        let dir = TempDir::new().unwrap();
+        let analyzer = CodeAnalyzer::new(dir.path());
+
+        let result = analyzer.analyze_module("missing.py");
+        assert!(matches!(result, Err(AnalyzerError::FileNotFound(_))));
+    }
+
+    #[test]
+    fn test_language_override() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("notes.txt"), "class Parser:\n    def parse(self):\n        pass\n").unwrap();
+        let analyzer = CodeAnalyzer::new(dir.path());
+
+        let graph = analyzer.analyze_module_with_language("notes.txt", Some("Python")).unwrap();
+        let has = |node_type: &str, label: &str| {
+            graph.nodes.iter().any(|n| n.node_type == node_type && n.label == label)
+        };
+        assert!(has("class", "Parser"));
+        assert!(has("method", "parse"));
+
+        assert!(matches!(
+            analyzer.analyze_module_with_language("notes.txt", Some("cobol")),
+            Err(AnalyzerError::UnknownLanguage(..))
+        ));
+    }
+
+    #[test]
+    fn test_analyze_imports_resolved_and_unresolved() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("app")).unwrap();
+        fs::write(dir.path().join("app/utils.py"), "def helper():\n    pass\n").unwrap();
+        fs::write(
+            dir.path().join("app/main.py"),
+            "import requests\nfrom app.utils import helper\n",
+        )
+        .unwrap();
+
+        let imports = CodeAnalyzer::new(dir.path()).analyze_imports("app/main.py").unwrap();
+        let resolved: Vec<(&str, &str)> = imports
+            .resolved
+            .iter()
+            .map(|r| (r.import.path.as_str(), r.target.as_str()))
+            .collect();
+        assert_eq!(resolved, vec![("app.utils", "app/utils.py")]);
+        let unresolved: Vec<&str> = imports.unresolved.iter().map(|i| i.path.as_str()).collect();
+        assert_eq!(unresolved, vec!["requests"]);
+    }
+
+    #[test]
+    fn test_analyze_module_empty_file() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("empty.py"), "").unwrap();
+        let analyzer = CodeAnalyzer::new(dir.path());
+
+        let graph = analyzer.analyze_module("empty.py").unwrap();
+        // 只有文件根节点，没有任何符号
+        assert_eq!(graph.nodes.len(), 1);
+        assert!(graph.edges.is_empty());
+    }
+
+    #[test]
+    fn test_analyze_content_python() {
+        let content = "class Foo:\n    def bar(self):\n        pass\n\ndef baz():\n    pass\n";
+        let graph = CodeAnalyzer::analyze_content("pkg/mod.py", content);
+
+        let labels: Vec<(&str, &str)> = graph
+            .nodes
+            .iter()
+            .map(|n| (n.node_type.as_str(), n.label.as_str()))
+            .collect();
+        assert!(labels.contains(&("file", "mod.py")));
+        assert!(labels.contains(&("class", "Foo")));
+        assert!(labels.contains(&("function", "baz")));
+        assert!(graph.nodes.iter().any(|n| n.id == "file::pkg/mod.py::class::Foo::method::bar"));
+    }
+
+    #[test]
+    fn test_analyze_module_merges_duplicate_nodes() {
+        let dir = TempDir::new().unwrap();
+        // property 的 getter/setter 会生成相同的方法 id；继承自身会生成自环边
+        let content = "class Foo(Foo):\n    @property\n    def size(self):\n        pass\n\n    @size.setter\n    async def size(self, v):\n        pass\n";
+        fs::write(dir.path().join("a.py"), content).unwrap();
+        let analyzer = CodeAnalyzer::new(dir.path());
+
+        let graph = analyzer.analyze_module("a.py").unwrap();
+        let size: Vec<&GraphNode> = graph.nodes.iter().filter(|n| n.label == "size").collect();
+        assert_eq!(size.len(), 1);
+        assert_eq!(size[0].line_number, Some(3));
+        assert_eq!(size[0].metadata.get("decorators").map(String::as_str), Some("property"));
+        assert_eq!(size[0].metadata.get("async").map(String::as_str), Some("true"));
+        assert!(graph.edges.iter().all(|e| e.source != e.target));
+    }
+
+    #[test]
+    fn test_bom_and_crlf_match_clean_source() {
+        let clean = "import os\n\nclass Foo(Base):\n    def bar(self):\n        pass\n\ndef baz():\n    pass\n";
+        let messy = format!("\u{feff}{}", clean.replace('\n', "\r\n"));
+
+        let clean_dir = TempDir::new().unwrap();
+        let messy_dir = TempDir::new().unwrap();
+        fs::write(clean_dir.path().join("a.py"), clean).unwrap();
+        fs::write(messy_dir.path().join("a.py"), messy).unwrap();
+
+        let clean_graph = CodeAnalyzer::new(clean_dir.path()).analyze_module("a.py").unwrap();
+        let messy_graph = CodeAnalyzer::new(messy_dir.path()).analyze_module("a.py").unwrap();
+        assert!(clean_graph.nodes.iter().any(|n| n.label == "bar" && n.line_number == Some(4)));
+        assert_eq!(
+            serde_json::to_value(&clean_graph).unwrap(),
+            serde_json::to_value(&messy_graph).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_gbk_source_is_analyzed() {
+        let dir = TempDir::new().unwrap();
+        // GBK 编码："# 中文注释" 和 "你好"
+        let mut content = b"# \xd6\xd0\xce\xc4\xd7\xa2\xca\xcd\n".to_vec();
+        content.extend_from_slice(b"def hello():\n    return '\xc4\xe3\xba\xc3'\n");
+        fs::write(dir.path().join("legacy.py"), content).unwrap();
+        let analyzer = CodeAnalyzer::new(dir.path());
+
+        let graph = analyzer.analyze_module("legacy.py").unwrap();
+        assert!(graph.nodes.iter().any(|n| n.node_type == "function" && n.label == "hello" && n.line_number == Some(2)));
+
+        let project = analyzer.analyze_project().unwrap();
+        assert!(project.nodes.iter().any(|n| n.node_type == "file" && n.label == "legacy.py"));
+    }
+
+    #[test]
+    fn test_test_files_tagged_and_excludable() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("foo.ts"), "export function foo() {}\n").unwrap();
+        fs::write(dir.path().join("foo.test.ts"), "import { foo } from './foo';\n").unwrap();
+
+        let graph = CodeAnalyzer::new(dir.path()).analyze_project().unwrap();
+        let is_test = |label: &str| {
+            graph.nodes.iter().find(|n| n.label == label).unwrap().metadata.get("is_test").cloned()
+        };
+        assert_eq!(is_test("foo.test.ts").as_deref(), Some("true"));
+        assert_eq!(is_test("foo.ts"), None);
+
+        let graph = CodeAnalyzer::new(dir.path()).with_exclude_tests(true).analyze_project().unwrap();
+        assert!(graph.nodes.iter().any(|n| n.label == "foo.ts"));
+        assert!(!graph.nodes.iter().any(|n| n.label == "foo.test.ts"));
+    }
+
+    #[test]
+    fn test_related_files_linked_by_naming() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("tests")).unwrap();
+        fs::write(dir.path().join("user_service.py"), "def get_user():\n    pass\n").unwrap();
+        fs::write(dir.path().join("tests/test_user_service.py"), "def test_get_user():\n    pass\n").unwrap();
+        fs::write(dir.path().join("billing.py"), "def charge():\n    pass\n").unwrap();
+
+        let graph = CodeAnalyzer::new(dir.path()).analyze_project().unwrap();
+        let related: Vec<_> = graph.edges.iter().filter(|e| e.edge_type == "related").collect();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].source, CodeAnalyzer::path_to_id("user_service.py"));
+        assert_eq!(related[0].target, CodeAnalyzer::path_to_id("tests/test_user_service.py"));
+
+        let graph = CodeAnalyzer::new(dir.path()).with_related_files(false).analyze_project().unwrap();
+        assert!(!graph.edges.iter().any(|e| e.edge_type == "related"));
+    }
+
+    #[test]
+    fn test_directories_contain_files() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("src/api")).unwrap();
+        fs::write(dir.path().join("main.py"), "def main():\n    pass\n").unwrap();
+        fs::write(dir.path().join("src/api/users.py"), "def users():\n    pass\n").unwrap();
+
+        let graph = CodeAnalyzer::new(dir.path()).analyze_project().unwrap();
+        let has_contains = |source: &str, target: &str| {
+            graph.edges.iter().any(|e| e.source == source && e.target == target && e.edge_type == "contains")
+        };
+        assert!(has_contains("dir::src/api", &CodeAnalyzer::path_to_id("src/api/users.py")));
+        // 中间目录也有节点，并包含子目录
+        assert!(graph.nodes.iter().any(|n| n.id == "dir::src" && n.node_type == "directory"));
+        assert!(has_contains("dir::src", "dir::src/api"));
+        // 根目录下的文件没有目录节点
+        let main_id = CodeAnalyzer::path_to_id("main.py");
+        assert!(!graph.edges.iter().any(|e| e.target == main_id && e.edge_type == "contains"));
+    }
+
+    #[test]
+    fn test_extensionless_script_uses_shebang() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("deploy"), "#!/usr/bin/env python3\ndef main():\n    pass\n").unwrap();
+        fs::write(dir.path().join("Makefile"), "all:\n\tcargo build\n").unwrap();
+        let analyzer = CodeAnalyzer::new(dir.path());
+
+        let graph = analyzer.analyze_module("deploy").unwrap();
+        assert!(graph.nodes.iter().any(|n| n.node_type == "function" && n.label == "main"));
+
+        let project = analyzer.analyze_project().unwrap();
+        let file_nodes: Vec<_> = project.nodes.iter().filter(|n| n.node_type == "file").collect();
+        assert_eq!(file_nodes.len(), 1);
+        assert_eq!(file_nodes[0].label, "deploy");
+        assert_eq!(file_nodes[0].metadata.get("language").map(String::as_str), Some("Python"));
+    }
+
+    #[test]
+    fn test_analyze_project_merges_repeated_imports() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.js"), "import { f } from './b';\nimport { g } from './b';\n").unwrap();
+        fs::write(dir.path().join("b.js"), "export function f() {}\nexport function g() {}\n").unwrap();
+        let analyzer = CodeAnalyzer::new(dir.path());
+
+        let graph = analyzer.analyze_project().unwrap();
+        let imports: Vec<&GraphEdge> = graph.edges.iter().filter(|e| e.edge_type == "imports").collect();
+        assert_eq!(imports.len(), 1);
+        assert_eq!(imports[0].count, 2);
+        assert_eq!(imports[0].label, "b ×2");
+    }
+
+    #[test]
+    fn test_analyze_project_resolves_imported_base_class() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("base.js"), "export class Base {}\n").unwrap();
+        fs::write(
+            dir.path().join("child.js"),
+            "import { Base } from './base';\nclass Local {}\nclass Child extends Base {}\nclass Inner extends Local {}\nclass Ext extends Component {}\n",
+        )
+        .unwrap();
+        let analyzer = CodeAnalyzer::new(dir.path());
+
+        let graph = analyzer.analyze_project().unwrap();
+        let inherits: Vec<(&str, &str)> = graph
+            .edges
+            .iter()
+            .filter(|e| e.edge_type == "inherits")
+            .map(|e| (e.source.as_str(), e.target.as_str()))
+            .collect();
+        assert_eq!(inherits, vec![("file::child.js::class::Child", "file::base.js::class::Base")]);
+        let ids: HashSet<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert!(graph.edges.iter().all(|e| ids.contains(e.source.as_str()) && ids.contains(e.target.as_str())));
+
+        // 模块图谱中同文件内的继承保持不变
+        let module = analyzer.analyze_module("child.js").unwrap();
+        assert!(module
+            .edges
+            .iter()
+            .any(|e| e.edge_type == "inherits" && e.target == "file::child.js::class::Local"));
+    }
+
+    #[test]
+    fn test_analyze_project_too_many_files() {
+        let dir = TempDir::new().unwrap();
+        for name in ["a.py", "b.py", "c.py"] {
+            fs::write(dir.path().join(name), "x = 1\n").unwrap();
+        }
+
+        let result = CodeAnalyzer::new(dir.path()).with_max_files(2).analyze_project();
+        assert!(matches!(result, Err(AnalyzerError::TooManyFiles { limit: 2 })));
+        assert!(CodeAnalyzer::new(dir.path()).with_max_files(3).analyze_project().is_ok());
+    }
+
+    #[test]
+    fn test_analyze_project_missing_dir() {
+        let dir = TempDir::new().unwrap();
+        let analyzer = CodeAnalyzer::new(dir.path().join("nope"));
+
+        let result = analyzer.analyze_project();
+        assert!(matches!(result, Err(AnalyzerError::ProjectNotFound(_))));
+    }
+
+    #[test]
+    fn test_analyze_project_cached() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.py"), "import b\n").unwrap();
+        fs::write(dir.path().join("b.py"), "def f():\n    pass\n").unwrap();
+
+        let cache = ProjectGraphCache::default();
+        let reads = Arc::new(AtomicUsize::new(0));
+        let analyzer = CodeAnalyzer::new(dir.path()).with_read_counter(reads.clone());
+
+        let first = analyzer.analyze_project_cached(&cache).unwrap();
+        assert_eq!(reads.load(Ordering::Relaxed), 2);
+
+        // 文件未变化：命中缓存，不再读取文件
+        let second = analyzer.analyze_project_cached(&cache).unwrap();
+        assert_eq!(reads.load(Ordering::Relaxed), 2);
+        assert_eq!(first.nodes.len(), second.nodes.len());
+        assert_eq!(first.edges.len(), second.edges.len());
+
+        // 文件变化：重新分析
+        fs::write(dir.path().join("b.py"), "def f():\n    return 1\n").unwrap();
+        analyzer.analyze_project_cached(&cache).unwrap();
+        assert_eq!(reads.load(Ordering::Relaxed), 4);
+        assert_eq!(cache.len(), 1);
+    }
+}
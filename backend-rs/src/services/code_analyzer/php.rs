@@ -0,0 +1,194 @@
+//! PHP 语言分析
+
+use regex::Regex;
+use once_cell::sync::Lazy;
+
+use super::types::{GraphData, GraphEdge, GraphNode};
+
+static RE_CLASS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:(?:abstract|final|readonly)\s+)*class\s+(\w+)(?:\s+extends\s+([\w\\]+))?(?:\s+implements\s+([\w\\,\s]+))?").unwrap()
+});
+static RE_INTERFACE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^interface\s+(\w+)(?:\s+extends\s+([\w\\,\s]+))?").unwrap()
+});
+static RE_FUNCTION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:(?:public|private|protected|static|abstract|final)\s+)*function\s+&?(\w+)\s*\(").unwrap()
+});
+
+/// 取名称中最后一段（去掉命名空间前缀）
+fn short_name(name: &str) -> &str {
+    name.trim().rsplit('\\').next().unwrap_or(name)
+}
+
+/// 分析 PHP 模块
+///
+/// 通过花括号深度判断 `function` 是否位于 class/interface 内部。
+pub fn analyze_php_module(
+    graph: &mut GraphData,
+    file_id: &str,
+    _content: &str,
+    lines: &[&str],
+    file_path: &str,
+) {
+    let mut depth: i32 = 0;
+    // 当前所在的 class/interface：(声明时的深度, 节点 ID, 名称)
+    let mut current: Option<(i32, String, String)> = None;
+    // 是否已进入当前 class 的花括号（左花括号可能在下一行）
+    let mut entered = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        let stripped = line.trim();
+
+        if let Some((class_depth, _, _)) = &current {
+            if depth > *class_depth {
+                entered = true;
+            } else if entered {
+                current = None;
+            }
+        }
+
+        if let Some(caps) = RE_CLASS.captures(stripped) {
+            let class_name = caps.get(1).unwrap().as_str();
+            let class_id = format!("{}::class::{}", file_id, class_name);
+
+            graph.nodes.push(GraphNode {
+                id: class_id.clone(),
+                label: class_name.to_string(),
+                node_type: "class".to_string(),
+                file_path: Some(file_path.to_string()),
+                line_number: Some(i + 1),
+                metadata: std::collections::HashMap::new(),
+            });
+            graph.edges.push(GraphEdge::contains(file_id, &class_id));
+
+            if let Some(base) = caps.get(2) {
+                let base_id = format!("{}::class::{}", file_id, short_name(base.as_str()));
+                graph.edges.push(GraphEdge::inherits(&class_id, &base_id));
+            }
+            if let Some(ifaces) = caps.get(3) {
+                for iface in ifaces.as_str().split(',').map(short_name).filter(|s| !s.is_empty()) {
+                    let iface_id = format!("{}::interface::{}", file_id, iface);
+                    graph.edges.push(GraphEdge::new(&class_id, &iface_id, "implements", "implements"));
+                }
+            }
+
+            current = Some((depth, class_id, class_name.to_string()));
+            entered = false;
+        } else if let Some(caps) = RE_INTERFACE.captures(stripped) {
+            let iface_name = caps.get(1).unwrap().as_str();
+            let iface_id = format!("{}::interface::{}", file_id, iface_name);
+
+            graph.nodes.push(GraphNode {
+                id: iface_id.clone(),
+                label: iface_name.to_string(),
+                node_type: "interface".to_string(),
+                file_path: Some(file_path.to_string()),
+                line_number: Some(i + 1),
+                metadata: std::collections::HashMap::new(),
+            });
+            graph.edges.push(GraphEdge::contains(file_id, &iface_id));
+
+            if let Some(parents) = caps.get(2) {
+                for parent in parents.as_str().split(',').map(short_name).filter(|s| !s.is_empty()) {
+                    let parent_id = format!("{}::interface::{}", file_id, parent);
+                    graph.edges.push(GraphEdge::inherits(&iface_id, &parent_id));
+                }
+            }
+
+            current = Some((depth, iface_id, iface_name.to_string()));
+            entered = false;
+        } else if let Some(caps) = RE_FUNCTION.captures(stripped) {
+            let func_name = caps.get(1).unwrap().as_str();
+
+            match &current {
+                Some((_, class_id, class_name)) => {
+                    let func_id = format!("{}::method::{}", class_id, func_name);
+                    let mut metadata = std::collections::HashMap::new();
+                    metadata.insert("class".to_string(), class_name.clone());
+                    graph.nodes.push(GraphNode {
+                        id: func_id.clone(),
+                        label: func_name.to_string(),
+                        node_type: "method".to_string(),
+                        file_path: Some(file_path.to_string()),
+                        line_number: Some(i + 1),
+                        metadata,
+                    });
+                    graph.edges.push(GraphEdge::new(class_id, &func_id, "contains", "has method"));
+                }
+                None => {
+                    let func_id = format!("{}::func::{}", file_id, func_name);
+                    graph.nodes.push(GraphNode {
+                        id: func_id.clone(),
+                        label: func_name.to_string(),
+                        node_type: "function".to_string(),
+                        file_path: Some(file_path.to_string()),
+                        line_number: Some(i + 1),
+                        metadata: std::collections::HashMap::new(),
+                    });
+                    graph.edges.push(GraphEdge::contains(file_id, &func_id));
+                }
+            }
+        }
+
+        depth += line.matches('{').count() as i32 - line.matches('}').count() as i32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_php_module() {
+        let content = r#"<?php
+namespace App\Models;
+
+interface Payable
+{
+    public function pay(): void;
+}
+
+final class Invoice extends \App\Base implements Payable, \Countable
+{
+    public function pay(): void
+    {
+        if ($this->due) {
+            return;
+        }
+    }
+
+    private static function build(array $attrs)
+    {
+    }
+}
+
+function helper($x) {
+    return $x;
+}
+"#;
+        let lines: Vec<&str> = content.lines().collect();
+        let mut graph = GraphData::default();
+        analyze_php_module(&mut graph, "file::a.php", content, &lines, "a.php");
+
+        let count = |label: &str, node_type: &str| {
+            graph.nodes.iter().filter(|n| n.label == label && n.node_type == node_type).count()
+        };
+        assert_eq!(count("Payable", "interface"), 1);
+        assert_eq!(count("Invoice", "class"), 1);
+        assert_eq!(count("pay", "method"), 2);
+        assert_eq!(count("build", "method"), 1);
+        assert_eq!(count("helper", "function"), 1);
+
+        let has_edge = |source: &str, target: &str, edge_type: &str| {
+            graph.edges.iter().any(|e| e.source == source && e.target == target && e.edge_type == edge_type)
+        };
+        assert!(has_edge("file::a.php::class::Invoice", "file::a.php::class::Base", "inherits"));
+        assert!(has_edge("file::a.php::class::Invoice", "file::a.php::interface::Payable", "implements"));
+        assert!(has_edge("file::a.php::class::Invoice", "file::a.php::interface::Countable", "implements"));
+        assert!(has_edge(
+            "file::a.php::class::Invoice",
+            "file::a.php::class::Invoice::method::build",
+            "contains"
+        ));
+    }
+}
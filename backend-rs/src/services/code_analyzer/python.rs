@@ -10,7 +10,14 @@ static RE_CLASS: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"^class\s+(\w+)\s*(?:\(([^)]*)\))?:").unwrap()
 });
 static RE_FUNC: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"^(\s*)def\s+(\w+)\s*\(").unwrap()
+    Regex::new(r"^(\s*)(async\s+)?def\s+(\w+)\s*\(").unwrap()
+});
+static RE_DECORATOR: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^@([\w.]+)").unwrap()
+});
+
+static RE_CONST: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^([A-Z][A-Z0-9_]*)\s*(?::[^=]+)?=([^=].*)$").unwrap()
 });
 
 /// 分析 Python 模块
@@ -23,10 +30,18 @@ pub fn analyze_python_module(
 ) {
     let mut current_class: Option<String> = None;
     let mut current_class_id: Option<String> = None;
+    // 尚未附加到定义上的装饰器
+    let mut pending_decorators: Vec<String> = Vec::new();
 
     for (i, line) in lines.iter().enumerate() {
         let stripped = line.trim();
 
+        // 装饰器：记录后附加到下一个函数/类定义，不影响类上下文
+        if let Some(caps) = RE_DECORATOR.captures(stripped) {
+            pending_decorators.push(caps.get(1).unwrap().as_str().to_string());
+            continue;
+        }
+
         // 类定义
         if let Some(caps) = RE_CLASS.captures(stripped) {
             let class_name = caps.get(1).unwrap().as_str();
@@ -45,9 +60,13 @@ pub fn analyze_python_module(
                 metadata: {
                     let mut m = std::collections::HashMap::new();
                     m.insert("bases".to_string(), bases.to_string());
+                    if !pending_decorators.is_empty() {
+                        m.insert("decorators".to_string(), pending_decorators.join(","));
+                    }
                     m
                 },
             };
+            pending_decorators.clear();
             graph.nodes.push(node);
             graph.edges.push(GraphEdge::contains(file_id, &class_id));
 
@@ -67,16 +86,25 @@ pub fn analyze_python_module(
         // 函数/方法定义
         if let Some(caps) = RE_FUNC.captures(stripped) {
             let _indent_str = caps.get(1).unwrap().as_str();
-            let func_name = caps.get(2).unwrap().as_str();
+            let is_async = caps.get(2).is_some();
+            let func_name = caps.get(3).unwrap().as_str();
 
             // 计算原始行的缩进
             let indent = line.len() - line.trim_start().len();
 
+            let mut metadata = std::collections::HashMap::new();
+            if is_async {
+                metadata.insert("async".to_string(), "true".to_string());
+            }
+            if !pending_decorators.is_empty() {
+                metadata.insert("decorators".to_string(), pending_decorators.join(","));
+                pending_decorators.clear();
+            }
+
             if indent > 0 {
                 if let Some(ref cls_id) = current_class_id {
                     // 方法
                     let func_id = format!("{}::method::{}", cls_id, func_name);
-                    let mut metadata = std::collections::HashMap::new();
                     metadata.insert("class".to_string(), current_class.clone().unwrap_or_default());
                     graph.nodes.push(GraphNode {
                         id: func_id.clone(),
@@ -99,19 +127,99 @@ pub fn analyze_python_module(
                     node_type: "function".to_string(),
                     file_path: Some(file_path.to_string()),
                     line_number: Some(i + 1),
-                    metadata: std::collections::HashMap::new(),
+                    metadata,
                 });
                 graph.edges.push(GraphEdge::contains(file_id, &func_id));
             }
             continue;
         }
 
-        // 重置类上下文：遇到顶层非缩进的非注释代码
-        if !stripped.is_empty() && !line.starts_with(char::is_whitespace) && !stripped.starts_with('#') {
-            if !stripped.starts_with("class ") && !stripped.starts_with("def ") {
-                current_class = None;
-                current_class_id = None;
+        // 模块级常量（UPPER_CASE = ...，排除 lambda 赋值）
+        if !line.starts_with(char::is_whitespace) {
+            if let Some(caps) = RE_CONST.captures(stripped) {
+                let rhs = caps.get(2).unwrap().as_str().trim();
+                if !rhs.starts_with("lambda") {
+                    let name = caps.get(1).unwrap().as_str();
+                    let const_id = format!("{}::const::{}", file_id, name);
+                    graph.nodes.push(GraphNode::constant(&const_id, name, file_path, i + 1));
+                    graph.edges.push(GraphEdge::contains(file_id, &const_id));
+                }
             }
         }
+
+        // 重置类上下文：遇到顶层非缩进的非注释代码
+        if !stripped.is_empty()
+            && !line.starts_with(char::is_whitespace)
+            && !stripped.starts_with('#')
+            && !stripped.starts_with("class ")
+            && !stripped.starts_with("def ")
+            && !stripped.starts_with("async def ")
+        {
+            current_class = None;
+            current_class_id = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze(content: &str) -> GraphData {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut graph = GraphData::default();
+        analyze_python_module(&mut graph, "file::m.py", content, &lines, "m.py");
+        graph
+    }
+
+    #[test]
+    fn test_async_method_in_class() {
+        let graph = analyze("class Client:\n    @retry\n    async def fetch(self):\n        pass\n\n    def close(self):\n        pass\n");
+
+        let fetch = graph
+            .nodes
+            .iter()
+            .find(|n| n.id == "file::m.py::class::Client::method::fetch")
+            .expect("async method should be detected");
+        assert_eq!(fetch.metadata.get("async").map(String::as_str), Some("true"));
+        assert_eq!(fetch.metadata.get("decorators").map(String::as_str), Some("retry"));
+        // 装饰器不应重置类上下文
+        assert!(graph.nodes.iter().any(|n| n.id == "file::m.py::class::Client::method::close"));
+    }
+
+    #[test]
+    fn test_decorated_top_level_function() {
+        let graph = analyze("@app.route(\"/users\")\n@login_required\nasync def list_users():\n    pass\n");
+
+        let func = graph
+            .nodes
+            .iter()
+            .find(|n| n.id == "file::m.py::func::list_users")
+            .expect("decorated function should be detected");
+        assert_eq!(func.node_type, "function");
+        assert_eq!(
+            func.metadata.get("decorators").map(String::as_str),
+            Some("app.route,login_required")
+        );
+    }
+
+    #[test]
+    fn test_python_constants() {
+        let content = "MAX_RETRIES = 3\nTIMEOUT: float = 1.5\nHANDLER = lambda x: x\nlower = 1\nclass Foo:\n    LIMIT = 1\n";
+        let lines: Vec<&str> = content.lines().collect();
+        let mut graph = GraphData::default();
+        analyze_python_module(&mut graph, "file::m.py", content, &lines, "m.py");
+
+        let constants: Vec<&str> = graph
+            .nodes
+            .iter()
+            .filter(|n| n.node_type == "constant")
+            .map(|n| n.label.as_str())
+            .collect();
+        assert_eq!(constants, vec!["MAX_RETRIES", "TIMEOUT"]);
+        assert!(graph
+            .edges
+            .iter()
+            .any(|e| e.source == "file::m.py" && e.target == "file::m.py::const::MAX_RETRIES"));
     }
 }
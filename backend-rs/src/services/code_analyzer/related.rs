@@ -0,0 +1,86 @@
+//! 基于命名约定的相关文件识别
+//!
+//! 去掉扩展名、角色后缀（`.model`、`.controller`）和测试标记（`_test`、`test_`、`.spec`）后
+//! 主干相同的文件视为相关，如 `user_service.py` 与 `user_service_test.py`、`user.model.ts` 与 `user.controller.ts`。
+
+use std::collections::BTreeMap;
+
+use super::detect;
+use super::types::{GraphData, GraphEdge};
+
+/// 过于通用、不能说明文件相关的主干
+const GENERIC_STEMS: &[&str] = &[
+    "index", "main", "mod", "lib", "__init__", "init", "utils", "util", "types", "app", "setup", "conftest", "test",
+];
+
+/// 同一主干的文件超过该数量时视为通用命名，不建立关联
+const MAX_GROUP_SIZE: usize = 6;
+
+/// 文件的命名主干（小写）
+fn naming_stem(file_name: &str) -> String {
+    // `user.model.ts` -> `user`
+    let base = file_name.split('.').next().unwrap_or_default();
+    // Java 等语言的 `UserServiceTest`
+    let base = base
+        .strip_suffix("Tests")
+        .or_else(|| base.strip_suffix("Test"))
+        .filter(|s| !s.is_empty())
+        .unwrap_or(base)
+        .to_lowercase();
+
+    let base = base.strip_prefix("test_").unwrap_or(&base);
+    base.strip_suffix("_test")
+        .or_else(|| base.strip_suffix("_tests"))
+        .or_else(|| base.strip_suffix("_spec"))
+        .unwrap_or(base)
+        .to_string()
+}
+
+/// 为主干相同的文件节点添加 `related` 边
+///
+/// 每组以路径排序后第一个非测试文件为中心，连向组内其他文件，避免组内两两连边。
+pub fn link_related_files(graph: &mut GraphData) {
+    let mut groups: BTreeMap<String, Vec<(&str, &str)>> = BTreeMap::new();
+    for node in graph.nodes.iter().filter(|n| n.node_type == "file") {
+        let Some(path) = node.file_path.as_deref() else {
+            continue;
+        };
+        let stem = naming_stem(&node.label);
+        if stem.is_empty() || GENERIC_STEMS.contains(&stem.as_str()) {
+            continue;
+        }
+        groups.entry(stem).or_default().push((path, node.id.as_str()));
+    }
+
+    let mut edges = Vec::new();
+    for files in groups.values_mut() {
+        if files.len() < 2 || files.len() > MAX_GROUP_SIZE {
+            continue;
+        }
+        files.sort();
+        let hub = files.iter().position(|(path, _)| !detect::is_test_path(path)).unwrap_or(0);
+        let (_, hub_id) = files[hub];
+        for (i, (_, id)) in files.iter().enumerate() {
+            if i != hub {
+                edges.push(GraphEdge::new(hub_id, *id, "related", "naming"));
+            }
+        }
+    }
+    graph.edges.extend(edges);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_naming_stem() {
+        assert_eq!(naming_stem("user_service.py"), "user_service");
+        assert_eq!(naming_stem("user_service_test.py"), "user_service");
+        assert_eq!(naming_stem("test_user_service.py"), "user_service");
+        assert_eq!(naming_stem("user.model.ts"), "user");
+        assert_eq!(naming_stem("user.controller.spec.ts"), "user");
+        assert_eq!(naming_stem("UserService.java"), "userservice");
+        assert_eq!(naming_stem("UserServiceTest.java"), "userservice");
+    }
+}
@@ -0,0 +1,168 @@
+//! Ruby 语言分析
+
+use regex::Regex;
+use once_cell::sync::Lazy;
+
+use super::types::{GraphData, GraphEdge, GraphNode};
+
+static RE_CLASS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^class\s+([\w:]+)(?:\s*<\s*([\w:]+))?").unwrap()
+});
+static RE_MODULE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^module\s+([\w:]+)").unwrap()
+});
+static RE_DEF: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^def\s+(?:self\.)?(\w+[?!=]?)").unwrap()
+});
+
+/// 分析 Ruby 模块
+///
+/// 通过缩进判断 `def` 所属的 class/module（Ruby 代码通常遵循两空格缩进约定）。
+pub fn analyze_ruby_module(
+    graph: &mut GraphData,
+    file_id: &str,
+    _content: &str,
+    lines: &[&str],
+    file_path: &str,
+) {
+    // 当前所在的 class/module 栈：(缩进, 节点 ID, 名称)
+    let mut scopes: Vec<(usize, String, String)> = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        let stripped = line.trim();
+        if stripped.is_empty() || stripped.starts_with('#') {
+            continue;
+        }
+
+        let indent = line.len() - line.trim_start().len();
+
+        // 离开缩进更深或同级的作用域
+        while scopes.last().is_some_and(|(scope_indent, _, _)| *scope_indent >= indent) {
+            scopes.pop();
+        }
+        let parent_id = scopes.last().map(|(_, id, _)| id.clone()).unwrap_or_else(|| file_id.to_string());
+
+        // 类定义
+        if let Some(caps) = RE_CLASS.captures(stripped) {
+            let class_name = caps.get(1).unwrap().as_str();
+            let base = caps.get(2).map(|m| m.as_str());
+            let class_id = format!("{}::class::{}", file_id, class_name);
+
+            graph.nodes.push(GraphNode {
+                id: class_id.clone(),
+                label: class_name.to_string(),
+                node_type: "class".to_string(),
+                file_path: Some(file_path.to_string()),
+                line_number: Some(i + 1),
+                metadata: std::collections::HashMap::new(),
+            });
+            graph.edges.push(GraphEdge::contains(&parent_id, &class_id));
+
+            if let Some(base) = base {
+                let base_id = format!("{}::class::{}", file_id, base);
+                graph.edges.push(GraphEdge::inherits(&class_id, &base_id));
+            }
+
+            scopes.push((indent, class_id, class_name.to_string()));
+            continue;
+        }
+
+        // 模块定义
+        if let Some(caps) = RE_MODULE.captures(stripped) {
+            let module_name = caps.get(1).unwrap().as_str();
+            let module_id = format!("{}::module::{}", file_id, module_name);
+
+            graph.nodes.push(GraphNode {
+                id: module_id.clone(),
+                label: module_name.to_string(),
+                node_type: "module".to_string(),
+                file_path: Some(file_path.to_string()),
+                line_number: Some(i + 1),
+                metadata: std::collections::HashMap::new(),
+            });
+            graph.edges.push(GraphEdge::contains(&parent_id, &module_id));
+
+            scopes.push((indent, module_id, module_name.to_string()));
+            continue;
+        }
+
+        // 方法/函数定义
+        if let Some(caps) = RE_DEF.captures(stripped) {
+            let func_name = caps.get(1).unwrap().as_str();
+
+            match scopes.last() {
+                Some((_, scope_id, scope_name)) => {
+                    let func_id = format!("{}::method::{}", scope_id, func_name);
+                    let mut metadata = std::collections::HashMap::new();
+                    metadata.insert("class".to_string(), scope_name.clone());
+                    graph.nodes.push(GraphNode {
+                        id: func_id.clone(),
+                        label: func_name.to_string(),
+                        node_type: "method".to_string(),
+                        file_path: Some(file_path.to_string()),
+                        line_number: Some(i + 1),
+                        metadata,
+                    });
+                    graph.edges.push(GraphEdge::new(scope_id, &func_id, "contains", "has method"));
+                }
+                None => {
+                    let func_id = format!("{}::func::{}", file_id, func_name);
+                    graph.nodes.push(GraphNode {
+                        id: func_id.clone(),
+                        label: func_name.to_string(),
+                        node_type: "function".to_string(),
+                        file_path: Some(file_path.to_string()),
+                        line_number: Some(i + 1),
+                        metadata: std::collections::HashMap::new(),
+                    });
+                    graph.edges.push(GraphEdge::contains(file_id, &func_id));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_ruby_module() {
+        let content = r#"
+module Billing
+  class Invoice < Base
+    def self.build(attrs)
+    end
+
+    def paid?
+      true
+    end
+  end
+end
+
+def helper
+end
+"#;
+        let lines: Vec<&str> = content.lines().collect();
+        let mut graph = GraphData::default();
+        analyze_ruby_module(&mut graph, "file::a.rb", content, &lines, "a.rb");
+
+        let find = |label: &str| graph.nodes.iter().find(|n| n.label == label).unwrap();
+        assert_eq!(find("Billing").node_type, "module");
+        assert_eq!(find("Invoice").node_type, "class");
+        assert_eq!(find("build").node_type, "method");
+        assert_eq!(find("paid?").node_type, "method");
+        assert_eq!(find("helper").node_type, "function");
+
+        let has_edge = |source: &str, target: &str, edge_type: &str| {
+            graph.edges.iter().any(|e| e.source == source && e.target == target && e.edge_type == edge_type)
+        };
+        assert!(has_edge("file::a.rb::module::Billing", "file::a.rb::class::Invoice", "contains"));
+        assert!(has_edge("file::a.rb::class::Invoice", "file::a.rb::class::Base", "inherits"));
+        assert!(has_edge(
+            "file::a.rb::class::Invoice",
+            "file::a.rb::class::Invoice::method::paid?",
+            "contains"
+        ));
+    }
+}
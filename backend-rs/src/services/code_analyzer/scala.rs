@@ -0,0 +1,195 @@
+//! Scala 语言分析
+
+use regex::Regex;
+use once_cell::sync::Lazy;
+use std::collections::HashSet;
+
+use super::types::{GraphData, GraphEdge, GraphNode};
+
+static RE_TYPE_DEF: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^((?:(?:private|protected)(?:\[\w+\])?\s+|final\s+|sealed\s+|abstract\s+|implicit\s+|open\s+|case\s+)*)(class|object|trait)\s+(\w+)",
+    )
+    .unwrap()
+});
+static RE_EXTENDS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\bextends\s+(\w+)").unwrap()
+});
+static RE_WITH: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\bwith\s+(\w+)").unwrap()
+});
+static RE_DEF: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^(?:(?:override|private|protected|final|implicit|inline|transparent)(?:\[\w+\])?\s+)*def\s+(\w+|[!#%&*+\-/:<=>?@\\^|~]+)",
+    )
+    .unwrap()
+});
+
+/// 分析 Scala 模块
+///
+/// 通过缩进判断 `def` 所属的 class/object/trait（同时适用于花括号和 Scala 3 缩进语法）；
+/// `extends` 生成继承边，`with` 混入的 trait 生成实现边。
+pub fn analyze_scala_module(
+    graph: &mut GraphData,
+    file_id: &str,
+    _content: &str,
+    lines: &[&str],
+    file_path: &str,
+) {
+    // 先收集本文件定义的 trait，用于确定继承目标的节点类型
+    let traits: HashSet<&str> = lines
+        .iter()
+        .filter_map(|line| RE_TYPE_DEF.captures(line.trim()))
+        .filter(|caps| &caps[2] == "trait")
+        .map(|caps| caps.get(3).unwrap().as_str())
+        .collect();
+    let type_id = |name: &str| {
+        let kind = if traits.contains(name) { "interface" } else { "class" };
+        format!("{}::{}::{}", file_id, kind, name)
+    };
+
+    // 当前所在的类型定义栈：(缩进, 节点 ID, 名称)
+    let mut scopes: Vec<(usize, String, String)> = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        let stripped = line.trim();
+        if stripped.is_empty() || stripped.starts_with("//") || stripped.starts_with('*') || stripped.starts_with("/*") {
+            continue;
+        }
+
+        let indent = line.len() - line.trim_start().len();
+
+        // 离开缩进更深或同级的作用域（包括与类型定义同级的 `}`）
+        while scopes.last().is_some_and(|(scope_indent, _, _)| *scope_indent >= indent) {
+            scopes.pop();
+        }
+        let parent_id = scopes.last().map(|(_, id, _)| id.clone()).unwrap_or_else(|| file_id.to_string());
+
+        // class / case class / object / trait
+        if let Some(caps) = RE_TYPE_DEF.captures(stripped) {
+            let modifiers = caps.get(1).unwrap().as_str();
+            let keyword = caps.get(2).unwrap().as_str();
+            let name = caps.get(3).unwrap().as_str();
+
+            let (node_id, node_type) = match keyword {
+                "trait" => (format!("{}::interface::{}", file_id, name), "interface"),
+                "object" => (format!("{}::object::{}", file_id, name), "class"),
+                _ => (format!("{}::class::{}", file_id, name), "class"),
+            };
+            let mut metadata = std::collections::HashMap::new();
+            if keyword == "object" {
+                metadata.insert("kind".to_string(), "object".to_string());
+            }
+            if modifiers.split_whitespace().any(|m| m == "case") {
+                metadata.insert("case".to_string(), "true".to_string());
+            }
+
+            graph.nodes.push(GraphNode {
+                id: node_id.clone(),
+                label: name.to_string(),
+                node_type: node_type.to_string(),
+                file_path: Some(file_path.to_string()),
+                line_number: Some(i + 1),
+                metadata,
+            });
+            graph.edges.push(GraphEdge::contains(&parent_id, &node_id));
+
+            if let Some(base) = RE_EXTENDS.captures(stripped) {
+                graph.edges.push(GraphEdge::inherits(&node_id, type_id(&base[1])));
+            }
+            for mixin in RE_WITH.captures_iter(stripped) {
+                let trait_id = format!("{}::interface::{}", file_id, &mixin[1]);
+                graph.edges.push(GraphEdge::new(&node_id, &trait_id, "implements", "mixes in"));
+            }
+
+            scopes.push((indent, node_id, name.to_string()));
+            continue;
+        }
+
+        // 方法/函数定义
+        if let Some(caps) = RE_DEF.captures(stripped) {
+            let func_name = caps.get(1).unwrap().as_str();
+
+            match scopes.last() {
+                Some((_, scope_id, scope_name)) => {
+                    let func_id = format!("{}::method::{}", scope_id, func_name);
+                    let mut metadata = std::collections::HashMap::new();
+                    metadata.insert("class".to_string(), scope_name.clone());
+                    graph.nodes.push(GraphNode {
+                        id: func_id.clone(),
+                        label: func_name.to_string(),
+                        node_type: "method".to_string(),
+                        file_path: Some(file_path.to_string()),
+                        line_number: Some(i + 1),
+                        metadata,
+                    });
+                    graph.edges.push(GraphEdge::new(scope_id, &func_id, "contains", "has method"));
+                }
+                None => {
+                    let func_id = format!("{}::func::{}", file_id, func_name);
+                    graph.nodes.push(GraphNode {
+                        id: func_id.clone(),
+                        label: func_name.to_string(),
+                        node_type: "function".to_string(),
+                        file_path: Some(file_path.to_string()),
+                        line_number: Some(i + 1),
+                        metadata: std::collections::HashMap::new(),
+                    });
+                    graph.edges.push(GraphEdge::contains(file_id, &func_id));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_scala_module() {
+        let content = r#"
+package shapes
+
+sealed trait Shape extends Product with Serializable {
+  def area: Double
+}
+
+trait Named {
+  def name: String = "shape"
+}
+
+final case class Circle(radius: Double) extends Shape with Named {
+  override def area: Double = math.Pi * radius * radius
+}
+
+object Circle {
+  def unit(): Circle = Circle(1)
+}
+
+def describe(s: Shape): String = s.toString
+"#;
+        let lines: Vec<&str> = content.lines().collect();
+        let mut graph = GraphData::default();
+        analyze_scala_module(&mut graph, "file::a.scala", content, &lines, "a.scala");
+
+        let find = |id: &str| graph.nodes.iter().find(|n| n.id == id).unwrap();
+        assert_eq!(find("file::a.scala::interface::Shape").node_type, "interface");
+        let circle = find("file::a.scala::class::Circle");
+        assert_eq!(circle.node_type, "class");
+        assert_eq!(circle.metadata.get("case").map(String::as_str), Some("true"));
+        assert_eq!(find("file::a.scala::object::Circle").metadata.get("kind").map(String::as_str), Some("object"));
+        assert_eq!(find("file::a.scala::class::Circle::method::area").node_type, "method");
+        assert_eq!(find("file::a.scala::object::Circle::method::unit").node_type, "method");
+        assert_eq!(find("file::a.scala::interface::Shape::method::area").node_type, "method");
+        assert_eq!(find("file::a.scala::func::describe").node_type, "function");
+
+        let has_edge = |source: &str, target: &str, edge_type: &str| {
+            graph.edges.iter().any(|e| e.source == source && e.target == target && e.edge_type == edge_type)
+        };
+        assert!(has_edge("file::a.scala::class::Circle", "file::a.scala::interface::Shape", "inherits"));
+        assert!(has_edge("file::a.scala::class::Circle", "file::a.scala::interface::Named", "implements"));
+        assert!(has_edge("file::a.scala::interface::Shape", "file::a.scala::class::Product", "inherits"));
+        assert!(has_edge("file::a.scala", "file::a.scala::object::Circle", "contains"));
+    }
+}
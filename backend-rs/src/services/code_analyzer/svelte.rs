@@ -0,0 +1,106 @@
+//! Svelte 单文件组件分析
+//!
+//! 只分析 `<script>` 块（包括 `<script lang="ts">` 和 `<script context="module">`），复用 JS/TS 分析器。
+
+use regex::Regex;
+use once_cell::sync::Lazy;
+
+use super::javascript;
+use super::types::GraphData;
+
+static RE_SCRIPT_OPEN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"<script\b[^>]*>").unwrap()
+});
+
+const SCRIPT_CLOSE: &str = "</script>";
+
+/// 保留 `<script>` 块内的内容，其余行替换为空行，使行号与原文件一致
+///
+/// 脚本通常整体缩进，去掉公共缩进后顶层声明才能被 JS/TS 分析器识别。
+fn script_lines<'a>(lines: &[&'a str]) -> Vec<&'a str> {
+    let mut in_script = false;
+    let script: Vec<&str> = lines
+        .iter()
+        .map(|&line| {
+            let mut rest = line;
+            if !in_script {
+                match RE_SCRIPT_OPEN.find(rest) {
+                    Some(open) => {
+                        in_script = true;
+                        rest = &rest[open.end()..];
+                    }
+                    None => return "",
+                }
+            }
+            match rest.find(SCRIPT_CLOSE) {
+                Some(close) => {
+                    in_script = false;
+                    &rest[..close]
+                }
+                None => rest,
+            }
+        })
+        .collect();
+
+    let indent = script
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    script
+        .into_iter()
+        .map(|line| if line.trim().is_empty() { "" } else { line.get(indent..).unwrap_or(line.trim_start()) })
+        .collect()
+}
+
+/// 分析 Svelte 组件
+pub fn analyze_svelte_module(
+    graph: &mut GraphData,
+    file_id: &str,
+    _content: &str,
+    lines: &[&str],
+    file_path: &str,
+) {
+    let script = script_lines(lines);
+    let script_content = script.join("\n");
+    javascript::analyze_js_module(graph, file_id, &script_content, &script, file_path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_svelte_component() {
+        let content = r#"<script context="module" lang="ts">
+  export const prerender = true;
+</script>
+
+<script lang="ts">
+  import { onMount } from 'svelte';
+  export let name: string;
+
+  function greet() {
+    alert(`Hello ${name}`);
+  }
+</script>
+
+<button on:click={greet}>function notAFunction() {}</button>
+
+<style>
+  button { color: red; }
+</style>
+"#;
+        let lines: Vec<&str> = content.lines().collect();
+        let mut graph = GraphData::default();
+        analyze_svelte_module(&mut graph, "file::App.svelte", content, &lines, "App.svelte");
+
+        let greet = graph.nodes.iter().find(|n| n.label == "greet").unwrap();
+        assert_eq!(greet.node_type, "function");
+        assert_eq!(greet.line_number, Some(9));
+        assert!(graph.nodes.iter().any(|n| n.label == "prerender" && n.node_type == "constant"));
+        // 模板中的文本不参与分析
+        assert!(!graph.nodes.iter().any(|n| n.label == "notAFunction"));
+    }
+}
@@ -6,7 +6,7 @@ use std::collections::HashMap;
 /// 支持分析的文件扩展名
 pub const SUPPORTED_EXTENSIONS: &[&str] = &[
     ".py", ".js", ".jsx", ".ts", ".tsx", ".java", ".go",
-    ".c", ".cpp", ".h", ".hpp", ".cs", ".rb", ".rs", ".vue",
+    ".c", ".cpp", ".h", ".hpp", ".cs", ".rb", ".rs", ".vue", ".php", ".scala", ".svelte",
 ];
 
 /// 需要跳过的目录
@@ -57,6 +57,18 @@ impl GraphNode {
         }
     }
 
+    /// 常量/模块级变量节点
+    pub fn constant(id: impl Into<String>, label: impl Into<String>, file_path: impl Into<String>, line_number: usize) -> Self {
+        Self {
+            id: id.into(),
+            label: label.into(),
+            node_type: "constant".to_string(),
+            file_path: Some(file_path.into()),
+            line_number: Some(line_number),
+            metadata: HashMap::new(),
+        }
+    }
+
     pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
         self.metadata.insert(key.into(), value.into());
         self
@@ -72,6 +84,8 @@ pub struct GraphEdge {
     pub edge_type: String,
     #[serde(default)]
     pub label: String,
+    /// 同一对节点间被合并的关系次数（如重复导入）
+    pub count: usize,
 }
 
 impl GraphEdge {
@@ -81,6 +95,7 @@ impl GraphEdge {
             target: target.into(),
             edge_type: edge_type.into(),
             label: label.into(),
+            count: 1,
         }
     }
 
@@ -104,11 +119,75 @@ pub struct GraphData {
     pub edges: Vec<GraphEdge>,
 }
 
+impl GraphData {
+    /// 按 id 合并重复节点并移除自环边
+    ///
+    /// 保留首次出现的节点及其行号（缺失时取后续节点的值），元数据取并集，同名键以先出现的为准。
+    pub fn dedup(&mut self) {
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut merged: Vec<GraphNode> = Vec::with_capacity(self.nodes.len());
+
+        for node in self.nodes.drain(..) {
+            match index.get(&node.id) {
+                Some(&i) => {
+                    let existing = &mut merged[i];
+                    existing.line_number = existing.line_number.or(node.line_number);
+                    existing.file_path = existing.file_path.take().or(node.file_path);
+                    for (key, value) in node.metadata {
+                        existing.metadata.entry(key).or_insert(value);
+                    }
+                }
+                None => {
+                    index.insert(node.id.clone(), merged.len());
+                    merged.push(node);
+                }
+            }
+        }
+
+        self.nodes = merged;
+        self.edges.retain(|e| e.source != e.target);
+    }
+
+    /// 只保留指定类型的节点和边（`None` 表示不过滤），过滤时同时移除端点已不存在的边
+    pub fn retain_types(&mut self, node_types: Option<&[String]>, edge_types: Option<&[String]>) {
+        if node_types.is_none() && edge_types.is_none() {
+            return;
+        }
+        if let Some(types) = node_types {
+            self.nodes.retain(|n| types.contains(&n.node_type));
+        }
+        let ids: std::collections::HashSet<&str> = self.nodes.iter().map(|n| n.id.as_str()).collect();
+        self.edges.retain(|e| {
+            ids.contains(e.source.as_str())
+                && ids.contains(e.target.as_str())
+                && edge_types.is_none_or(|types| types.contains(&e.edge_type))
+        });
+    }
+}
+
 /// 导入信息
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ImportInfo {
     /// 导入路径
     pub path: String,
     /// 显示名称
     pub display_name: String,
 }
+
+/// 已解析到项目文件的导入
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedImport {
+    #[serde(flatten)]
+    pub import: ImportInfo,
+    /// 目标文件（相对于项目根目录）
+    pub target: String,
+}
+
+/// 单个文件的导入解析结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FileImports {
+    /// 能解析到项目内文件的导入
+    pub resolved: Vec<ResolvedImport>,
+    /// 无法解析的导入（外部依赖或路径不匹配，不会生成依赖边）
+    pub unresolved: Vec<ImportInfo>,
+}
@@ -0,0 +1,107 @@
+//! 文件分析结果缓存
+//!
+//! 按 (base_url, 模型, 实际发送的 Prompt, temperature, max_tokens) 缓存 `analyze_file` 的结果。
+//! Prompt 已包含文件路径和内容，Prompt 模板、生成参数或提供方变化后自然不再命中；
+//! 同一会话内重试或重复生成时内容未变的文件不再重复调用 LLM。
+
+use parking_lot::Mutex;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+
+use super::generator::FileAnalysisResult;
+
+/// 默认缓存的文件数
+pub const DEFAULT_ANALYSIS_CACHE_CAPACITY: usize = 256;
+
+/// 文件分析结果 LRU 缓存，超出容量时淘汰最久未使用的条目
+pub struct AnalysisCache {
+    /// 最近使用的条目在队尾
+    entries: Mutex<VecDeque<(u64, FileAnalysisResult)>>,
+    capacity: usize,
+}
+
+impl AnalysisCache {
+    /// 创建缓存（容量至少为 1）
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::new()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// 计算缓存键
+    pub fn key(base_url: &str, model: &str, prompt: &str, temperature: Option<f64>, max_tokens: Option<u32>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        base_url.hash(&mut hasher);
+        model.hash(&mut hasher);
+        prompt.hash(&mut hasher);
+        temperature.map(f64::to_bits).hash(&mut hasher);
+        max_tokens.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// 查找缓存，命中时将条目移到队尾
+    pub fn get(&self, key: u64) -> Option<FileAnalysisResult> {
+        let mut entries = self.entries.lock();
+        let pos = entries.iter().position(|(k, _)| *k == key)?;
+        let entry = entries.remove(pos)?;
+        let result = entry.1.clone();
+        entries.push_back(entry);
+        Some(result)
+    }
+
+    /// 写入缓存
+    pub fn insert(&self, key: u64, result: FileAnalysisResult) {
+        let mut entries = self.entries.lock();
+        entries.retain(|(k, _)| *k != key);
+        entries.push_back((key, result));
+        while entries.len() > self.capacity {
+            entries.pop_front();
+        }
+    }
+}
+
+impl Default for AnalysisCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_ANALYSIS_CACHE_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://api.openai.com";
+
+    fn result(doc: &str) -> FileAnalysisResult {
+        FileAnalysisResult {
+            doc_content: doc.to_string(),
+            graph_data: None,
+            tokens: None,
+        }
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let cache = AnalysisCache::new(2);
+        let a = AnalysisCache::key(URL, "gpt-4o", "prompt a", Some(0.3), Some(4096));
+        let b = AnalysisCache::key(URL, "gpt-4o", "prompt b", Some(0.3), Some(4096));
+        let c = AnalysisCache::key(URL, "gpt-4o", "prompt c", Some(0.3), Some(4096));
+        assert_ne!(a, AnalysisCache::key(URL, "gpt-4o", "prompt a", Some(0.7), Some(4096)));
+        assert_ne!(a, AnalysisCache::key(URL, "gpt-4o", "prompt a", Some(0.3), Some(1024)));
+        assert_ne!(a, AnalysisCache::key(URL, "claude-3-5-sonnet", "prompt a", Some(0.3), Some(4096)));
+        // 不同提供方的同名模型不共享结果
+        assert_ne!(a, AnalysisCache::key("https://other.example.com", "gpt-4o", "prompt a", Some(0.3), Some(4096)));
+
+        cache.insert(a, result("a"));
+        cache.insert(b, result("b"));
+        // 访问 a 后 b 成为最久未使用
+        assert_eq!(cache.get(a).unwrap().doc_content, "a");
+        cache.insert(c, result("c"));
+
+        assert!(cache.get(b).is_none());
+        assert!(cache.get(a).is_some());
+        assert!(cache.get(c).is_some());
+    }
+}
@@ -0,0 +1,306 @@
+//! API 清单
+//!
+//! 从各文件文档末尾的 `<!-- API_START -->` 标记中解析接口列表，汇总为 `_api.json`，
+//! 便于工具对比不同版本的 API 变化。
+
+use serde::{Deserialize, Serialize};
+
+/// API 清单文件名（位于文档根目录）
+pub const API_INVENTORY_FILE_NAME: &str = "_api.json";
+
+/// 单个文件接口提取结果的调试文件后缀（与文件文档同目录，如 `users.py.api.md`）
+pub const API_ARTIFACT_SUFFIX: &str = ".api.md";
+
+const API_START_MARKER: &str = "<!-- API_START -->";
+const API_END_MARKER: &str = "<!-- API_END -->";
+
+/// 标记需要认证的接口的后缀
+const AUTH_TAG: &str = "[auth]";
+
+/// 单个接口
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiEndpoint {
+    /// HTTP 方法（大写，如 `GET`、`WS`）
+    pub method: String,
+    /// 接口路径
+    pub path: String,
+    /// 定义该接口的源文件（相对于源码根目录）
+    pub source_file: String,
+    /// 是否需要认证
+    pub auth_required: bool,
+    /// 接口说明
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+}
+
+/// 被多个文件重复声明的接口（通常是复制粘贴导致的重复注册）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiConflict {
+    /// HTTP 方法
+    pub method: String,
+    /// 接口路径
+    pub path: String,
+    /// 声明该接口的源文件（按路径排序）
+    pub source_files: Vec<String>,
+}
+
+/// 项目的 API 清单
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApiInventory {
+    /// 生成时间
+    pub generated_at: String,
+    /// 按路径、方法排序的接口列表
+    pub endpoints: Vec<ApiEndpoint>,
+    /// 在多个文件中重复声明的 (方法, 路径)
+    #[serde(default)]
+    pub conflicts: Vec<ApiConflict>,
+}
+
+impl ApiInventory {
+    /// 汇总多个文件文档中的接口，参数为 (源文件相对路径, 文档内容)
+    pub fn from_documents<'a, I>(documents: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let mut endpoints: Vec<ApiEndpoint> = documents
+            .into_iter()
+            .flat_map(|(source_file, doc)| parse_api_markers(doc, source_file))
+            .collect();
+        endpoints.sort_by(|a, b| {
+            (&a.path, &a.method, &a.source_file).cmp(&(&b.path, &b.method, &b.source_file))
+        });
+        endpoints.dedup_by(|a, b| a.path == b.path && a.method == b.method && a.source_file == b.source_file);
+
+        // 排序后同一 (路径, 方法) 的接口相邻，来自不同文件即为冲突
+        let conflicts = endpoints
+            .chunk_by(|a, b| a.path == b.path && a.method == b.method)
+            .filter(|group| group.len() > 1)
+            .map(|group| ApiConflict {
+                method: group[0].method.clone(),
+                path: group[0].path.clone(),
+                source_files: group.iter().map(|e| e.source_file.clone()).collect(),
+            })
+            .collect();
+
+        Self {
+            generated_at: chrono::Local::now().to_rfc3339(),
+            endpoints,
+            conflicts,
+        }
+    }
+}
+
+/// 解析单个文档中的接口列表
+///
+/// 接口行格式为 `- [GET] /api/users - 说明`，说明末尾带 `[auth]` 表示需要认证。
+/// 文档中没有标记或标记声明不包含接口（`包含API接口: 否`）时返回空列表；
+/// 缺少结束标记的块截止到下一个 HTML 注释标记，无法识别的行被忽略。
+pub fn parse_api_markers(doc: &str, source_file: &str) -> Vec<ApiEndpoint> {
+    api_blocks(doc)
+        .filter(|block| !declares_no_api(block))
+        .flat_map(|block| block.lines().filter_map(|line| parse_endpoint_line(line, source_file)))
+        .collect()
+}
+
+/// 依次取出文档中所有 API 标记块的内容
+fn api_blocks(doc: &str) -> impl Iterator<Item = &str> {
+    doc.match_indices(API_START_MARKER).map(move |(start, _)| {
+        let block = &doc[start + API_START_MARKER.len()..];
+        match block.find(API_END_MARKER) {
+            Some(end) => &block[..end],
+            // 结束标记丢失时不要把后续的图谱数据等内容当作接口
+            None => block.find("<!--").map_or(block, |end| &block[..end]),
+        }
+    })
+}
+
+/// 标记块是否声明了不包含接口
+fn declares_no_api(block: &str) -> bool {
+    block.lines().any(|line| {
+        line.trim()
+            .trim_matches('*')
+            .strip_prefix("包含API接口")
+            .map(|rest| rest.trim_start_matches([':', '：', '*', ' ']).starts_with('否'))
+            .unwrap_or(false)
+    })
+}
+
+/// 渲染单个文件的接口提取结果，用于排查汇总时丢失的接口
+///
+/// 包含解析出的接口表和原始标记内容；文档中没有 API 标记时返回 `None`。
+pub fn render_api_artifact(doc: &str, source_file: &str) -> Option<String> {
+    let block = api_blocks(doc).next()?.trim();
+
+    let endpoints = parse_api_markers(doc, source_file);
+    let mut out = format!("### {} 的接口列表\n\n", source_file);
+    if endpoints.is_empty() {
+        out.push_str("**该文件未定义API接口**\n");
+    } else {
+        out.push_str("| 序号 | 方法 | 路径 | 功能描述 | 认证要求 |\n|------|------|------|----------|----------|\n");
+        for (i, e) in endpoints.iter().enumerate() {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {} |\n",
+                i + 1,
+                e.method,
+                e.path,
+                e.description.replace('|', "\\|"),
+                if e.auth_required { "需要" } else { "未明确" }
+            ));
+        }
+    }
+    out.push_str(&format!("\n#### 原始标记\n\n```text\n{}\n```\n", block));
+    Some(out)
+}
+
+fn parse_endpoint_line(line: &str, source_file: &str) -> Option<ApiEndpoint> {
+    let rest = line.trim().strip_prefix('-')?.trim_start().strip_prefix('[')?;
+    let (method, rest) = rest.split_once(']')?;
+    let method = method.trim().to_uppercase();
+    if method.is_empty() || !method.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let rest = rest.trim();
+    let (path, description) = match rest.split_once(char::is_whitespace) {
+        Some((path, desc)) => (path, desc.trim().trim_start_matches(['-', '–', ':', '：']).trim()),
+        None => (rest, ""),
+    };
+    let path = path.trim_matches('`');
+    if path.is_empty() {
+        return None;
+    }
+
+    let (description, auth_required) = match description.strip_suffix(AUTH_TAG) {
+        Some(desc) => (desc.trim_end(), true),
+        None => (description, path.ends_with(AUTH_TAG)),
+    };
+
+    Some(ApiEndpoint {
+        method,
+        path: path.trim_end_matches(AUTH_TAG).to_string(),
+        source_file: source_file.to_string(),
+        auth_required,
+        description: description.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aggregate_markers_from_two_files() {
+        let users = "# users.py\n\n说明\n\n<!-- API_START -->\n包含API接口: 是\n接口列表:\n\
+            - [GET] /api/users - 获取用户列表\n\
+            - [delete] /api/users/{id} - 删除指定用户 [auth]\n\
+            <!-- API_END -->\n\n<!-- GRAPH_DATA_START -->\n- [PUT] /not/an/api - 不在标记内\n";
+        let health = "# health.py\n\n<!-- API_START -->\n包含API接口: 是\n接口列表:\n- [GET] /health\n<!-- API_END -->";
+        let util = "# util.py\n\n<!-- API_START -->\n包含API接口: 否\n<!-- API_END -->";
+
+        let inventory = ApiInventory::from_documents([
+            ("api/users.py", users),
+            ("api/health.py", health),
+            ("util.py", util),
+        ]);
+
+        let summary: Vec<(&str, &str, &str, bool)> = inventory
+            .endpoints
+            .iter()
+            .map(|e| (e.method.as_str(), e.path.as_str(), e.source_file.as_str(), e.auth_required))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("GET", "/api/users", "api/users.py", false),
+                ("DELETE", "/api/users/{id}", "api/users.py", true),
+                ("GET", "/health", "api/health.py", false),
+            ]
+        );
+        assert_eq!(inventory.endpoints[1].description, "删除指定用户");
+    }
+
+    #[test]
+    fn test_duplicate_endpoints_reported_as_conflicts() {
+        let doc = |extra: &str| {
+            format!(
+                "<!-- API_START -->\n包含API接口: 是\n- [GET] /api/users - 获取用户列表\n{}<!-- API_END -->",
+                extra
+            )
+        };
+        let users = doc("- [POST] /api/users - 创建用户\n");
+        let legacy = doc("");
+        let admin = "<!-- API_START -->\n包含API接口: 是\n- [get] /api/users\n- [DELETE] /api/users/{id}\n<!-- API_END -->";
+
+        let inventory = ApiInventory::from_documents([
+            ("api/users.py", users.as_str()),
+            ("api/legacy_users.py", legacy.as_str()),
+            ("api/admin.py", admin),
+        ]);
+
+        assert_eq!(
+            inventory.conflicts,
+            vec![ApiConflict {
+                method: "GET".to_string(),
+                path: "/api/users".to_string(),
+                source_files: vec![
+                    "api/admin.py".to_string(),
+                    "api/legacy_users.py".to_string(),
+                    "api/users.py".to_string(),
+                ],
+            }]
+        );
+        let json = serde_json::to_value(&inventory).unwrap();
+        assert_eq!(json["conflicts"][0]["path"], "/api/users");
+    }
+
+    #[test]
+    fn test_render_api_artifact() {
+        let doc = "# users.py\n\n<!-- API_START -->\n包含API接口: 是\n接口列表:\n\
+            - [GET] /api/users - 获取用户列表\n\
+            - GET /api/broken - 缺少方括号\n\
+            - [DELETE] /api/users/{id} - 删除 [auth]\n<!-- API_END -->";
+        let artifact = render_api_artifact(doc, "api/users.py").unwrap();
+        assert!(artifact.contains("| 1 | GET | /api/users | 获取用户列表 | 未明确 |"));
+        assert!(artifact.contains("| 2 | DELETE | /api/users/{id} | 删除 | 需要 |"));
+        // 原始标记保留未能解析的行
+        assert!(artifact.contains("- GET /api/broken - 缺少方括号"));
+
+        let none = render_api_artifact("# util.py\n\n<!-- API_START -->\n包含API接口: 否\n<!-- API_END -->", "util.py");
+        assert!(none.unwrap().contains("该文件未定义API接口"));
+        assert!(render_api_artifact("# plain", "plain.py").is_none());
+    }
+
+    #[test]
+    fn test_parse_marker_blocks() {
+        let doc = "# routes.py\n\n<!-- API_START -->\n**包含API接口**: 是\n接口列表:\n\
+            - [GET] /api/items - 列出条目\n\
+            - [POST] `/api/items` - 创建条目 [auth]\n\
+            - [WS] /ws/items\n\
+            - [] /api/empty-method\n\
+            - [GET]\n\
+            <!-- API_END -->";
+        let endpoints = parse_api_markers(doc, "routes.py");
+        let summary: Vec<(&str, &str, &str, bool)> = endpoints
+            .iter()
+            .map(|e| (e.method.as_str(), e.path.as_str(), e.description.as_str(), e.auth_required))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("GET", "/api/items", "列出条目", false),
+                ("POST", "/api/items", "创建条目", true),
+                ("WS", "/ws/items", "", false),
+            ]
+        );
+
+        // 声明不包含接口时忽略块内的任何行
+        let none = "<!-- API_START -->\n包含API接口：否\n- [GET] /should/not/appear\n<!-- API_END -->";
+        assert!(parse_api_markers(none, "a.py").is_empty());
+
+        // 缺少结束标记时不读入后续的图谱数据
+        let unterminated = "<!-- API_START -->\n包含API接口: 是\n- [GET] /ok\n\
+            <!-- GRAPH_DATA_START -->\n- [PUT] /graph/line\n";
+        let paths: Vec<String> = parse_api_markers(unterminated, "b.py").into_iter().map(|e| e.path).collect();
+        assert_eq!(paths, vec!["/ok"]);
+    }
+}
@@ -5,6 +5,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use tokio::fs;
 use tracing::{debug, info};
 
@@ -31,6 +32,7 @@ pub struct CheckpointData {
 /// 断点续传服务
 pub struct CheckpointService {
     /// 源码根目录
+    #[allow(dead_code)] // 断点按文档目录定位，暂不需要源码路径
     source_root: PathBuf,
     /// 文档根目录
     docs_root: PathBuf,
@@ -42,6 +44,10 @@ pub struct CheckpointService {
     checkpoint_file: PathBuf,
     /// 文档路径映射（相对路径 -> 文档路径）
     doc_path_map: std::collections::HashMap<String, String>,
+    /// 上次保存后新完成的节点数
+    unsaved_nodes: u32,
+    /// 上次保存的时间
+    last_saved: Instant,
 }
 
 impl CheckpointService {
@@ -56,6 +62,8 @@ impl CheckpointService {
             data: CheckpointData::default(),
             checkpoint_file,
             doc_path_map: std::collections::HashMap::new(),
+            unsaved_nodes: 0,
+            last_saved: Instant::now(),
         }
     }
 
@@ -93,7 +101,7 @@ impl CheckpointService {
     }
 
     /// 保存断点文件
-    pub async fn save_checkpoint(&self) -> Result<(), CheckpointError> {
+    pub async fn save_checkpoint(&mut self) -> Result<(), CheckpointError> {
         let content = serde_json::to_string_pretty(&self.data)
             .map_err(|e| CheckpointError::SerializeError(e.to_string()))?;
 
@@ -101,10 +109,29 @@ impl CheckpointService {
             .await
             .map_err(|e| CheckpointError::IoError(self.checkpoint_file.clone(), e))?;
 
+        self.unsaved_nodes = 0;
+        self.last_saved = Instant::now();
         debug!("Checkpoint saved");
         Ok(())
     }
 
+    /// 按 `checkpoint_interval_nodes` / `checkpoint_interval_secs` 判断是否需要中途保存，
+    /// 需要时保存断点并返回 `true`
+    pub async fn save_if_due(&mut self) -> Result<bool, CheckpointError> {
+        if self.unsaved_nodes == 0 {
+            return Ok(false);
+        }
+        let by_count = self.config.checkpoint_interval_nodes > 0
+            && self.unsaved_nodes >= self.config.checkpoint_interval_nodes;
+        let by_time = self.config.checkpoint_interval_secs > 0
+            && self.last_saved.elapsed() >= Duration::from_secs(self.config.checkpoint_interval_secs);
+        if !by_count && !by_time {
+            return Ok(false);
+        }
+        self.save_checkpoint().await?;
+        Ok(true)
+    }
+
     /// 扫描已存在的文档
     pub async fn scan_existing_docs(&mut self) -> Result<(), CheckpointError> {
         if !self.docs_root.exists() {
@@ -229,6 +256,7 @@ impl CheckpointService {
     /// 标记文件完成
     pub fn mark_file_completed(&mut self, relative_path: &str, doc_path: &str) {
         self.data.completed_files.insert(relative_path.to_string());
+        self.unsaved_nodes += 1;
         self.doc_path_map.insert(
             format!("file:{}", relative_path),
             doc_path.to_string(),
@@ -238,6 +266,7 @@ impl CheckpointService {
     /// 标记目录完成
     pub fn mark_dir_completed(&mut self, relative_path: &str, doc_path: &str) {
         self.data.completed_dirs.insert(relative_path.to_string());
+        self.unsaved_nodes += 1;
         self.doc_path_map.insert(
             format!("dir:{}", relative_path),
             doc_path.to_string(),
@@ -255,6 +284,7 @@ impl CheckpointService {
     }
 
     /// 标记 API 文档完成
+    #[allow(dead_code)] // API 文档（API_DOC.md）生成尚未接入处理流程
     pub fn mark_api_doc_completed(&mut self) {
         self.data.api_doc_completed = true;
     }
@@ -264,12 +294,34 @@ impl CheckpointService {
         self.data.project_graph_completed = true;
     }
 
+    /// 清除文件及其所有上级目录的完成标记，使它们重新生成
+    pub fn invalidate_file(&mut self, relative_path: &str) {
+        self.data.completed_files.remove(relative_path);
+        let mut dir = relative_path;
+        while let Some((parent, _)) = dir.rsplit_once('/') {
+            self.data.completed_dirs.remove(parent);
+            dir = parent;
+        }
+        // 根目录
+        self.data.completed_dirs.remove("");
+    }
+
+    /// 清除最终文档（README、阅读指南、API 文档、项目图谱）的完成标记，使其重新生成
+    pub fn reset_final_docs(&mut self) {
+        self.data.readme_completed = false;
+        self.data.reading_guide_completed = false;
+        self.data.api_doc_completed = false;
+        self.data.project_graph_completed = false;
+    }
+
     /// 检查文件是否已完成（仅检查记录，不验证文件存在）
+    #[cfg_attr(not(test), allow(dead_code))] // 处理流程使用会校验文档存在的 verify_file_completed
     pub fn is_file_completed(&self, relative_path: &str) -> bool {
         self.data.completed_files.contains(relative_path)
     }
 
     /// 检查目录是否已完成（仅检查记录，不验证文件存在）
+    #[cfg_attr(not(test), allow(dead_code))] // 处理流程使用会校验文档存在的 verify_dir_completed
     pub fn is_dir_completed(&self, relative_path: &str) -> bool {
         self.data.completed_dirs.contains(relative_path)
     }
@@ -351,6 +403,7 @@ impl CheckpointService {
     }
 
     /// 检查 API 文档是否已完成
+    #[allow(dead_code)] // API 文档（API_DOC.md）生成尚未接入处理流程
     pub fn is_api_doc_completed(&self) -> bool {
         self.data.api_doc_completed
     }
@@ -361,6 +414,7 @@ impl CheckpointService {
     }
 
     /// 获取文档路径
+    #[allow(dead_code)] // 文档路径由 DocumentGenerator 按节点推导，映射表只随断点持久化
     pub fn get_doc_path(&self, key: &str) -> Option<&String> {
         self.doc_path_map.get(key)
     }
@@ -383,6 +437,7 @@ impl CheckpointService {
 
 /// 断点服务错误类型
 #[derive(Debug, thiserror::Error)]
+#[allow(clippy::enum_variant_names)]
 pub enum CheckpointError {
     #[error("IO错误 ({0}): {1}")]
     IoError(PathBuf, #[source] std::io::Error),
@@ -399,6 +454,36 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    #[tokio::test]
+    async fn test_interim_save_after_n_nodes() {
+        let dir = TempDir::new().unwrap();
+        let docs_root = dir.path().join("docs");
+        let config = DocGenConfig {
+            checkpoint_interval_nodes: 2,
+            checkpoint_interval_secs: 0,
+            ..Default::default()
+        };
+        let mut service = CheckpointService::new(dir.path().join("source"), docs_root.clone(), config);
+        service.initialize().await.unwrap();
+        let checkpoint_file = docs_root.join(".checkpoint.json");
+
+        service.mark_file_completed("a.py", "/docs/a.py.md");
+        assert!(!service.save_if_due().await.unwrap());
+        assert!(!checkpoint_file.exists());
+
+        // 第 2 个节点完成时触发中途保存
+        service.mark_dir_completed("src", "/docs/src/_dir_summary.md");
+        assert!(service.save_if_due().await.unwrap());
+        let saved: CheckpointData =
+            serde_json::from_str(&std::fs::read_to_string(&checkpoint_file).unwrap()).unwrap();
+        assert!(saved.completed_files.contains("a.py"));
+        assert!(saved.completed_dirs.contains("src"));
+
+        // 保存后计数清零
+        service.mark_file_completed("b.py", "/docs/b.py.md");
+        assert!(!service.save_if_due().await.unwrap());
+    }
+
     #[tokio::test]
     async fn test_checkpoint_save_load() {
         let dir = TempDir::new().unwrap();
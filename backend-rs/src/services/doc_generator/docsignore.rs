@@ -0,0 +1,134 @@
+//! `.docsignore` 支持
+//!
+//! 源码根目录下的 `.docsignore` 使用 gitignore 风格的模式排除文件和目录，
+//! 让排除规则随仓库一起版本管理，而不必写在服务端配置里。
+
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// 忽略文件名（位于源码根目录）
+pub const DOCS_IGNORE_FILE_NAME: &str = ".docsignore";
+
+/// 单条规则
+#[derive(Debug)]
+struct Rule {
+    pattern: glob::Pattern,
+    /// `!` 开头：重新包含之前被排除的路径
+    negated: bool,
+    /// `/` 结尾：只匹配目录
+    dir_only: bool,
+    /// 包含 `/`：相对于根目录匹配完整路径，否则匹配任意层级的名称
+    anchored: bool,
+}
+
+/// 解析后的 `.docsignore` 规则集
+#[derive(Debug, Default)]
+pub struct DocsIgnore {
+    rules: Vec<Rule>,
+}
+
+impl DocsIgnore {
+    /// 读取 `root` 下的 `.docsignore`，文件不存在或无法读取时返回空规则集
+    pub fn load(root: &Path) -> Self {
+        let path = root.join(DOCS_IGNORE_FILE_NAME);
+        if !path.is_file() {
+            return Self::default();
+        }
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                let ignore = Self::parse(&content);
+                info!("Loaded {} rules from {}", ignore.rules.len(), path.display());
+                ignore
+            }
+            Err(e) => {
+                warn!("Failed to read {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// 解析 gitignore 风格的内容：空行和 `#` 注释忽略，支持 `!` 取反、`/` 结尾仅匹配目录、
+    /// 含 `/` 的模式相对根目录匹配、`**` 匹配任意层级
+    pub fn parse(content: &str) -> Self {
+        let rules = content
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+                let (negated, line) = match line.strip_prefix('!') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let dir_only = line.ends_with('/');
+                let line = line.trim_end_matches('/');
+                let anchored = line.contains('/');
+                let line = line.trim_start_matches('/');
+                if line.is_empty() {
+                    return None;
+                }
+                match glob::Pattern::new(line) {
+                    Ok(pattern) => Some(Rule { pattern, negated, dir_only, anchored }),
+                    Err(e) => {
+                        warn!("Invalid {} pattern '{}': {}", DOCS_IGNORE_FILE_NAME, line, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// 判断相对路径（`/` 分隔）是否被排除，最后一条匹配的规则生效
+    pub fn is_ignored(&self, relative_path: &str, is_dir: bool) -> bool {
+        let name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+
+        let mut ignored = false;
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let matched = if rule.anchored {
+                rule.pattern.matches_with(relative_path, options)
+            } else {
+                rule.pattern.matches_with(name, options)
+            };
+            if matched {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gitignore_style_rules() {
+        let ignore = DocsIgnore::parse(
+            "# 示例代码\nexamples/\n*.generated.ts\n!keep.generated.ts\n/scripts/tmp_*.py\ndocs/**/draft.md\n",
+        );
+
+        assert!(ignore.is_ignored("examples", true));
+        assert!(ignore.is_ignored("pkg/examples", true));
+        // `examples/` 只匹配目录
+        assert!(!ignore.is_ignored("examples", false));
+
+        assert!(ignore.is_ignored("src/api.generated.ts", false));
+        assert!(!ignore.is_ignored("src/keep.generated.ts", false));
+
+        assert!(ignore.is_ignored("scripts/tmp_a.py", false));
+        assert!(!ignore.is_ignored("src/scripts/tmp_a.py", false));
+
+        assert!(ignore.is_ignored("docs/a/b/draft.md", false));
+        assert!(!ignore.is_ignored("src/main.py", false));
+    }
+}
@@ -4,20 +4,29 @@
 
 use chrono::Local;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 use tracing::{debug, error, info, warn};
 
+use super::analysis_cache::AnalysisCache;
 use super::prompts;
-use super::types::{DirGraphData, DocGenConfig, FileGraphData, FileNode, LlmGraphRawData};
-use crate::llm::{ChatMessage, ChatOptions, CollectMode, LlmClient};
+use super::types::{
+    DirGraphData, DocGenConfig, FileGraphData, FileNode, ImportDeclaration, LlmGraphEdge,
+    LlmGraphNode, LlmGraphRawData, ReadmeMode,
+};
+use crate::services::code_analyzer::detect;
+use crate::llm::{ChatMessage, ChatOptions, CollectMode, LlmClient, TokenUsage};
 
 /// 文件分析结果：包含文档内容和可选的图谱数据
+#[derive(Clone)]
 pub struct FileAnalysisResult {
     /// 文档内容（不含图谱数据标记）
     pub doc_content: String,
     /// 图谱数据（如果解析成功）
     pub graph_data: Option<FileGraphData>,
+    /// 本次分析消耗的 token 数（命中缓存或上游未返回用量时为空）
+    pub tokens: Option<u64>,
 }
 
 /// 目录分析结果：包含文档内容和可选的图谱数据
@@ -26,6 +35,8 @@ pub struct DirAnalysisResult {
     pub doc_content: String,
     /// 图谱数据（如果解析成功）
     pub graph_data: Option<DirGraphData>,
+    /// 本次总结消耗的 token 数（上游未返回用量时为空）
+    pub tokens: Option<u64>,
 }
 
 /// 文档生成器
@@ -34,42 +45,66 @@ pub struct DocumentGenerator {
     docs_root: PathBuf,
     /// 配置
     config: DocGenConfig,
+    /// 文件分析结果缓存
+    analysis_cache: Arc<AnalysisCache>,
+    /// 分析文件时是否读取缓存（关闭时仍会用新结果刷新缓存）
+    cache_lookup: bool,
 }
 
 impl DocumentGenerator {
     /// 创建新的文档生成器
     pub fn new(docs_root: PathBuf, config: DocGenConfig) -> Self {
-        Self { docs_root, config }
+        Self {
+            docs_root,
+            config,
+            analysis_cache: Arc::new(AnalysisCache::default()),
+            cache_lookup: true,
+        }
+    }
+
+    /// 使用共享的文件分析结果缓存（跨任务复用）
+    pub fn with_analysis_cache(mut self, cache: Arc<AnalysisCache>) -> Self {
+        self.analysis_cache = cache;
+        self
+    }
+
+    /// 设置分析文件时是否读取缓存；从头重新生成时关闭，确保每个文件都重新调用 LLM
+    pub fn with_cache_lookup(mut self, enabled: bool) -> Self {
+        self.cache_lookup = enabled;
+        self
     }
 
     /// 获取文件的文档路径
     ///
     /// 例如: src/utils/helper.py -> docs_root/src/utils/helper.py.md
     pub fn get_file_doc_path(&self, node: &FileNode) -> PathBuf {
-        let doc_name = format!("{}.md", node.name);
-        let parent = Path::new(&node.relative_path).parent();
-
-        match parent {
-            Some(p) if !p.as_os_str().is_empty() => self.docs_root.join(p).join(doc_name),
-            _ => self.docs_root.join(doc_name),
-        }
+        self.docs_dir_for(&node.relative_path, true)
+            .join(format!("{}.md", node.name))
     }
 
     /// 获取目录的文档路径
     ///
     /// 例如: src/utils -> docs_root/src/utils/_dir_summary.md
     pub fn get_dir_doc_path(&self, node: &FileNode) -> PathBuf {
-        if node.relative_path.is_empty() {
-            // 根目录
-            self.docs_root.join(&self.config.dir_summary_name)
-        } else {
-            self.docs_root
-                .join(&node.relative_path)
-                .join(&self.config.dir_summary_name)
+        self.docs_dir_for(&node.relative_path, false)
+            .join(&self.config.dir_summary_name)
+    }
+
+    /// 节点文档所在的目录
+    ///
+    /// 相对路径同时接受 `/` 和 `\` 分隔符，文件取其父目录，目录取自身
+    fn docs_dir_for(&self, relative_path: &str, is_file: bool) -> PathBuf {
+        let mut segments: Vec<&str> = relative_path_segments(relative_path).collect();
+        if is_file {
+            segments.pop();
         }
+        let mut dir = self.docs_root.clone();
+        dir.extend(segments);
+        dir
     }
 
     /// 获取节点的文档路径
+    #[allow(dead_code)] // 调用方都已知节点类型，直接使用 get_file_doc_path / get_dir_doc_path
     pub fn get_doc_path(&self, node: &FileNode) -> PathBuf {
         if node.is_file {
             self.get_file_doc_path(node)
@@ -79,19 +114,57 @@ impl DocumentGenerator {
     }
 
     /// 分析代码文件并生成文档（包含知识图谱数据提取）
+    ///
+    /// 模型、Prompt 与生成参数都未变的文件直接返回缓存结果，不再调用 LLM（`with_cache_lookup(false)` 时除外）
     pub async fn analyze_file(
         &self,
         node: &FileNode,
         llm_client: &LlmClient,
         model: &str,
+    ) -> Result<FileAnalysisResult, GeneratorError> {
+        self.analyze_file_inner(node, llm_client, model, None, self.cache_lookup).await
+    }
+
+    /// 分析代码文件，可在分析 Prompt 末尾追加用户的补充要求
+    ///
+    /// 用于显式重新生成，总是调用 LLM；无补充要求时用新结果刷新缓存
+    pub async fn analyze_file_with_instructions(
+        &self,
+        node: &FileNode,
+        llm_client: &LlmClient,
+        model: &str,
+        instructions: Option<&str>,
+    ) -> Result<FileAnalysisResult, GeneratorError> {
+        self.analyze_file_inner(node, llm_client, model, instructions, false).await
+    }
+
+    async fn analyze_file_inner(
+        &self,
+        node: &FileNode,
+        llm_client: &LlmClient,
+        model: &str,
+        instructions: Option<&str>,
+        use_cache: bool,
     ) -> Result<FileAnalysisResult, GeneratorError> {
         // 读取文件内容
-        let content = fs::read_to_string(&node.path)
+        let content = fs::read(&node.path)
             .await
+            .and_then(|bytes| detect::decode_source(bytes, &node.path))
             .map_err(|e| GeneratorError::IoError(node.path.clone(), e))?;
+        let content = detect::normalize_source(&content);
+
+        let instructions = instructions.filter(|i| !i.trim().is_empty());
 
         // 构建 prompt
-        let prompt = prompts::format_code_analysis_prompt(&node.relative_path, &content);
+        let prompt = if self.config.extract_graph {
+            prompts::format_code_analysis_prompt(&node.relative_path, &content)
+        } else {
+            prompts::format_code_analysis_prompt_without_graph(&node.relative_path, &content)
+        };
+        let prompt = match instructions {
+            Some(instructions) => prompts::append_extra_instructions(prompt, instructions),
+            None => prompt,
+        };
 
         // 调用 LLM
         let messages = vec![ChatMessage {
@@ -100,82 +173,143 @@ impl DocumentGenerator {
         }];
 
         let options = ChatOptions {
-            temperature: Some(0.3),
-            max_tokens: Some(8192), // 代码分析需要较大的 token 限制
+            temperature: Some(self.config.file_temperature),
+            max_tokens: Some(self.config.file_max_tokens),
             ..Default::default()
         };
 
-        let result = llm_client
-            .stream_and_collect(messages, model, options, CollectMode::ContentOnly)
-            .await
-            .map_err(|e| GeneratorError::LlmError(e.to_string()))?;
-
-        // 验证 LLM 响应非空
-        if result.content.trim().is_empty() {
-            return Err(GeneratorError::LlmError(format!(
-                "LLM returned empty response for file: {}",
-                node.relative_path
-            )));
+        let cache_key = AnalysisCache::key(
+            llm_client.base_url(),
+            model,
+            &messages[0].content,
+            options.temperature,
+            options.max_tokens,
+        );
+        if use_cache {
+            if let Some(cached) = self.analysis_cache.get(cache_key) {
+                debug!("Analysis cache hit: {}", node.relative_path);
+                return Ok(self.annotate_lines(FileAnalysisResult { tokens: None, ..cached }));
+            }
         }
 
-        // 解析响应，分离文档内容和图谱数据
-        let (doc_content, raw_graph) = self.parse_llm_response_raw(&result.content, &node.relative_path);
+        // 响应为空或剥离图谱后文档为空时整体重试，避免保存空文档
+        let max_attempts = self.config.empty_doc_retries + 1;
+        let mut attempt = 0;
+        // 重试的调用同样计入用量
+        let mut tokens: Option<u64> = None;
+        let (doc_content, raw_graph) = loop {
+            attempt += 1;
+            let result = llm_client
+                .stream_and_collect(messages.clone(), model, options.clone(), CollectMode::ContentOnly)
+                .await
+                .map_err(|e| GeneratorError::LlmError(e.to_string()))?;
+            add_usage(&mut tokens, result.usage);
+
+            let error = if result.content.trim().is_empty() {
+                format!("LLM returned empty response for file: {}", node.relative_path)
+            } else {
+                let (doc_content, raw_graph, repair_usage) =
+                    self.parse_file_response(&result.content, node, llm_client, model).await;
+                add_usage(&mut tokens, repair_usage);
+                if !doc_content.trim().is_empty() {
+                    break (doc_content, raw_graph);
+                }
+                format!("Parsed document content is empty for file: {}", node.relative_path)
+            };
 
-        // 验证解析后的文档内容非空
-        if doc_content.trim().is_empty() {
-            return Err(GeneratorError::LlmError(format!(
-                "Parsed document content is empty for file: {}",
-                node.relative_path
-            )));
-        }
+            if attempt >= max_attempts {
+                return Err(GeneratorError::LlmError(error));
+            }
+            warn!("{} (attempt {}/{}), retrying", error, attempt, max_attempts);
+        };
 
         let graph_data = raw_graph.map(|raw| FileGraphData::new(node.relative_path.clone(), raw));
 
-        Ok(FileAnalysisResult {
+        let result = FileAnalysisResult {
             doc_content,
             graph_data,
-        })
+            tokens,
+        };
+        // 带补充要求时 Prompt 不同，缓存键也不同，不会被默认分析命中；
+        // 图谱解析失败的结果不缓存，下次生成仍有机会拿到图谱
+        if result.graph_data.is_some() || !self.config.extract_graph {
+            self.analysis_cache.insert(cache_key, result.clone());
+        }
+        Ok(self.annotate_lines(result))
+    }
+
+    /// 按配置在文档末尾附加组件位置表（缓存中保存的是未附加的结果）
+    fn annotate_lines(&self, mut result: FileAnalysisResult) -> FileAnalysisResult {
+        if self.config.line_annotations {
+            if let Some(graph) = &result.graph_data {
+                result.doc_content = append_line_annotations(&result.doc_content, &graph.nodes);
+            }
+        }
+        result
+    }
+
+    /// 解析文件/目录分析响应，分离文档和图谱；图谱 JSON 损坏时按配置追问一次
+    ///
+    /// 第三项为追问修正消耗的 token 用量（未追问时为空）
+    async fn parse_file_response(
+        &self,
+        response: &str,
+        node: &FileNode,
+        llm_client: &LlmClient,
+        model: &str,
+    ) -> (String, Option<LlmGraphRawData>, Option<TokenUsage>) {
+        // 关闭图谱提取时整个响应即文档
+        if !self.config.extract_graph {
+            return (response.to_string(), None, None);
+        }
+
+        let (mut doc_content, mut raw_graph) = self.parse_llm_response_raw(response, &node.relative_path);
+        let mut repair_usage = None;
+
+        // 有图谱标记但解析失败：追问一次修正后的 JSON
+        if raw_graph.is_none() && self.config.retry_malformed_graph {
+            if let Some((stripped_doc, graph_section)) = split_graph_block(response) {
+                let (repaired, usage) = self
+                    .repair_graph_json(&node.relative_path, graph_section, llm_client, model)
+                    .await;
+                repair_usage = usage;
+                if let Some(repaired) = repaired {
+                    doc_content = stripped_doc;
+                    raw_graph = Some(repaired);
+                }
+            }
+        }
+
+        (doc_content, raw_graph, repair_usage)
     }
 
     /// 解析 LLM 响应，分离文档内容和原始图谱数据
     ///
     /// 查找 `<!-- GRAPH_DATA_START -->` 和 `<!-- GRAPH_DATA_END -->` 之间的 JSON 数据
     fn parse_llm_response_raw(&self, response: &str, path: &str) -> (String, Option<LlmGraphRawData>) {
-        const GRAPH_START: &str = "<!-- GRAPH_DATA_START -->";
-        const GRAPH_END: &str = "<!-- GRAPH_DATA_END -->";
-
-        // 查找图谱数据标记
-        let start_pos = response.find(GRAPH_START);
-        let end_pos = response.find(GRAPH_END);
-
         // 调试日志：显示是否找到图谱标记
-        if start_pos.is_none() || end_pos.is_none() {
+        if !response.contains(GRAPH_START) || !response.contains(GRAPH_END) {
             info!("[{}] LLM 响应中未找到图谱数据标记 (GRAPH_DATA_START: {}, GRAPH_DATA_END: {})",
                 path,
-                start_pos.is_some(),
-                end_pos.is_some()
+                response.contains(GRAPH_START),
+                response.contains(GRAPH_END)
             );
         }
 
-        match (start_pos, end_pos) {
-            (Some(start), Some(end)) if start < end => {
-                // 提取文档内容（去除图谱数据部分）
-                let doc_content = format!(
-                    "{}{}",
-                    response[..start].trim_end(),
-                    response[end + GRAPH_END.len()..].trim_start()
-                );
-
-                // 提取图谱 JSON
-                let graph_section = &response[start + GRAPH_START.len()..end];
-
+        match split_graph_block(response) {
+            Some((doc_content, graph_section)) => {
                 // 在图谱部分中查找 JSON（可能被 ```json 包裹）
                 let json_str = self.extract_json_from_section(graph_section);
 
                 match json_str {
                     Some(json) => {
-                        match serde_json::from_str::<LlmGraphRawData>(&json) {
-                            Ok(raw_data) => {
+                        match serde_json::from_str::<serde_json::Value>(&json) {
+                            Ok(value) if !value.is_object() => {
+                                warn!("{} 的图谱 JSON 不是对象", path);
+                                (response.to_string(), None)
+                            }
+                            Ok(value) => {
+                                let raw_data = validate_graph_value(&value, path);
                                 info!("成功解析 {} 的知识图谱: {} 节点, {} 边",
                                     path, raw_data.nodes.len(), raw_data.edges.len());
                                 (doc_content, Some(raw_data))
@@ -200,6 +334,57 @@ impl DocumentGenerator {
         }
     }
 
+    /// 图谱 JSON 解析失败时追问一次修正后的 JSON
+    ///
+    /// 只重试一次以控制成本；失败时图谱为 `None`，文档照常保存。
+    /// 同时返回追问的 token 用量，修正失败的追问同样计入
+    async fn repair_graph_json(
+        &self,
+        path: &str,
+        broken_graph: &str,
+        llm_client: &LlmClient,
+        model: &str,
+    ) -> (Option<LlmGraphRawData>, Option<TokenUsage>) {
+        info!("[{}] 图谱 JSON 解析失败，请求 LLM 修正", path);
+
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: prompts::format_graph_repair_prompt(path, broken_graph.trim()),
+        }];
+        let options = ChatOptions {
+            temperature: Some(self.config.file_temperature),
+            max_tokens: Some(self.config.file_max_tokens),
+            ..Default::default()
+        };
+
+        let result = match llm_client
+            .stream_and_collect(messages, model, options, CollectMode::ContentOnly)
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => {
+                warn!("[{}] 图谱修正请求失败: {}", path, e);
+                return (None, None);
+            }
+        };
+
+        let value = self
+            .extract_json_from_section(&result.content)
+            .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok());
+        let repaired = match value {
+            Some(value) if value.is_object() => {
+                let raw_data = validate_graph_value(&value, path);
+                info!("[{}] 图谱修正成功: {} 节点, {} 边", path, raw_data.nodes.len(), raw_data.edges.len());
+                Some(raw_data)
+            }
+            _ => {
+                warn!("[{}] 修正后的图谱 JSON 仍然无效", path);
+                None
+            }
+        };
+        (repaired, result.usage)
+    }
+
     /// 从图谱部分提取 JSON 字符串
     ///
     /// 支持以下格式：
@@ -243,13 +428,8 @@ impl DocumentGenerator {
     ///
     /// 例如: src/utils/helper.py -> docs_root/src/utils/helper.py.graph.json
     pub fn get_file_graph_path(&self, node: &FileNode) -> PathBuf {
-        let graph_name = format!("{}.graph.json", node.name);
-        let parent = Path::new(&node.relative_path).parent();
-
-        match parent {
-            Some(p) if !p.as_os_str().is_empty() => self.docs_root.join(p).join(graph_name),
-            _ => self.docs_root.join(graph_name),
-        }
+        self.docs_dir_for(&node.relative_path, true)
+            .join(format!("{}.graph.json", node.name))
     }
 
     /// 保存文件图谱数据
@@ -302,11 +482,13 @@ impl DocumentGenerator {
         llm_client: &LlmClient,
         model: &str,
     ) -> Result<DirAnalysisResult, GeneratorError> {
-        let prompt = prompts::format_directory_summary_prompt(
-            &node.name,
-            &node.relative_path,
-            sub_documents,
-        );
+        // 分组汇总与最终总结的用量都计入该目录
+        let (sub_documents, mut tokens) = self.fit_sub_documents(node, sub_documents, llm_client, model).await?;
+        let prompt = if self.config.extract_graph {
+            prompts::format_directory_summary_prompt(&node.name, &node.relative_path, &sub_documents)
+        } else {
+            prompts::format_directory_summary_prompt_without_graph(&node.name, &node.relative_path, &sub_documents)
+        };
 
         let messages = vec![ChatMessage {
             role: "user".to_string(),
@@ -314,8 +496,8 @@ impl DocumentGenerator {
         }];
 
         let options = ChatOptions {
-            temperature: Some(0.3),
-            max_tokens: Some(8192),
+            temperature: Some(self.config.dir_temperature),
+            max_tokens: Some(self.config.dir_max_tokens),
             ..Default::default()
         };
 
@@ -323,6 +505,7 @@ impl DocumentGenerator {
             .stream_and_collect(messages, model, options, CollectMode::ContentOnly)
             .await
             .map_err(|e| GeneratorError::LlmError(e.to_string()))?;
+        add_usage(&mut tokens, result.usage);
 
         // 验证 LLM 响应非空
         if result.content.trim().is_empty() {
@@ -333,7 +516,9 @@ impl DocumentGenerator {
         }
 
         // 解析响应，分离文档内容和图谱数据
-        let (doc_content, raw_graph) = self.parse_llm_response_raw(&result.content, &node.relative_path);
+        let (doc_content, raw_graph, repair_usage) =
+            self.parse_file_response(&result.content, node, llm_client, model).await;
+        add_usage(&mut tokens, repair_usage);
 
         // 验证解析后的文档内容非空
         if doc_content.trim().is_empty() {
@@ -348,9 +533,98 @@ impl DocumentGenerator {
         Ok(DirAnalysisResult {
             doc_content,
             graph_data,
+            tokens,
         })
     }
 
+    /// 将子文档压缩到 token 预算内
+    ///
+    /// 未超出预算时原样返回；否则按预算分组，每组先由 LLM 汇总，再用各组汇总替代原文档（map-reduce）。
+    /// 汇总后仍超出预算时继续下一轮，最多 `MAX_REDUCE_ROUNDS` 轮。同时返回各组汇总消耗的 token 总数。
+    async fn fit_sub_documents(
+        &self,
+        node: &FileNode,
+        sub_documents: &str,
+        llm_client: &LlmClient,
+        model: &str,
+    ) -> Result<(String, Option<u64>), GeneratorError> {
+        let budget = self.config.dir_summary_token_budget.max(1);
+        let mut documents = sub_documents.to_string();
+        let mut tokens = None;
+
+        for round in 1..=MAX_REDUCE_ROUNDS {
+            if estimate_tokens(&documents) <= budget {
+                break;
+            }
+            let batches = group_by_budget(&split_child_documents(&documents), budget);
+            if batches.len() < 2 {
+                // 只剩一份文档，无法再分组，交给最终总结处理
+                break;
+            }
+
+            info!(
+                "[{}] 子文档约 {} tokens，超出预算 {}，第 {} 轮分 {} 组汇总",
+                node.relative_path,
+                estimate_tokens(&documents),
+                budget,
+                round,
+                batches.len()
+            );
+
+            let mut summaries = Vec::with_capacity(batches.len());
+            for (i, batch) in batches.iter().enumerate() {
+                let (summary, usage) = self
+                    .summarize_batch(node, i + 1, batches.len(), batch, llm_client, model)
+                    .await?;
+                add_usage(&mut tokens, usage);
+                summaries.push(format!("### 第 {} 组子模块\n\n{}", i + 1, summary.trim()));
+            }
+            documents = summaries.join(CHILD_DOC_SEPARATOR);
+        }
+
+        Ok((documents, tokens))
+    }
+
+    /// 汇总一组子文档，返回汇总内容和 token 用量
+    async fn summarize_batch(
+        &self,
+        node: &FileNode,
+        batch_index: usize,
+        batch_count: usize,
+        sub_documents: &str,
+        llm_client: &LlmClient,
+        model: &str,
+    ) -> Result<(String, Option<TokenUsage>), GeneratorError> {
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: prompts::format_directory_batch_prompt(
+                &node.name,
+                &node.relative_path,
+                batch_index,
+                batch_count,
+                sub_documents,
+            ),
+        }];
+        let options = ChatOptions {
+            temperature: Some(self.config.dir_temperature),
+            max_tokens: Some(self.config.dir_max_tokens),
+            ..Default::default()
+        };
+
+        let result = llm_client
+            .stream_and_collect(messages, model, options, CollectMode::ContentOnly)
+            .await
+            .map_err(|e| GeneratorError::LlmError(e.to_string()))?;
+
+        if result.content.trim().is_empty() {
+            return Err(GeneratorError::LlmError(format!(
+                "LLM returned empty batch summary for directory: {} ({}/{})",
+                node.relative_path, batch_index, batch_count
+            )));
+        }
+        Ok((result.content, result.usage))
+    }
+
     /// 保存目录总结文档
     pub async fn save_dir_summary(
         &self,
@@ -369,13 +643,8 @@ impl DocumentGenerator {
     /// 例如: src/utils -> docs_root/src/utils/_dir.graph.json
     /// 根目录 -> docs_root/_dir.graph.json
     pub fn get_dir_graph_path(&self, node: &FileNode) -> PathBuf {
-        if node.relative_path.is_empty() {
-            self.docs_root.join("_dir.graph.json")
-        } else {
-            self.docs_root
-                .join(&node.relative_path)
-                .join("_dir.graph.json")
-        }
+        self.docs_dir_for(&node.relative_path, false)
+            .join("_dir.graph.json")
     }
 
     /// 保存目录图谱数据
@@ -423,8 +692,8 @@ impl DocumentGenerator {
         }];
 
         let options = ChatOptions {
-            temperature: Some(0.3),
-            max_tokens: Some(16384), // README 需要更大的 token 限制
+            temperature: Some(self.config.synthesis_temperature),
+            max_tokens: Some(self.config.synthesis_max_tokens),
             ..Default::default()
         };
 
@@ -437,6 +706,8 @@ impl DocumentGenerator {
     }
 
     /// 保存 README
+    ///
+    /// README 已存在时按 `readme_mode` 处理：覆盖、只替换自动生成区块或跳过。
     pub async fn save_readme(
         &self,
         project_name: &str,
@@ -444,7 +715,25 @@ impl DocumentGenerator {
     ) -> Result<PathBuf, GeneratorError> {
         let doc_path = self.docs_root.join(&self.config.readme_name);
         let formatted = self.format_readme(project_name, content);
-        self.save_document(&doc_path, &formatted).await?;
+
+        let document = match self.config.readme_mode {
+            ReadmeMode::Overwrite => formatted,
+            ReadmeMode::SkipIfExists if doc_path.is_file() => {
+                info!("README exists, skipped: {}", doc_path.display());
+                return Ok(doc_path);
+            }
+            ReadmeMode::SkipIfExists => formatted,
+            ReadmeMode::AppendSection => {
+                let existing = match fs::read_to_string(&doc_path).await {
+                    Ok(existing) => existing,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+                    Err(e) => return Err(GeneratorError::IoError(doc_path, e)),
+                };
+                replace_auto_section(&existing, &formatted)
+            }
+        };
+
+        self.save_document(&doc_path, &document).await?;
         info!("README saved: {}", doc_path.display());
         Ok(doc_path)
     }
@@ -470,8 +759,8 @@ impl DocumentGenerator {
         }];
 
         let options = ChatOptions {
-            temperature: Some(0.3),
-            max_tokens: Some(16384),
+            temperature: Some(self.config.synthesis_temperature),
+            max_tokens: Some(self.config.synthesis_max_tokens),
             ..Default::default()
         };
 
@@ -520,11 +809,40 @@ impl DocumentGenerator {
             }
         }
 
-        Ok(summaries.join("\n\n---\n\n"))
+        Ok(summaries.join(CHILD_DOC_SEPARATOR))
+    }
+
+    /// 读取目录下直接子文件的图谱数据（缺失或无法解析的跳过；关闭图谱提取时不读取旧图谱）
+    pub async fn read_child_file_graphs(&self, node: &FileNode) -> Vec<FileGraphData> {
+        let mut graphs = Vec::new();
+        if !self.config.extract_graph {
+            return graphs;
+        }
+
+        for child in node.children.iter().filter(|c| c.is_file) {
+            let graph_path = self.get_file_graph_path(child);
+            let Ok(content) = fs::read_to_string(&graph_path).await else {
+                continue;
+            };
+            match serde_json::from_str::<FileGraphData>(&content) {
+                Ok(graph) => graphs.push(graph),
+                Err(e) => debug!("Skipping unreadable file graph {}: {}", graph_path.display(), e),
+            }
+        }
+
+        graphs
     }
 
     /// 保存文档到文件
     async fn save_document(&self, path: &Path, content: &str) -> Result<(), GeneratorError> {
+        let normalized;
+        let content = if self.config.normalize_markdown {
+            normalized = normalize_markdown(content);
+            normalized.as_str()
+        } else {
+            content
+        };
+
         // 确保父目录存在
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)
@@ -547,10 +865,7 @@ impl DocumentGenerator {
                 if metadata.len() == 0 {
                     return Err(GeneratorError::IoError(
                         path.to_path_buf(),
-                        std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            "File was written but is empty"
-                        ),
+                        std::io::Error::other("File was written but is empty"),
                     ));
                 }
             }
@@ -565,6 +880,10 @@ impl DocumentGenerator {
     /// 格式化文件文档
     fn format_file_doc(&self, node: &FileNode, summary: &str) -> String {
         let now = Local::now().format("%Y-%m-%d %H:%M:%S");
+        if self.config.front_matter {
+            let front_matter = self.render_front_matter(&node.name, &node.relative_path, &now.to_string());
+            return format!("{}{}\n", front_matter, summary);
+        }
         format!(
             r#"# 文件分析: {}
 
@@ -579,6 +898,20 @@ impl DocumentGenerator {
         )
     }
 
+    /// 按配置的模板渲染 YAML front matter
+    ///
+    /// 占位符替换为双引号字符串（JSON 转义与 YAML 双引号标量兼容），路径中的特殊字符不会破坏 YAML。
+    fn render_front_matter(&self, title: &str, source: &str, date: &str) -> String {
+        let quote = |value: &str| serde_json::Value::String(value.to_string()).to_string();
+        let body = self
+            .config
+            .front_matter_template
+            .replace("{title}", &quote(title))
+            .replace("{source}", &quote(source))
+            .replace("{date}", &quote(date));
+        format!("---\n{}\n---\n\n", body.trim_end())
+    }
+
     /// 格式化目录文档
     fn format_dir_doc(&self, node: &FileNode, summary: &str) -> String {
         let now = Local::now().format("%Y-%m-%d %H:%M:%S");
@@ -587,6 +920,10 @@ impl DocumentGenerator {
         } else {
             &node.relative_path
         };
+        if self.config.front_matter {
+            let front_matter = self.render_front_matter(&node.name, path_display, &now.to_string());
+            return format!("{}{}\n", front_matter, summary);
+        }
 
         format!(
             r#"# 目录分析: {}
@@ -651,6 +988,107 @@ impl DocumentGenerator {
     }
 }
 
+/// 代码块围栏的标记字符（``` 或 ~~~），不是围栏行时返回 None
+fn fence_marker(line: &str) -> Option<char> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    ['`', '~'].into_iter().find(|&c| trimmed.starts_with(&c.to_string().repeat(3)))
+}
+
+/// 在文档末尾追加组件位置表，只列出带行号的图谱节点（按行号排序），没有时原样返回
+pub(crate) fn append_line_annotations(doc: &str, nodes: &[LlmGraphNode]) -> String {
+    let mut located: Vec<(&LlmGraphNode, usize)> = nodes
+        .iter()
+        .filter_map(|node| node.line.map(|line| (node, line)))
+        .collect();
+    if located.is_empty() {
+        return doc.to_string();
+    }
+    located.sort_by_key(|(_, line)| *line);
+
+    let mut out = doc.trim_end().to_string();
+    out.push_str("\n\n## 组件位置\n\n| 组件 | 类型 | 行号 |\n|------|------|------|\n");
+    for (node, line) in located {
+        out.push_str(&format!(
+            "| `{}` | {} | line {} |\n",
+            node.label.replace('|', "\\|"),
+            node.node_type,
+            line
+        ));
+    }
+    out
+}
+
+/// 规范化 Markdown 空白，便于通过 markdownlint
+///
+/// 去掉行尾空白，三个及以上连续换行压缩为一个空行，文末恰好一个换行；代码块内容保持原样。
+pub(crate) fn normalize_markdown(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut fence: Option<char> = None;
+    let mut blank_run = 0;
+
+    for line in content.lines() {
+        if let Some(marker) = fence {
+            out.push_str(line);
+            out.push('\n');
+            if fence_marker(line) == Some(marker) {
+                fence = None;
+            }
+            continue;
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank_run += 1;
+            if blank_run > 1 || out.is_empty() {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+            fence = fence_marker(line);
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+
+    let trimmed_len = out.trim_end_matches('\n').len();
+    out.truncate(trimmed_len);
+    out.push('\n');
+    out
+}
+
+/// README 自动生成区块的起止标记
+const AUTO_SECTION_START: &str = "<!-- AUTO-GENERATED -->";
+const AUTO_SECTION_END: &str = "<!-- /AUTO-GENERATED -->";
+
+/// 用 `generated` 替换 `existing` 中的自动生成区块，区块外的人工内容保持不变
+///
+/// 没有区块时追加到末尾；只有开始标记时替换到文件末尾。
+fn replace_auto_section(existing: &str, generated: &str) -> String {
+    let section = format!("{}\n{}\n{}", AUTO_SECTION_START, generated.trim_end(), AUTO_SECTION_END);
+
+    let (before, after) = match existing.find(AUTO_SECTION_START) {
+        Some(start) => {
+            let rest = &existing[start..];
+            let after = rest.find(AUTO_SECTION_END).map_or("", |end| &rest[end + AUTO_SECTION_END.len()..]);
+            (&existing[..start], after)
+        }
+        None => (existing, ""),
+    };
+
+    let before = before.trim_end();
+    let mut document = String::with_capacity(before.len() + section.len() + after.len() + 2);
+    if !before.is_empty() {
+        document.push_str(before);
+        document.push_str("\n\n");
+    }
+    document.push_str(&section);
+    document.push_str(if after.trim().is_empty() { "\n" } else { after });
+    document
+}
+
 /// 生成器错误类型
 #[derive(Debug, thiserror::Error)]
 pub enum GeneratorError {
@@ -661,6 +1099,211 @@ pub enum GeneratorError {
     LlmError(String),
 }
 
+/// 允许的图谱节点类型
+const GRAPH_NODE_TYPES: &[&str] = &[
+    "class", "function", "method", "interface", "struct", "enum", "constant",
+];
+
+/// 允许的图谱边类型
+const GRAPH_EDGE_TYPES: &[&str] = &["contains", "imports", "calls", "inherits", "implements"];
+
+/// 无法识别的节点类型归一化后的默认值
+const DEFAULT_NODE_TYPE: &str = "function";
+
+/// 无法识别的边类型归一化后的默认值
+const DEFAULT_EDGE_TYPE: &str = "calls";
+
+/// 子文档之间的分隔符（每份子文档以 `### {名称}` 开头）
+const CHILD_DOC_SEPARATOR: &str = "\n\n---\n\n";
+
+/// 子文档分组汇总的最大轮数
+const MAX_REDUCE_ROUNDS: usize = 3;
+
+/// 粗略估算文本的 token 数：ASCII 约 4 个字符一个 token，其他字符（如中文）按一个字符一个 token
+pub(crate) fn estimate_tokens(text: &str) -> usize {
+    let (ascii, other) = text
+        .chars()
+        .fold((0usize, 0usize), |(a, o), c| if c.is_ascii() { (a + 1, o) } else { (a, o + 1) });
+    ascii.div_ceil(4) + other
+}
+
+/// 将一次 LLM 调用的用量累加到节点的 token 总数；上游未返回用量时保持不变
+fn add_usage(tokens: &mut Option<u64>, usage: Option<TokenUsage>) {
+    if let Some(usage) = usage {
+        *tokens = Some(tokens.unwrap_or(0) + usage.total_tokens);
+    }
+}
+
+/// 按分隔符拆分子文档；文档内部自带的 `---` 分隔线不会被拆开
+fn split_child_documents(documents: &str) -> Vec<&str> {
+    let mut parts: Vec<&str> = Vec::new();
+    let mut start = 0;
+    for (pos, _) in documents.match_indices(CHILD_DOC_SEPARATOR) {
+        let next = pos + CHILD_DOC_SEPARATOR.len();
+        if documents[next..].starts_with("### ") {
+            parts.push(&documents[start..pos]);
+            start = next;
+        }
+    }
+    parts.push(&documents[start..]);
+    parts.retain(|p| !p.trim().is_empty());
+    parts
+}
+
+/// 按 token 预算将子文档顺序分组；单份超出预算的文档独占一组
+fn group_by_budget(documents: &[&str], budget: usize) -> Vec<String> {
+    let mut batches: Vec<String> = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut current_tokens = 0;
+
+    for doc in documents {
+        let tokens = estimate_tokens(doc);
+        if !current.is_empty() && current_tokens + tokens > budget {
+            batches.push(current.join(CHILD_DOC_SEPARATOR));
+            current.clear();
+            current_tokens = 0;
+        }
+        current.push(doc);
+        current_tokens += tokens;
+    }
+    if !current.is_empty() {
+        batches.push(current.join(CHILD_DOC_SEPARATOR));
+    }
+    batches
+}
+
+/// 拆分相对路径，同时接受 `/` 和 `\` 分隔符，忽略空段和 `.`
+///
+/// Windows 上扫描得到的 `src\utils\a.py` 与前端传入的 `src/utils/a.py` 指向同一文档
+pub fn relative_path_segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split(['/', '\\']).filter(|s| !s.is_empty() && *s != ".")
+}
+
+/// 将节点类型归一化为允许的取值（大小写、常见别名），无法识别时返回 None
+fn normalize_node_type(raw: &str) -> Option<&'static str> {
+    let lower = raw.trim().to_lowercase();
+    let normalized = match lower.as_str() {
+        "func" | "fn" => "function",
+        "const" | "variable" | "var" => "constant",
+        "trait" | "protocol" => "interface",
+        other => other,
+    };
+    GRAPH_NODE_TYPES.iter().copied().find(|t| *t == normalized)
+}
+
+/// 将边类型归一化为允许的取值（大小写、常见别名），无法识别时返回 None
+fn normalize_edge_type(raw: &str) -> Option<&'static str> {
+    let lower = raw.trim().to_lowercase();
+    let normalized = match lower.as_str() {
+        "contain" | "has" | "defines" => "contains",
+        "import" | "uses" | "depends_on" => "imports",
+        "call" | "invokes" => "calls",
+        "inherit" | "extends" => "inherits",
+        "implement" => "implements",
+        other => other,
+    };
+    GRAPH_EDGE_TYPES.iter().copied().find(|t| *t == normalized)
+}
+
+const GRAPH_START: &str = "<!-- GRAPH_DATA_START -->";
+const GRAPH_END: &str = "<!-- GRAPH_DATA_END -->";
+
+/// 拆分 LLM 响应中的图谱块
+///
+/// 返回（去除图谱块后的文档内容, 图谱标记之间的原始内容）；未找到成对标记时返回 `None`
+fn split_graph_block(response: &str) -> Option<(String, &str)> {
+    let start = response.find(GRAPH_START)?;
+    let end = response.find(GRAPH_END)?;
+    if start >= end {
+        return None;
+    }
+
+    let doc_content = format!(
+        "{}{}",
+        response[..start].trim_end(),
+        response[end + GRAPH_END.len()..].trim_start()
+    );
+    Some((doc_content, &response[start + GRAPH_START.len()..end]))
+}
+
+/// 校验 LLM 返回的图谱 JSON，逐条丢弃缺少必填字段的节点/边
+///
+/// - 节点必须有非空 `id`；缺少 `label` 时取 `id` 的最后一段
+/// - 边必须有非空 `source` 和 `target`
+/// - 未知的 `type` 归一化为默认值
+fn validate_graph_value(value: &serde_json::Value, path: &str) -> LlmGraphRawData {
+    let str_field = |item: &serde_json::Value, key: &str| {
+        item.get(key)
+            .and_then(|v| v.as_str())
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+
+    let mut dropped_nodes = 0;
+    let mut dropped_edges = 0;
+    let mut normalized = 0;
+
+    let mut nodes = Vec::new();
+    for item in value.get("nodes").and_then(|v| v.as_array()).into_iter().flatten() {
+        let Some(id) = str_field(item, "id") else {
+            dropped_nodes += 1;
+            continue;
+        };
+        let raw_type = str_field(item, "type").unwrap_or_default();
+        let node_type = normalize_node_type(&raw_type).unwrap_or_else(|| {
+            normalized += 1;
+            DEFAULT_NODE_TYPE
+        });
+        let label = str_field(item, "label")
+            .unwrap_or_else(|| id.rsplit("::").next().unwrap_or(&id).to_string());
+        let line = item.get("line").and_then(|v| v.as_u64()).map(|l| l as usize);
+
+        nodes.push(LlmGraphNode {
+            id,
+            label,
+            node_type: node_type.to_string(),
+            line,
+        });
+    }
+
+    let mut edges = Vec::new();
+    for item in value.get("edges").and_then(|v| v.as_array()).into_iter().flatten() {
+        let (Some(source), Some(target)) = (str_field(item, "source"), str_field(item, "target"))
+        else {
+            dropped_edges += 1;
+            continue;
+        };
+        let raw_type = str_field(item, "type").unwrap_or_default();
+        let edge_type = normalize_edge_type(&raw_type).unwrap_or_else(|| {
+            normalized += 1;
+            DEFAULT_EDGE_TYPE
+        });
+
+        edges.push(LlmGraphEdge {
+            source,
+            target,
+            edge_type: edge_type.to_string(),
+        });
+    }
+
+    let imports = value
+        .get("imports")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|item| serde_json::from_value::<ImportDeclaration>(item.clone()).ok())
+        .collect();
+
+    if dropped_nodes + dropped_edges + normalized > 0 {
+        warn!(
+            "[{}] 图谱校验: 丢弃 {} 个节点、{} 条边，归一化 {} 个未知类型",
+            path, dropped_nodes, dropped_edges, normalized
+        );
+    }
+
+    LlmGraphRawData { nodes, edges, imports }
+}
+
 /// 生成项目结构字符串（用于 Prompt）
 pub fn format_project_structure(root: &FileNode, indent: usize) -> String {
     let mut result = String::new();
@@ -683,6 +1326,11 @@ pub fn format_project_structure(root: &FileNode, indent: usize) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::{spawn_mock_llm, MockLlm, MOCK_COMPLETION_TOKENS, MOCK_PROMPT_TOKENS};
+    use tempfile::TempDir;
+
+    /// 模拟服务每次调用报告的 token 总数
+    const MOCK_TOKENS_PER_CALL: u64 = MOCK_PROMPT_TOKENS + MOCK_COMPLETION_TOKENS;
 
     #[test]
     fn test_get_file_doc_path() {
@@ -720,6 +1368,192 @@ mod tests {
         assert_eq!(doc_path, PathBuf::from("/docs/src/utils/_dir_summary.md"));
     }
 
+    #[tokio::test]
+    async fn test_readme_append_section_preserves_manual_content() {
+        let docs = TempDir::new().unwrap();
+        let config = DocGenConfig {
+            readme_mode: ReadmeMode::AppendSection,
+            ..DocGenConfig::default()
+        };
+        let generator = DocumentGenerator::new(docs.path().to_path_buf(), config);
+        let readme = docs.path().join("README.md");
+        std::fs::write(&readme, "# My Project\n\nHand-written intro.\n").unwrap();
+
+        generator.save_readme("demo", "first generated").await.unwrap();
+        generator.save_readme("demo", "second generated").await.unwrap();
+
+        let content = std::fs::read_to_string(&readme).unwrap();
+        assert!(content.starts_with("# My Project\n\nHand-written intro.\n\n<!-- AUTO-GENERATED -->\n"));
+        assert!(content.contains("second generated"));
+        assert!(!content.contains("first generated"));
+        assert_eq!(content.matches("<!-- AUTO-GENERATED -->").count(), 1);
+        assert!(content.trim_end().ends_with("<!-- /AUTO-GENERATED -->"));
+
+        // 区块之后的人工内容同样保留
+        let with_footer = replace_auto_section(&content, "third");
+        let with_footer = format!("{}\n## Footer\n", with_footer.trim_end());
+        let refreshed = replace_auto_section(&with_footer, "fourth");
+        assert!(refreshed.contains("fourth") && !refreshed.contains("third"));
+        assert!(refreshed.ends_with("<!-- /AUTO-GENERATED -->\n## Footer\n"));
+    }
+
+    #[test]
+    fn test_normalize_markdown() {
+        let messy = "\n# Title  \n\n\n\nText\t\n```python\ndef f():  \n\n\n\n    pass\n```\n\n\n\nEnd   \n\n\n";
+        assert_eq!(
+            normalize_markdown(messy),
+            "# Title\n\nText\n```python\ndef f():  \n\n\n\n    pass\n```\n\nEnd\n"
+        );
+        assert_eq!(normalize_markdown("a"), "a\n");
+    }
+
+    #[tokio::test]
+    async fn test_normalize_markdown_on_save() {
+        let docs = TempDir::new().unwrap();
+        let config = DocGenConfig {
+            normalize_markdown: true,
+            ..DocGenConfig::default()
+        };
+        let generator = DocumentGenerator::new(docs.path().to_path_buf(), config);
+        let path = generator.save_readme("demo", "Intro   \n\n\n\nMore").await.unwrap();
+
+        let saved = std::fs::read_to_string(path).unwrap();
+        assert!(saved.contains("Intro\n\nMore"));
+        assert!(saved.ends_with('\n') && !saved.ends_with("\n\n"));
+        assert!(saved.lines().all(|l| l == l.trim_end()));
+    }
+
+    #[tokio::test]
+    async fn test_readme_skip_if_exists() {
+        let docs = TempDir::new().unwrap();
+        let config = DocGenConfig {
+            readme_mode: ReadmeMode::SkipIfExists,
+            ..DocGenConfig::default()
+        };
+        let generator = DocumentGenerator::new(docs.path().to_path_buf(), config);
+        let readme = docs.path().join("README.md");
+        std::fs::write(&readme, "manual").unwrap();
+
+        generator.save_readme("demo", "generated").await.unwrap();
+        assert_eq!(std::fs::read_to_string(&readme).unwrap(), "manual");
+    }
+
+    #[test]
+    fn test_front_matter_header() {
+        let config = DocGenConfig {
+            front_matter: true,
+            ..DocGenConfig::default()
+        };
+        let generator = DocumentGenerator::new(PathBuf::from("/docs"), config);
+        let node = FileNode::new_file(
+            "a: b.py".to_string(),
+            PathBuf::from("/src/a: b.py"),
+            "src/a: b.py".to_string(),
+            2,
+        );
+
+        let doc = generator.format_file_doc(&node, "summary");
+        let rest = doc.strip_prefix("---\n").expect("front matter should open the document");
+        let (front_matter, body) = rest.split_once("\n---\n").unwrap();
+        assert_eq!(body.trim(), "summary");
+
+        // 每行都是 `key: "双引号字符串"`，值可按 YAML 双引号标量解析
+        let fields: std::collections::HashMap<&str, String> = front_matter
+            .lines()
+            .map(|line| {
+                let (key, value) = line.split_once(": ").unwrap();
+                (key, serde_json::from_str::<String>(value).unwrap())
+            })
+            .collect();
+        assert_eq!(fields["title"], "a: b.py");
+        assert_eq!(fields["source"], "src/a: b.py");
+        assert!(fields.contains_key("date"));
+
+        // 默认保持原有标题头
+        let default = DocumentGenerator::new(PathBuf::from("/docs"), DocGenConfig::default());
+        assert!(default.format_file_doc(&node, "summary").starts_with("# 文件分析: a: b.py"));
+    }
+
+    #[test]
+    fn test_doc_paths_accept_both_separators() {
+        let generator = DocumentGenerator::new(
+            PathBuf::from("/docs"),
+            DocGenConfig::default(),
+        );
+
+        for relative in ["src/utils/helper.py", "src\\utils\\helper.py", "./src\\utils/helper.py"] {
+            let node = FileNode::new_file(
+                "helper.py".to_string(),
+                PathBuf::from(relative),
+                relative.to_string(),
+                2,
+            );
+            assert_eq!(generator.get_file_doc_path(&node), PathBuf::from("/docs/src/utils/helper.py.md"));
+            assert_eq!(
+                generator.get_file_graph_path(&node),
+                PathBuf::from("/docs/src/utils/helper.py.graph.json")
+            );
+        }
+
+        let dir = FileNode::new_dir("utils".to_string(), PathBuf::from("src\\utils"), "src\\utils".to_string(), 1);
+        assert_eq!(generator.get_dir_graph_path(&dir), PathBuf::from("/docs/src/utils/_dir.graph.json"));
+        let root = FileNode::new_dir("proj".to_string(), PathBuf::new(), String::new(), 0);
+        assert_eq!(generator.get_dir_doc_path(&root), PathBuf::from("/docs/_dir_summary.md"));
+    }
+
+    #[test]
+    fn test_validate_graph_drops_invalid_entries() {
+        let value = serde_json::json!({
+            "nodes": [
+                { "id": "class::a.py::Foo", "label": "Foo", "type": "Class", "line": 3 },
+                { "id": "function::a.py::bar", "type": "func" },
+                { "label": "missing id", "type": "class" },
+                { "id": "weird::a.py::x", "label": "x", "type": "widget" },
+                "not an object"
+            ],
+            "edges": [
+                { "source": "class::a.py::Foo", "target": "function::a.py::bar", "type": "contains" },
+                { "source": "class::a.py::Foo", "type": "calls" },
+                { "source": "class::a.py::Foo", "target": "class::a.py::Base", "type": "extends" },
+                { "source": "a", "target": "b", "type": "mystery" }
+            ],
+            "imports": [{ "module": "os" }, { "items": ["x"] }]
+        });
+
+        let data = validate_graph_value(&value, "a.py");
+
+        let nodes: Vec<(&str, &str)> = data
+            .nodes
+            .iter()
+            .map(|n| (n.label.as_str(), n.node_type.as_str()))
+            .collect();
+        assert_eq!(nodes, vec![("Foo", "class"), ("bar", "function"), ("x", "function")]);
+        assert_eq!(data.nodes[0].line, Some(3));
+
+        let edges: Vec<&str> = data.edges.iter().map(|e| e.edge_type.as_str()).collect();
+        assert_eq!(edges, vec!["contains", "inherits", "calls"]);
+
+        assert_eq!(data.imports.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_llm_response_with_malformed_graph() {
+        let generator = DocumentGenerator::new(PathBuf::from("/docs"), DocGenConfig::default());
+        let response = r#"# Doc
+
+<!-- GRAPH_DATA_START -->
+```json
+{"nodes": [{"id": "function::a.py::run", "type": "function"}, {"type": "class"}], "edges": []}
+```
+<!-- GRAPH_DATA_END -->"#;
+
+        let (doc, graph) = generator.parse_llm_response_raw(response, "a.py");
+        assert_eq!(doc, "# Doc");
+        let graph = graph.unwrap();
+        assert_eq!(graph.nodes.len(), 1);
+        assert_eq!(graph.nodes[0].label, "run");
+    }
+
     #[test]
     fn test_format_project_structure() {
         let mut root = FileNode::new_dir(
@@ -739,4 +1573,220 @@ mod tests {
         let structure = format_project_structure(&root, 0);
         assert!(structure.contains("main.py"));
     }
+
+    /// 创建连接模拟 LLM 的生成器，文档写入临时源码目录下的 `.docs`
+    async fn mock_generator(responses: Vec<String>, config: DocGenConfig) -> (MockLlm, DocumentGenerator, LlmClient, TempDir) {
+        let mock = spawn_mock_llm(responses).await;
+        let client = LlmClient::new("sk-test", &mock.base_url, false).unwrap();
+        let source = TempDir::new().unwrap();
+        let generator = DocumentGenerator::new(source.path().join(".docs"), config);
+        (mock, generator, client, source)
+    }
+
+    /// 在源码目录写入文件并返回对应的文件节点
+    fn source_file(source: &TempDir, name: &str, content: &str) -> FileNode {
+        let file_path = source.path().join(name);
+        std::fs::write(&file_path, content).unwrap();
+        FileNode::new_file(name.to_string(), file_path, name.to_string(), 0)
+    }
+
+    #[tokio::test]
+    async fn test_stage_options_from_config() {
+        let config = DocGenConfig {
+            file_temperature: 0.1,
+            file_max_tokens: 1000,
+            synthesis_temperature: 0.9,
+            synthesis_max_tokens: 3000,
+            ..DocGenConfig::default()
+        };
+        let (mock, generator, client, source) = mock_generator(vec!["# main.py\n\nDoc.".to_string()], config).await;
+
+        let node = source_file(&source, "main.py", "print('hi')\n");
+        generator.analyze_file(&node, &client, "gpt-4o").await.unwrap();
+        generator.generate_readme("demo", "/demo", "docs", &client, "gpt-4o").await.unwrap();
+
+        let requests = mock.requests.lock();
+        assert_eq!(requests[0]["temperature"], 0.1);
+        assert_eq!(requests[0]["max_tokens"], 1000);
+        assert_eq!(requests[1]["temperature"], 0.9);
+        assert_eq!(requests[1]["max_tokens"], 3000);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_graph_retried_once() {
+        let broken = "# main.py\n\nDoc.\n\n<!-- GRAPH_DATA_START -->\n```json\n{\"nodes\": [{\"id\": \"function::main.py::run\",}\n```\n<!-- GRAPH_DATA_END -->";
+        let repaired = "{\"nodes\": [{\"id\": \"function::main.py::run\", \"label\": \"run\", \"type\": \"function\"}], \"edges\": []}";
+        let (mock, generator, client, source) =
+            mock_generator(vec![broken.to_string(), repaired.to_string()], DocGenConfig::default()).await;
+
+        let node = source_file(&source, "main.py", "def run():\n    pass\n");
+        let result = generator.analyze_file(&node, &client, "gpt-4o").await.unwrap();
+
+        assert_eq!(mock.request_count(), 2);
+        // 修正追问的用量同样计入文件
+        assert_eq!(result.tokens, Some(2 * MOCK_TOKENS_PER_CALL));
+        assert!(!result.doc_content.contains("GRAPH_DATA_START"));
+        let graph_data = result.graph_data.expect("graph data should be repaired");
+        assert_eq!(graph_data.nodes[0].id, "function::main.py::run");
+
+        generator.save_file_graph(&node, &graph_data).await.unwrap();
+        let saved = std::fs::read_to_string(generator.get_file_graph_path(&node)).unwrap();
+        assert!(saved.contains("function::main.py::run"));
+    }
+
+    #[tokio::test]
+    async fn test_empty_doc_retried() {
+        // 只有图谱块，剥离后文档为空
+        let graph_only = "<!-- GRAPH_DATA_START -->\n```json\n{\"nodes\": [], \"edges\": []}\n```\n<!-- GRAPH_DATA_END -->";
+        let responses = vec![graph_only.to_string(), "# main.py\n\nDoc.".to_string()];
+
+        let (mock, generator, client, source) = mock_generator(responses.clone(), DocGenConfig::default()).await;
+        let node = source_file(&source, "main.py", "def run():\n    pass\n");
+        let result = generator.analyze_file(&node, &client, "gpt-4o").await.unwrap();
+        assert_eq!(mock.request_count(), 2);
+        assert_eq!(result.doc_content, "# main.py\n\nDoc.");
+
+        // 不重试时直接失败
+        let config = DocGenConfig {
+            empty_doc_retries: 0,
+            ..DocGenConfig::default()
+        };
+        let (mock, generator, client, source) = mock_generator(responses, config).await;
+        let node = source_file(&source, "main.py", "def run():\n    pass\n");
+        assert!(matches!(
+            generator.analyze_file(&node, &client, "gpt-4o").await,
+            Err(GeneratorError::LlmError(_))
+        ));
+        assert_eq!(mock.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_line_annotations_from_graph() {
+        let response = "# parser.py\n\nParses input.\n\n<!-- GRAPH_DATA_START -->\n```json\n{\"nodes\": [\
+            {\"id\": \"function::parser.py::helper\", \"label\": \"helper\", \"type\": \"function\"},\
+            {\"id\": \"class::parser.py::Parser\", \"label\": \"Parser\", \"type\": \"class\", \"line\": 10}\
+            ], \"edges\": []}\n```\n<!-- GRAPH_DATA_END -->";
+        let config = DocGenConfig {
+            line_annotations: true,
+            ..DocGenConfig::default()
+        };
+        let (_mock, generator, client, source) = mock_generator(vec![response.to_string(); 2], config).await;
+        let node = source_file(&source, "parser.py", "class Parser:\n    pass\n");
+
+        let result = generator.analyze_file(&node, &client, "gpt-4o").await.unwrap();
+        assert!(result.doc_content.starts_with("# parser.py\n\nParses input."));
+        assert!(result.doc_content.contains("## 组件位置"));
+        assert!(result.doc_content.contains("| `Parser` | class | line 10 |"));
+        // 没有行号的节点不列出
+        assert!(!result.doc_content.contains("helper"));
+
+        // 默认关闭
+        let generator = DocumentGenerator::new(source.path().join(".docs"), DocGenConfig::default());
+        let result = generator.analyze_file(&node, &client, "gpt-4o").await.unwrap();
+        assert!(!result.doc_content.contains("## 组件位置"));
+    }
+
+    #[tokio::test]
+    async fn test_analysis_cache_hit_by_content() {
+        let response = "# main.py\n\nDoc.\n\n<!-- GRAPH_DATA_START -->\n```json\n{\"nodes\": [], \"edges\": []}\n```\n<!-- GRAPH_DATA_END -->";
+        let (mock, generator, client, source) = mock_generator(vec![response.to_string(); 6], DocGenConfig::default()).await;
+        let node = source_file(&source, "main.py", "def run():\n    pass\n");
+
+        let cache = Arc::new(AnalysisCache::default());
+        let generator = generator.with_analysis_cache(cache.clone());
+        let first = generator.analyze_file(&node, &client, "gpt-4o").await.unwrap();
+        let second = generator.analyze_file(&node, &client, "gpt-4o").await.unwrap();
+        assert_eq!(mock.request_count(), 1);
+        assert_eq!(first.doc_content, second.doc_content);
+
+        // 共享缓存的另一个生成器同样命中
+        let other = DocumentGenerator::new(source.path().join(".docs2"), DocGenConfig::default())
+            .with_analysis_cache(cache.clone());
+        other.analyze_file(&node, &client, "gpt-4o").await.unwrap();
+        assert_eq!(mock.request_count(), 1);
+
+        // 内容变化后重新调用 LLM
+        std::fs::write(&node.path, "def run():\n    return 1\n").unwrap();
+        generator.analyze_file(&node, &client, "gpt-4o").await.unwrap();
+        assert_eq!(mock.request_count(), 2);
+
+        // 显式重新生成不读缓存
+        generator
+            .analyze_file_with_instructions(&node, &client, "gpt-4o", None)
+            .await
+            .unwrap();
+        assert_eq!(mock.request_count(), 3);
+
+        // 生成参数不同视为不同的分析
+        let hotter = DocumentGenerator::new(
+            source.path().join(".docs"),
+            DocGenConfig {
+                file_temperature: DocGenConfig::default().file_temperature + 0.5,
+                ..DocGenConfig::default()
+            },
+        )
+        .with_analysis_cache(cache.clone());
+        hotter.analyze_file(&node, &client, "gpt-4o").await.unwrap();
+        assert_eq!(mock.request_count(), 4);
+
+        // 关闭缓存读取时重新调用 LLM，结果仍写回缓存
+        let fresh = DocumentGenerator::new(source.path().join(".docs"), DocGenConfig::default())
+            .with_analysis_cache(cache.clone())
+            .with_cache_lookup(false);
+        fresh.analyze_file(&node, &client, "gpt-4o").await.unwrap();
+        assert_eq!(mock.request_count(), 5);
+        generator.analyze_file(&node, &client, "gpt-4o").await.unwrap();
+        assert_eq!(mock.request_count(), 5);
+
+        // 同名模型但 base_url 不同的提供方不复用结果
+        let other_provider = LlmClient::new("sk-test", format!("{}/", mock.base_url), false).unwrap();
+        generator.analyze_file(&node, &other_provider, "gpt-4o").await.unwrap();
+        assert_eq!(mock.request_count(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_analysis_without_graph_not_cached() {
+        let (mock, generator, client, source) =
+            mock_generator(vec!["# main.py\n\nDoc.".to_string(); 2], DocGenConfig::default()).await;
+        let node = source_file(&source, "main.py", "def run():\n    pass\n");
+
+        assert!(generator.analyze_file(&node, &client, "gpt-4o").await.unwrap().graph_data.is_none());
+        generator.analyze_file(&node, &client, "gpt-4o").await.unwrap();
+        assert_eq!(mock.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_large_directory_summarized_in_batches() {
+        let config = DocGenConfig {
+            dir_summary_token_budget: 200,
+            ..DocGenConfig::default()
+        };
+        let (mock, generator, client, _source) =
+            mock_generator(vec!["# src\n\nSummary of modules.".to_string()], config).await;
+
+        // 30 份子文档，每份约 50 tokens，远超 200 的预算；文档内部的分隔线不应被拆开
+        let sub_documents = (0..30)
+            .map(|i| format!("### mod{}.py\n\n{}\n\n---\n\nfooter", i, "word ".repeat(36)))
+            .collect::<Vec<_>>()
+            .join(CHILD_DOC_SEPARATOR);
+        assert_eq!(split_child_documents(&sub_documents).len(), 30);
+
+        let node = FileNode::new_dir("src".to_string(), PathBuf::from("src"), "src".to_string(), 0);
+        let result = generator
+            .summarize_directory(&node, &sub_documents, &client, "gpt-4o")
+            .await
+            .unwrap();
+        assert_eq!(result.doc_content.trim(), "# src\n\nSummary of modules.");
+
+        let requests = mock.requests.lock();
+        assert!(requests.len() > 2);
+        // 每组汇总和最终总结的用量都计入目录
+        assert_eq!(result.tokens, Some(requests.len() as u64 * MOCK_TOKENS_PER_CALL));
+        let prompt = |i: usize| requests[i]["messages"][0]["content"].as_str().unwrap().to_string();
+        assert!(prompt(0).contains("1/"));
+        // 最终总结只包含各组汇总，不再包含原始子文档
+        let last = prompt(requests.len() - 1);
+        assert!(last.contains("### 第 1 组子模块"));
+        assert!(!last.contains("mod0.py"));
+    }
 }
@@ -0,0 +1,101 @@
+//! Git 变更文件查询
+//!
+//! 供增量生成使用：列出源码目录中相对某个 git 引用发生变化的文件。
+
+use std::path::Path;
+use tokio::process::Command;
+
+/// Git 查询错误
+#[derive(Debug, thiserror::Error)]
+pub enum GitError {
+    #[error("无效的 git 引用: {0}")]
+    InvalidRef(String),
+
+    #[error("执行 git 失败: {0}")]
+    Spawn(#[from] std::io::Error),
+
+    #[error("git 命令失败: {0}")]
+    Command(String),
+}
+
+/// 列出 `source_path` 下相对 `since` 发生变化的文件（含未跟踪的新文件）
+///
+/// 路径相对于 `source_path`，使用 `/` 分隔；已删除的文件也会列出，由调用方按扫描结果过滤。
+pub async fn changed_files_since(source_path: &Path, since: &str) -> Result<Vec<String>, GitError> {
+    let since = since.trim();
+    // 以 `-` 开头的引用会被 git 当作选项解析
+    if since.is_empty() || since.starts_with('-') {
+        return Err(GitError::InvalidRef(since.to_string()));
+    }
+
+    let mut files = run_git(source_path, &["diff", "--name-only", "-z", "--relative", since, "--"]).await?;
+    files.extend(run_git(source_path, &["ls-files", "-z", "--others", "--exclude-standard"]).await?);
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// 执行 git 并按 NUL 拆分输出（配合 `-z`：路径原样输出，不会被引号包裹或转义非 ASCII 字符）
+async fn run_git(dir: &Path, args: &[&str]) -> Result<Vec<String>, GitError> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output().await?;
+    if !output.status.success() {
+        return Err(GitError::Command(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(output
+        .stdout
+        .split(|&b| b == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| String::from_utf8_lossy(path).replace('\\', "/"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[tokio::test]
+    async fn test_changed_files_since() {
+        let repo = TempDir::new().unwrap();
+        std::fs::create_dir_all(repo.path().join("src")).unwrap();
+        std::fs::write(repo.path().join("src/a.py"), "a = 1\n").unwrap();
+        std::fs::write(repo.path().join("src/b.py"), "b = 1\n").unwrap();
+        std::fs::write(repo.path().join("top.py"), "t = 1\n").unwrap();
+        std::fs::write(repo.path().join("src/数据.py"), "d = 1\n").unwrap();
+        git(repo.path(), &["init", "-q"]);
+        git(repo.path(), &["add", "."]);
+        git(repo.path(), &["commit", "-q", "-m", "init"]);
+
+        std::fs::write(repo.path().join("src/a.py"), "a = 2\n").unwrap();
+        std::fs::write(repo.path().join("src/new.py"), "n = 1\n").unwrap();
+        std::fs::write(repo.path().join("top.py"), "t = 2\n").unwrap();
+        // 非 ASCII 和带空格的文件名（默认输出会被引号包裹并转义）
+        std::fs::write(repo.path().join("src/数据.py"), "d = 2\n").unwrap();
+        std::fs::write(repo.path().join("src/工具 模块.py"), "u = 1\n").unwrap();
+
+        // 相对源码目录（仓库子目录）的路径，不包含目录之外的变更
+        let files = changed_files_since(&repo.path().join("src"), "HEAD").await.unwrap();
+        assert_eq!(files, vec!["a.py", "new.py", "工具 模块.py", "数据.py"]);
+
+        assert!(matches!(
+            changed_files_since(repo.path(), "--output=/tmp/x").await,
+            Err(GitError::InvalidRef(_))
+        ));
+        assert!(changed_files_since(repo.path(), "no-such-ref").await.is_err());
+
+        let plain = TempDir::new().unwrap();
+        assert!(matches!(changed_files_since(plain.path(), "HEAD").await, Err(GitError::Command(_))));
+    }
+}
@@ -20,12 +20,14 @@
 //! let service = DocGenService::with_default_config();
 //! let llm_client = Arc::new(LlmClient::new("api_key", "https://api.openai.com/v1", false)?);
 //!
-//! let (task, progress_rx) = service.start_generation(
+//! let (task, progress_rx, _tree) = service.start_generation(
 //!     source_path,
 //!     None,  // 自动生成文档路径
+//!     None,  // 整个项目
 //!     llm_client,
 //!     "gpt-4".to_string(),
 //!     true,  // 启用断点续传
+//!     false, // 逐节点生成（true 时只重新生成 README 等最终文档）
 //! ).await?;
 //!
 //! // 监听进度
@@ -34,12 +36,19 @@
 //! }
 //! ```
 
+pub mod api_inventory;
+mod analysis_cache;
 mod checkpoint;
+mod docsignore;
 mod generator;
+pub mod git;
 mod processor;
 pub mod prompts;
 mod scanner;
+mod task_log;
 pub mod types;
 
-pub use processor::DocGenService;
-pub use types::{ProjectGraphData, SharedDocTask, TaskStats, WsDocMessage};
+pub use analysis_cache::AnalysisCache;
+pub use generator::{relative_path_segments, DocumentGenerator};
+pub use processor::{resolve_docs_path, DocGenService, GraphInvalidateSummary, ProcessorError};
+pub use types::{GenerationReport, ProjectGraphData, SharedDocTask, SharedFileTree, TaskStats, WsDocMessage, REPORT_FILE_NAME};
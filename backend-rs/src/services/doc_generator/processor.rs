@@ -2,19 +2,25 @@
 //!
 //! 主调度器，负责协调文件和目录的处理顺序
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::{broadcast, RwLock, Semaphore};
 use tracing::{error, info, warn};
 use futures::stream::{self, StreamExt};
 use chrono::Local;
+use serde::Serialize;
 
+use super::analysis_cache::AnalysisCache;
+use super::api_inventory::{render_api_artifact, ApiInventory, API_ARTIFACT_SUFFIX, API_INVENTORY_FILE_NAME};
 use super::checkpoint::CheckpointService;
-use super::generator::{format_project_structure, DocumentGenerator};
-use super::scanner::DirectoryScanner;
+use super::generator::{estimate_tokens, format_project_structure, relative_path_segments, DocumentGenerator};
+use super::scanner::{DirectoryScanner, ScanError, ScanResult};
+use super::task_log::{spawn_task_logger, TASK_LOG_FILE_NAME};
 use super::types::{
-    DirGraphData, DocGenConfig, DocTask, FileGraphData, FileNode, LlmGraphEdge, LlmGraphNode,
-    NodeStatus, ProjectGraphData, SharedDocTask, TaskStatus, WsDocMessage,
+    DirGraphData, DocGenConfig, DocTask, FailureMode, FileGraphData, FileNode, FileOrder, GenerationReport, LlmGraphEdge,
+    LlmGraphNode, NodeStatus, ProjectGraphData, SharedDocTask, SharedFileTree, TaskStatus, WsDocMessage,
+    REPORT_FILE_NAME,
 };
 use crate::llm::LlmClient;
 
@@ -43,6 +49,8 @@ pub struct LevelProcessor {
     progress_tx: broadcast::Sender<WsDocMessage>,
     /// 并行控制信号量
     semaphore: Arc<Semaphore>,
+    /// 只生成最终文档（README、阅读指南、项目图谱），跳过逐节点处理
+    final_only: bool,
 }
 
 impl LevelProcessor {
@@ -55,7 +63,7 @@ impl LevelProcessor {
         model: String,
         config: DocGenConfig,
     ) -> (Self, broadcast::Receiver<WsDocMessage>) {
-        let (progress_tx, progress_rx) = broadcast::channel(100);
+        let (progress_tx, progress_rx) = broadcast::channel(config.progress_channel_capacity.max(1));
 
         // 限制并行度（最小1，最大10）
         let concurrency = config.concurrency.clamp(1, 10);
@@ -70,21 +78,70 @@ impl LevelProcessor {
             config,
             progress_tx,
             semaphore: Arc::new(Semaphore::new(concurrency)),
+            final_only: false,
         };
 
         (processor, progress_rx)
     }
 
+    /// 只生成最终文档，直接使用磁盘上已有的文件和目录文档
+    pub fn with_final_only(mut self, final_only: bool) -> Self {
+        self.final_only = final_only;
+        self
+    }
+
     /// 订阅进度消息
     pub fn subscribe(&self) -> broadcast::Receiver<WsDocMessage> {
         self.progress_tx.subscribe()
     }
 
+    /// 共享的文件树，可在处理过程中查询各节点状态
+    pub fn tree(&self) -> SharedFileTree {
+        Arc::clone(&self.root)
+    }
+
     /// 处理所有层级
     ///
     /// 核心逻辑：按深度从深到浅处理，每一层同时处理该层的文件和目录（并发）
     /// 这样当处理某个目录时，它的所有子节点（文件+子目录）的文档都已完成
+    ///
+    /// 结束时（无论成功与否）在文档根目录写入 `_report.json`
     pub async fn process_all_levels(&self, task: SharedDocTask) -> Result<(), ProcessorError> {
+        let result = self.run_all_levels(&task).await;
+
+        // 成功时报告已在发送 Completed 之前写入
+        if let Err(e) = &result {
+            {
+                let mut t = task.write().await;
+                if t.status == TaskStatus::Running {
+                    t.fail(e.to_string());
+                }
+            }
+            self.write_report(&task).await;
+        }
+        result
+    }
+
+    /// 写入 `_report.json`，失败只记录日志
+    async fn write_report(&self, task: &SharedDocTask) {
+        let (report, docs_path) = {
+            let t = task.read().await;
+            let root = self.root.read().await;
+            (GenerationReport::build(&t, &root), t.docs_path.clone())
+        };
+
+        let report_path = docs_path.join(REPORT_FILE_NAME);
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => {
+                if let Err(e) = tokio::fs::write(&report_path, json).await {
+                    warn!("Failed to write generation report {}: {}", report_path.display(), e);
+                }
+            }
+            Err(e) => warn!("Failed to serialize generation report: {}", e),
+        }
+    }
+
+    async fn run_all_levels(&self, task: &SharedDocTask) -> Result<(), ProcessorError> {
         // 更新任务状态
         {
             let mut t = task.write().await;
@@ -94,13 +151,22 @@ impl LevelProcessor {
             t.stats.total_dirs = root.get_all_dirs().len();
         }
 
-        // 按深度统一处理文件和目录
-        info!("Starting level-by-level processing...");
-        self.process_by_depth(&task).await?;
+        if self.final_only {
+            // 逐节点文档已存在，全部计为跳过
+            info!("Final-only mode: skipping per-node processing");
+            let mut t = task.write().await;
+            t.stats.processed_files = t.stats.total_files;
+            t.stats.processed_dirs = t.stats.total_dirs;
+            t.stats.skipped_count = t.stats.total_files + t.stats.total_dirs;
+        } else {
+            // 按深度统一处理文件和目录
+            info!("Starting level-by-level processing...");
+            self.process_by_depth(task).await?;
+        }
 
         // 生成最终文档
         info!("Generating final documents...");
-        self.generate_final_docs(&task).await?;
+        self.generate_final_docs(task).await?;
 
         // 保存最终断点
         self.checkpoint.write().await.save_checkpoint().await.map_err(|e| {
@@ -113,8 +179,21 @@ impl LevelProcessor {
             t.complete();
         }
 
-        // 发送完成消息
+        // 发送完成消息（stats 中包含被跳过的过大文件）
         let stats = task.read().await.stats.clone();
+        if !stats.oversized_skipped.is_empty() {
+            warn!(
+                "{} oversized files were skipped: {}",
+                stats.oversized_skipped.len(),
+                stats
+                    .oversized_skipped
+                    .iter()
+                    .map(|f| format!("{} ({} bytes)", f.path, f.size))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        self.write_report(task).await;
         let _ = self.progress_tx.send(WsDocMessage::Completed { stats });
 
         Ok(())
@@ -136,6 +215,7 @@ impl LevelProcessor {
             path: PathBuf,
             depth: u32,
             is_file: bool,
+            size: u64,
         }
 
         let all_nodes: Vec<NodeInfo> = {
@@ -150,6 +230,7 @@ impl LevelProcessor {
                     path: file.path.clone(),
                     depth: file.depth,
                     is_file: true,
+                    size: file.size.unwrap_or(0),
                 });
             }
 
@@ -161,6 +242,7 @@ impl LevelProcessor {
                     path: dir.path.clone(),
                     depth: dir.depth,
                     is_file: false,
+                    size: 0,
                 });
             }
 
@@ -202,7 +284,15 @@ impl LevelProcessor {
                 return Err(ProcessorError::Cancelled);
             }
 
-            let (files_at_depth, dirs_at_depth) = depth_groups.remove(&depth).unwrap_or_default();
+            let (mut files_at_depth, dirs_at_depth) = depth_groups.remove(&depth).unwrap_or_default();
+            files_at_depth.sort_by(|a, b| {
+                let rank = |n: &NodeInfo| self.priority_rank(&n.relative_path);
+                rank(a).cmp(&rank(b)).then_with(|| match self.config.order {
+                    FileOrder::Name => a.relative_path.cmp(&b.relative_path),
+                    FileOrder::SizeAsc => a.size.cmp(&b.size).then_with(|| a.relative_path.cmp(&b.relative_path)),
+                    FileOrder::SizeDesc => b.size.cmp(&a.size).then_with(|| a.relative_path.cmp(&b.relative_path)),
+                })
+            });
             info!("Processing depth {}: {} files, {} directories",
                   depth, files_at_depth.len(), dirs_at_depth.len());
 
@@ -247,6 +337,16 @@ impl LevelProcessor {
         Ok(())
     }
 
+    /// 文件在 `priority_paths` 中的位置，不在列表中的排在所有优先文件之后
+    fn priority_rank(&self, relative_path: &str) -> usize {
+        let relative_path = relative_path.replace('\\', "/");
+        self.config
+            .priority_paths
+            .iter()
+            .position(|p| p.replace('\\', "/").trim_matches('/') == relative_path)
+            .unwrap_or(usize::MAX)
+    }
+
     /// 并发处理合并后的文件和目录任务
     ///
     /// 将文件和目录放在同一个流中统一调度，确保真正的交错并发处理
@@ -257,7 +357,7 @@ impl LevelProcessor {
         processed_count: &Arc<std::sync::atomic::AtomicUsize>,
         total_nodes: usize,
     ) -> Result<(), ProcessorError> {
-        let task_stream = stream::iter(tasks.into_iter());
+        let task_stream = stream::iter(tasks);
 
         task_stream
             .for_each_concurrent(self.config.concurrency, |node_task| {
@@ -270,6 +370,7 @@ impl LevelProcessor {
                 let progress_tx = self.progress_tx.clone();
                 let root = self.root.clone();
                 let processed_count = processed_count.clone();
+                let failure_mode = self.config.failure_mode;
 
                 async move {
                     // 获取信号量许可
@@ -287,14 +388,14 @@ impl LevelProcessor {
                         NodeTask::File { name, relative_path, path } => {
                             Self::process_single_file(
                                 &task, &checkpoint, &doc_generator, &llm_client, &model,
-                                &progress_tx, &root, &processed_count, total_nodes,
+                                &progress_tx, &root, &processed_count, total_nodes, failure_mode,
                                 name, relative_path, path,
                             ).await;
                         }
                         NodeTask::Dir { name, relative_path, path } => {
                             Self::process_single_dir(
                                 &task, &checkpoint, &doc_generator, &llm_client, &model,
-                                &progress_tx, &root, &processed_count, total_nodes,
+                                &progress_tx, &root, &processed_count, total_nodes, failure_mode,
                                 name, relative_path, path,
                             ).await;
                         }
@@ -316,7 +417,35 @@ impl LevelProcessor {
         Ok(())
     }
 
+    /// 记录节点失败
+    ///
+    /// 快速失败模式下将任务置为失败并通知客户端，其余节点随即停止调度；
+    /// 尽力模式下只累计 `failed_count` 并发送 `NodeFailed`，任务继续运行。
+    async fn handle_node_failure(
+        task: &SharedDocTask,
+        progress_tx: &broadcast::Sender<WsDocMessage>,
+        failure_mode: FailureMode,
+        relative_path: &str,
+        error_msg: String,
+    ) {
+        let mut t = task.write().await;
+        t.stats.failed_count += 1;
+        match failure_mode {
+            FailureMode::FastFail => {
+                t.fail(error_msg.clone());
+                let _ = progress_tx.send(WsDocMessage::Error { message: error_msg });
+            }
+            FailureMode::BestEffort => {
+                let _ = progress_tx.send(WsDocMessage::NodeFailed {
+                    path: relative_path.to_string(),
+                    message: error_msg,
+                });
+            }
+        }
+    }
+
     /// 处理单个文件
+    #[allow(clippy::too_many_arguments)]
     async fn process_single_file(
         task: &SharedDocTask,
         checkpoint: &Arc<RwLock<CheckpointService>>,
@@ -327,6 +456,7 @@ impl LevelProcessor {
         root: &Arc<RwLock<FileNode>>,
         processed_count: &Arc<std::sync::atomic::AtomicUsize>,
         total_nodes: usize,
+        failure_mode: FailureMode,
         name: String,
         relative_path: String,
         path: PathBuf,
@@ -368,6 +498,8 @@ impl LevelProcessor {
 
         info!("Analyzing file: {}", relative_path);
 
+        let started = Instant::now();
+
         // 构造 FileNode 用于分析
         let file_node = FileNode::new_file(name.clone(), path.clone(), relative_path.clone(), 0);
 
@@ -381,18 +513,9 @@ impl LevelProcessor {
                         {
                             let mut cp = checkpoint.write().await;
                             cp.mark_file_completed(&relative_path, &doc_path.to_string_lossy());
-                        }
-
-                        // 更新节点状态
-                        {
-                            let mut root_guard = root.write().await;
-                            update_node_status_recursive(
-                                &mut root_guard,
-                                &relative_path,
-                                NodeStatus::Completed,
-                                Some(doc_path.to_string_lossy().to_string()),
-                                true,
-                            );
+                            if let Err(e) = cp.save_if_due().await {
+                                warn!("Failed to save interim checkpoint: {}", e);
+                            }
                         }
 
                         // 保存图谱数据（如果有）
@@ -409,59 +532,40 @@ impl LevelProcessor {
                             info!("文件 {} 未提取到图谱数据", relative_path);
                         }
 
+                        // 更新节点状态
+                        let outcome = NodeOutcome::completed(&doc_path, started, analysis_result.tokens);
+                        record_node_outcome(&mut *root.write().await, &relative_path, true, &outcome);
+
                         // 发送完成消息
                         let _ = progress_tx.send(WsDocMessage::FileCompleted {
                             path: relative_path.clone(),
                         });
+                        let _ = progress_tx.send(outcome.timed_message(&relative_path));
 
                         // 更新统计
                         {
                             let mut t = task.write().await;
                             t.stats.processed_files += 1;
+                            t.stats.record_timing(&relative_path, outcome.duration_ms);
                             t.update_progress(None);
                         }
                     }
                     Err(e) => {
                         let error_msg = format!("Failed to save document {}: {}", relative_path, e);
                         error!("{}", error_msg);
-                        {
-                            let mut root_guard = root.write().await;
-                            update_node_status_recursive(
-                                &mut root_guard,
-                                &relative_path,
-                                NodeStatus::Failed,
-                                None,
-                                true,
-                            );
-                        }
-                        // 设置任务为失败状态，触发快速失败
-                        {
-                            let mut t = task.write().await;
-                            t.fail(error_msg.clone());
-                        }
-                        let _ = progress_tx.send(WsDocMessage::Error { message: error_msg });
+                        // 分析已完成，用量照常计入
+                        let outcome = NodeOutcome::failed(started, analysis_result.tokens, &error_msg);
+                        record_node_outcome(&mut *root.write().await, &relative_path, true, &outcome);
+                        Self::handle_node_failure(task, progress_tx, failure_mode, &relative_path, error_msg).await;
                     }
                 }
             }
             Err(e) => {
                 let error_msg = format!("Failed to analyze file {}: {}", relative_path, e);
                 error!("{}", error_msg);
-                {
-                    let mut root_guard = root.write().await;
-                    update_node_status_recursive(
-                        &mut root_guard,
-                        &relative_path,
-                        NodeStatus::Failed,
-                        None,
-                        true,
-                    );
-                }
-                // 设置任务为失败状态，触发快速失败
-                {
-                    let mut t = task.write().await;
-                    t.fail(error_msg.clone());
-                }
-                let _ = progress_tx.send(WsDocMessage::Error { message: error_msg });
+                let outcome = NodeOutcome::failed(started, None, &error_msg);
+                record_node_outcome(&mut *root.write().await, &relative_path, true, &outcome);
+                Self::handle_node_failure(task, progress_tx, failure_mode, &relative_path, error_msg).await;
             }
         }
 
@@ -469,6 +573,7 @@ impl LevelProcessor {
     }
 
     /// 处理单个目录
+    #[allow(clippy::too_many_arguments)]
     async fn process_single_dir(
         task: &SharedDocTask,
         checkpoint: &Arc<RwLock<CheckpointService>>,
@@ -479,6 +584,7 @@ impl LevelProcessor {
         root: &Arc<RwLock<FileNode>>,
         processed_count: &Arc<std::sync::atomic::AtomicUsize>,
         total_nodes: usize,
+        failure_mode: FailureMode,
         name: String,
         relative_path: String,
         path: PathBuf,
@@ -543,24 +649,23 @@ impl LevelProcessor {
                 .unwrap_or_else(|| FileNode::new_dir(name.clone(), path.clone(), relative_path.clone(), 0))
         };
 
+        let started = Instant::now();
+
         // 生成目录总结（同一次 LLM 调用中提取文档和图谱）
         match doc_generator.summarize_directory(&dir_node, &sub_documents, llm_client, model).await {
-            Ok(analysis_result) => {
+            Ok(mut analysis_result) => {
+                // 根据子文件图谱补充同目录文件间的依赖关系
+                let sibling_graphs = doc_generator.read_child_file_graphs(&dir_node).await;
+                link_sibling_files(&mut analysis_result.graph_data, &relative_path, &sibling_graphs);
+
                 match doc_generator.save_dir_summary(&dir_node, &analysis_result.doc_content).await {
                     Ok(doc_path) => {
                         {
                             let mut cp = checkpoint.write().await;
                             cp.mark_dir_completed(&relative_path, &doc_path.to_string_lossy());
-                        }
-                        {
-                            let mut root_guard = root.write().await;
-                            update_node_status_recursive(
-                                &mut root_guard,
-                                &relative_path,
-                                NodeStatus::Completed,
-                                Some(doc_path.to_string_lossy().to_string()),
-                                false,
-                            );
+                            if let Err(e) = cp.save_if_due().await {
+                                warn!("Failed to save interim checkpoint: {}", e);
+                            }
                         }
 
                         // 保存目录图谱数据（如果有）
@@ -577,41 +682,35 @@ impl LevelProcessor {
                             info!("目录 {} 未提取到图谱数据", relative_path);
                         }
 
+                        let outcome = NodeOutcome::completed(&doc_path, started, analysis_result.tokens);
+                        record_node_outcome(&mut *root.write().await, &relative_path, false, &outcome);
+
                         let _ = progress_tx.send(WsDocMessage::DirCompleted {
                             path: relative_path.clone(),
                         });
+                        let _ = progress_tx.send(outcome.timed_message(&relative_path));
 
-                        task.write().await.stats.processed_dirs += 1;
+                        {
+                            let mut t = task.write().await;
+                            t.stats.processed_dirs += 1;
+                            t.stats.record_timing(&relative_path, outcome.duration_ms);
+                        }
                     }
                     Err(e) => {
                         let error_msg = format!("Failed to save directory document {}: {}", relative_path, e);
                         error!("{}", error_msg);
-                        {
-                            let mut root_guard = root.write().await;
-                            update_node_status_recursive(&mut root_guard, &relative_path, NodeStatus::Failed, None, false);
-                        }
-                        // 设置任务为失败状态，触发快速失败
-                        {
-                            let mut t = task.write().await;
-                            t.fail(error_msg.clone());
-                        }
-                        let _ = progress_tx.send(WsDocMessage::Error { message: error_msg });
+                        let outcome = NodeOutcome::failed(started, analysis_result.tokens, &error_msg);
+                        record_node_outcome(&mut *root.write().await, &relative_path, false, &outcome);
+                        Self::handle_node_failure(task, progress_tx, failure_mode, &relative_path, error_msg).await;
                     }
                 }
             }
             Err(e) => {
                 let error_msg = format!("Failed to generate directory summary {}: {}", relative_path, e);
                 error!("{}", error_msg);
-                {
-                    let mut root_guard = root.write().await;
-                    update_node_status_recursive(&mut root_guard, &relative_path, NodeStatus::Failed, None, false);
-                }
-                // 设置任务为失败状态，触发快速失败
-                {
-                    let mut t = task.write().await;
-                    t.fail(error_msg.clone());
-                }
-                let _ = progress_tx.send(WsDocMessage::Error { message: error_msg });
+                let outcome = NodeOutcome::failed(started, None, &error_msg);
+                record_node_outcome(&mut *root.write().await, &relative_path, false, &outcome);
+                Self::handle_node_failure(task, progress_tx, failure_mode, &relative_path, error_msg).await;
             }
         }
 
@@ -632,41 +731,57 @@ impl LevelProcessor {
         // 读取所有文档
         let all_documents = self.read_all_documents().await;
 
-        // 生成 README
-        if !self.checkpoint.read().await.is_readme_completed() {
-            info!("Generating README...");
+        // README 与阅读指南相互独立，按 synthesis_concurrency 并发生成；
+        // 各自完成后单独标记断点，一个失败不影响另一个已完成的结果
+        let synthesis_permits = Semaphore::new(self.config.synthesis_concurrency.max(1));
+        let (readme_pending, reading_guide_pending) = {
+            let checkpoint = self.checkpoint.read().await;
+            (!checkpoint.is_readme_completed(), !checkpoint.is_reading_guide_completed())
+        };
+
+        // 两份文档可能同时进行，只发送一次合并的进度，避免进度在两者之间来回跳动
+        let pending_names: Vec<&str> = [
+            (readme_pending, self.config.readme_name.as_str()),
+            (reading_guide_pending, self.config.reading_guide_name.as_str()),
+        ]
+        .into_iter()
+        .filter_map(|(pending, name)| pending.then_some(name))
+        .collect();
+        if !pending_names.is_empty() {
             let _ = self.progress_tx.send(WsDocMessage::Progress {
                 progress: 92.0,
-                current_file: Some("README.md".to_string()),
+                current_file: Some(pending_names.join(", ")),
                 stats: task.read().await.stats.clone(),
             });
+        }
+
+        let readme = async {
+            if !readme_pending {
+                return Ok(());
+            }
+            let _permit = synthesis_permits.acquire().await.expect("semaphore closed");
+            info!("Generating README...");
 
             let content = self
                 .doc_generator
                 .generate_readme(&project_name, &project_path, &all_documents, &self.llm_client, &self.model)
                 .await
-                .map_err(|e| {
-                    let error_msg = format!("Failed to generate README: {}", e);
-                    let _ = self.progress_tx.send(WsDocMessage::Error { message: error_msg.clone() });
-                    ProcessorError::GeneratorError(error_msg)
-                })?;
-
-            self.doc_generator.save_readme(&project_name, &content).await.map_err(|e| {
-                let error_msg = format!("Failed to save README: {}", e);
-                let _ = self.progress_tx.send(WsDocMessage::Error { message: error_msg.clone() });
-                ProcessorError::GeneratorError(error_msg)
-            })?;
+                .map_err(|e| self.synthesis_error(format!("Failed to generate README: {}", e)))?;
+
+            self.doc_generator
+                .save_readme(&project_name, &content)
+                .await
+                .map_err(|e| self.synthesis_error(format!("Failed to save README: {}", e)))?;
             self.checkpoint.write().await.mark_readme_completed();
-        }
+            Ok::<(), ProcessorError>(())
+        };
 
-        // 生成阅读指南
-        if !self.checkpoint.read().await.is_reading_guide_completed() {
+        let reading_guide = async {
+            if !reading_guide_pending {
+                return Ok(());
+            }
+            let _permit = synthesis_permits.acquire().await.expect("semaphore closed");
             info!("Generating reading guide...");
-            let _ = self.progress_tx.send(WsDocMessage::Progress {
-                progress: 96.0,
-                current_file: Some("READING_GUIDE.md".to_string()),
-                stats: task.read().await.stats.clone(),
-            });
 
             let content = self
                 .doc_generator
@@ -678,22 +793,29 @@ impl LevelProcessor {
                     &self.model,
                 )
                 .await
-                .map_err(|e| {
-                    let error_msg = format!("Failed to generate reading guide: {}", e);
-                    let _ = self.progress_tx.send(WsDocMessage::Error { message: error_msg.clone() });
-                    ProcessorError::GeneratorError(error_msg)
-                })?;
-
-            self.doc_generator.save_reading_guide(&project_name, &content).await.map_err(|e| {
-                let error_msg = format!("Failed to save reading guide: {}", e);
-                let _ = self.progress_tx.send(WsDocMessage::Error { message: error_msg.clone() });
-                ProcessorError::GeneratorError(error_msg)
-            })?;
+                .map_err(|e| self.synthesis_error(format!("Failed to generate reading guide: {}", e)))?;
+
+            self.doc_generator
+                .save_reading_guide(&project_name, &content)
+                .await
+                .map_err(|e| self.synthesis_error(format!("Failed to save reading guide: {}", e)))?;
             self.checkpoint.write().await.mark_reading_guide_completed();
+            Ok(())
+        };
+
+        let (readme_result, reading_guide_result) = tokio::join!(readme, reading_guide);
+        if readme_result.is_err() || reading_guide_result.is_err() {
+            // 保存已完成部分的断点，恢复时只重试失败的文档
+            let _ = self.checkpoint.write().await.save_checkpoint().await;
         }
+        readme_result?;
+        reading_guide_result?;
+
+        // API 清单只依赖已有文件文档，每次都重新汇总
+        self.write_api_inventory().await;
 
         // 聚合项目级图谱
-        if !self.checkpoint.read().await.is_project_graph_completed() {
+        if self.config.extract_graph && !self.checkpoint.read().await.is_project_graph_completed() {
             info!("Aggregating project graph...");
             let _ = self.progress_tx.send(WsDocMessage::Progress {
                 progress: 98.0,
@@ -715,6 +837,124 @@ impl LevelProcessor {
         Ok(())
     }
 
+    /// 广播最终文档阶段的错误并转换为 ProcessorError
+    fn synthesis_error(&self, error_msg: String) -> ProcessorError {
+        let _ = self.progress_tx.send(WsDocMessage::Error { message: error_msg.clone() });
+        ProcessorError::GeneratorError(error_msg)
+    }
+
+    /// 从各文件文档的 API 标记汇总 `_api.json`，失败只记录警告
+    ///
+    /// 开启 `keep_api_artifacts` 时先为每个文件写出 `*.api.md` 中间结果。
+    async fn write_api_inventory(&self) {
+        let files: Vec<(String, String)> = {
+            let root = self.root.read().await;
+            root.get_all_files()
+                .into_iter()
+                .filter_map(|f| f.doc_path.clone().map(|doc| (f.relative_path.clone(), doc)))
+                .collect()
+        };
+
+        let mut documents = Vec::with_capacity(files.len());
+        for (relative_path, doc_path) in files {
+            if let Ok(content) = self.doc_generator.read_document(std::path::Path::new(&doc_path)).await {
+                if self.config.keep_api_artifacts {
+                    write_api_artifact(&doc_path, &relative_path, &content).await;
+                }
+                documents.push((relative_path, content));
+            }
+        }
+
+        let inventory = ApiInventory::from_documents(documents.iter().map(|(p, d)| (p.as_str(), d.as_str())));
+        for conflict in &inventory.conflicts {
+            warn!(
+                "Endpoint {} {} is declared in multiple files: {}",
+                conflict.method,
+                conflict.path,
+                conflict.source_files.join(", ")
+            );
+        }
+        let path = self.doc_generator.docs_root().join(API_INVENTORY_FILE_NAME);
+        match serde_json::to_string_pretty(&inventory) {
+            Ok(json) => {
+                if let Err(e) = tokio::fs::write(&path, json).await {
+                    warn!("Failed to write API inventory {}: {}", path.display(), e);
+                }
+            }
+            Err(e) => warn!("Failed to serialize API inventory: {}", e),
+        }
+    }
+
+    /// 重新生成单个文件的文档和图谱
+    ///
+    /// 只重新分析 `relative_path` 对应的文件，保存其 `.md` 和 `.graph.json`，
+    /// 更新断点后重新聚合项目图谱。`instructions` 会追加到分析 Prompt 末尾。返回文档路径。
+    pub async fn regenerate_file(
+        &self,
+        relative_path: &str,
+        instructions: Option<&str>,
+    ) -> Result<PathBuf, ProcessorError> {
+        let (file_node, project_name) = {
+            let root = self.root.read().await;
+            let node = find_node_recursive_ref(&root, relative_path)
+                .filter(|n| n.is_file)
+                .cloned()
+                .ok_or_else(|| ProcessorError::NotFound(relative_path.to_string()))?;
+            (node, root.name.clone())
+        };
+
+        info!("Regenerating file: {}", relative_path);
+
+        let analysis_result = self
+            .doc_generator
+            .analyze_file_with_instructions(&file_node, &self.llm_client, &self.model, instructions)
+            .await
+            .map_err(|e| ProcessorError::GeneratorError(e.to_string()))?;
+
+        let doc_path = self
+            .doc_generator
+            .save_file_summary(&file_node, &analysis_result.doc_content)
+            .await
+            .map_err(|e| ProcessorError::GeneratorError(e.to_string()))?;
+
+        if let Some(graph_data) = &analysis_result.graph_data {
+            self.doc_generator
+                .save_file_graph(&file_node, graph_data)
+                .await
+                .map_err(|e| ProcessorError::GeneratorError(e.to_string()))?;
+        }
+
+        {
+            let mut root = self.root.write().await;
+            update_node_status_recursive(
+                &mut root,
+                relative_path,
+                NodeStatus::Completed,
+                Some(doc_path.to_string_lossy().to_string()),
+                true,
+            );
+        }
+
+        self.checkpoint
+            .write()
+            .await
+            .mark_file_completed(relative_path, &doc_path.to_string_lossy());
+
+        if self.config.extract_graph {
+            self.aggregate_project_graph(&project_name).await?;
+            self.checkpoint.write().await.mark_project_graph_completed();
+        }
+
+        self.checkpoint
+            .write()
+            .await
+            .save_checkpoint()
+            .await
+            .map_err(|e| ProcessorError::CheckpointError(e.to_string()))?;
+
+        Ok(doc_path)
+    }
+
     /// 聚合项目级图谱
     ///
     /// 遍历所有 .graph.json 文件（包括文件图谱和目录图谱），
@@ -723,104 +963,21 @@ impl LevelProcessor {
         use tokio::fs;
 
         let docs_root = self.doc_generator.docs_root();
-        let mut all_nodes: Vec<LlmGraphNode> = Vec::new();
-        let mut all_edges: Vec<LlmGraphEdge> = Vec::new();
-        let mut file_count = 0;
-        let mut dir_count = 0;
 
-        // 递归收集所有 .graph.json 文件
-        let graph_files = self.collect_graph_files(docs_root).await;
+        // 递归收集扫描根目录下的 .graph.json 文件（只生成子目录时不混入其他目录的旧图谱）
+        let graph_root = docs_root.join(&self.root.read().await.relative_path);
+        let mut graph_files = self.collect_graph_files(&graph_root).await;
+        // 目录遍历顺序与平台相关，排序后合并结果才可复现
+        graph_files.sort();
         info!("Found {} graph files to aggregate", graph_files.len());
 
-        for graph_path in &graph_files {
-            let file_name = graph_path.file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("");
-
-            match fs::read_to_string(graph_path).await {
-                Ok(content) => {
-                    if file_name == "_dir.graph.json" {
-                        // 目录图谱
-                        match serde_json::from_str::<DirGraphData>(&content) {
-                            Ok(graph_data) => {
-                                // 添加目录节点
-                                all_nodes.push(LlmGraphNode {
-                                    id: graph_data.dir_id.clone(),
-                                    label: graph_data.dir_path.split('/').last()
-                                        .unwrap_or_else(|| if graph_data.dir_path.is_empty() { project_name } else { &graph_data.dir_path })
-                                        .to_string(),
-                                    node_type: "directory".to_string(),
-                                    line: None,
-                                });
-
-                                // 添加目录内的节点
-                                all_nodes.extend(graph_data.nodes.clone());
-
-                                // 添加边
-                                all_edges.extend(graph_data.edges.clone());
-
-                                // 根据导入声明生成跨模块依赖边
-                                for import in &graph_data.imports {
-                                    let target_file_id = self.resolve_import_target(&import.module, &graph_data.dir_path);
-                                    if let Some(target_id) = target_file_id {
-                                        all_edges.push(LlmGraphEdge {
-                                            source: graph_data.dir_id.clone(),
-                                            target: target_id,
-                                            edge_type: "imports".to_string(),
-                                        });
-                                    }
-                                }
-
-                                dir_count += 1;
-                            }
-                            Err(e) => {
-                                warn!("解析目录图谱文件 {} 失败: {}", graph_path.display(), e);
-                            }
-                        }
-                    } else {
-                        // 文件图谱
-                        match serde_json::from_str::<FileGraphData>(&content) {
-                            Ok(graph_data) => {
-                                // 添加文件节点
-                                all_nodes.push(LlmGraphNode {
-                                    id: graph_data.file_id.clone(),
-                                    label: graph_data.file_path.split('/').last()
-                                        .unwrap_or(&graph_data.file_path).to_string(),
-                                    node_type: "file".to_string(),
-                                    line: None,
-                                });
-
-                                // 添加文件内的节点
-                                all_nodes.extend(graph_data.nodes.clone());
-
-                                // 添加边
-                                all_edges.extend(graph_data.edges.clone());
-
-                                // 根据导入声明生成跨文件依赖边
-                                for import in &graph_data.imports {
-                                    let target_file_id = self.resolve_import_target(&import.module, &graph_data.file_path);
-                                    if let Some(target_id) = target_file_id {
-                                        all_edges.push(LlmGraphEdge {
-                                            source: graph_data.file_id.clone(),
-                                            target: target_id,
-                                            edge_type: "imports".to_string(),
-                                        });
-                                    }
-                                }
-
-                                file_count += 1;
-                            }
-                            Err(e) => {
-                                warn!("解析文件图谱 {} 失败: {}", graph_path.display(), e);
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    warn!("读取图谱文件 {} 失败: {}", graph_path.display(), e);
-                }
-            }
-        }
+        let graphs = load_graph_files(graph_files, self.config.graph_read_concurrency).await;
+        let MergedGraphs { nodes: mut all_nodes, edges: mut all_edges, file_graphs, dir_count } =
+            merge_graphs(graphs, project_name);
+        let file_count = file_graphs.len();
+
+        // 将指向导入文件中函数的调用关联为跨文件 calls 边
+        link_cross_file_calls(&file_graphs, &mut all_edges);
 
         // 从文件树生成目录包含关系边
         {
@@ -828,15 +985,7 @@ impl LevelProcessor {
             self.generate_structure_edges(&root, &mut all_nodes, &mut all_edges);
         }
 
-        // 去重节点（根据 ID）
-        let mut seen_ids = std::collections::HashSet::new();
-        all_nodes.retain(|node| seen_ids.insert(node.id.clone()));
-
-        // 去重边（根据 source + target + type）
-        let mut seen_edges = std::collections::HashSet::new();
-        all_edges.retain(|edge| {
-            seen_edges.insert(format!("{}->{}:{}", edge.source, edge.target, edge.edge_type))
-        });
+        dedup_graph(&mut all_nodes, &mut all_edges);
 
         // 创建项目图谱
         let project_graph = ProjectGraphData {
@@ -944,43 +1093,28 @@ impl LevelProcessor {
         graph_files
     }
 
-    /// 尝试解析导入的目标文件 ID
-    ///
-    /// 根据导入路径尝试匹配项目中的文件
-    fn resolve_import_target(&self, module: &str, _source_file: &str) -> Option<String> {
-        // 简单实现：将模块路径转换为文件 ID
-        // 实际项目中可能需要更复杂的解析逻辑
-
-        // 如果是相对导入（以 . 或 .. 开头）
-        if module.starts_with('.') {
-            // 暂时返回 None，因为解析相对路径需要更多上下文
-            return None;
-        }
-
-        // 对于绝对导入，尝试构建文件 ID
-        // 这里只是一个简单的启发式方法
-        let normalized = module.replace('.', "/");
-        Some(format!("file::{}", normalized))
-    }
-
-    /// 读取所有文档内容
+    /// 读取所有文档内容，总量受 `synthesis_token_budget` 限制
     async fn read_all_documents(&self) -> String {
         let root = self.root.read().await;
         let documents = self.collect_documents_recursive(&root).await;
-        documents.join("\n\n---\n\n")
+        fit_documents_to_budget(documents, self.config.synthesis_token_budget)
     }
 
     /// 递归收集文档内容（使用 Box::pin 解决递归异步问题）
     fn collect_documents_recursive<'a>(
         &'a self,
         node: &'a FileNode,
-    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Vec<String>> + Send + 'a>> {
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Vec<CollectedDocument>> + Send + 'a>> {
         Box::pin(async move {
             let mut documents = Vec::new();
 
             if let Some(doc_path) = &node.doc_path {
                 if let Ok(content) = self.doc_generator.read_document(std::path::Path::new(doc_path)).await {
-                    documents.push(format!("### {}\n\n{}", node.relative_path, content));
+                    documents.push(CollectedDocument {
+                        is_file: node.is_file,
+                        depth: node.depth,
+                        text: format!("### {}\n\n{}", node.relative_path, content),
+                    });
                 }
             }
 
@@ -994,6 +1128,7 @@ impl LevelProcessor {
     }
 
     /// 根据路径读取子节点文档
+    #[allow(dead_code)] // 目录总结改为在持有读锁时收集子文档，按路径读取的入口暂未使用
     async fn read_child_documents_by_path(&self, relative_path: &str) -> String {
         if let Some(dir_node) = self.find_dir_node(relative_path).await {
             if let Ok(content) = self.doc_generator.read_child_summaries(&dir_node).await {
@@ -1004,11 +1139,13 @@ impl LevelProcessor {
     }
 
     /// 查找目录节点
+    #[allow(dead_code)] // 仅被 read_child_documents_by_path 使用
     async fn find_dir_node(&self, relative_path: &str) -> Option<FileNode> {
         let root = self.root.read().await;
         self.find_node_recursive(&root, relative_path)
     }
 
+    #[allow(dead_code)] // 仅被 find_dir_node 使用；持锁查找请用 find_node_recursive_ref
     fn find_node_recursive(&self, node: &FileNode, relative_path: &str) -> Option<FileNode> {
         if node.relative_path == relative_path {
             return Some(node.clone());
@@ -1054,107 +1191,1946 @@ fn update_node_status_recursive(
     }
 
     for child in &mut node.children {
-        update_node_status_recursive(child, relative_path, status.clone(), doc_path.clone(), is_file);
+        update_node_status_recursive(child, relative_path, status, doc_path.clone(), is_file);
     }
 }
 
-/// 处理器错误类型
-#[derive(Debug, thiserror::Error)]
-pub enum ProcessorError {
-    #[error("Task cancelled")]
-    Cancelled,
+/// 节点处理结果
+///
+/// 耗时和用量只计算一次：写入 `FileNode` 供生成报告使用，同时用于 `NodeTimed` 消息和最慢节点统计
+struct NodeOutcome {
+    status: NodeStatus,
+    doc_path: Option<String>,
+    duration_ms: u64,
+    tokens: Option<u64>,
+    error: Option<String>,
+}
 
-    #[error("Checkpoint error: {0}")]
-    CheckpointError(String),
+impl NodeOutcome {
+    fn completed(doc_path: &Path, started: Instant, tokens: Option<u64>) -> Self {
+        Self {
+            status: NodeStatus::Completed,
+            doc_path: Some(doc_path.to_string_lossy().to_string()),
+            duration_ms: started.elapsed().as_millis() as u64,
+            tokens,
+            error: None,
+        }
+    }
 
-    #[error("Generator error: {0}")]
-    GeneratorError(String),
+    fn failed(started: Instant, tokens: Option<u64>, error: &str) -> Self {
+        Self {
+            status: NodeStatus::Failed,
+            doc_path: None,
+            duration_ms: started.elapsed().as_millis() as u64,
+            tokens,
+            error: Some(error.to_string()),
+        }
+    }
 
-    #[error("LLM error: {0}")]
-    LlmError(String),
+    fn timed_message(&self, relative_path: &str) -> WsDocMessage {
+        WsDocMessage::NodeTimed {
+            path: relative_path.to_string(),
+            duration_ms: self.duration_ms,
+            tokens: self.tokens,
+        }
+    }
 }
 
-/// 文档生成服务（主入口）
-pub struct DocGenService {
-    config: DocGenConfig,
+/// 将处理结果写入对应节点，找到后即停止遍历
+fn record_node_outcome(node: &mut FileNode, relative_path: &str, is_file: bool, outcome: &NodeOutcome) -> bool {
+    if node.relative_path == relative_path && node.is_file == is_file {
+        node.status = outcome.status;
+        node.doc_path = outcome.doc_path.clone();
+        node.duration_ms = Some(outcome.duration_ms);
+        node.tokens = outcome.tokens;
+        node.error = outcome.error.clone();
+        return true;
+    }
+
+    node.children
+        .iter_mut()
+        .any(|child| record_node_outcome(child, relative_path, is_file, outcome))
 }
 
-impl DocGenService {
-    /// 创建新的文档生成服务
-    pub fn new(config: DocGenConfig) -> Self {
-        Self { config }
+/// 尝试解析导入的目标文件 ID
+///
+/// 根据导入路径尝试匹配项目中的文件
+fn resolve_import_target(module: &str, _source_file: &str) -> Option<String> {
+    // 简单实现：将模块路径转换为文件 ID
+    // 实际项目中可能需要更复杂的解析逻辑
+
+    // 如果是相对导入（以 . 或 .. 开头）
+    if module.starts_with('.') {
+        // 暂时返回 None，因为解析相对路径需要更多上下文
+        return None;
     }
 
-    /// 使用默认配置创建
-    pub fn with_default_config() -> Self {
-        Self::new(DocGenConfig::default())
-    }
+    // 对于绝对导入，尝试构建文件 ID
+    // 这里只是一个简单的启发式方法
+    let normalized = module.replace('.', "/");
+    Some(format!("file::{}", normalized))
+}
 
-    /// 启动文档生成任务
-    pub async fn start_generation(
-        &self,
-        source_path: PathBuf,
-        docs_path: Option<PathBuf>,
-        llm_client: Arc<LlmClient>,
-        model: String,
-        resume: bool,
-    ) -> Result<(SharedDocTask, broadcast::Receiver<WsDocMessage>), ProcessorError> {
-        // 计算文档路径：默认放在项目根目录下的 .docs 目录
-        let docs_path = docs_path.unwrap_or_else(|| {
-            source_path.join(".docs")
-        });
+/// 将单个文件的接口提取结果写入文档旁的 `*.api.md`，失败只记录警告
+async fn write_api_artifact(doc_path: &str, relative_path: &str, content: &str) {
+    let Some(artifact) = render_api_artifact(content, relative_path) else {
+        return;
+    };
+    let path = format!("{}{}", doc_path.strip_suffix(".md").unwrap_or(doc_path), API_ARTIFACT_SUFFIX);
+    if let Err(e) = tokio::fs::write(&path, artifact).await {
+        warn!("Failed to write API artifact {}: {}", path, e);
+    }
+}
 
-        // 创建任务
-        let task_id = uuid::Uuid::new_v4().to_string();
-        let task = Arc::new(RwLock::new(DocTask::new(
-            task_id,
-            source_path.clone(),
-            docs_path.clone(),
-        )));
+/// 汇总给最终文档阶段的单份文档
+struct CollectedDocument {
+    is_file: bool,
+    depth: u32,
+    /// `### {相对路径}` 开头的文档内容
+    text: String,
+}
 
-        // 扫描目录
-        let scanner = DirectoryScanner::new(self.config.clone());
-        let root = scanner
-            .scan(&source_path)
-            .map_err(|e| ProcessorError::GeneratorError(e.to_string()))?;
+/// 最终文档阶段的文档分隔符
+const FINAL_DOC_SEPARATOR: &str = "\n\n---\n\n";
+
+/// 按预算挑选文档并按原顺序拼接
+///
+/// 超出预算时按重要性挑选：目录总结优先于文件文档，浅层优先于深层；放不下的文档整份省略，
+/// 并在末尾注明省略数量。
+fn fit_documents_to_budget(documents: Vec<CollectedDocument>, budget: usize) -> String {
+    let total: usize = documents.iter().map(|d| estimate_tokens(&d.text)).sum();
+    if total <= budget {
+        return documents.into_iter().map(|d| d.text).collect::<Vec<_>>().join(FINAL_DOC_SEPARATOR);
+    }
 
-        // 创建断点服务
-        let mut checkpoint =
-            CheckpointService::new(source_path.clone(), docs_path.clone(), self.config.clone());
-        checkpoint
-            .initialize()
-            .await
-            .map_err(|e| ProcessorError::CheckpointError(e.to_string()))?;
+    let mut order: Vec<usize> = (0..documents.len()).collect();
+    order.sort_by_key(|&i| (documents[i].is_file, documents[i].depth));
 
-        // 如果启用断点续传，加载断点
-        if resume {
-            let _ = checkpoint.load_checkpoint().await;
-            let _ = checkpoint.scan_existing_docs().await;
+    let mut keep = vec![false; documents.len()];
+    let mut used = 0;
+    for i in order {
+        let tokens = estimate_tokens(&documents[i].text) + estimate_tokens(FINAL_DOC_SEPARATOR);
+        if used + tokens <= budget {
+            used += tokens;
+            keep[i] = true;
         }
+    }
 
-        // 创建文档生成器
-        let doc_generator = DocumentGenerator::new(docs_path, self.config.clone());
+    let omitted = keep.iter().filter(|k| !**k).count();
+    warn!(
+        "Documents exceed synthesis budget (~{} > {} tokens), omitted {} of {} documents",
+        total,
+        budget,
+        omitted,
+        documents.len()
+    );
+
+    let mut parts: Vec<String> = documents
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(d, k)| k.then_some(d.text))
+        .collect();
+    parts.push(format!("（另有 {} 份文档因长度限制未列出）", omitted));
+    parts.join(FINAL_DOC_SEPARATOR)
+}
 
-        // 创建处理器
-        let (processor, progress_rx) = LevelProcessor::new(
-            root,
-            checkpoint,
-            doc_generator,
-            llm_client,
-            model,
-            self.config.clone(),
-        );
+/// 已解析的图谱文件
+enum LoadedGraph {
+    File(FileGraphData),
+    Dir(DirGraphData),
+}
 
-        // 在后台运行处理
-        let task_clone = Arc::clone(&task);
-        tokio::spawn(async move {
-            if let Err(e) = processor.process_all_levels(task_clone.clone()).await {
-                error!("Document generation failed: {}", e);
-                let mut t = task_clone.write().await;
+/// 读取并解析单个图谱文件，失败时记录警告并跳过
+fn load_graph_file(graph_path: &std::path::Path) -> Option<LoadedGraph> {
+    let content = match std::fs::read_to_string(graph_path) {
+        Ok(content) => content,
+        Err(e) => {
+            warn!("读取图谱文件 {} 失败: {}", graph_path.display(), e);
+            return None;
+        }
+    };
+
+    if graph_path.file_name().and_then(|n| n.to_str()) == Some("_dir.graph.json") {
+        serde_json::from_str::<DirGraphData>(&content)
+            .map(LoadedGraph::Dir)
+            .map_err(|e| warn!("解析目录图谱文件 {} 失败: {}", graph_path.display(), e))
+            .ok()
+    } else {
+        serde_json::from_str::<FileGraphData>(&content)
+            .map(LoadedGraph::File)
+            .map_err(|e| warn!("解析文件图谱 {} 失败: {}", graph_path.display(), e))
+            .ok()
+    }
+}
+
+/// 并发读取并解析图谱文件（最多 `concurrency` 个同时进行），结果保持 `graph_files` 的顺序
+async fn load_graph_files(graph_files: Vec<PathBuf>, concurrency: usize) -> Vec<LoadedGraph> {
+    let mut loaded: Vec<(usize, Option<LoadedGraph>)> = stream::iter(graph_files.into_iter().enumerate())
+        .map(|(index, path)| async move {
+            let graph = tokio::task::spawn_blocking(move || load_graph_file(&path))
+                .await
+                .unwrap_or(None);
+            (index, graph)
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    loaded.sort_by_key(|(index, _)| *index);
+    loaded.into_iter().filter_map(|(_, graph)| graph).collect()
+}
+
+/// 合并后的图谱数据
+struct MergedGraphs {
+    nodes: Vec<LlmGraphNode>,
+    edges: Vec<LlmGraphEdge>,
+    /// 各文件图谱，用于解析跨文件调用
+    file_graphs: Vec<FileGraphData>,
+    dir_count: usize,
+}
+
+/// 按顺序合并文件图谱和目录图谱：添加文件/目录节点、内部节点和边，并根据导入声明生成依赖边
+fn merge_graphs(graphs: Vec<LoadedGraph>, project_name: &str) -> MergedGraphs {
+    let mut merged = MergedGraphs {
+        nodes: Vec::new(),
+        edges: Vec::new(),
+        file_graphs: Vec::new(),
+        dir_count: 0,
+    };
+
+    for graph in graphs {
+        match graph {
+            LoadedGraph::Dir(graph_data) => {
+                merged.nodes.push(LlmGraphNode {
+                    id: graph_data.dir_id.clone(),
+                    label: graph_data.dir_path.split('/').next_back()
+                        .unwrap_or_else(|| if graph_data.dir_path.is_empty() { project_name } else { &graph_data.dir_path })
+                        .to_string(),
+                    node_type: "directory".to_string(),
+                    line: None,
+                });
+                merged.nodes.extend(graph_data.nodes);
+                merged.edges.extend(graph_data.edges);
+
+                // 根据导入声明生成跨模块依赖边
+                for import in &graph_data.imports {
+                    if let Some(target_id) = resolve_import_target(&import.module, &graph_data.dir_path) {
+                        merged.edges.push(LlmGraphEdge {
+                            source: graph_data.dir_id.clone(),
+                            target: target_id,
+                            edge_type: "imports".to_string(),
+                        });
+                    }
+                }
+                merged.dir_count += 1;
+            }
+            LoadedGraph::File(graph_data) => {
+                merged.nodes.push(LlmGraphNode {
+                    id: graph_data.file_id.clone(),
+                    label: graph_data.file_path.split('/').next_back()
+                        .unwrap_or(&graph_data.file_path).to_string(),
+                    node_type: "file".to_string(),
+                    line: None,
+                });
+                merged.nodes.extend(graph_data.nodes.clone());
+                merged.edges.extend(graph_data.edges.clone());
+
+                // 根据导入声明生成跨文件依赖边
+                for import in &graph_data.imports {
+                    if let Some(target_id) = resolve_import_target(&import.module, &graph_data.file_path) {
+                        merged.edges.push(LlmGraphEdge {
+                            source: graph_data.file_id.clone(),
+                            target: target_id,
+                            edge_type: "imports".to_string(),
+                        });
+                    }
+                }
+                merged.file_graphs.push(graph_data);
+            }
+        }
+    }
+
+    merged
+}
+
+/// 去重节点（根据 ID）和边（根据 source + target + type）
+fn dedup_graph(nodes: &mut Vec<LlmGraphNode>, edges: &mut Vec<LlmGraphEdge>) {
+    let mut seen_ids = std::collections::HashSet::new();
+    nodes.retain(|node| seen_ids.insert(node.id.clone()));
+
+    let mut seen_edges = std::collections::HashSet::new();
+    edges.retain(|edge| {
+        seen_edges.insert(format!("{}->{}:{}", edge.source, edge.target, edge.edge_type))
+    });
+}
+
+/// 节点是否属于某个文件
+///
+/// 文件节点 ID 为 `file::{path}`，文件内节点 ID 为 `{type}::{path}::...`
+fn node_belongs_to_file(id: &str, file_path: &str) -> bool {
+    if id.strip_prefix("file::") == Some(file_path) {
+        return true;
+    }
+    id.split_once("::")
+        .and_then(|(_, rest)| rest.strip_prefix(file_path))
+        .is_some_and(|rest| rest.starts_with("::"))
+}
+
+/// 增量更新项目图谱的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphInvalidateSummary {
+    /// 重新读取到图谱数据的文件
+    pub updated_files: Vec<String>,
+    /// 图谱文件已不存在、被移出项目图谱的文件
+    pub removed_files: Vec<String>,
+    /// 更新后的节点数
+    pub node_count: usize,
+    /// 更新后的边数
+    pub edge_count: usize,
+}
+
+/// 增量更新 `_project_graph.json`
+///
+/// 移除变更文件的节点和边，重新读取这些文件的 `.graph.json` 并合并，
+/// 其余文件的图谱数据保持不变，无需全量重新聚合。
+async fn update_project_graph(
+    docs_root: &std::path::Path,
+    changed_files: &[String],
+) -> Result<GraphInvalidateSummary, ProcessorError> {
+    use tokio::fs;
+
+    let project_graph_path = docs_root.join("_project_graph.json");
+    let content = fs::read_to_string(&project_graph_path)
+        .await
+        .map_err(|_| ProcessorError::NotFound(project_graph_path.display().to_string()))?;
+    let mut project_graph: ProjectGraphData = serde_json::from_str(&content)
+        .map_err(|e| ProcessorError::GeneratorError(format!("解析项目图谱失败: {}", e)))?;
+
+    let changed: Vec<String> = changed_files
+        .iter()
+        .map(|p| p.replace('\\', "/").trim_start_matches("./").to_string())
+        .filter(|p| !p.is_empty())
+        .collect();
+    let is_changed = |id: &str| changed.iter().any(|path| node_belongs_to_file(id, path));
+
+    // 移除变更文件的节点，以及从这些节点出发的边
+    let removed_ids: std::collections::HashSet<String> = project_graph
+        .nodes
+        .iter()
+        .filter(|n| is_changed(&n.id))
+        .map(|n| n.id.clone())
+        .collect();
+    project_graph.nodes.retain(|n| !removed_ids.contains(&n.id));
+    project_graph.edges.retain(|e| !removed_ids.contains(&e.source));
+
+    // 重新读取变更文件的图谱
+    let mut updated_files = Vec::new();
+    let mut removed_files = Vec::new();
+    let mut changed_graphs = Vec::new();
+    for path in &changed {
+        let graph_path = docs_root.join(format!("{}.graph.json", path));
+        let graph_data = match fs::read_to_string(&graph_path).await {
+            Ok(content) => serde_json::from_str::<FileGraphData>(&content).map_err(|e| {
+                ProcessorError::GeneratorError(format!("解析文件图谱 {} 失败: {}", graph_path.display(), e))
+            })?,
+            Err(_) => {
+                removed_files.push(path.clone());
+                continue;
+            }
+        };
+
+        project_graph.nodes.push(LlmGraphNode {
+            id: graph_data.file_id.clone(),
+            label: graph_data.file_path.split('/').next_back().unwrap_or(&graph_data.file_path).to_string(),
+            node_type: "file".to_string(),
+            line: None,
+        });
+        project_graph.nodes.extend(graph_data.nodes.clone());
+        updated_files.push(path.clone());
+        changed_graphs.push(graph_data);
+    }
+
+    // 变更文件的边（含导入边），跨文件调用需结合其余文件的符号重新关联
+    let mut new_edges: Vec<LlmGraphEdge> = Vec::new();
+    for graph_data in &changed_graphs {
+        new_edges.extend(graph_data.edges.clone());
+        for import in &graph_data.imports {
+            if let Some(target) = resolve_import_target(&import.module, &graph_data.file_path) {
+                new_edges.push(LlmGraphEdge {
+                    source: graph_data.file_id.clone(),
+                    target,
+                    edge_type: "imports".to_string(),
+                });
+            }
+        }
+    }
+
+    let mut symbol_graphs: Vec<FileGraphData> = project_graph
+        .nodes
+        .iter()
+        .filter(|n| n.node_type == "file")
+        .filter_map(|n| n.id.strip_prefix("file::"))
+        .filter(|path| !changed.iter().any(|c| c == path))
+        .map(|path| FileGraphData {
+            file_path: path.to_string(),
+            file_id: format!("file::{}", path),
+            nodes: project_graph
+                .nodes
+                .iter()
+                .filter(|n| n.node_type != "file" && node_belongs_to_file(&n.id, path))
+                .cloned()
+                .collect(),
+            edges: Vec::new(),
+            imports: Vec::new(),
+        })
+        .collect();
+    symbol_graphs.extend(changed_graphs);
+    link_cross_file_calls(&symbol_graphs, &mut new_edges);
+    project_graph.edges.extend(new_edges);
+
+    // 指向已删除且未重新出现的节点的边（如目录包含边、其他文件的调用边）一并移除
+    let node_ids: std::collections::HashSet<&str> =
+        project_graph.nodes.iter().map(|n| n.id.as_str()).collect();
+    let dangling: std::collections::HashSet<String> = removed_ids
+        .iter()
+        .filter(|id| !node_ids.contains(id.as_str()))
+        .cloned()
+        .collect();
+    project_graph.edges.retain(|e| !dangling.contains(&e.target));
+
+    dedup_graph(&mut project_graph.nodes, &mut project_graph.edges);
+    project_graph.file_count = project_graph.nodes.iter().filter(|n| n.node_type == "file").count();
+    project_graph.generated_at = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let json_content = serde_json::to_string_pretty(&project_graph)
+        .map_err(|e| ProcessorError::GeneratorError(format!("序列化项目图谱失败: {}", e)))?;
+    fs::write(&project_graph_path, json_content)
+        .await
+        .map_err(|e| ProcessorError::GeneratorError(format!("保存项目图谱失败: {}", e)))?;
+
+    info!(
+        "项目图谱增量更新: 更新 {} 个文件, 移除 {} 个文件 ({} 节点, {} 边)",
+        updated_files.len(),
+        removed_files.len(),
+        project_graph.nodes.len(),
+        project_graph.edges.len()
+    );
+
+    Ok(GraphInvalidateSummary {
+        updated_files,
+        removed_files,
+        node_count: project_graph.nodes.len(),
+        edge_count: project_graph.edges.len(),
+    })
+}
+
+/// 是否为文档生成写入文档目录的文件（文档、图谱、报告、断点均为 `.md` / `.json`，另有任务日志）
+fn is_generated_artifact(name: &str) -> bool {
+    name.ends_with(".md") || name.ends_with(".json") || name == TASK_LOG_FILE_NAME
+}
+
+/// 删除整个文档目录前的安全检查
+///
+/// 拒绝等于或包含已知源码目录的路径；再遍历目录，出现符号链接或非生成文件（如 `.git`、源码文件）时拒绝，
+/// 这样即使服务重启后不再记得源码目录，也不会删除源码树。
+async fn check_docs_dir_deletable(docs_path: PathBuf, source_roots: Vec<PathBuf>) -> Result<(), ProcessorError> {
+    tokio::task::spawn_blocking(move || {
+        let unsafe_delete = |reason: String| ProcessorError::UnsafeDelete(format!("{}: {}", docs_path.display(), reason));
+        let canonical = |path: &std::path::Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+        let docs_root = canonical(&docs_path);
+        for root in &source_roots {
+            if canonical(root).starts_with(&docs_root) {
+                return Err(unsafe_delete(format!("包含源码目录 {}", root.display())));
+            }
+        }
+
+        for entry in walkdir::WalkDir::new(&docs_path).min_depth(1) {
+            let entry = entry.map_err(|e| unsafe_delete(format!("无法遍历目录: {}", e)))?;
+            let file_type = entry.file_type();
+            let generated = file_type.is_dir()
+                || (file_type.is_file() && is_generated_artifact(&entry.file_name().to_string_lossy()));
+            if !generated {
+                return Err(unsafe_delete(format!("包含非生成文件 {}", entry.path().display())));
+            }
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| ProcessorError::GeneratorError(format!("检查文档目录失败: {}", e)))?
+}
+
+/// 可被跨文件调用的符号类型
+const CALLABLE_NODE_TYPES: &[&str] = &["function", "class", "method"];
+
+/// 解析导入模块对应的项目文件（相对路径）
+fn resolve_import_file(
+    module: &str,
+    source_file: &str,
+    project_files: &std::collections::HashSet<&str>,
+) -> Option<String> {
+    if let Some(resolved) = crate::services::code_analyzer::imports::resolve_import(module, source_file, project_files) {
+        return Some(resolved);
+    }
+
+    // 回退：按去掉扩展名后的路径匹配（如 Python 的 `from .b import x`、`import b`）
+    let dots = module.chars().take_while(|c| *c == '.').count();
+    let module_path = module[dots..].replace('.', "/");
+    if module_path.is_empty() {
+        return None;
+    }
+
+    let mut base = std::path::Path::new(source_file)
+        .parent()
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default();
+    for _ in 1..dots {
+        base = base.rsplit_once('/').map(|(parent, _)| parent.to_string()).unwrap_or_default();
+    }
+
+    let mut candidates = Vec::new();
+    if !base.is_empty() {
+        candidates.push(format!("{}/{}", base, module_path));
+    }
+    if dots == 0 || base.is_empty() {
+        candidates.push(module_path);
+    }
+
+    candidates.into_iter().find_map(|candidate| {
+        project_files
+            .iter()
+            .find(|path| path.rsplit_once('.').map(|(stem, _)| stem) == Some(candidate.as_str()))
+            .map(|path| path.to_string())
+    })
+}
+
+/// 为目录图谱补充同目录文件之间的 imports / calls 边
+///
+/// imports 边由各文件图谱的导入声明解析得到（文件 → 文件）；calls 边复用跨文件调用解析，
+/// 只保留调用方和被调用方位于不同兄弟文件的边。LLM 未给出目录图谱时，有关联才创建。
+fn link_sibling_files(dir_graph: &mut Option<DirGraphData>, dir_path: &str, file_graphs: &[FileGraphData]) {
+    use std::collections::HashSet;
+
+    let project_files: HashSet<&str> = file_graphs.iter().map(|g| g.file_path.as_str()).collect();
+    let file_node = |graph: &FileGraphData| LlmGraphNode {
+        id: graph.file_id.clone(),
+        label: graph.file_path.rsplit('/').next().unwrap_or(&graph.file_path).to_string(),
+        node_type: "file".to_string(),
+        line: None,
+    };
+    let owner = |id: &str| file_graphs.iter().find(|g| node_belongs_to_file(id, &g.file_path));
+
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+
+    for graph in file_graphs {
+        for imp in &graph.imports {
+            let target = resolve_import_file(&imp.module, &graph.file_path, &project_files)
+                .filter(|path| path != &graph.file_path)
+                .and_then(|path| file_graphs.iter().find(|g| g.file_path == path));
+            if let Some(target) = target {
+                nodes.push(file_node(graph));
+                nodes.push(file_node(target));
+                edges.push(LlmGraphEdge {
+                    source: graph.file_id.clone(),
+                    target: target.file_id.clone(),
+                    edge_type: "imports".to_string(),
+                });
+            }
+        }
+    }
+
+    let mut calls: Vec<LlmGraphEdge> = file_graphs
+        .iter()
+        .flat_map(|g| g.edges.iter().filter(|e| e.edge_type == "calls").cloned())
+        .collect();
+    link_cross_file_calls(file_graphs, &mut calls);
+    for edge in calls {
+        let (Some(from), Some(to)) = (owner(&edge.source), owner(&edge.target)) else {
+            continue;
+        };
+        if from.file_path == to.file_path {
+            continue;
+        }
+        for (graph, id) in [(from, &edge.source), (to, &edge.target)] {
+            if let Some(node) = graph.nodes.iter().find(|n| &n.id == id) {
+                nodes.push(node.clone());
+            }
+        }
+        edges.push(edge);
+    }
+
+    if edges.is_empty() {
+        return;
+    }
+
+    let graph = dir_graph.get_or_insert_with(|| DirGraphData::new(dir_path.to_string(), Default::default()));
+    graph.nodes.extend(nodes);
+    graph.edges.extend(edges);
+    dedup_graph(&mut graph.nodes, &mut graph.edges);
+}
+
+/// 将无法在本文件内解析的 calls 边关联到其他文件中定义的函数
+///
+/// 依次尝试：
+/// 1. 本文件导入的模块中定义的同名符号（`items` 中显式列出的优先）
+/// 2. 整个项目中唯一定义该名称的符号
+///
+/// 已解析的边会改写目标；名称冲突且无法通过导入消歧时保持原样。
+fn link_cross_file_calls(file_graphs: &[FileGraphData], edges: &mut [LlmGraphEdge]) {
+    use std::collections::{HashMap, HashSet};
+
+    let project_files: HashSet<&str> = file_graphs.iter().map(|g| g.file_path.as_str()).collect();
+
+    let node_ids: HashSet<&str> = file_graphs
+        .iter()
+        .flat_map(|g| g.nodes.iter().map(|n| n.id.as_str()))
+        .collect();
+
+    // 文件路径 -> (符号名 -> 节点 ID)
+    let mut symbols_by_file: HashMap<&str, HashMap<&str, &str>> = HashMap::new();
+    // 符号名 -> 定义它的所有节点 ID
+    let mut symbols_global: HashMap<&str, Vec<&str>> = HashMap::new();
+    for graph in file_graphs {
+        for node in &graph.nodes {
+            if CALLABLE_NODE_TYPES.contains(&node.node_type.as_str()) {
+                symbols_by_file
+                    .entry(graph.file_path.as_str())
+                    .or_default()
+                    .entry(node.label.as_str())
+                    .or_insert(node.id.as_str());
+                symbols_global.entry(node.label.as_str()).or_default().push(node.id.as_str());
+            }
+        }
+    }
+
+    // (source, 原 target) -> 新 target
+    let mut rewrites: HashMap<(String, String), String> = HashMap::new();
+
+    for graph in file_graphs {
+        let imported: Vec<(String, &Vec<String>)> = graph
+            .imports
+            .iter()
+            .filter_map(|imp| {
+                resolve_import_file(&imp.module, &graph.file_path, &project_files)
+                    .filter(|path| path != &graph.file_path)
+                    .map(|path| (path, &imp.items))
+            })
+            .collect();
+
+        for edge in &graph.edges {
+            if edge.edge_type != "calls" || node_ids.contains(edge.target.as_str()) {
+                continue;
+            }
+
+            let name = called_name(&edge.target);
+            if name.is_empty() {
+                continue;
+            }
+
+            let lookup = |path: &str| symbols_by_file.get(path).and_then(|s| s.get(name)).copied();
+
+            let target = imported
+                .iter()
+                .filter(|(_, items)| items.iter().any(|item| item == name))
+                .find_map(|(path, _)| lookup(path))
+                .or_else(|| imported.iter().find_map(|(path, _)| lookup(path)))
+                .or_else(|| match symbols_global.get(name).map(Vec::as_slice) {
+                    Some([only]) => Some(*only),
+                    _ => None,
+                });
+
+            if let Some(target) = target {
+                rewrites.insert((edge.source.clone(), edge.target.clone()), target.to_string());
+            }
+        }
+    }
+
+    if rewrites.is_empty() {
+        return;
+    }
+
+    info!("Linked {} cross-file call edges", rewrites.len());
+    for edge in edges.iter_mut() {
+        if edge.edge_type == "calls" {
+            if let Some(target) = rewrites.get(&(edge.source.clone(), edge.target.clone())) {
+                edge.target = target.clone();
+            }
+        }
+    }
+}
+
+/// 从调用目标 ID 中提取被调用的名称
+///
+/// 例如 `function::a.py::helper` -> `helper`，`utils.helper()` -> `helper`
+fn called_name(target: &str) -> &str {
+    let last = target.rsplit("::").next().unwrap_or(target);
+    let last = last.trim_end_matches("()");
+    last.rsplit('.').next().unwrap_or(last).trim()
+}
+
+/// 处理器错误类型
+#[derive(Debug, thiserror::Error)]
+pub enum ProcessorError {
+    #[error("Task cancelled")]
+    Cancelled,
+
+    #[error("Checkpoint error: {0}")]
+    CheckpointError(String),
+
+    #[error("Generator error: {0}")]
+    GeneratorError(String),
+
+    #[allow(dead_code)] // LLM 失败目前包装在 GeneratorError 中返回
+    #[error("LLM error: {0}")]
+    LlmError(String),
+
+    #[error("File not found in source tree: {0}")]
+    NotFound(String),
+
+    #[error("Invalid subtree: {0}")]
+    InvalidSubtree(String),
+
+    #[error("Missing file docs: {0}")]
+    MissingDocs(String),
+
+    #[error("No supported files: {0}")]
+    NoSupportedFiles(String),
+
+    #[error("Too many files: {0}")]
+    TooManyFiles(String),
+
+    #[error("Not a generated docs directory: {0}")]
+    NotDocsDir(String),
+
+    #[error("Refusing to delete docs directory: {0}")]
+    UnsafeDelete(String),
+}
+
+/// 校验并规范化子目录参数，返回相对于 `source_path` 的路径
+///
+/// 子目录必须存在、是目录，且（解析符号链接后）位于源码目录内；空字符串表示整个项目，返回 `None`。
+pub fn resolve_subtree(
+    source_path: &std::path::Path,
+    subtree: &str,
+) -> Result<Option<PathBuf>, ProcessorError> {
+    let invalid = |reason: &str| ProcessorError::InvalidSubtree(format!("{} ({})", subtree, reason));
+
+    if subtree.starts_with(['/', '\\'])
+        || relative_path_segments(subtree).any(|s| s == ".." || s.contains(':'))
+    {
+        return Err(invalid("必须是源码目录内的相对路径"));
+    }
+    let relative: PathBuf = relative_path_segments(subtree).collect();
+    if relative.as_os_str().is_empty() {
+        return Ok(None);
+    }
+
+    let source = source_path
+        .canonicalize()
+        .map_err(|_| invalid("源码目录不存在"))?;
+    let target = source_path
+        .join(&relative)
+        .canonicalize()
+        .map_err(|_| invalid("目录不存在"))?;
+    if !target.starts_with(&source) {
+        return Err(invalid("位于源码目录之外"));
+    }
+    if !target.is_dir() {
+        return Err(invalid("不是目录"));
+    }
+    Ok(Some(relative))
+}
+
+/// 计算文档输出目录
+///
+/// 优先使用显式指定的 `docs_path`；否则配置了 `output_root` 时为 `{output_root}/{项目目录名}`，
+/// 未配置时为 `{source_path}/.docs`。
+pub fn resolve_docs_path(
+    source_path: &std::path::Path,
+    docs_path: Option<PathBuf>,
+    output_root: Option<&std::path::Path>,
+) -> PathBuf {
+    if let Some(docs_path) = docs_path {
+        return docs_path;
+    }
+    match output_root {
+        Some(root) => {
+            let project_name = source_path
+                .canonicalize()
+                .ok()
+                .as_deref()
+                .unwrap_or(source_path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "project".to_string());
+            root.join(project_name)
+        }
+        None => source_path.join(".docs"),
+    }
+}
+
+/// 文档生成服务（主入口）
+pub struct DocGenService {
+    config: DocGenConfig,
+    /// 跨任务共享的文件分析结果缓存，未设置时每个任务使用独立缓存
+    analysis_cache: Option<Arc<AnalysisCache>>,
+}
+
+impl DocGenService {
+    /// 创建新的文档生成服务
+    pub fn new(config: DocGenConfig) -> Self {
+        Self {
+            config,
+            analysis_cache: None,
+        }
+    }
+
+    /// 使用共享的文件分析结果缓存
+    pub fn with_analysis_cache(mut self, cache: Arc<AnalysisCache>) -> Self {
+        self.analysis_cache = Some(cache);
+        self
+    }
+
+    /// 创建文档生成器，设置了共享缓存时复用之
+    fn document_generator(&self, docs_path: PathBuf) -> DocumentGenerator {
+        let generator = DocumentGenerator::new(docs_path, self.config.clone());
+        match &self.analysis_cache {
+            Some(cache) => generator.with_analysis_cache(cache.clone()),
+            None => generator,
+        }
+    }
+
+    /// 使用默认配置创建
+    pub fn with_default_config() -> Self {
+        Self::new(DocGenConfig::default())
+    }
+
+    /// 重新生成单个文件的文档，并刷新项目图谱
+    ///
+    /// `instructions` 为追加到分析 Prompt 的补充要求（如"重点关注安全问题"）
+    pub async fn regenerate_file(
+        &self,
+        source_path: PathBuf,
+        docs_path: Option<PathBuf>,
+        relative_path: &str,
+        instructions: Option<&str>,
+        llm_client: Arc<LlmClient>,
+        model: String,
+    ) -> Result<PathBuf, ProcessorError> {
+        let docs_path = resolve_docs_path(&source_path, docs_path, None);
+
+        let scanner = DirectoryScanner::new(self.config.clone()).with_excluded_dir(&docs_path);
+        let root = scanner
+            .scan(&source_path)
+            .map_err(|e| ProcessorError::GeneratorError(e.to_string()))?;
+
+        let mut checkpoint =
+            CheckpointService::new(source_path.clone(), docs_path.clone(), self.config.clone());
+        checkpoint
+            .initialize()
+            .await
+            .map_err(|e| ProcessorError::CheckpointError(e.to_string()))?;
+        let _ = checkpoint.load_checkpoint().await;
+
+        let doc_generator = self.document_generator(docs_path);
+        let (processor, _progress_rx) = LevelProcessor::new(
+            root,
+            checkpoint,
+            doc_generator,
+            llm_client,
+            model,
+            self.config.clone(),
+        );
+
+        processor.regenerate_file(relative_path, instructions).await
+    }
+
+    /// 增量更新项目图谱：只重新读取 `changed_files` 对应的 `.graph.json`
+    pub async fn invalidate_graph_files(
+        &self,
+        docs_path: PathBuf,
+        changed_files: &[String],
+    ) -> Result<GraphInvalidateSummary, ProcessorError> {
+        update_project_graph(&docs_path, changed_files).await
+    }
+
+    /// 清除文档目录的断点，下次生成将从头开始
+    ///
+    /// `delete_docs` 时同时删除整个文档目录。为防误删源码：目录中必须有断点、报告或项目图谱等生成标记，
+    /// 不能等于或包含 `source_roots` 中的任何源码目录，且只能包含本工具生成的文件（见 `is_generated_artifact`）。
+    /// 返回是否删除了断点文件。
+    pub async fn clear_checkpoint(
+        &self,
+        docs_path: PathBuf,
+        delete_docs: bool,
+        source_roots: &[PathBuf],
+    ) -> Result<bool, ProcessorError> {
+        let checkpoint_file = docs_path.join(".checkpoint.json");
+        let had_checkpoint = checkpoint_file.is_file();
+
+        if delete_docs {
+            let generated = had_checkpoint
+                || [REPORT_FILE_NAME, "_project_graph.json", API_INVENTORY_FILE_NAME]
+                    .iter()
+                    .any(|name| docs_path.join(name).is_file());
+            if !generated {
+                return Err(ProcessorError::NotDocsDir(docs_path.display().to_string()));
+            }
+            check_docs_dir_deletable(docs_path.clone(), source_roots.to_vec()).await?;
+        }
+
+        CheckpointService::new(PathBuf::new(), docs_path.clone(), self.config.clone())
+            .clear()
+            .await
+            .map_err(|e| ProcessorError::CheckpointError(e.to_string()))?;
+
+        if delete_docs {
+            tokio::fs::remove_dir_all(&docs_path)
+                .await
+                .map_err(|e| ProcessorError::GeneratorError(format!("删除文档目录失败 {}: {}", docs_path.display(), e)))?;
+            info!("Deleted docs directory: {}", docs_path.display());
+        }
+
+        Ok(had_checkpoint)
+    }
+
+    /// 扫描源码目录，构建待处理的文件树（排除文档目录）
+    ///
+    /// `subtree` 指定时只扫描该子目录，节点路径仍相对于 `source_path`
+    pub fn scan_source(
+        &self,
+        source_path: &std::path::Path,
+        docs_path: &std::path::Path,
+        subtree: Option<&str>,
+    ) -> Result<ScanResult, ProcessorError> {
+        // 文档目录可能位于源码树内，需要排除
+        let scanner = DirectoryScanner::new(self.config.clone()).with_excluded_dir(docs_path);
+        let subtree = match subtree {
+            Some(subtree) => resolve_subtree(source_path, subtree)?,
+            None => None,
+        };
+        match &subtree {
+            Some(subtree) => scanner.scan_subtree(source_path, subtree),
+            None => scanner.scan_detailed(source_path),
+        }
+        .map_err(|e| match e {
+            ScanError::TooManyFiles { .. } => ProcessorError::TooManyFiles(e.to_string()),
+            other => ProcessorError::GeneratorError(other.to_string()),
+        })
+    }
+
+    /// 启动文档生成任务
+    ///
+    /// 指定 `subtree` 时只为该子目录生成文档，相对路径仍以 `source_path` 为基准。
+    /// `final_only` 时跳过逐节点分析，只基于已有文档重新生成 README、阅读指南和项目图谱。
+    #[allow(clippy::too_many_arguments)]
+    pub async fn start_generation(
+        &self,
+        source_path: PathBuf,
+        docs_path: Option<PathBuf>,
+        subtree: Option<&str>,
+        llm_client: Arc<LlmClient>,
+        model: String,
+        resume: bool,
+        final_only: bool,
+    ) -> Result<(SharedDocTask, broadcast::Receiver<WsDocMessage>, SharedFileTree), ProcessorError> {
+        // 计算文档路径：默认放在项目根目录下的 .docs 目录
+        let docs_path = resolve_docs_path(&source_path, docs_path, None);
+
+        // 创建任务
+        let task_id = uuid::Uuid::new_v4().to_string();
+        let task = Arc::new(RwLock::new(DocTask::new(
+            task_id,
+            source_path.clone(),
+            docs_path.clone(),
+        )));
+
+        let scan_result = self.scan_source(&source_path, &docs_path, subtree)?;
+        let root = scan_result.root;
+        if root.file_count() == 0 {
+            let mut message = format!(
+                "未找到支持的源码文件（支持的扩展名: {}）",
+                self.config.supported_extensions.join(", ")
+            );
+            if !scan_result.oversized.is_empty() {
+                message.push_str(&format!(
+                    "；另有 {} 个文件超过大小上限被跳过",
+                    scan_result.oversized.len()
+                ));
+            }
+            return Err(ProcessorError::NoSupportedFiles(message));
+        }
+        task.write().await.stats.oversized_skipped = scan_result.oversized;
+
+        // 创建断点服务
+        let mut checkpoint =
+            CheckpointService::new(source_path.clone(), docs_path.clone(), self.config.clone());
+        checkpoint
+            .initialize()
+            .await
+            .map_err(|e| ProcessorError::CheckpointError(e.to_string()))?;
+
+        // 如果启用断点续传，加载断点；只生成最终文档或增量生成时总是基于已有文档
+        let changed_files = self.config.changed_files.as_ref().filter(|_| !final_only);
+        if resume || final_only || changed_files.is_some() {
+            let _ = checkpoint.load_checkpoint().await;
+            let _ = checkpoint.scan_existing_docs().await;
+        }
+
+        if let Some(changed_files) = changed_files {
+            let tree_files: std::collections::HashSet<String> =
+                root.get_all_files().iter().map(|f| f.relative_path.clone()).collect();
+            let mut matched = 0;
+            for path in changed_files {
+                let path = path.replace('\\', "/");
+                if tree_files.contains(&path) {
+                    checkpoint.invalidate_file(&path);
+                    matched += 1;
+                }
+            }
+            checkpoint.reset_final_docs();
+            info!(
+                "Incremental generation: {} of {} changed files are in the scanned tree",
+                matched,
+                changed_files.len()
+            );
+        }
+
+        let mut root = root;
+        if final_only {
+            // 恢复各节点的文档路径，并确认每个文件都已有文档
+            checkpoint.update_node_status(&mut root);
+            let missing: Vec<String> = root
+                .get_all_files()
+                .iter()
+                .filter(|f| f.doc_path.is_none())
+                .map(|f| f.relative_path.clone())
+                .collect();
+            if !missing.is_empty() {
+                let preview = missing.iter().take(5).cloned().collect::<Vec<_>>().join(", ");
+                return Err(ProcessorError::MissingDocs(format!(
+                    "{} 个文件尚未生成文档: {}{}",
+                    missing.len(),
+                    preview,
+                    if missing.len() > 5 { ", ..." } else { "" }
+                )));
+            }
+            checkpoint.reset_final_docs();
+        }
+
+        // 创建文档生成器；不续传时从头生成，不读取跨任务的分析缓存
+        let doc_generator = self
+            .document_generator(docs_path.clone())
+            .with_cache_lookup(resume || final_only || changed_files.is_some());
+
+        // 创建处理器
+        let (processor, progress_rx) = LevelProcessor::new(
+            root,
+            checkpoint,
+            doc_generator,
+            llm_client,
+            model,
+            self.config.clone(),
+        );
+        let processor = processor.with_final_only(final_only);
+        let tree = processor.tree();
+
+        // 可选的任务生命周期日志
+        if self.config.task_log {
+            spawn_task_logger(task.clone(), docs_path.join(TASK_LOG_FILE_NAME), processor.subscribe());
+        }
+
+        // 在后台运行处理
+        let task_clone = Arc::clone(&task);
+        tokio::spawn(async move {
+            if let Err(e) = processor.process_all_levels(task_clone.clone()).await {
+                error!("Document generation failed: {}", e);
+                let mut t = task_clone.write().await;
                 t.fail(e.to_string());
             }
         });
 
-        Ok((task, progress_rx))
+        Ok((task, progress_rx, tree))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::doc_generator::types::{ImportDeclaration, LlmGraphRawData};
+    use crate::services::doc_generator::types::{TaskStats, SLOWEST_NODES_LIMIT};
+    use crate::test_utils::{spawn_mock_llm, spawn_mock_llm_with_delay, MockLlm, MOCK_COMPLETION_TOKENS, MOCK_PROMPT_TOKENS};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// 无法连接的 LLM 地址，请求必然失败
+    const UNREACHABLE_LLM: &str = "http://127.0.0.1:9";
+
+    fn test_client(base_url: &str) -> Arc<LlmClient> {
+        Arc::new(LlmClient::new("sk-test", base_url, false).unwrap())
+    }
+
+    /// 以默认文档目录启动一次文档生成，返回 `start_generation` 的原始结果
+    async fn start_generation(
+        config: DocGenConfig,
+        source: &Path,
+        base_url: &str,
+        subtree: Option<&str>,
+        resume: bool,
+        final_only: bool,
+    ) -> Result<(SharedDocTask, broadcast::Receiver<WsDocMessage>), ProcessorError> {
+        let (task, rx, _) = DocGenService::new(config)
+            .start_generation(
+                source.to_path_buf(),
+                None,
+                subtree,
+                test_client(base_url),
+                "gpt-4o".to_string(),
+                resume,
+                final_only,
+            )
+            .await?;
+        Ok((task, rx))
+    }
+
+    /// 使用模拟 LLM 运行一次文档生成，返回直到 `Completed` 或 `Error`（含）为止收到的全部消息
+    async fn run_generation(
+        config: DocGenConfig,
+        source: &Path,
+        mock: &MockLlm,
+        subtree: Option<&str>,
+        resume: bool,
+        final_only: bool,
+    ) -> Vec<WsDocMessage> {
+        let (_task, mut rx) = start_generation(config, source, &mock.base_url, subtree, resume, final_only)
+            .await
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let mut messages = Vec::new();
+            loop {
+                let msg = rx.recv().await.unwrap();
+                let done = matches!(msg, WsDocMessage::Completed { .. } | WsDocMessage::Error { .. });
+                messages.push(msg);
+                if done {
+                    break messages;
+                }
+            }
+        })
+        .await
+        .expect("generation timed out")
+    }
+
+    /// 取出成功完成时的统计，生成失败时 panic
+    fn completed_stats(messages: &[WsDocMessage]) -> &TaskStats {
+        match messages.last() {
+            Some(WsDocMessage::Completed { stats }) => stats,
+            other => panic!("generation failed: {:?}", other),
+        }
+    }
+
+    fn llm_response(name: &str) -> String {
+        format!(
+            "# {name}\n\nRegenerated doc.\n\n<!-- GRAPH_DATA_START -->\n```json\n{{\"nodes\": [{{\"id\": \"function::a.py::{name}\", \"label\": \"{name}\", \"type\": \"function\"}}], \"edges\": []}}\n```\n<!-- GRAPH_DATA_END -->"
+        )
+    }
+
+    fn graph(file_path: &str, nodes: &[(&str, &str)], edges: &[(&str, &str)], imports: &[(&str, &[&str])]) -> FileGraphData {
+        FileGraphData {
+            file_path: file_path.to_string(),
+            file_id: format!("file::{}", file_path),
+            nodes: nodes
+                .iter()
+                .map(|(label, node_type)| LlmGraphNode {
+                    id: format!("{}::{}::{}", node_type, file_path, label),
+                    label: label.to_string(),
+                    node_type: node_type.to_string(),
+                    line: None,
+                })
+                .collect(),
+            edges: edges
+                .iter()
+                .map(|(source, target)| LlmGraphEdge {
+                    source: source.to_string(),
+                    target: target.to_string(),
+                    edge_type: "calls".to_string(),
+                })
+                .collect(),
+            imports: imports
+                .iter()
+                .map(|(module, items)| ImportDeclaration {
+                    module: module.to_string(),
+                    items: items.iter().map(|s| s.to_string()).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_link_cross_file_calls() {
+        let graphs = vec![
+            graph(
+                "app/main.py",
+                &[("run", "function")],
+                &[
+                    ("function::app/main.py::run", "function::app/main.py::helper"),
+                    ("function::app/main.py::run", "format"),
+                ],
+                &[("app.utils", &["helper"]), (".fmt", &[])],
+            ),
+            graph("app/utils.py", &[("helper", "function")], &[], &[]),
+            graph("app/fmt.py", &[("format", "function")], &[], &[]),
+            // 同名函数，但未被导入
+            graph("other/helpers.py", &[("helper", "function"), ("format", "function")], &[], &[]),
+        ];
+
+        let mut edges: Vec<LlmGraphEdge> = graphs.iter().flat_map(|g| g.edges.clone()).collect();
+        link_cross_file_calls(&graphs, &mut edges);
+
+        let targets: Vec<&str> = edges.iter().map(|e| e.target.as_str()).collect();
+        assert_eq!(
+            targets,
+            vec!["function::app/utils.py::helper", "function::app/fmt.py::format"]
+        );
+    }
+
+    #[test]
+    fn test_fit_documents_to_budget() {
+        let doc = |is_file: bool, depth: u32, name: &str, len: usize| CollectedDocument {
+            is_file,
+            depth,
+            text: format!("### {}\n\n{}", name, "x".repeat(len)),
+        };
+
+        let small = fit_documents_to_budget(vec![doc(false, 0, "", 10), doc(true, 1, "a.py", 10)], 1000);
+        assert!(small.contains("### a.py") && !small.contains("未列出"));
+
+        let mut documents = vec![doc(false, 0, "", 2_000), doc(false, 1, "src", 2_000)];
+        documents.extend((0..500).map(|i| doc(true, 2, &format!("src/f{}.py", i), 4_000)));
+        let budget = 10_000;
+        let prompt = fit_documents_to_budget(documents, budget);
+
+        assert!(estimate_tokens(&prompt) <= budget + 50);
+        assert!(prompt.starts_with("### \n\n"));
+        assert!(prompt.contains("### src\n\n"));
+        assert!(prompt.contains("f0.py"));
+        assert!(!prompt.contains("f499.py"));
+        assert!(prompt.ends_with("份文档因长度限制未列出）"));
+    }
+
+    #[tokio::test]
+    async fn test_parallel_graph_loading_matches_sequential() {
+        let docs = TempDir::new().unwrap();
+        let mut paths = Vec::new();
+        for i in 0..40 {
+            let dir = format!("pkg{}", i % 4);
+            std::fs::create_dir_all(docs.path().join(&dir)).unwrap();
+            let file_path = format!("{}/m{}.py", dir, i);
+            let data = graph(
+                &file_path,
+                &[("run", "function"), ("helper", "function")],
+                &[("run", "helper")],
+                &[(&format!("pkg{}.m{}", (i + 1) % 4, i + 1), &["run"])],
+            );
+            let path = docs.path().join(format!("{}.graph.json", file_path));
+            std::fs::write(&path, serde_json::to_string(&data).unwrap()).unwrap();
+            paths.push(path);
+
+            if i < 4 {
+                let dir_graph = DirGraphData::new(dir.clone(), LlmGraphRawData::default());
+                let path = docs.path().join(&dir).join("_dir.graph.json");
+                std::fs::write(&path, serde_json::to_string(&dir_graph).unwrap()).unwrap();
+                paths.push(path);
+            }
+        }
+        // 损坏的文件在两种方式下都被跳过
+        let broken = docs.path().join("broken.graph.json");
+        std::fs::write(&broken, "{").unwrap();
+        paths.push(broken);
+        paths.sort();
+
+        let sequential = merge_graphs(paths.iter().filter_map(|p| load_graph_file(p)).collect(), "demo");
+        let parallel = merge_graphs(load_graph_files(paths, 8).await, "demo");
+
+        assert_eq!(parallel.file_graphs.len(), 40);
+        assert_eq!(parallel.dir_count, 4);
+        assert_eq!(
+            serde_json::to_string(&(&parallel.nodes, &parallel.edges)).unwrap(),
+            serde_json::to_string(&(&sequential.nodes, &sequential.edges)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_link_sibling_files() {
+        let graphs = vec![
+            graph(
+                "app/main.py",
+                &[("run", "function")],
+                &[("function::app/main.py::run", "helper")],
+                &[("app.utils", &["helper"])],
+            ),
+            graph("app/utils.py", &[("helper", "function")], &[], &[]),
+        ];
+
+        let mut dir_graph = None;
+        link_sibling_files(&mut dir_graph, "app", &graphs);
+        let dir_graph = dir_graph.expect("sibling links should create a dir graph");
+
+        assert_eq!(dir_graph.dir_id, "dir::app");
+        let edges: Vec<(&str, &str, &str)> = dir_graph
+            .edges
+            .iter()
+            .map(|e| (e.source.as_str(), e.target.as_str(), e.edge_type.as_str()))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("file::app/main.py", "file::app/utils.py", "imports"),
+                ("function::app/main.py::run", "function::app/utils.py::helper", "calls"),
+            ]
+        );
+        let node_ids: Vec<&str> = dir_graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        for edge in &dir_graph.edges {
+            assert!(node_ids.contains(&edge.source.as_str()) && node_ids.contains(&edge.target.as_str()));
+        }
+
+        // 没有兄弟文件关联时不创建目录图谱
+        let mut empty = None;
+        link_sibling_files(&mut empty, "app", &graphs[1..]);
+        assert!(empty.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_graph_extraction_disabled() {
+        let source = TempDir::new().unwrap();
+        std::fs::create_dir_all(source.path().join("pkg")).unwrap();
+        std::fs::write(source.path().join("pkg/a.py"), "def run():\n    pass\n").unwrap();
+        let docs = source.path().join(".docs");
+
+        let mock = spawn_mock_llm(vec![llm_response("run")]).await;
+        let config = DocGenConfig {
+            extract_graph: false,
+            ..DocGenConfig::default()
+        };
+        completed_stats(&run_generation(config, source.path(), &mock, None, false, false).await);
+
+        assert!(docs.join("pkg/a.py.md").exists());
+        let graph_files: Vec<_> = walkdir::WalkDir::new(&docs)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".graph.json"))
+            .collect();
+        assert!(graph_files.is_empty(), "unexpected graph files: {:?}", graph_files);
+        assert!(!docs.join("_project_graph.json").exists());
+
+        let requests = mock.requests.lock();
+        let prompt = requests[0]["messages"][0]["content"].as_str().unwrap();
+        assert!(prompt.contains("pkg/a.py"));
+        assert!(!prompt.contains("GRAPH_DATA_START"));
+    }
+
+    #[tokio::test]
+    async fn test_fresh_generation_bypasses_analysis_cache() {
+        let source = TempDir::new().unwrap();
+        std::fs::write(source.path().join("a.py"), "def cache_probe():\n    pass\n").unwrap();
+
+        let mock = spawn_mock_llm(vec![llm_response("cache_probe")]).await;
+        let client = test_client(&mock.base_url);
+        let service = DocGenService::new(DocGenConfig::default()).with_analysis_cache(Arc::new(AnalysisCache::default()));
+        let file_analyses = || {
+            mock.requests
+                .lock()
+                .iter()
+                .filter(|r| r["messages"][0]["content"].as_str().unwrap_or_default().contains("def cache_probe"))
+                .count()
+        };
+
+        // 分别写入新的文档目录，只有缓存能让文件分析跳过 LLM
+        for (docs_dir, resume, expected) in [("docs1", false, 1), ("docs2", true, 1), ("docs3", false, 2)] {
+            let (_task, mut rx, _) = service
+                .start_generation(
+                    source.path().to_path_buf(),
+                    Some(source.path().join(docs_dir)),
+                    None,
+                    client.clone(),
+                    "gpt-4o".to_string(),
+                    resume,
+                    false,
+                )
+                .await
+                .unwrap();
+            loop {
+                if let WsDocMessage::Completed { .. } = rx.recv().await.unwrap() {
+                    break;
+                }
+            }
+            assert_eq!(file_analyses(), expected, "{} (resume={})", docs_dir, resume);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keep_api_artifacts() {
+        let source = TempDir::new().unwrap();
+        std::fs::create_dir_all(source.path().join("api")).unwrap();
+        std::fs::write(source.path().join("api/users.py"), "@app.get('/api/users')\ndef users():\n    pass\n").unwrap();
+        let docs = source.path().join(".docs");
+
+        let response = "# users.py\n\nUser routes.\n\n<!-- API_START -->\n包含API接口: 是\n接口列表:\n\
+            - [GET] /api/users - 获取用户列表\n<!-- API_END -->";
+        let mock = spawn_mock_llm(vec![response.to_string()]).await;
+        let config = DocGenConfig {
+            keep_api_artifacts: true,
+            ..DocGenConfig::default()
+        };
+        completed_stats(&run_generation(config, source.path(), &mock, None, false, false).await);
+
+        let artifact = std::fs::read_to_string(docs.join("api/users.py.api.md")).unwrap();
+        assert!(artifact.contains("| 1 | GET | /api/users | 获取用户列表 |"));
+        assert!(docs.join(API_INVENTORY_FILE_NAME).exists());
+    }
+
+    #[tokio::test]
+    async fn test_regenerate_single_file() {
+        let source = TempDir::new().unwrap();
+        std::fs::write(source.path().join("a.py"), "def run():\n    pass\n").unwrap();
+        std::fs::write(source.path().join("b.py"), "def other():\n    pass\n").unwrap();
+        let docs = source.path().join(".docs");
+
+        // 已存在的 b.py 文档不应被改动
+        std::fs::create_dir_all(&docs).unwrap();
+        std::fs::write(docs.join("b.py.md"), "old b doc").unwrap();
+
+        let mock = spawn_mock_llm(vec![llm_response("run")]).await;
+        let client = test_client(&mock.base_url);
+
+        let service = DocGenService::with_default_config();
+        let doc_path = service
+            .regenerate_file(source.path().to_path_buf(), None, "a.py", None, client.clone(), "gpt-4o".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(doc_path, docs.join("a.py.md"));
+        assert!(std::fs::read_to_string(&doc_path).unwrap().contains("Regenerated doc."));
+        assert!(docs.join("a.py.graph.json").exists());
+        assert_eq!(std::fs::read_to_string(docs.join("b.py.md")).unwrap(), "old b doc");
+        assert!(!docs.join("b.py.graph.json").exists());
+        assert_eq!(mock.request_count(), 1);
+
+        // 项目图谱包含新生成的节点
+        let project_graph: ProjectGraphData = serde_json::from_str(
+            &std::fs::read_to_string(docs.join("_project_graph.json")).unwrap(),
+        )
+        .unwrap();
+        assert!(project_graph.nodes.iter().any(|n| n.id == "function::a.py::run"));
+
+        // 断点记录该文件已完成
+        let checkpoint = std::fs::read_to_string(docs.join(".checkpoint.json")).unwrap();
+        assert!(checkpoint.contains("a.py"));
+
+        // 不存在的文件返回 NotFound
+        let result = service
+            .regenerate_file(source.path().to_path_buf(), None, "missing.py", None, client, "gpt-4o".to_string())
+            .await;
+        assert!(matches!(result, Err(ProcessorError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_regenerate_file_with_instructions() {
+        let source = TempDir::new().unwrap();
+        std::fs::write(source.path().join("a.py"), "def run():\n    pass\n").unwrap();
+
+        let mock = spawn_mock_llm(vec![llm_response("run")]).await;
+        let client = test_client(&mock.base_url);
+
+        DocGenService::with_default_config()
+            .regenerate_file(
+                source.path().to_path_buf(),
+                None,
+                "a.py",
+                Some("重点关注安全问题"),
+                client,
+                "gpt-4o".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let requests = mock.requests.lock().clone();
+        assert_eq!(requests.len(), 1);
+        let prompt = requests[0]["messages"][0]["content"].as_str().unwrap();
+        assert!(prompt.contains("重点关注安全问题"));
+        assert!(prompt.contains("a.py"));
+    }
+
+    #[tokio::test]
+    async fn test_changed_files_limit_processing() {
+        let source = TempDir::new().unwrap();
+        std::fs::write(source.path().join("a.py"), "def run_a():\n    pass\n").unwrap();
+        std::fs::write(source.path().join("b.py"), "def run_b():\n    pass\n").unwrap();
+
+        let mock = spawn_mock_llm(vec![llm_response("run")]).await;
+        completed_stats(&run_generation(DocGenConfig::default(), source.path(), &mock, None, false, false).await);
+        let first_run = mock.request_count();
+
+        let config = DocGenConfig {
+            changed_files: Some(vec!["a.py".to_string(), "deleted.py".to_string()]),
+            ..DocGenConfig::default()
+        };
+        completed_stats(&run_generation(config, source.path(), &mock, None, false, false).await);
+
+        let requests: Vec<String> = mock.requests.lock()[first_run..].iter().map(|r| r.to_string()).collect();
+        assert_eq!(requests.iter().filter(|r| r.contains("def run_a")).count(), 1);
+        assert!(!requests.iter().any(|r| r.contains("def run_b")));
+        // 根目录总结和最终文档重新生成
+        assert!(requests.len() > 1);
+        assert!(source.path().join(".docs").join("b.py.md").is_file());
+    }
+
+    #[tokio::test]
+    async fn test_node_timing_and_tokens_reported() {
+        let source = TempDir::new().unwrap();
+        std::fs::write(source.path().join("a.py"), "def run():\n    pass\n").unwrap();
+
+        let mock = spawn_mock_llm_with_delay(vec![llm_response("run")], Duration::from_millis(20)).await;
+        let messages = run_generation(DocGenConfig::default(), source.path(), &mock, None, false, false).await;
+        let stats = completed_stats(&messages);
+
+        let timing = |file: bool| {
+            messages.iter().find_map(|m| match m {
+                WsDocMessage::NodeTimed { path, duration_ms, tokens } if (path == "a.py") == file => {
+                    Some((*duration_ms, *tokens))
+                }
+                _ => None,
+            })
+        };
+        let (duration_ms, tokens) = timing(true).expect("no timing message for a.py");
+        let (_, dir_tokens) = timing(false).expect("no timing message for the root directory");
+        assert!((20..10_000).contains(&duration_ms));
+        assert!(stats.slowest_nodes.iter().any(|t| t.path == "a.py" && t.duration_ms == duration_ms));
+
+        // 用量取自模拟服务在流末尾返回的 usage 块
+        let per_call = MOCK_PROMPT_TOKENS + MOCK_COMPLETION_TOKENS;
+        assert_eq!(tokens, Some(per_call));
+        assert_eq!(dir_tokens, Some(per_call));
+
+        let report_path = source.path().join(".docs").join(REPORT_FILE_NAME);
+        let report: GenerationReport =
+            serde_json::from_str(&std::fs::read_to_string(report_path).unwrap()).unwrap();
+        assert_eq!(report.files[0].tokens, Some(per_call));
+        // 报告与进度消息使用同一份耗时记录
+        assert_eq!(report.files[0].duration_ms, Some(duration_ms));
+        assert_eq!(report.totals.tokens, Some(2 * per_call));
+    }
+
+    /// 以单并发运行生成，返回各文件分析请求的先后顺序
+    async fn file_processing_order(config: DocGenConfig) -> Vec<String> {
+        let source = TempDir::new().unwrap();
+        std::fs::write(source.path().join("big.py"), format!("def big():\n{}", "    x = 1\n".repeat(200))).unwrap();
+        std::fs::write(source.path().join("small.py"), "def small():\n    pass\n").unwrap();
+
+        let mock = spawn_mock_llm(vec![llm_response("run")]).await;
+        let config = DocGenConfig { concurrency: 1, ..config };
+        completed_stats(&run_generation(config, source.path(), &mock, None, false, false).await);
+
+        let requests = mock.requests.lock();
+        requests
+            .iter()
+            .filter_map(|body| {
+                let text = body.to_string();
+                ["big.py", "small.py"].into_iter().find(|name| text.contains(&format!("def {}", &name[..name.len() - 3])))
+            })
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_size_asc_processes_smaller_file_first() {
+        let order = file_processing_order(DocGenConfig { order: FileOrder::SizeAsc, ..DocGenConfig::default() }).await;
+        assert_eq!(order.first().map(String::as_str), Some("small.py"));
+
+        let order = file_processing_order(DocGenConfig {
+            order: FileOrder::SizeAsc,
+            priority_paths: vec!["big.py".to_string()],
+            ..DocGenConfig::default()
+        })
+        .await;
+        assert_eq!(order.first().map(String::as_str), Some("big.py"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_subtree_only() {
+        let source = TempDir::new().unwrap();
+        std::fs::create_dir_all(source.path().join("src")).unwrap();
+        std::fs::create_dir_all(source.path().join("other")).unwrap();
+        std::fs::write(source.path().join("src/a.py"), "def run():\n    pass\n").unwrap();
+        std::fs::write(source.path().join("other/b.py"), "def skip():\n    pass\n").unwrap();
+
+        // 其他目录遗留的旧图谱不应被聚合进项目图谱
+        let docs = source.path().join(".docs");
+        std::fs::create_dir_all(docs.join("other")).unwrap();
+        let stale = FileGraphData::new("other/b.py".to_string(), Default::default());
+        std::fs::write(docs.join("other/b.py.graph.json"), serde_json::to_string(&stale).unwrap()).unwrap();
+
+        let mock = spawn_mock_llm(vec![llm_response("run")]).await;
+        completed_stats(&run_generation(DocGenConfig::default(), source.path(), &mock, Some("src"), false, false).await);
+
+        assert!(docs.join("src/a.py.md").is_file());
+        assert!(docs.join("src/_dir_summary.md").is_file());
+        assert!(!docs.join("other/b.py.md").exists());
+
+        let graph: ProjectGraphData =
+            serde_json::from_str(&std::fs::read_to_string(docs.join("_project_graph.json")).unwrap()).unwrap();
+        assert!(graph.nodes.iter().any(|n| n.id == "file::src/a.py"));
+        assert!(graph.nodes.iter().all(|n| !n.id.contains("other")));
+    }
+
+    #[test]
+    fn test_resolve_subtree_validation() {
+        let source = TempDir::new().unwrap();
+        std::fs::create_dir_all(source.path().join("src/utils")).unwrap();
+        std::fs::write(source.path().join("src/a.py"), "").unwrap();
+
+        assert_eq!(
+            resolve_subtree(source.path(), "src\\utils/").unwrap(),
+            Some(PathBuf::from("src").join("utils"))
+        );
+        assert_eq!(resolve_subtree(source.path(), "").unwrap(), None);
+        for bad in ["missing", "src/a.py", "../outside", "/etc"] {
+            assert!(
+                matches!(resolve_subtree(source.path(), bad), Err(ProcessorError::InvalidSubtree(_))),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    /// 以指定失败策略生成文档，`bad.py` 是不完整的 UTF-16 内容，解码必然失败
+    async fn run_with_failing_file(failure_mode: FailureMode) -> (TempDir, Vec<WsDocMessage>) {
+        let source = TempDir::new().unwrap();
+        std::fs::write(source.path().join("good.py"), "def run():\n    pass\n").unwrap();
+        std::fs::write(source.path().join("bad.py"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let mock = spawn_mock_llm(vec![llm_response("run")]).await;
+        let config = DocGenConfig {
+            failure_mode,
+            concurrency: 1,
+            ..DocGenConfig::default()
+        };
+        let messages = run_generation(config, source.path(), &mock, None, false, false).await;
+        (source, messages)
+    }
+
+    #[tokio::test]
+    async fn test_fast_fail_stops_on_node_failure() {
+        let (_source, messages) = run_with_failing_file(FailureMode::FastFail).await;
+        match messages.last().unwrap() {
+            WsDocMessage::Error { message } => assert!(message.contains("bad.py")),
+            other => panic!("expected error, got {:?}", other),
+        }
+        assert!(!messages.iter().any(|m| matches!(m, WsDocMessage::Completed { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_best_effort_continues_after_node_failure() {
+        let (source, messages) = run_with_failing_file(FailureMode::BestEffort).await;
+        assert!(messages
+            .iter()
+            .any(|m| matches!(m, WsDocMessage::NodeFailed { path, .. } if path == "bad.py")));
+        assert_eq!(completed_stats(&messages).failed_count, 1);
+        let docs = source.path().join(".docs");
+        assert!(docs.join("good.py.md").is_file());
+        assert!(!docs.join("bad.py.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_final_only_skips_file_analysis() {
+        let source = TempDir::new().unwrap();
+        std::fs::write(source.path().join("a.py"), "def run():\n    pass\n").unwrap();
+        let docs = source.path().join(".docs");
+        std::fs::create_dir_all(&docs).unwrap();
+        std::fs::write(docs.join("a.py.md"), "# a.py\n\nExisting doc.").unwrap();
+
+        let mock = spawn_mock_llm(vec!["# demo\n\nProject overview.".to_string()]).await;
+        let messages = run_generation(DocGenConfig::default(), source.path(), &mock, None, true, true).await;
+        completed_stats(&messages);
+        assert!(!messages.iter().any(|m| matches!(m, WsDocMessage::FileStarted { .. })));
+
+        assert!(docs.join("README.md").is_file());
+        assert_eq!(std::fs::read_to_string(docs.join("a.py.md")).unwrap(), "# a.py\n\nExisting doc.");
+        let requests = mock.requests.lock();
+        assert!(!requests.is_empty());
+        for request in requests.iter() {
+            let prompt = request["messages"][0]["content"].as_str().unwrap();
+            assert!(!prompt.contains("请分析以下代码文件"), "unexpected file analysis request");
+        }
+        // README 使用已有的文件文档
+        assert!(requests.iter().any(|r| r["messages"][0]["content"].as_str().unwrap().contains("Existing doc.")));
+    }
+
+    #[tokio::test]
+    async fn test_final_only_requires_existing_docs() {
+        let source = TempDir::new().unwrap();
+        std::fs::write(source.path().join("a.py"), "def run():\n    pass\n").unwrap();
+
+        let result = start_generation(DocGenConfig::default(), source.path(), UNREACHABLE_LLM, None, true, true).await;
+        match result {
+            Err(ProcessorError::MissingDocs(msg)) => assert!(msg.contains("a.py")),
+            _ => panic!("expected MissingDocs"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_final_docs_synthesized_concurrently() {
+        for (concurrency, expected_peak) in [(2, 2), (1, 1)] {
+            let source = TempDir::new().unwrap();
+            std::fs::write(source.path().join("a.py"), "def run():\n    pass\n").unwrap();
+
+            // 单个文件：文件分析和目录总结依次进行，只有 README 与阅读指南可能同时请求；
+            // 每个请求保持一段时间，确保并发时两者的处理区间重叠
+            let mock = spawn_mock_llm_with_delay(vec!["# Doc\n\nContent.".to_string()], Duration::from_millis(200)).await;
+            let config = DocGenConfig {
+                synthesis_concurrency: concurrency,
+                ..DocGenConfig::default()
+            };
+            let (readme_name, guide_name) = (config.readme_name.clone(), config.reading_guide_name.clone());
+            let messages = run_generation(config, source.path(), &mock, None, false, false).await;
+            completed_stats(&messages);
+
+            let final_progress: Vec<_> = messages
+                .into_iter()
+                .filter_map(|m| match m {
+                    WsDocMessage::Progress { progress, current_file, .. } if (92.0..98.0).contains(&progress) => {
+                        Some((progress, current_file))
+                    }
+                    _ => None,
+                })
+                .collect();
+
+            assert_eq!(mock.peak_in_flight(), expected_peak, "synthesis_concurrency = {}", concurrency);
+            assert_eq!(final_progress, vec![(92.0, Some(format!("{}, {}", readme_name, guide_name)))]);
+            let docs_path = source.path().join(".docs");
+            assert!(docs_path.join(&readme_name).exists());
+            assert!(docs_path.join(&guide_name).exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_too_many_files_rejected_before_llm_call() {
+        let source = TempDir::new().unwrap();
+        for name in ["a.py", "b.py", "c.py"] {
+            std::fs::write(source.path().join(name), "x = 1\n").unwrap();
+        }
+
+        let mock = spawn_mock_llm(vec!["doc".to_string()]).await;
+        let config = DocGenConfig {
+            max_files: 2,
+            ..DocGenConfig::default()
+        };
+        let result = start_generation(config, source.path(), &mock.base_url, None, false, false).await;
+        match result {
+            Err(ProcessorError::TooManyFiles(msg)) => assert!(msg.contains("超过上限 2 个")),
+            _ => panic!("expected TooManyFiles"),
+        }
+        assert_eq!(mock.request_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_empty_project_rejected() {
+        let source = TempDir::new().unwrap();
+        std::fs::write(source.path().join("notes.txt"), "hello").unwrap();
+        std::fs::create_dir_all(source.path().join("empty")).unwrap();
+
+        let result = start_generation(DocGenConfig::default(), source.path(), UNREACHABLE_LLM, None, false, false).await;
+        match result {
+            Err(ProcessorError::NoSupportedFiles(msg)) => assert!(msg.contains("py, js")),
+            _ => panic!("expected NoSupportedFiles"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_lists_failed_file() {
+        let source = TempDir::new().unwrap();
+        std::fs::write(source.path().join("a.py"), "def run():\n    pass\n").unwrap();
+
+        // 文件分析必然失败
+        let (task, _rx) = start_generation(DocGenConfig::default(), source.path(), UNREACHABLE_LLM, None, false, false)
+            .await
+            .unwrap();
+
+        let report_path = task.read().await.docs_path.join(REPORT_FILE_NAME);
+        let report: GenerationReport = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let Ok(content) = tokio::fs::read_to_string(&report_path).await {
+                    break serde_json::from_str(&content).unwrap();
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(report.status, TaskStatus::Failed);
+        assert_eq!(report.totals.total_files, 1);
+        assert_eq!(report.totals.failed_files, 1);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].path, "a.py");
+        assert!(report.failed[0].is_file);
+        assert!(report.failed[0].error.contains("Failed to analyze file a.py"));
+        assert_eq!(report.files[0].status, NodeStatus::Failed);
+        assert!(report.files[0].duration_ms.is_some());
+    }
+
+    #[test]
+    fn test_resolve_docs_path() {
+        let source = std::path::Path::new("/work/my-app");
+        let root = std::path::Path::new("/srv/docs");
+
+        assert_eq!(resolve_docs_path(source, None, None), source.join(".docs"));
+        assert_eq!(resolve_docs_path(source, None, Some(root)), root.join("my-app"));
+        // 显式路径优先
+        assert_eq!(
+            resolve_docs_path(source, Some(PathBuf::from("/tmp/out")), Some(root)),
+            PathBuf::from("/tmp/out")
+        );
+    }
+
+    #[test]
+    fn test_record_timing_keeps_slowest() {
+        let mut stats = TaskStats::default();
+        for i in 0..(SLOWEST_NODES_LIMIT as u64 + 5) {
+            stats.record_timing(format!("f{}.py", i), i);
+        }
+        assert_eq!(stats.slowest_nodes.len(), SLOWEST_NODES_LIMIT);
+        assert_eq!(stats.slowest_nodes[0].duration_ms, SLOWEST_NODES_LIMIT as u64 + 4);
+        assert!(stats.slowest_nodes.windows(2).all(|w| w[0].duration_ms >= w[1].duration_ms));
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_updates_only_changed_file() {
+        let docs = TempDir::new().unwrap();
+        let write_graph = |g: &FileGraphData| {
+            std::fs::write(
+                docs.path().join(format!("{}.graph.json", g.file_path)),
+                serde_json::to_string(g).unwrap(),
+            )
+            .unwrap();
+        };
+
+        let a = graph("a.py", &[("old", "function")], &[], &[]);
+        let b = graph(
+            "b.py",
+            &[("user", "function")],
+            &[("function::b.py::user", "function::a.py::old")],
+            &[],
+        );
+        write_graph(&a);
+        write_graph(&b);
+
+        let node = |id: &str, node_type: &str| LlmGraphNode {
+            id: id.to_string(),
+            label: id.rsplit("::").next().unwrap().to_string(),
+            node_type: node_type.to_string(),
+            line: None,
+        };
+        let edge = |source: &str, target: &str, edge_type: &str| LlmGraphEdge {
+            source: source.to_string(),
+            target: target.to_string(),
+            edge_type: edge_type.to_string(),
+        };
+        let initial = ProjectGraphData {
+            project_name: "demo".to_string(),
+            file_count: 2,
+            nodes: vec![
+                node("dir::", "directory"),
+                node("file::a.py", "file"),
+                node("function::a.py::old", "function"),
+                node("file::b.py", "file"),
+                node("function::b.py::user", "function"),
+            ],
+            edges: vec![
+                edge("dir::", "file::a.py", "contains"),
+                edge("dir::", "file::b.py", "contains"),
+                edge("function::b.py::user", "function::a.py::old", "calls"),
+            ],
+            generated_at: String::new(),
+        };
+        std::fs::write(
+            docs.path().join("_project_graph.json"),
+            serde_json::to_string(&initial).unwrap(),
+        )
+        .unwrap();
+
+        // a.py 的函数被重命名
+        write_graph(&graph("a.py", &[("renamed", "function")], &[], &[]));
+
+        let service = DocGenService::with_default_config();
+        let summary = service
+            .invalidate_graph_files(docs.path().to_path_buf(), &["a.py".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(summary.updated_files, vec!["a.py"]);
+
+        let updated: ProjectGraphData = serde_json::from_str(
+            &std::fs::read_to_string(docs.path().join("_project_graph.json")).unwrap(),
+        )
+        .unwrap();
+        let ids: Vec<&str> = updated.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert!(ids.contains(&"function::a.py::renamed"));
+        assert!(!ids.contains(&"function::a.py::old"));
+        // 未变更的 b.py 保持不变
+        assert!(ids.contains(&"function::b.py::user"));
+        assert!(updated.edges.iter().any(|e| e.source == "dir::" && e.target == "file::a.py"));
+        assert!(updated.edges.iter().any(|e| e.source == "dir::" && e.target == "file::b.py"));
+        // 指向已删除函数的调用边被移除
+        assert!(!updated.edges.iter().any(|e| e.target == "function::a.py::old"));
+        assert_eq!(updated.file_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_delete_docs_refuses_source_trees() {
+        let service = DocGenService::new(DocGenConfig::default());
+
+        // 文档目录被误设为源码根目录：含源码文件和 .git，不能删除
+        let source = TempDir::new().unwrap();
+        std::fs::create_dir(source.path().join(".git")).unwrap();
+        std::fs::write(source.path().join(".git").join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        std::fs::write(source.path().join("main.py"), "x = 1\n").unwrap();
+        std::fs::write(source.path().join(".checkpoint.json"), "{}").unwrap();
+        let result = service.clear_checkpoint(source.path().to_path_buf(), true, &[]).await;
+        assert!(matches!(result, Err(ProcessorError::UnsafeDelete(_))));
+        assert!(source.path().join("main.py").is_file());
+        assert!(source.path().join(".checkpoint.json").is_file());
+
+        // 文档目录包含已知源码目录（即使源码目录中只有生成文件形式的内容）
+        let parent = TempDir::new().unwrap();
+        let nested_source = parent.path().join("project");
+        std::fs::create_dir(&nested_source).unwrap();
+        std::fs::write(nested_source.join("NOTES.md"), "notes").unwrap();
+        std::fs::write(parent.path().join(REPORT_FILE_NAME), "{}").unwrap();
+        let result = service
+            .clear_checkpoint(parent.path().to_path_buf(), true, std::slice::from_ref(&nested_source))
+            .await;
+        assert!(matches!(result, Err(ProcessorError::UnsafeDelete(_))));
+        assert!(nested_source.join("NOTES.md").is_file());
+    }
+
+    #[tokio::test]
+    async fn test_delete_docs_removes_generated_tree() {
+        let source = TempDir::new().unwrap();
+        let docs = source.path().join(".docs");
+        std::fs::create_dir_all(docs.join("src")).unwrap();
+        for name in [".checkpoint.json", REPORT_FILE_NAME, TASK_LOG_FILE_NAME, "README.md"] {
+            std::fs::write(docs.join(name), "x").unwrap();
+        }
+        std::fs::write(docs.join("src").join("main.py.md"), "doc").unwrap();
+        std::fs::write(docs.join("src").join("main.py.graph.json"), "{}").unwrap();
+
+        let removed = DocGenService::new(DocGenConfig::default())
+            .clear_checkpoint(docs.clone(), true, &[source.path().to_path_buf()])
+            .await
+            .unwrap();
+        assert!(removed);
+        assert!(!docs.exists());
+        assert!(source.path().is_dir());
     }
 }
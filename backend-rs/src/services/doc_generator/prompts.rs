@@ -2,6 +2,8 @@
 //!
 //! 定义代码分析、目录总结、README生成等 Prompt 模板
 
+use once_cell::sync::Lazy;
+
 /// 代码文件分析 Prompt
 pub const CODE_ANALYSIS_PROMPT: &str = r#"请分析以下代码文件，生成详细的技术文档。
 
@@ -50,6 +52,7 @@ pub const CODE_ANALYSIS_PROMPT: &str = r#"请分析以下代码文件，生成
    **注意**：
    - 只列出代码中明确定义的接口，不要推测或编造
    - 路径中的动态参数用 {{param}} 格式表示
+   - 需要登录或鉴权才能访问的接口，在该行末尾加上 [auth]，如 `- [DELETE] /api/users/{{id}} - 删除指定用户 [auth]`
    - 确保不遗漏任何接口
 
 7. 知识图谱数据提取（重要）：
@@ -163,6 +166,25 @@ pub const DIRECTORY_SUMMARY_PROMPT: &str = r#"请根据以下子模块的文档
 请用中文回答，保持专业和简洁。
 "#;
 
+/// 目录子文档分组汇总 Prompt（子文档超出 token 预算时，先逐组汇总再生成目录总结）
+pub const DIRECTORY_BATCH_SUMMARY_PROMPT: &str = r#"目录 {dir_name}（路径: {dir_path}）的子模块较多，以下是其中第 {batch_index}/{batch_count} 组子模块的文档。
+
+子模块文档:
+{sub_documents}
+
+请为这一组子模块生成简明的汇总，供后续生成目录总结使用：
+1. 逐个列出子模块名称及其核心职责（每个一到两句）
+2. 子模块之间的导入、调用和依赖关系
+3. 主要的类、接口和公开函数
+
+要求：
+- 保留子模块名称，不要遗漏任何子模块
+- 不要输出知识图谱数据
+- 只根据给出的文档汇总，不要推测
+
+请用中文回答，保持专业和简洁。
+"#;
+
 /// README 生成 Prompt
 pub const README_PROMPT: &str = r#"请根据以下所有模块的文档，生成项目的README文档。
 
@@ -337,6 +359,7 @@ pub const READING_GUIDE_PROMPT: &str = r#"请根据以下项目文档，生成
 "#;
 
 /// API 接口提取 Prompt（第一阶段）
+#[allow(dead_code)] // API 文档（API_DOC.md）生成尚未接入处理流程
 pub const API_EXTRACT_PROMPT: &str = r#"请从以下代码文件分析文档中**精确提取**所有API接口信息。
 
 文件路径: {file_path}
@@ -380,6 +403,7 @@ pub const API_EXTRACT_PROMPT: &str = r#"请从以下代码文件分析文档中*
 "#;
 
 /// API 接口汇总 Prompt（第二阶段）
+#[allow(dead_code)] // API 文档（API_DOC.md）生成尚未接入处理流程
 pub const API_SUMMARY_PROMPT: &str = r#"请根据以下各文件提取的API接口信息，生成一份**精确、完整**的接口清单。
 
 项目名称: {project_name}
@@ -457,6 +481,42 @@ pub const API_SUMMARY_PROMPT: &str = r#"请根据以下各文件提取的API接
 - 禁止添加模板中没有的章节
 "#;
 
+/// 图谱 JSON 修复 Prompt（图谱数据解析失败时的单次重试）
+pub const GRAPH_REPAIR_PROMPT: &str = r#"之前为文件 {file_path} 输出的知识图谱 JSON 无法解析，内容如下：
+
+{broken_graph}
+
+请修正为合法的 JSON 并重新输出，结构保持为：
+{"nodes": [{"id": "...", "label": "...", "type": "...", "line": 1}], "edges": [{"source": "...", "target": "...", "type": "..."}], "imports": [{"module": "...", "items": ["..."]}]}
+
+要求：
+- 只输出 JSON 对象本身，不要输出任何解释或文档内容
+- 不要编造原内容中没有的节点或关系
+"#;
+
+/// 图谱提取章节的标题，章节延续到结尾的回答要求之前
+const GRAPH_SECTION_TITLE: &str = "知识图谱数据提取";
+const CLOSING_INSTRUCTION: &str = "请用中文回答";
+
+/// 去掉知识图谱提取章节的代码分析 Prompt
+static CODE_ANALYSIS_PROMPT_NO_GRAPH: Lazy<String> = Lazy::new(|| without_graph_section(CODE_ANALYSIS_PROMPT));
+
+/// 去掉知识图谱提取章节的目录总结 Prompt
+static DIRECTORY_SUMMARY_PROMPT_NO_GRAPH: Lazy<String> =
+    Lazy::new(|| without_graph_section(DIRECTORY_SUMMARY_PROMPT));
+
+/// 删除模板中的知识图谱提取章节（从章节标题所在行到结尾的回答要求）
+fn without_graph_section(template: &str) -> String {
+    let Some(title) = template.find(GRAPH_SECTION_TITLE) else {
+        return template.to_string();
+    };
+    let start = template[..title].rfind('\n').map_or(0, |i| i + 1);
+    let Some(end) = template[title..].find(CLOSING_INSTRUCTION).map(|i| title + i) else {
+        return template.to_string();
+    };
+    format!("{}{}", &template[..start], &template[end..])
+}
+
 /// 格式化代码分析 Prompt
 pub fn format_code_analysis_prompt(file_path: &str, code_content: &str) -> String {
     CODE_ANALYSIS_PROMPT
@@ -464,6 +524,18 @@ pub fn format_code_analysis_prompt(file_path: &str, code_content: &str) -> Strin
         .replace("{code_content}", code_content)
 }
 
+/// 格式化不含知识图谱提取要求的代码分析 Prompt
+pub fn format_code_analysis_prompt_without_graph(file_path: &str, code_content: &str) -> String {
+    CODE_ANALYSIS_PROMPT_NO_GRAPH
+        .replace("{file_path}", file_path)
+        .replace("{code_content}", code_content)
+}
+
+/// 在 Prompt 末尾追加用户的补充要求（如"重点关注安全问题"）
+pub fn append_extra_instructions(prompt: String, instructions: &str) -> String {
+    format!("{}\n额外要求（请在满足以上格式的前提下优先遵循）:\n{}\n", prompt, instructions.trim())
+}
+
 /// 格式化目录总结 Prompt
 pub fn format_directory_summary_prompt(
     dir_name: &str,
@@ -476,6 +548,34 @@ pub fn format_directory_summary_prompt(
         .replace("{sub_documents}", sub_documents)
 }
 
+/// 格式化不含知识图谱提取要求的目录总结 Prompt
+pub fn format_directory_summary_prompt_without_graph(
+    dir_name: &str,
+    dir_path: &str,
+    sub_documents: &str,
+) -> String {
+    DIRECTORY_SUMMARY_PROMPT_NO_GRAPH
+        .replace("{dir_name}", dir_name)
+        .replace("{dir_path}", dir_path)
+        .replace("{sub_documents}", sub_documents)
+}
+
+/// 格式化目录子文档分组汇总 Prompt
+pub fn format_directory_batch_prompt(
+    dir_name: &str,
+    dir_path: &str,
+    batch_index: usize,
+    batch_count: usize,
+    sub_documents: &str,
+) -> String {
+    DIRECTORY_BATCH_SUMMARY_PROMPT
+        .replace("{dir_name}", dir_name)
+        .replace("{dir_path}", dir_path)
+        .replace("{batch_index}", &batch_index.to_string())
+        .replace("{batch_count}", &batch_count.to_string())
+        .replace("{sub_documents}", sub_documents)
+}
+
 /// 格式化 README Prompt
 pub fn format_readme_prompt(
     project_name: &str,
@@ -501,6 +601,7 @@ pub fn format_reading_guide_prompt(
 }
 
 /// 格式化 API 提取 Prompt
+#[allow(dead_code)] // API 文档（API_DOC.md）生成尚未接入处理流程
 pub fn format_api_extract_prompt(file_path: &str, file_doc: &str) -> String {
     API_EXTRACT_PROMPT
         .replace("{file_path}", file_path)
@@ -508,12 +609,20 @@ pub fn format_api_extract_prompt(file_path: &str, file_doc: &str) -> String {
 }
 
 /// 格式化 API 汇总 Prompt
+#[allow(dead_code)] // API 文档（API_DOC.md）生成尚未接入处理流程
 pub fn format_api_summary_prompt(project_name: &str, api_details: &str) -> String {
     API_SUMMARY_PROMPT
         .replace("{project_name}", project_name)
         .replace("{api_details}", api_details)
 }
 
+/// 格式化图谱 JSON 修复 Prompt
+pub fn format_graph_repair_prompt(file_path: &str, broken_graph: &str) -> String {
+    GRAPH_REPAIR_PROMPT
+        .replace("{file_path}", file_path)
+        .replace("{broken_graph}", broken_graph)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -532,4 +641,21 @@ mod tests {
         assert!(result.contains("/project/src"));
         assert!(result.contains("doc content"));
     }
+
+    #[test]
+    fn test_prompts_without_graph_section() {
+        let result = format_code_analysis_prompt_without_graph("test.py", "print('hello')");
+        assert!(result.contains("print('hello')"));
+        assert!(result.contains("API_START"));
+        assert!(!result.contains(GRAPH_SECTION_TITLE));
+        assert!(!result.contains("GRAPH_DATA_START"));
+        assert!(result.trim_end().ends_with("请用中文回答，保持专业和简洁。"));
+
+        let result = format_directory_summary_prompt_without_graph("src", "/project/src", "doc content");
+        assert!(result.contains("4. 设计模式"));
+        assert!(!result.contains("GRAPH_DATA_START"));
+
+        // 默认 Prompt 保留图谱章节
+        assert!(format_code_analysis_prompt("test.py", "").contains("GRAPH_DATA_START"));
+    }
 }
@@ -2,17 +2,46 @@
 //!
 //! 扫描源码目录，构建文件树结构
 
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 use tracing::{debug, info, warn};
 
-use super::types::{DocGenConfig, FileNode};
+use super::docsignore::DocsIgnore;
+use super::types::{DocGenConfig, FileNode, SkippedFile};
+use crate::services::code_analyzer::detect;
+
+/// 扫描结果
+#[derive(Debug)]
+pub struct ScanResult {
+    /// 文件树根节点
+    pub root: FileNode,
+    /// 因超过 `max_file_size` 被跳过的文件
+    pub oversized: Vec<SkippedFile>,
+}
+
+/// 单次扫描过程中累积的状态
+#[derive(Default)]
+struct ScanState {
+    /// 因超过 `max_file_size` 被跳过的文件
+    oversized: Vec<SkippedFile>,
+    /// 已收集的文件数
+    file_count: usize,
+    /// 已扫描目录的规范化路径，用于发现符号链接循环
+    visited: HashSet<PathBuf>,
+    /// 源码根目录下 `.docsignore` 的规则（每次扫描读取一次）
+    docs_ignore: DocsIgnore,
+}
 
 /// 目录扫描器
 pub struct DirectoryScanner {
     config: DocGenConfig,
     /// 编译后的忽略模式（glob patterns）
     ignore_patterns: Vec<glob::Pattern>,
+    /// 编译后的包含模式，非空时只收集匹配的文件
+    include_patterns: Vec<glob::Pattern>,
+    /// 需要排除的目录（如输出到源码树内的文档目录），已规范化为绝对路径
+    excluded_dirs: Vec<PathBuf>,
 }
 
 impl DirectoryScanner {
@@ -31,15 +60,43 @@ impl DirectoryScanner {
                 }
             })
             .collect();
+        let include_patterns = config
+            .include_patterns
+            .iter()
+            .map(|p| p.replace('\\', "/").trim_matches('/').to_string())
+            .filter(|p| !p.is_empty())
+            .filter_map(|p| {
+                match glob::Pattern::new(&p) {
+                    Ok(pattern) => Some(pattern),
+                    Err(e) => {
+                        warn!("Invalid include pattern '{}': {}", p, e);
+                        None
+                    }
+                }
+            })
+            .collect();
 
         Self {
             config,
             ignore_patterns,
+            include_patterns,
+            excluded_dirs: Vec::new(),
         }
     }
 
+    /// 排除指定目录（目录可以尚不存在）
+    pub fn with_excluded_dir(mut self, dir: &Path) -> Self {
+        self.excluded_dirs.push(normalize_path(dir));
+        self
+    }
+
     /// 扫描目录，构建文件树
     pub fn scan(&self, root_path: &Path) -> Result<FileNode, ScanError> {
+        self.scan_detailed(root_path).map(|result| result.root)
+    }
+
+    /// 扫描目录，同时返回被跳过的过大文件列表
+    pub fn scan_detailed(&self, root_path: &Path) -> Result<ScanResult, ScanError> {
         if !root_path.exists() {
             return Err(ScanError::PathNotFound(root_path.to_path_buf()));
         }
@@ -48,23 +105,57 @@ impl DirectoryScanner {
             return Err(ScanError::NotADirectory(root_path.to_path_buf()));
         }
 
-        info!("Starting directory scan: {}", root_path.display());
-        let root = self.scan_dir(root_path, root_path, 0)?;
+        self.scan_from(root_path, root_path, 0)
+    }
+
+    /// 只扫描 `root_path` 下的子目录 `subtree`
+    ///
+    /// 节点的相对路径和深度仍以 `root_path` 为基准，生成的文档位置与全量扫描一致
+    pub fn scan_subtree(&self, root_path: &Path, subtree: &Path) -> Result<ScanResult, ScanError> {
+        let start = root_path.join(subtree);
+        if !start.exists() {
+            return Err(ScanError::PathNotFound(start));
+        }
+
+        if !start.is_dir() {
+            return Err(ScanError::NotADirectory(start));
+        }
+
+        let depth = subtree.components().count() as u32;
+        self.scan_from(&start, root_path, depth)
+    }
+
+    /// 从 `start` 开始扫描，相对路径基于 `root_path`
+    fn scan_from(&self, start: &Path, root_path: &Path, depth: u32) -> Result<ScanResult, ScanError> {
+        info!("Starting directory scan: {}", start.display());
+        let mut state = ScanState {
+            docs_ignore: DocsIgnore::load(root_path),
+            ..Default::default()
+        };
+        let root = self.scan_dir(start, root_path, depth, &mut state)?;
         info!(
-            "Scan completed: {} files, {} directories",
+            "Scan completed: {} files, {} directories, {} oversized skipped",
             root.file_count(),
-            root.get_all_dirs().len()
+            root.get_all_dirs().len(),
+            state.oversized.len()
         );
 
-        Ok(root)
+        Ok(ScanResult {
+            root,
+            oversized: state.oversized,
+        })
     }
 
     /// 递归扫描目录
+    ///
+    /// 已收集的文件数超过 `max_files` 时立即返回错误；超过 `max_depth` 的子目录和
+    /// 已扫描过的目录（符号链接循环）跳过
     fn scan_dir(
         &self,
         path: &Path,
         root_path: &Path,
         depth: u32,
+        state: &mut ScanState,
     ) -> Result<FileNode, ScanError> {
         let name = path
             .file_name()
@@ -78,6 +169,14 @@ impl DirectoryScanner {
 
         let mut node = FileNode::new_dir(name, path.to_path_buf(), relative_path, depth);
 
+        // 同一目录经符号链接再次出现时不再进入，返回的空目录会被上层丢弃
+        if let Ok(canonical) = fs::canonicalize(path) {
+            if !state.visited.insert(canonical) {
+                warn!("Skipping already scanned directory (symlink loop?): {}", path.display());
+                return Ok(node);
+            }
+        }
+
         // 读取目录内容
         let entries = fs::read_dir(path).map_err(|e| ScanError::IoError(path.to_path_buf(), e))?;
 
@@ -94,15 +193,34 @@ impl DirectoryScanner {
                 continue;
             }
 
+            let child_relative = entry_path
+                .strip_prefix(root_path)
+                .map(|p| p.to_string_lossy().to_string().replace('\\', "/"))
+                .unwrap_or_default();
+            if state.docs_ignore.is_ignored(&child_relative, entry_path.is_dir()) {
+                debug!("Ignored by .docsignore: {}", child_relative);
+                continue;
+            }
+
             if entry_path.is_dir() {
+                if depth + 1 > self.config.max_depth {
+                    warn!(
+                        "Skipping directory deeper than max_depth {}: {}",
+                        self.config.max_depth,
+                        entry_path.display()
+                    );
+                    continue;
+                }
+
                 // 递归扫描子目录
-                match self.scan_dir(&entry_path, root_path, depth + 1) {
+                match self.scan_dir(&entry_path, root_path, depth + 1, state) {
                     Ok(child) => {
                         // 只添加非空目录或包含支持文件的目录
                         if !child.children.is_empty() {
                             children.push(child);
                         }
                     }
+                    Err(e @ ScanError::TooManyFiles { .. }) => return Err(e),
                     Err(e) => {
                         warn!("Failed to scan subdirectory {}: {}", entry_path.display(), e);
                     }
@@ -110,10 +228,10 @@ impl DirectoryScanner {
             } else if entry_path.is_file() {
                 // 检查是否是支持的文件类型
                 if self.is_supported_file(&entry_path) {
-                    let child_relative = entry_path
-                        .strip_prefix(root_path)
-                        .map(|p| p.to_string_lossy().to_string().replace('\\', "/"))
-                        .unwrap_or_default();
+                    if !self.is_included(&child_relative) {
+                        debug!("Not in include patterns: {}", child_relative);
+                        continue;
+                    }
 
                     let mut file_node = FileNode::new_file(
                         entry_name,
@@ -133,10 +251,18 @@ impl DirectoryScanner {
                                 entry_path.display(),
                                 metadata.len()
                             );
+                            state.oversized.push(SkippedFile {
+                                path: file_node.relative_path,
+                                size: metadata.len(),
+                            });
                             continue;
                         }
                     }
 
+                    state.file_count += 1;
+                    if state.file_count > self.config.max_files {
+                        return Err(ScanError::TooManyFiles { limit: self.config.max_files });
+                    }
                     children.push(file_node);
                 }
             }
@@ -155,6 +281,23 @@ impl DirectoryScanner {
         Ok(node)
     }
 
+    /// 检查文件是否在包含列表中（列表为空时全部包含）
+    ///
+    /// 模式可以匹配相对路径、文件名，或作为目录前缀（`src/api` 包含其下所有文件）
+    fn is_included(&self, relative_path: &str) -> bool {
+        if self.include_patterns.is_empty() {
+            return true;
+        }
+        let file_name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+        self.include_patterns.iter().any(|pattern| {
+            pattern.matches(relative_path)
+                || pattern.matches(file_name)
+                || relative_path
+                    .strip_prefix(pattern.as_str())
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+
     /// 检查是否应该忽略该路径
     fn should_ignore(&self, path: &Path, name: &str) -> bool {
         // 忽略隐藏文件/目录（以 . 开头）
@@ -182,20 +325,42 @@ impl DirectoryScanner {
             return true;
         }
 
+        if !self.excluded_dirs.is_empty() && path.is_dir() {
+            let normalized = normalize_path(path);
+            if self.excluded_dirs.contains(&normalized) {
+                return true;
+            }
+        }
+
         false
     }
 
     /// 检查是否是支持的文件类型
+    ///
+    /// 没有扩展名的文件根据 shebang 推断语言（如 `#!/usr/bin/env python`）。
     fn is_supported_file(&self, path: &Path) -> bool {
         if let Some(ext) = path.extension() {
             let ext_str = ext.to_string_lossy().to_lowercase();
             self.config.supported_extensions.contains(&ext_str)
+        } else if let Some(ext) = detect::sniff_file_extension(path) {
+            self.config.supported_extensions.iter().any(|e| e == &ext[1..])
         } else {
             false
         }
     }
 }
 
+/// 规范化路径用于比较：优先 canonicalize，路径不存在时只规范化父目录
+fn normalize_path(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    match (path.parent().and_then(|p| p.canonicalize().ok()), path.file_name()) {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
 /// 扫描错误类型
 #[derive(Debug, thiserror::Error)]
 pub enum ScanError {
@@ -207,6 +372,9 @@ pub enum ScanError {
 
     #[error("IO错误 ({0}): {1}")]
     IoError(PathBuf, #[source] std::io::Error),
+
+    #[error("源文件数量超过上限 {limit} 个，请确认源码路径是否正确")]
+    TooManyFiles { limit: usize },
 }
 
 #[cfg(test)]
@@ -259,6 +427,84 @@ mod tests {
         assert!(!all_names.contains(&".git"));
     }
 
+    #[test]
+    fn test_oversized_file_reported() {
+        let test_dir = create_test_dir();
+        let big = vec![b'x'; 2048];
+        fs::write(test_dir.path().join("src").join("big.py"), big).unwrap();
+
+        let config = DocGenConfig {
+            max_file_size: 1024,
+            ..DocGenConfig::default()
+        };
+        let scanner = DirectoryScanner::new(config);
+        let result = scanner.scan_detailed(test_dir.path()).unwrap();
+
+        assert_eq!(result.root.file_count(), 2);
+        assert_eq!(
+            result.oversized,
+            vec![SkippedFile { path: "src/big.py".to_string(), size: 2048 }]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_loop_terminates() {
+        let test_dir = create_test_dir();
+        // src/utils/loop -> src，形成循环
+        std::os::unix::fs::symlink(test_dir.path().join("src"), test_dir.path().join("src/utils/loop")).unwrap();
+
+        let root = DirectoryScanner::new(DocGenConfig::default()).scan(test_dir.path()).unwrap();
+        assert_eq!(root.file_count(), 2);
+        assert!(!root.get_all_dirs().iter().any(|d| d.relative_path.contains("loop")));
+    }
+
+    #[test]
+    fn test_max_depth_limits_recursion() {
+        let test_dir = create_test_dir();
+        let config = DocGenConfig {
+            max_depth: 1,
+            ..DocGenConfig::default()
+        };
+        let root = DirectoryScanner::new(config).scan(test_dir.path()).unwrap();
+
+        // src（深度 1）被扫描，src/utils（深度 2）被跳过
+        let files: Vec<String> = root.get_all_files().iter().map(|f| f.relative_path.clone()).collect();
+        assert_eq!(files, vec!["src/main.py"]);
+    }
+
+    #[test]
+    fn test_docsignore_excludes_examples() {
+        let test_dir = create_test_dir();
+        let examples = test_dir.path().join("examples");
+        fs::create_dir(&examples).unwrap();
+        fs::write(examples.join("demo.py"), "print('demo')").unwrap();
+        fs::write(test_dir.path().join(".docsignore"), "# 示例不生成文档\nexamples/\n").unwrap();
+
+        let root = DirectoryScanner::new(DocGenConfig::default()).scan(test_dir.path()).unwrap();
+        let mut files: Vec<String> = root.get_all_files().iter().map(|f| f.relative_path.clone()).collect();
+        files.sort();
+        assert_eq!(files, vec!["src/main.py", "src/utils/helper.py"]);
+    }
+
+    #[test]
+    fn test_include_patterns_allowlist() {
+        let test_dir = create_test_dir();
+        fs::write(test_dir.path().join("src").join("cli.py"), "x = 1\n").unwrap();
+        fs::create_dir_all(test_dir.path().join("node_modules").join("utils")).unwrap();
+        fs::write(test_dir.path().join("node_modules").join("utils").join("dep.py"), "x = 1\n").unwrap();
+
+        let config = DocGenConfig {
+            include_patterns: vec!["src/utils".to_string(), "**/cli.py".to_string()],
+            ..DocGenConfig::default()
+        };
+        let root = DirectoryScanner::new(config).scan(test_dir.path()).unwrap();
+
+        let mut files: Vec<&str> = root.get_all_files().iter().map(|f| f.relative_path.as_str()).collect();
+        files.sort();
+        assert_eq!(files, vec!["src/cli.py", "src/utils/helper.py"]);
+    }
+
     #[test]
     fn test_should_ignore() {
         let scanner = DirectoryScanner::new(DocGenConfig::default());
@@ -283,4 +529,49 @@ mod tests {
         assert!(!scanner.is_supported_file(Path::new("data.json")));
         assert!(!scanner.is_supported_file(Path::new("README.md")));
     }
+
+    #[test]
+    fn test_scan_subtree_keeps_root_relative_paths() {
+        let dir = create_test_dir();
+        let scanner = DirectoryScanner::new(DocGenConfig::default());
+        let root = scanner.scan_subtree(dir.path(), Path::new("src")).unwrap().root;
+
+        assert_eq!(root.relative_path, "src");
+        assert_eq!(root.depth, 1);
+        let files = root.get_all_files();
+        assert!(!files.is_empty());
+        assert!(files.iter().all(|f| f.relative_path.starts_with("src/")));
+
+        assert!(matches!(
+            scanner.scan_subtree(dir.path(), Path::new("missing")),
+            Err(ScanError::PathNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_excluded_docs_dir_ignored() {
+        let dir = create_test_dir();
+        let docs = dir.path().join("generated");
+        fs::create_dir_all(&docs).unwrap();
+        fs::write(docs.join("notes.py"), "x = 1\n").unwrap();
+
+        let scanner = DirectoryScanner::new(DocGenConfig::default()).with_excluded_dir(&docs);
+        let root = scanner.scan(dir.path()).unwrap();
+        assert!(root.get_all_files().iter().all(|f| !f.relative_path.starts_with("generated")));
+
+        // 未排除时会被扫描到
+        let root = DirectoryScanner::new(DocGenConfig::default()).scan(dir.path()).unwrap();
+        assert!(root.get_all_files().iter().any(|f| f.relative_path == "generated/notes.py"));
+    }
+
+    #[test]
+    fn test_extensionless_script_supported() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("deploy"), "#!/usr/bin/env python\nprint('hi')\n").unwrap();
+        fs::write(dir.path().join("Makefile"), "all:\n\techo hi\n").unwrap();
+        let scanner = DirectoryScanner::new(DocGenConfig::default());
+
+        assert!(scanner.is_supported_file(&dir.path().join("deploy")));
+        assert!(!scanner.is_supported_file(&dir.path().join("Makefile")));
+    }
 }
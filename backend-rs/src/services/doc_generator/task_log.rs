@@ -0,0 +1,129 @@
+//! 任务生命周期日志
+//!
+//! 启用 `task_log` 时，将任务开始、各节点的开始/完成/失败以及任务结束事件
+//! 以 JSON Lines 格式写入文档目录下的 `_task.log`，用户反馈问题时可以直接附上。
+
+use chrono::Local;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use super::types::{SharedDocTask, WsDocMessage};
+
+/// 任务日志文件名（位于文档根目录）
+pub const TASK_LOG_FILE_NAME: &str = "_task.log";
+
+/// 启动日志写入任务
+///
+/// 订阅进度通道直到发送端关闭（处理器结束），最后写入任务的最终状态。
+/// 周期性的 `progress` 消息不写入日志。
+pub fn spawn_task_logger(
+    task: SharedDocTask,
+    log_path: PathBuf,
+    mut rx: broadcast::Receiver<WsDocMessage>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut file = match File::create(&log_path).await {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Failed to create task log {}: {}", log_path.display(), e);
+                return;
+            }
+        };
+
+        let started = {
+            let t = task.read().await;
+            json!({
+                "type": "task_started",
+                "task_id": t.id,
+                "source_path": t.source_path,
+                "docs_path": t.docs_path,
+            })
+        };
+        write_entry(&mut file, started).await;
+
+        loop {
+            match rx.recv().await {
+                Ok(WsDocMessage::Progress { .. }) => {}
+                Ok(msg) => match serde_json::to_value(&msg) {
+                    Ok(value) => write_entry(&mut file, value).await,
+                    Err(e) => warn!("Failed to serialize task log entry: {}", e),
+                },
+                Err(RecvError::Lagged(skipped)) => {
+                    write_entry(&mut file, json!({ "type": "log_lagged", "skipped": skipped })).await;
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+
+        let finished = {
+            let t = task.read().await;
+            json!({
+                "type": "task_finished",
+                "status": t.status,
+                "error": t.error,
+                "failed_count": t.stats.failed_count,
+            })
+        };
+        write_entry(&mut file, finished).await;
+    })
+}
+
+/// 追加一行带时间戳的日志，写入失败只记录警告
+async fn write_entry(file: &mut File, mut entry: Value) {
+    if let Value::Object(map) = &mut entry {
+        map.insert(
+            "timestamp".to_string(),
+            Value::String(Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string()),
+        );
+    }
+    let line = format!("{}\n", entry);
+    if let Err(e) = file.write_all(line.as_bytes()).await.and(file.flush().await) {
+        warn!("Failed to write task log: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::doc_generator::types::{DocTask, TaskStats};
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    #[tokio::test]
+    async fn test_completed_task_log() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut task = DocTask::new("task-1".to_string(), PathBuf::from("src"), dir.path().to_path_buf());
+        task.start();
+        let task = Arc::new(RwLock::new(task));
+
+        let (tx, rx) = broadcast::channel(16);
+        let log_path = dir.path().join(TASK_LOG_FILE_NAME);
+        let handle = spawn_task_logger(task.clone(), log_path.clone(), rx);
+
+        tx.send(WsDocMessage::FileStarted { path: "a.py".to_string() }).unwrap();
+        tx.send(WsDocMessage::Progress { progress: 50.0, current_file: None, stats: TaskStats::default() })
+            .unwrap();
+        tx.send(WsDocMessage::FileCompleted { path: "a.py".to_string() }).unwrap();
+        task.write().await.complete();
+        tx.send(WsDocMessage::Completed { stats: TaskStats::default() }).unwrap();
+        drop(tx);
+        handle.await.unwrap();
+
+        let content = std::fs::read_to_string(&log_path).unwrap();
+        let entries: Vec<Value> = content.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        let types: Vec<&str> = entries.iter().map(|e| e["type"].as_str().unwrap()).collect();
+        assert_eq!(
+            types,
+            vec!["task_started", "file_started", "file_completed", "completed", "task_finished"]
+        );
+        assert_eq!(entries[0]["task_id"], "task-1");
+        assert_eq!(entries[1]["path"], "a.py");
+        assert_eq!(entries[4]["status"], "completed");
+        assert!(entries.iter().all(|e| e["timestamp"].is_string()));
+    }
+}
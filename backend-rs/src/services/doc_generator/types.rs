@@ -7,11 +7,14 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use crate::services::code_analyzer::detect;
+
 /// 节点处理状态
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum NodeStatus {
     /// 待处理
+    #[default]
     Pending,
     /// 处理中
     Processing,
@@ -23,12 +26,6 @@ pub enum NodeStatus {
     Skipped,
 }
 
-impl Default for NodeStatus {
-    fn default() -> Self {
-        Self::Pending
-    }
-}
-
 /// 文件/目录节点
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileNode {
@@ -53,6 +50,18 @@ pub struct FileNode {
     pub extension: Option<String>,
     /// 文件大小（字节）
     pub size: Option<u64>,
+    /// 是否为测试文件（仅文件有效）
+    #[serde(default)]
+    pub is_test: bool,
+    /// 处理耗时（毫秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// 消耗的 token 数，上游未返回用量时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<u64>,
+    /// 处理失败时的错误信息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 impl FileNode {
@@ -62,6 +71,7 @@ impl FileNode {
             .extension()
             .and_then(|ext| ext.to_str())
             .map(|s| s.to_lowercase());
+        let is_test = detect::is_test_path(&relative_path);
 
         Self {
             name,
@@ -72,8 +82,12 @@ impl FileNode {
             depth,
             doc_path: None,
             status: NodeStatus::Pending,
+            is_test,
             extension,
             size: None,
+            duration_ms: None,
+            tokens: None,
+            error: None,
         }
     }
 
@@ -90,6 +104,10 @@ impl FileNode {
             status: NodeStatus::Pending,
             extension: None,
             size: None,
+            is_test: false,
+            duration_ms: None,
+            tokens: None,
+            error: None,
         }
     }
 
@@ -115,7 +133,7 @@ impl FileNode {
         let mut dirs = Vec::new();
         self.collect_dirs(&mut dirs);
         // 按深度降序排序（最深的先处理）
-        dirs.sort_by(|a, b| b.depth.cmp(&a.depth));
+        dirs.sort_by_key(|d| std::cmp::Reverse(d.depth));
         dirs
     }
 
@@ -144,10 +162,11 @@ impl FileNode {
 }
 
 /// 任务状态
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum TaskStatus {
     /// 待处理
+    #[default]
     Pending,
     /// 运行中
     Running,
@@ -159,12 +178,6 @@ pub enum TaskStatus {
     Cancelled,
 }
 
-impl Default for TaskStatus {
-    fn default() -> Self {
-        Self::Pending
-    }
-}
-
 /// 任务统计信息
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TaskStats {
@@ -184,6 +197,33 @@ pub struct TaskStats {
     pub start_time: Option<u64>,
     /// 结束时间（Unix时间戳，毫秒）
     pub end_time: Option<u64>,
+    /// 因超过大小上限而跳过的文件
+    #[serde(default)]
+    pub oversized_skipped: Vec<SkippedFile>,
+    /// 耗时最长的节点（按耗时降序，最多保留 `SLOWEST_NODES_LIMIT` 个）
+    #[serde(default)]
+    pub slowest_nodes: Vec<NodeTiming>,
+}
+
+/// `TaskStats::slowest_nodes` 保留的节点数
+pub const SLOWEST_NODES_LIMIT: usize = 10;
+
+/// 单个节点的处理耗时
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeTiming {
+    /// 相对于源码根目录的路径
+    pub path: String,
+    /// 处理耗时（毫秒）
+    pub duration_ms: u64,
+}
+
+/// 扫描时被跳过的文件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedFile {
+    /// 相对于源码根目录的路径
+    pub path: String,
+    /// 文件大小（字节）
+    pub size: u64,
 }
 
 impl TaskStats {
@@ -197,6 +237,18 @@ impl TaskStats {
         (processed as f32 / total as f32) * 100.0
     }
 
+    /// 记录节点耗时，只保留最慢的若干个
+    pub fn record_timing(&mut self, path: impl Into<String>, duration_ms: u64) {
+        let pos = self
+            .slowest_nodes
+            .partition_point(|t| t.duration_ms >= duration_ms);
+        if pos >= SLOWEST_NODES_LIMIT {
+            return;
+        }
+        self.slowest_nodes.insert(pos, NodeTiming { path: path.into(), duration_ms });
+        self.slowest_nodes.truncate(SLOWEST_NODES_LIMIT);
+    }
+
     /// 计算耗时（毫秒）
     pub fn elapsed_ms(&self) -> Option<u64> {
         match (self.start_time, self.end_time) {
@@ -303,6 +355,162 @@ impl DocTask {
     }
 }
 
+/// 生成报告文件名（位于文档根目录）
+pub const REPORT_FILE_NAME: &str = "_report.json";
+
+/// 生成报告：任务结束时写入 `_report.json`，供 CI 等工具读取
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationReport {
+    /// 任务ID
+    pub task_id: String,
+    /// 任务最终状态
+    pub status: TaskStatus,
+    /// 任务级错误信息
+    pub error: Option<String>,
+    /// 生成时间
+    pub generated_at: String,
+    /// 汇总数据
+    pub totals: ReportTotals,
+    /// 每个文件的处理结果
+    pub files: Vec<NodeReport>,
+    /// 处理失败的文件和目录
+    pub failed: Vec<FailedNode>,
+    /// 因超过大小上限而跳过的文件
+    pub oversized_skipped: Vec<SkippedFile>,
+}
+
+/// 报告汇总数据
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReportTotals {
+    pub total_files: usize,
+    pub completed_files: usize,
+    pub failed_files: usize,
+    pub total_dirs: usize,
+    pub completed_dirs: usize,
+    pub failed_dirs: usize,
+    /// 断点续传跳过的节点数
+    pub skipped_count: usize,
+    /// 总耗时（毫秒）
+    pub elapsed_ms: Option<u64>,
+    /// 消耗的 token 总数，上游未返回用量时为空
+    pub tokens: Option<u64>,
+}
+
+/// 单个文件的处理结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeReport {
+    /// 相对于源码根目录的路径
+    pub path: String,
+    pub status: NodeStatus,
+    /// 处理耗时（毫秒）
+    pub duration_ms: Option<u64>,
+    /// 消耗的 token 数，上游未返回用量时为空
+    pub tokens: Option<u64>,
+    /// 生成的文档路径
+    pub doc_path: Option<String>,
+}
+
+/// 处理失败的节点
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedNode {
+    pub path: String,
+    pub is_file: bool,
+    pub error: String,
+}
+
+impl GenerationReport {
+    /// 根据任务状态和文件树生成报告
+    pub fn build(task: &DocTask, root: &FileNode) -> Self {
+        let files = root.get_all_files();
+        let dirs = root.get_all_dirs();
+
+        let failed = files
+            .iter()
+            .chain(dirs.iter())
+            .filter(|n| n.status == NodeStatus::Failed)
+            .map(|n| FailedNode {
+                path: n.relative_path.clone(),
+                is_file: n.is_file,
+                error: n.error.clone().unwrap_or_else(|| "Unknown error".to_string()),
+            })
+            .collect();
+
+        let count = |nodes: &[&FileNode], status: NodeStatus| nodes.iter().filter(|n| n.status == status).count();
+        let totals = ReportTotals {
+            total_files: files.len(),
+            completed_files: count(&files, NodeStatus::Completed),
+            failed_files: count(&files, NodeStatus::Failed),
+            total_dirs: dirs.len(),
+            completed_dirs: count(&dirs, NodeStatus::Completed),
+            failed_dirs: count(&dirs, NodeStatus::Failed),
+            skipped_count: task.stats.skipped_count,
+            elapsed_ms: task.stats.elapsed_ms(),
+            tokens: files
+                .iter()
+                .chain(dirs.iter())
+                .filter_map(|n| n.tokens)
+                .reduce(|a, b| a + b),
+        };
+
+        Self {
+            task_id: task.id.clone(),
+            status: task.status,
+            error: task.error.clone(),
+            generated_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            totals,
+            files: files
+                .iter()
+                .map(|n| NodeReport {
+                    path: n.relative_path.clone(),
+                    status: n.status,
+                    duration_ms: n.duration_ms,
+                    tokens: n.tokens,
+                    doc_path: n.doc_path.clone(),
+                })
+                .collect(),
+            failed,
+            oversized_skipped: task.stats.oversized_skipped.clone(),
+        }
+    }
+}
+
+/// 节点处理失败时的策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureMode {
+    /// 任一文件或目录失败即终止整个任务（适合 CI 校验）
+    #[default]
+    FastFail,
+    /// 记录失败并继续处理其余节点（适合长时间无人值守的运行）
+    BestEffort,
+}
+
+/// README 已存在时的写入方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadmeMode {
+    /// 直接覆盖
+    #[default]
+    Overwrite,
+    /// 保留人工编写的内容，只替换 `<!-- AUTO-GENERATED -->` 标记之间的自动生成部分
+    AppendSection,
+    /// 已存在时不写入
+    SkipIfExists,
+}
+
+/// 同一深度内文件的处理顺序
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOrder {
+    /// 按相对路径排序（结果可复现）
+    #[default]
+    Name,
+    /// 小文件优先（更快看到首批结果）
+    SizeAsc,
+    /// 大文件优先
+    SizeDesc,
+}
+
 /// 文档生成配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocGenConfig {
@@ -330,6 +538,11 @@ pub struct DocGenConfig {
     #[serde(default = "default_ignore_patterns")]
     pub ignore_patterns: Vec<String>,
 
+    /// 只处理匹配的文件（相对路径 glob，如 `src/**/*.py`，或目录前缀如 `src/api`）；
+    /// 为空时不限制，忽略规则仍然生效
+    #[serde(default)]
+    pub include_patterns: Vec<String>,
+
     /// 支持的文件扩展名
     #[serde(default = "default_supported_extensions")]
     pub supported_extensions: Vec<String>,
@@ -338,13 +551,133 @@ pub struct DocGenConfig {
     #[serde(default = "default_max_file_size")]
     pub max_file_size: u64,
 
+    /// 单次扫描允许的最大源文件数（默认 50000），超出时中止，防止误把整个主目录当作项目
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+
+    /// 目录扫描的最大深度（默认 32，根目录为 0），更深的目录记录警告后跳过
+    #[serde(default = "default_max_depth")]
+    pub max_depth: u32,
+
     /// 是否启用断点续传
     #[serde(default = "default_enable_checkpoint")]
     pub enable_checkpoint: bool,
 
+    /// 每完成多少个节点额外保存一次断点（默认 20，0 表示只在每层结束时保存）
+    #[serde(default = "default_checkpoint_interval_nodes")]
+    pub checkpoint_interval_nodes: u32,
+
+    /// 距上次保存超过多少秒时额外保存一次断点（默认 60，0 表示不按时间保存）
+    #[serde(default = "default_checkpoint_interval_secs")]
+    pub checkpoint_interval_secs: u64,
+
     /// 并行处理数量（默认3，最大10）
     #[serde(default = "default_concurrency")]
     pub concurrency: usize,
+
+    /// 是否让 LLM 在文件/目录分析中同时提取知识图谱（默认开启）
+    ///
+    /// 关闭后使用不含图谱章节的 Prompt，不解析也不保存 `.graph.json` 和项目图谱，可显著减少 token 用量
+    #[serde(default = "default_extract_graph")]
+    pub extract_graph: bool,
+
+    /// 文件分析响应为空（或剥离图谱后文档为空）时的整体重试次数（默认 1，0 表示不重试直接标记失败）
+    #[serde(default = "default_empty_doc_retries")]
+    pub empty_doc_retries: u32,
+
+    /// 图谱 JSON 解析失败时是否追问一次修正后的 JSON（默认开启）
+    #[serde(default = "default_retry_malformed_graph")]
+    pub retry_malformed_graph: bool,
+
+    /// 文件分析阶段的温度（默认 0.3）
+    #[serde(default = "default_stage_temperature")]
+    pub file_temperature: f64,
+
+    /// 文件分析阶段的最大 token 数（默认 8192）
+    #[serde(default = "default_analysis_max_tokens")]
+    pub file_max_tokens: u32,
+
+    /// 目录总结阶段的温度（默认 0.3）
+    #[serde(default = "default_stage_temperature")]
+    pub dir_temperature: f64,
+
+    /// 目录总结阶段的最大 token 数（默认 8192）
+    #[serde(default = "default_analysis_max_tokens")]
+    pub dir_max_tokens: u32,
+
+    /// 目录总结时子文档的 token 预算（估算值，默认 60000），超出时先分组汇总再生成总结
+    #[serde(default = "default_dir_summary_token_budget")]
+    pub dir_summary_token_budget: usize,
+
+    /// 最终文档阶段汇总全部文档时的 token 预算（估算值，默认 100000），
+    /// 超出时优先保留目录总结和浅层文档，其余省略
+    #[serde(default = "default_synthesis_token_budget")]
+    pub synthesis_token_budget: usize,
+
+    /// 最终文档（README、阅读指南）阶段的温度（默认 0.3）
+    #[serde(default = "default_stage_temperature")]
+    pub synthesis_temperature: f64,
+
+    /// 最终文档阶段的最大 token 数（默认 16384）
+    #[serde(default = "default_synthesis_max_tokens")]
+    pub synthesis_max_tokens: u32,
+
+    /// README 与阅读指南同时生成的数量（默认 2，1 表示依次生成）
+    #[serde(default = "default_synthesis_concurrency")]
+    pub synthesis_concurrency: usize,
+
+    /// 节点失败策略（默认快速失败）
+    #[serde(default)]
+    pub failure_mode: FailureMode,
+
+    /// 只重新生成这些文件（相对路径）及其上级目录的文档，其余已有文档直接沿用；
+    /// 最终文档总是重新生成。为空（None）时按断点续传设置处理全部文件
+    #[serde(default)]
+    pub changed_files: Option<Vec<String>>,
+
+    /// README 已存在时的写入方式（默认覆盖）
+    #[serde(default)]
+    pub readme_mode: ReadmeMode,
+
+    /// 同一深度内文件的处理顺序（默认按名称），不影响由深到浅的层级顺序
+    #[serde(default)]
+    pub order: FileOrder,
+
+    /// 优先处理的文件（相对路径），在所在深度内排到最前，按列表顺序处理
+    #[serde(default)]
+    pub priority_paths: Vec<String>,
+
+    /// 是否将任务生命周期事件写入文档目录下的 `_task.log`（默认关闭）
+    #[serde(default)]
+    pub task_log: bool,
+
+    /// 文件/目录文档是否以 YAML front matter 开头（替代默认的标题头，便于静态站点生成器使用）
+    #[serde(default)]
+    pub front_matter: bool,
+
+    /// 文件文档末尾是否附加组件位置表（图谱节点名称、类型及源码行号），便于从文档跳转到源码
+    #[serde(default)]
+    pub line_annotations: bool,
+
+    /// 调试用：汇总 API 清单前，将每个文件解析出的接口表写入文档旁的 `*.api.md`
+    #[serde(default)]
+    pub keep_api_artifacts: bool,
+
+    /// 保存前规范化 Markdown：去掉行尾空白、连续空行最多保留一行、结尾恰好一个换行（代码块内不变）
+    #[serde(default)]
+    pub normalize_markdown: bool,
+
+    /// front matter 模板，支持 `{title}`、`{source}`、`{date}` 占位符
+    #[serde(default = "default_front_matter_template")]
+    pub front_matter_template: String,
+
+    /// 进度广播通道容量（默认 1024），过小时慢速订阅者会丢失消息
+    #[serde(default = "default_progress_channel_capacity")]
+    pub progress_channel_capacity: usize,
+
+    /// 聚合项目图谱时并发读取解析 `.graph.json` 的数量（默认 16）
+    #[serde(default = "default_graph_read_concurrency")]
+    pub graph_read_concurrency: usize,
 }
 
 fn default_docs_suffix() -> String {
@@ -420,14 +753,78 @@ fn default_max_file_size() -> u64 {
     1024 * 1024 // 1MB
 }
 
+fn default_max_files() -> usize {
+    50_000
+}
+
+fn default_max_depth() -> u32 {
+    32
+}
+
 fn default_enable_checkpoint() -> bool {
     true
 }
 
+fn default_checkpoint_interval_nodes() -> u32 {
+    20
+}
+
+fn default_checkpoint_interval_secs() -> u64 {
+    60
+}
+
 fn default_concurrency() -> usize {
     3
 }
 
+fn default_extract_graph() -> bool {
+    true
+}
+
+fn default_empty_doc_retries() -> u32 {
+    1
+}
+
+fn default_retry_malformed_graph() -> bool {
+    true
+}
+
+fn default_stage_temperature() -> f64 {
+    0.3
+}
+
+fn default_analysis_max_tokens() -> u32 {
+    8192
+}
+
+fn default_dir_summary_token_budget() -> usize {
+    60000
+}
+
+fn default_synthesis_token_budget() -> usize {
+    100_000
+}
+
+fn default_front_matter_template() -> String {
+    "title: {title}\nsource: {source}\ndate: {date}".to_string()
+}
+
+pub(crate) fn default_progress_channel_capacity() -> usize {
+    1024
+}
+
+fn default_graph_read_concurrency() -> usize {
+    16
+}
+
+fn default_synthesis_max_tokens() -> u32 {
+    16384 // README 等最终文档需要更大的 token 限制
+}
+
+fn default_synthesis_concurrency() -> usize {
+    2
+}
+
 impl Default for DocGenConfig {
     fn default() -> Self {
         Self {
@@ -437,10 +834,40 @@ impl Default for DocGenConfig {
             api_doc_name: default_api_doc_name(),
             reading_guide_name: default_reading_guide_name(),
             ignore_patterns: default_ignore_patterns(),
+            include_patterns: Vec::new(),
             supported_extensions: default_supported_extensions(),
             max_file_size: default_max_file_size(),
+            max_files: default_max_files(),
+            max_depth: default_max_depth(),
             enable_checkpoint: default_enable_checkpoint(),
+            checkpoint_interval_nodes: default_checkpoint_interval_nodes(),
+            checkpoint_interval_secs: default_checkpoint_interval_secs(),
             concurrency: default_concurrency(),
+            extract_graph: default_extract_graph(),
+            empty_doc_retries: default_empty_doc_retries(),
+            retry_malformed_graph: default_retry_malformed_graph(),
+            file_temperature: default_stage_temperature(),
+            file_max_tokens: default_analysis_max_tokens(),
+            dir_temperature: default_stage_temperature(),
+            dir_max_tokens: default_analysis_max_tokens(),
+            dir_summary_token_budget: default_dir_summary_token_budget(),
+            synthesis_token_budget: default_synthesis_token_budget(),
+            synthesis_temperature: default_stage_temperature(),
+            synthesis_max_tokens: default_synthesis_max_tokens(),
+            synthesis_concurrency: default_synthesis_concurrency(),
+            failure_mode: FailureMode::default(),
+            changed_files: None,
+            readme_mode: ReadmeMode::default(),
+            order: FileOrder::default(),
+            priority_paths: Vec::new(),
+            task_log: false,
+            front_matter: false,
+            normalize_markdown: false,
+            line_annotations: false,
+            keep_api_artifacts: false,
+            front_matter_template: default_front_matter_template(),
+            progress_channel_capacity: default_progress_channel_capacity(),
+            graph_read_concurrency: default_graph_read_concurrency(),
         }
     }
 }
@@ -463,6 +890,16 @@ pub enum WsDocMessage {
     DirStarted { path: String },
     /// 目录处理完成
     DirCompleted { path: String },
+    /// 节点处理失败（仅 best_effort 模式，任务继续运行）
+    NodeFailed { path: String, message: String },
+    /// 节点（文件或目录）处理耗时
+    ///
+    /// `tokens` 为该节点消耗的 token 数，上游未返回用量时为空
+    NodeTimed {
+        path: String,
+        duration_ms: u64,
+        tokens: Option<u64>,
+    },
     /// 任务完成
     Completed { stats: TaskStats },
     /// 任务失败
@@ -474,6 +911,9 @@ pub enum WsDocMessage {
 /// 共享的任务状态（用于线程间通信）
 pub type SharedDocTask = Arc<RwLock<DocTask>>;
 
+/// 共享的文件树（处理器在生成过程中更新各节点状态）
+pub type SharedFileTree = Arc<RwLock<FileNode>>;
+
 // ============ 知识图谱相关类型 ============
 
 /// LLM 提取的知识图谱节点
@@ -514,7 +954,7 @@ pub struct ImportDeclaration {
 }
 
 /// LLM 从响应中提取的原始图谱数据
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct LlmGraphRawData {
     /// 节点列表
     #[serde(default)]
@@ -604,12 +1044,20 @@ pub struct ProjectGraphData {
     pub generated_at: String,
 }
 
-impl Default for LlmGraphRawData {
-    fn default() -> Self {
-        Self {
-            nodes: Vec::new(),
-            edges: Vec::new(),
-            imports: Vec::new(),
+impl ProjectGraphData {
+    /// 只保留指定类型的节点和边（`None` 表示不过滤），过滤时同时移除端点已不存在的边
+    pub fn retain_types(&mut self, node_types: Option<&[String]>, edge_types: Option<&[String]>) {
+        if node_types.is_none() && edge_types.is_none() {
+            return;
+        }
+        if let Some(types) = node_types {
+            self.nodes.retain(|n| types.contains(&n.node_type));
         }
+        let ids: std::collections::HashSet<&str> = self.nodes.iter().map(|n| n.id.as_str()).collect();
+        self.edges.retain(|e| {
+            ids.contains(e.source.as_str())
+                && ids.contains(e.target.as_str())
+                && edge_types.is_none_or(|types| types.contains(&e.edge_type))
+        });
     }
 }
@@ -2,11 +2,38 @@
 //!
 //! 封装 LlmClient，与配置系统集成
 
-use futures::Stream;
-use std::pin::Pin;
+use std::sync::Arc;
 
 use crate::config::get_config;
-use crate::llm::{ChatChunk, ChatMessage, ChatOptions, LlmClient, LlmError};
+use crate::llm::{ChatMessage, ChatOptions, ChatStream, CollectMode, LlmClient, LlmError};
+use crate::utils::RequestLogger;
+
+/// 单次请求对默认生成参数的覆盖
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ChatOverrides {
+    /// 温度（0.0 ~ 2.0）
+    pub temperature: Option<f64>,
+    /// 最大 token 数（至少为 1）
+    pub max_tokens: Option<u32>,
+}
+
+impl ChatOverrides {
+    /// 校验参数范围
+    pub fn validate(&self) -> Result<(), LlmError> {
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(LlmError::ConfigError(format!(
+                    "temperature 必须在 0 到 2 之间: {}",
+                    temperature
+                )));
+            }
+        }
+        if self.max_tokens == Some(0) {
+            return Err(LlmError::ConfigError("max_tokens 必须大于 0".to_string()));
+        }
+        Ok(())
+    }
+}
 
 /// LLM 服务
 pub struct LlmService {
@@ -29,6 +56,29 @@ impl LlmService {
         service
     }
 
+    /// 使用指定客户端、模型和生成参数创建服务（不读取全局配置）
+    #[cfg(test)]
+    pub fn with_client(client: LlmClient, model: impl Into<String>, temperature: f64, max_tokens: u32) -> Self {
+        Self {
+            client: Some(client),
+            model: model.into(),
+            temperature,
+            max_tokens,
+        }
+    }
+
+    /// 将请求写入请求日志
+    pub fn with_request_logger(mut self, logger: Arc<RequestLogger>) -> Self {
+        self.client = self.client.map(|client| client.with_request_logger(logger));
+        self
+    }
+
+    /// 指定写入请求日志的请求 ID
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.client = self.client.map(|client| client.with_request_id(request_id));
+        self
+    }
+
     /// 刷新客户端（重新读取配置）
     pub fn refresh_client(&mut self) {
         let config = get_config();
@@ -38,9 +88,13 @@ impl LlmService {
             return;
         }
 
-        match LlmClient::new(&config.api_key, &config.base_url, true) {
+        match LlmClient::with_settings(&config.api_key, &config.base_url, true, config.http_client_settings()) {
             Ok(client) => {
-                self.client = Some(client);
+                self.client = Some(
+                    client
+                        .with_browser_headers(&config.browser_header_settings())
+                        .with_default_max_tokens(config.max_tokens),
+                );
                 self.model = config.model;
                 self.temperature = config.temperature;
                 self.max_tokens = config.max_tokens;
@@ -51,21 +105,43 @@ impl LlmService {
         }
     }
 
+    /// 非流式调用：收集完整回复内容
+    pub async fn complete(&self, messages: Vec<ChatMessage>) -> Result<String, LlmError> {
+        let client = self.client()?;
+        let options = ChatOptions {
+            temperature: Some(self.temperature),
+            max_tokens: Some(self.max_tokens),
+            ..Default::default()
+        };
+
+        let result = client
+            .stream_and_collect(messages, &self.model, options, CollectMode::ContentOnly)
+            .await?;
+        Ok(result.content)
+    }
+
+    fn client(&self) -> Result<&LlmClient, LlmError> {
+        self.client
+            .as_ref()
+            .ok_or_else(|| LlmError::ConfigError("API Key not configured. Please set it in Settings.".to_string()))
+    }
+
     /// 流式聊天
+    ///
+    /// `overrides` 中给出的参数覆盖配置中的默认值，仅对本次请求生效
     pub fn stream_chat(
         &self,
         messages: Vec<ChatMessage>,
         model: Option<&str>,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatChunk, LlmError>> + Send>>, LlmError> {
-        let client = self
-            .client
-            .as_ref()
-            .ok_or_else(|| LlmError::ConfigError("API Key not configured. Please set it in Settings.".to_string()))?;
+        overrides: ChatOverrides,
+    ) -> Result<ChatStream, LlmError> {
+        overrides.validate()?;
+        let client = self.client()?;
 
         let model = model.unwrap_or(&self.model);
         let options = ChatOptions {
-            temperature: Some(self.temperature),
-            max_tokens: Some(self.max_tokens),
+            temperature: Some(overrides.temperature.unwrap_or(self.temperature)),
+            max_tokens: Some(overrides.max_tokens.unwrap_or(self.max_tokens)),
             ..Default::default()
         };
 
@@ -78,3 +154,38 @@ impl Default for LlmService {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{spawn_mock_sse, sse_body};
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_stream_chat_overrides_flow_into_request() {
+        let mock = spawn_mock_sse(vec![sse_body("ok", "stop")]).await;
+        let client = LlmClient::new("sk-test", &mock.base_url, false).unwrap();
+        let service = LlmService::with_client(client, "gpt-4o", 0.7, 4096);
+
+        let overrides = ChatOverrides {
+            temperature: Some(0.1),
+            max_tokens: Some(123),
+        };
+        let mut stream = service
+            .stream_chat(vec![ChatMessage::user("Hi")], None, overrides)
+            .unwrap();
+        while stream.next().await.is_some() {}
+
+        let requests = mock.requests.lock().clone();
+        assert_eq!(requests[0]["temperature"], serde_json::json!(0.1));
+        assert_eq!(requests[0]["max_tokens"], serde_json::json!(123));
+    }
+
+    #[test]
+    fn test_overrides_validate_ranges() {
+        assert!(ChatOverrides::default().validate().is_ok());
+        assert!(ChatOverrides { temperature: Some(2.5), ..Default::default() }.validate().is_err());
+        assert!(ChatOverrides { temperature: Some(f64::NAN), ..Default::default() }.validate().is_err());
+        assert!(ChatOverrides { max_tokens: Some(0), ..Default::default() }.validate().is_err());
+    }
+}
@@ -6,5 +6,5 @@ mod llm_service;
 mod prompt_service;
 
 pub use code_analyzer::CodeAnalyzer;
-pub use llm_service::LlmService;
+pub use llm_service::{ChatOverrides, LlmService};
 pub use prompt_service::PromptService;
@@ -2,7 +2,12 @@
 //!
 //! 负责构建 LLM 聊天消息和生成建议问题
 
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tracing::warn;
+
 use crate::llm::ChatMessage;
+use crate::services::LlmService;
 
 /// 系统提示词
 const SYSTEM_PROMPT: &str = r#"You are an expert code reviewer and programming assistant. Your role is to:
@@ -22,13 +27,44 @@ Respond in the same language as the user's question."#;
 /// 最大文件内容长度
 const MAX_CONTENT_LENGTH: usize = 8000;
 
+/// 建议问题数量
+const SUGGESTED_QUESTION_COUNT: usize = 5;
+
+/// 建议问题生成提示词（问题数量取自 `SUGGESTED_QUESTION_COUNT`）
+fn suggest_questions_prompt() -> String {
+    format!(
+        r#"Based on the project context below, suggest exactly {} specific questions a developer might ask to understand or review this code.
+
+Output only a numbered list, one question per line, like:
+1. ...
+2. ...
+
+Write the questions in the language the file and directory names suggest the team uses; default to English."#,
+        SUGGESTED_QUESTION_COUNT
+    )
+}
+
+/// 编号列表项：`1. xxx`、`1) xxx`、`- xxx`、`* xxx`
+static RE_LIST_ITEM: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:\d+[.)、]|[-*•])\s*(.+?)\s*$").unwrap()
+});
+
 /// Prompt 服务
-pub struct PromptService;
+pub struct PromptService {
+    /// 自定义系统提示词，未设置时使用内置的 `SYSTEM_PROMPT`
+    system_prompt: Option<String>,
+}
 
 impl PromptService {
     /// 创建新的 Prompt 服务
     pub fn new() -> Self {
-        Self
+        Self { system_prompt: None }
+    }
+
+    /// 使用自定义系统提示词（空白字符串视为未设置）
+    pub fn with_system_prompt(mut self, system_prompt: Option<String>) -> Self {
+        self.system_prompt = system_prompt.filter(|p| !p.trim().is_empty());
+        self
     }
 
     /// 构建聊天消息列表
@@ -44,7 +80,9 @@ impl PromptService {
         let mut messages = Vec::new();
 
         // 系统消息
-        messages.push(ChatMessage::system(SYSTEM_PROMPT));
+        messages.push(ChatMessage::system(
+            self.system_prompt.as_deref().unwrap_or(SYSTEM_PROMPT),
+        ));
 
         // 构建上下文消息
         let mut context_parts = Vec::new();
@@ -119,12 +157,89 @@ impl PromptService {
         questions
     }
 
-    /// 截断内容
+    /// 使用 LLM 生成与上下文相关的建议问题
+    ///
+    /// LLM 调用失败或无法解析出问题时回退到静态列表；不足 5 个时用静态问题补齐。
+    pub async fn suggest_questions_with_llm(
+        &self,
+        llm_service: &LlmService,
+        project_path: Option<&str>,
+        current_file: Option<&str>,
+        file_tree_summary: Option<&str>,
+    ) -> Vec<String> {
+        let fallback = self.generate_suggested_questions(project_path, current_file, file_tree_summary);
+        let messages = self.build_suggest_questions_messages(project_path, current_file, file_tree_summary);
+
+        let mut questions = match llm_service.complete(messages).await {
+            Ok(content) => Self::parse_numbered_questions(&content),
+            Err(e) => {
+                warn!("LLM suggested questions failed, using static list: {}", e);
+                return fallback;
+            }
+        };
+
+        if questions.is_empty() {
+            warn!("LLM returned no parsable questions, using static list");
+            return fallback;
+        }
+
+        for question in fallback {
+            if questions.len() >= SUGGESTED_QUESTION_COUNT {
+                break;
+            }
+            if !questions.contains(&question) {
+                questions.push(question);
+            }
+        }
+        questions.truncate(SUGGESTED_QUESTION_COUNT);
+        questions
+    }
+
+    /// 构建建议问题生成的消息
+    fn build_suggest_questions_messages(
+        &self,
+        project_path: Option<&str>,
+        current_file: Option<&str>,
+        file_tree_summary: Option<&str>,
+    ) -> Vec<ChatMessage> {
+        let mut context_parts = Vec::new();
+        if let Some(path) = project_path.filter(|p| !p.is_empty()) {
+            context_parts.push(format!("Project path: {}", path));
+        }
+        if let Some(tree) = file_tree_summary.filter(|t| !t.is_empty()) {
+            let truncated = Self::truncate_content(tree, MAX_CONTENT_LENGTH);
+            context_parts.push(format!("Project structure:\n```\n{}\n```", truncated));
+        }
+        if let Some(file) = current_file.filter(|f| !f.is_empty()) {
+            context_parts.push(format!("Current file: {}", file));
+        }
+
+        vec![
+            ChatMessage::system(suggest_questions_prompt()),
+            ChatMessage::user(context_parts.join("\n\n")),
+        ]
+    }
+
+    /// 从编号列表中解析问题
+    fn parse_numbered_questions(content: &str) -> Vec<String> {
+        content
+            .lines()
+            .filter_map(|line| RE_LIST_ITEM.captures(line))
+            .map(|caps| caps[1].trim_matches(['*', '"']).trim().to_string())
+            .filter(|q| !q.is_empty())
+            .take(SUGGESTED_QUESTION_COUNT)
+            .collect()
+    }
+
+    /// 截断内容（回退到字符边界，避免切断多字节字符）
     fn truncate_content(content: &str, max_len: usize) -> String {
         if content.len() <= max_len {
             content.to_string()
         } else {
-            format!("{}... (content truncated)", &content[..max_len])
+            format!(
+                "{}... (content truncated)",
+                &content[..content.floor_char_boundary(max_len)]
+            )
         }
     }
 
@@ -151,6 +266,27 @@ mod tests {
         assert_eq!(PromptService::extract_file_name("main.rs"), "main.rs");
     }
 
+    #[test]
+    fn test_truncate_content_multibyte() {
+        let truncated = PromptService::truncate_content("文档生成器", 4);
+        assert_eq!(truncated, "文... (content truncated)");
+    }
+
+    #[test]
+    fn test_custom_system_prompt_first() {
+        let prompt = "Review code following the ACME style guide. Answer in German.";
+        let service = PromptService::new().with_system_prompt(Some(prompt.to_string()));
+        let messages = service.build_chat_messages("Hi", Some("/p"), None, None, None, None);
+        assert_eq!(messages[0].role, "system");
+        assert_eq!(messages[0].content, prompt);
+        assert_eq!(messages.last().unwrap().content, "Hi");
+
+        // 空白提示词回退到内置提示词
+        let service = PromptService::new().with_system_prompt(Some("  ".to_string()));
+        let messages = service.build_chat_messages("Hi", None, None, None, None, None);
+        assert_eq!(messages[0].content, SYSTEM_PROMPT);
+    }
+
     #[test]
     fn test_generate_suggested_questions() {
         let service = PromptService::new();
@@ -161,4 +297,51 @@ mod tests {
         let questions = service.generate_suggested_questions(None, Some("main.rs"), None);
         assert_eq!(questions.len(), 5);
     }
+
+    #[test]
+    fn test_parse_numbered_questions() {
+        let content = "Here are some questions:\n1. What does main do?\n2) How is config loaded?\n- Where are errors handled?\n\n* **Why use a cache?**";
+        let questions = PromptService::parse_numbered_questions(content);
+        assert_eq!(
+            questions,
+            vec![
+                "What does main do?",
+                "How is config loaded?",
+                "Where are errors handled?",
+                "Why use a cache?",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_suggest_questions_with_llm() {
+        let reply = "1. How does the scanner skip ignored dirs?\n2. What does processor.rs coordinate?\n3. How are checkpoints saved?\n4. Which LLM formats are supported?\n5. How is the project graph aggregated?\n6. Extra question";
+        let mock = crate::test_utils::spawn_mock_llm(vec![reply.to_string()]).await;
+        let client = crate::llm::LlmClient::new("sk-test", &mock.base_url, false).unwrap();
+        let llm_service = LlmService::with_client(client, "gpt-4o", 0.7, 4096);
+
+        let questions = PromptService::new()
+            .suggest_questions_with_llm(&llm_service, Some("/proj"), Some("src/main.rs"), Some("src/\n  main.rs"))
+            .await;
+
+        assert_eq!(questions.len(), 5);
+        assert_eq!(questions[0], "How does the scanner skip ignored dirs?");
+        assert_eq!(questions[4], "How is the project graph aggregated?");
+
+        let request = mock.requests.lock()[0].to_string();
+        assert!(request.contains("src/main.rs"));
+        assert!(request.contains(&format!("suggest exactly {} specific questions", SUGGESTED_QUESTION_COUNT)));
+    }
+
+    #[tokio::test]
+    async fn test_suggest_questions_falls_back_on_error() {
+        // 没有可用的服务：连接失败后回退到静态问题
+        let client = crate::llm::LlmClient::new("sk-test", "http://127.0.0.1:9", false).unwrap();
+        let llm_service = LlmService::with_client(client, "gpt-4o", 0.7, 4096);
+
+        let questions = PromptService::new()
+            .suggest_questions_with_llm(&llm_service, None, None, None)
+            .await;
+        assert_eq!(questions, PromptService::new().generate_suggested_questions(None, None, None));
+    }
 }
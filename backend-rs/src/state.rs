@@ -5,10 +5,18 @@
 use dashmap::DashMap;
 use parking_lot::RwLock;
 use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::broadcast;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch};
+use tracing::info;
 
-use crate::services::doc_generator::{SharedDocTask, WsDocMessage};
+use crate::config::get_config;
+use crate::services::code_analyzer::ProjectGraphCache;
+use crate::services::doc_generator::types::TaskStatus;
+use crate::services::doc_generator::{AnalysisCache, SharedDocTask, SharedFileTree, WsDocMessage};
+use crate::utils::{ConversationStore, RequestLogger};
 
 /// 已完成路径的类型
 #[derive(Clone)]
@@ -33,6 +41,12 @@ pub struct TaskState {
     /// 正在处理中的文件/目录路径（已发送 Started 但未 Completed）
     pub in_progress_files: RwLock<HashSet<String>>,
     pub in_progress_dirs: RwLock<HashSet<String>>,
+    /// 任务进入终止状态（完成/失败/取消）的时间
+    pub finished_at: RwLock<Option<Instant>>,
+    /// 任务被清理时置为 true，通知 WebSocket 连接关闭
+    pub closed: watch::Sender<bool>,
+    /// 处理器的文件树，用于查询各节点状态
+    pub tree: Option<SharedFileTree>,
 }
 
 impl TaskState {
@@ -43,9 +57,30 @@ impl TaskState {
             completed_paths: RwLock::new(Vec::new()),
             in_progress_files: RwLock::new(HashSet::new()),
             in_progress_dirs: RwLock::new(HashSet::new()),
+            finished_at: RwLock::new(None),
+            closed: watch::channel(false).0,
+            tree: None,
         }
     }
 
+    /// 关联处理器的文件树
+    pub fn with_tree(mut self, tree: SharedFileTree) -> Self {
+        self.tree = Some(tree);
+        self
+    }
+
+    /// 记录任务结束时间（只记录第一次）
+    pub fn mark_finished(&self) {
+        self.finished_at.write().get_or_insert_with(Instant::now);
+    }
+
+    /// 任务是否已结束超过 `ttl`
+    pub fn is_expired(&self, ttl: Duration) -> bool {
+        self.finished_at
+            .read()
+            .is_some_and(|finished| finished.elapsed() >= ttl)
+    }
+
     /// 记录文件开始处理
     pub fn mark_file_started(&self, path: String) {
         self.in_progress_files.write().insert(path);
@@ -73,6 +108,29 @@ impl TaskState {
         self.completed_paths.read().clone()
     }
 
+    /// 构造用于同步客户端状态的消息：当前进度 + 已完成路径 + 处理中路径
+    ///
+    /// WebSocket 连接建立时以及订阅者落后（`Lagged`）丢失消息后发送。
+    pub async fn snapshot_messages(&self) -> Vec<WsDocMessage> {
+        let mut messages = {
+            let task = self.task.read().await;
+            vec![WsDocMessage::Progress {
+                progress: task.progress,
+                current_file: task.current_file.clone(),
+                stats: task.stats.clone(),
+            }]
+        };
+        messages.extend(self.get_completed_paths().into_iter().map(|path_type| match path_type {
+            CompletedPathType::File(path) => WsDocMessage::FileCompleted { path },
+            CompletedPathType::Dir(path) => WsDocMessage::DirCompleted { path },
+        }));
+        messages.extend(self.get_in_progress_paths().into_iter().map(|path_type| match path_type {
+            InProgressPathType::File(path) => WsDocMessage::FileStarted { path },
+            InProgressPathType::Dir(path) => WsDocMessage::DirStarted { path },
+        }));
+        messages
+    }
+
     /// 获取所有正在处理中的路径
     pub fn get_in_progress_paths(&self) -> Vec<InProgressPathType> {
         let mut result = Vec::new();
@@ -96,6 +154,46 @@ pub type DocTaskRegistry = DashMap<String, Arc<TaskState>>;
 pub struct AppState {
     /// 文档生成任务注册表
     pub doc_tasks: Arc<DocTaskRegistry>,
+    /// LLM 请求日志记录器
+    pub request_logger: Arc<RequestLogger>,
+    /// 已结束任务的保留时间
+    pub task_ttl: Duration,
+    /// 服务启动时间
+    pub started_at: Instant,
+    /// 就绪检查中模型连通性探测的缓存
+    pub readiness_cache: Arc<RwLock<Option<ReadinessProbe>>>,
+    /// 项目图谱缓存
+    pub graph_cache: Arc<ProjectGraphCache>,
+    /// 文件分析结果缓存，跨生成任务复用内容未变文件的 LLM 分析
+    pub analysis_cache: Arc<AnalysisCache>,
+    /// 聊天会话存储（未启用持久化时为 None）
+    pub conversation_store: Option<Arc<ConversationStore>>,
+    /// 按文档目录区分的任务启动锁，防止重复提交同时为同一目录启动多个任务
+    pub doc_start_locks: Arc<DashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
+    /// 全局任务启动闸门，使并发上限的统计与占位原子执行
+    pub doc_start_gate: Arc<tokio::sync::Mutex<()>>,
+    /// 已通过上限检查、尚未注册到任务表的启动数
+    pub starting_tasks: Arc<AtomicUsize>,
+}
+
+/// 已占用的任务启动名额，释放时归还
+pub struct TaskSlot(Arc<AtomicUsize>);
+
+impl Drop for TaskSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// 一次模型连通性探测的结果
+#[derive(Clone)]
+pub struct ReadinessProbe {
+    /// 探测时间
+    pub checked_at: Instant,
+    /// 探测时使用的配置指纹（base_url + model + api_key 的哈希）
+    pub config_key: String,
+    /// 探测结果，失败时为错误信息
+    pub result: Result<(), String>,
 }
 
 impl AppState {
@@ -103,7 +201,108 @@ impl AppState {
     pub fn new() -> Self {
         Self {
             doc_tasks: Arc::new(DashMap::new()),
+            request_logger: Arc::new(RequestLogger::default()),
+            task_ttl: Duration::from_secs(get_config().task_ttl_secs),
+            started_at: Instant::now(),
+            readiness_cache: Arc::new(RwLock::new(None)),
+            graph_cache: Arc::new(ProjectGraphCache::default()),
+            analysis_cache: Arc::new(AnalysisCache::default()),
+            conversation_store: get_config()
+                .persist_conversations
+                .then(|| Arc::new(ConversationStore::default())),
+            doc_start_locks: Arc::new(DashMap::new()),
+            doc_start_gate: Arc::new(tokio::sync::Mutex::new(())),
+            starting_tasks: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// 获取指定文档目录的任务启动锁
+    ///
+    /// 按规范化后的路径区分，`x/.docs`、`x/.docs/`、`./x/.docs` 共用同一把锁。
+    pub fn docs_path_lock(&self, docs_path: &Path) -> Arc<tokio::sync::Mutex<()>> {
+        self.doc_start_locks
+            .entry(normalize_docs_path(docs_path))
+            .or_default()
+            .clone()
+    }
+
+    /// 查找写入指定文档目录且处于 Pending/Running 状态的任务
+    pub async fn active_task_for_docs_path(&self, docs_path: &Path) -> Option<String> {
+        // 先收集任务句柄，避免跨 await 持有 DashMap 的分片锁
+        let tasks: Vec<SharedDocTask> = self
+            .doc_tasks
+            .iter()
+            .map(|entry| entry.value().task.clone())
+            .collect();
+
+        let docs_path = normalize_docs_path(docs_path);
+        for task in tasks {
+            let t = task.read().await;
+            if matches!(t.status, TaskStatus::Pending | TaskStatus::Running)
+                && normalize_docs_path(&t.docs_path) == docs_path
+            {
+                return Some(t.id.clone());
+            }
+        }
+        None
+    }
+
+    /// 清理已结束且超过保留时间的任务，返回清理数量
+    ///
+    /// 被清理任务的 WebSocket 连接会收到关闭通知；同时移除当前无人持有的文档目录启动锁。
+    pub fn evict_expired_tasks(&self) -> usize {
+        // 只有锁表自身持有引用时才可移除；获取锁与清理都在分片锁内进行，不会拿到已被移除的锁
+        self.doc_start_locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+
+        let expired: Vec<String> = self
+            .doc_tasks
+            .iter()
+            .filter(|entry| entry.value().is_expired(self.task_ttl))
+            .map(|entry| entry.key().clone())
+            .collect();
+
+        for task_id in &expired {
+            if let Some((_, task_state)) = self.doc_tasks.remove(task_id) {
+                task_state.closed.send_replace(true);
+                info!("Evicted finished task: {}", task_id);
+            }
+        }
+
+        expired.len()
+    }
+
+    /// 占用一个任务启动名额
+    ///
+    /// 运行中的任务与尚未注册的启动一起计入上限，检查和占位在全局闸门内完成，
+    /// 不同文档目录的并发启动也不会超出上限。名额应持有到任务注册进 `doc_tasks` 之后；
+    /// 已达上限时返回当前占用数。
+    pub async fn reserve_task_slot(&self, max_tasks: usize) -> Result<TaskSlot, usize> {
+        let _gate = self.doc_start_gate.lock().await;
+        let occupied = self.active_task_count().await + self.starting_tasks.load(Ordering::SeqCst);
+        if occupied >= max_tasks {
+            return Err(occupied);
         }
+        self.starting_tasks.fetch_add(1, Ordering::SeqCst);
+        Ok(TaskSlot(self.starting_tasks.clone()))
+    }
+
+    /// 统计处于 Pending/Running 状态的任务数
+    pub async fn active_task_count(&self) -> usize {
+        // 先收集任务句柄，避免跨 await 持有 DashMap 的分片锁
+        let tasks: Vec<SharedDocTask> = self
+            .doc_tasks
+            .iter()
+            .map(|entry| entry.value().task.clone())
+            .collect();
+
+        let mut count = 0;
+        for task in tasks {
+            let status = task.read().await.status;
+            if matches!(status, TaskStatus::Pending | TaskStatus::Running) {
+                count += 1;
+            }
+        }
+        count
     }
 }
 
@@ -113,7 +312,224 @@ impl Default for AppState {
     }
 }
 
+/// 规范化文档目录路径，用作启动锁和任务查找的键
+///
+/// 转为绝对路径并按词法处理 `.` / `..`，再对已存在的最长前缀解析符号链接；
+/// 文档目录尚未创建时，结果与创建后一致。
+fn normalize_docs_path(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+
+    let mut lexical = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                lexical.pop();
+            }
+            other => lexical.push(other),
+        }
+    }
+
+    for ancestor in lexical.ancestors() {
+        if let Ok(canonical) = ancestor.canonicalize() {
+            return match lexical.strip_prefix(ancestor) {
+                Ok(rest) if !rest.as_os_str().is_empty() => canonical.join(rest),
+                _ => canonical,
+            };
+        }
+    }
+    lexical
+}
+
 /// 创建可共享的应用状态
 pub fn create_shared_state() -> Arc<AppState> {
     Arc::new(AppState::new())
 }
+
+/// 任务清理的检查间隔
+const TASK_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 启动后台任务清理器，定期移除过期的已结束任务
+pub fn spawn_task_sweeper(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let period = TASK_SWEEP_INTERVAL.min(state.task_ttl.max(Duration::from_secs(1)));
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            state.evict_expired_tasks();
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::doc_generator::types::DocTask;
+    use std::path::PathBuf;
+
+    fn task_state(id: &str) -> Arc<TaskState> {
+        let task = DocTask::new(id.to_string(), PathBuf::from("src"), PathBuf::from("docs"));
+        let (tx, _) = broadcast::channel(1);
+        Arc::new(TaskState::new(Arc::new(tokio::sync::RwLock::new(task)), tx))
+    }
+
+    #[tokio::test]
+    async fn test_finished_task_evicted_after_ttl() {
+        let state = AppState {
+            task_ttl: Duration::from_millis(20),
+            ..AppState::new()
+        };
+
+        let finished = task_state("finished");
+        let running = task_state("running");
+        state.doc_tasks.insert("finished".to_string(), finished.clone());
+        state.doc_tasks.insert("running".to_string(), running);
+        finished.mark_finished();
+
+        let mut closed_rx = finished.closed.subscribe();
+
+        // 未到 TTL 时不清理
+        assert_eq!(state.evict_expired_tasks(), 0);
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(state.evict_expired_tasks(), 1);
+        assert!(!state.doc_tasks.contains_key("finished"));
+        assert!(state.doc_tasks.contains_key("running"));
+
+        // WebSocket 侧会收到关闭通知
+        closed_rx.changed().await.unwrap();
+        assert!(*closed_rx.borrow());
+    }
+
+    #[tokio::test]
+    async fn test_lagged_subscriber_resyncs_from_snapshot() {
+        let state = task_state("slow");
+        let mut rx = state.tx.subscribe();
+
+        // 容量为 1 的通道：订阅者来不及消费，必然 Lagged
+        for i in 0..10 {
+            let path = format!("f{}.py", i);
+            state.mark_file_completed(path.clone());
+            let _ = state.tx.send(WsDocMessage::FileCompleted { path });
+        }
+
+        let mut seen = HashSet::new();
+        let mut lagged = false;
+        loop {
+            match rx.try_recv() {
+                Ok(WsDocMessage::FileCompleted { path }) => {
+                    seen.insert(path);
+                }
+                Ok(_) => {}
+                Err(broadcast::error::TryRecvError::Lagged(_)) => {
+                    lagged = true;
+                    for msg in state.snapshot_messages().await {
+                        if let WsDocMessage::FileCompleted { path } = msg {
+                            seen.insert(path);
+                        }
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+
+        assert!(lagged);
+        let expected: HashSet<String> = (0..10).map(|i| format!("f{}.py", i)).collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn test_docs_path_lock_shared_across_path_spellings() {
+        let state = AppState::new();
+        let lock = state.docs_path_lock(Path::new("x/.docs"));
+        for spelling in ["x/.docs/", "./x/.docs", "x/y/../.docs"] {
+            assert!(Arc::ptr_eq(&lock, &state.docs_path_lock(Path::new(spelling))), "{}", spelling);
+        }
+        let absolute = std::env::current_dir().unwrap().join("x").join(".docs");
+        assert!(Arc::ptr_eq(&lock, &state.docs_path_lock(&absolute)));
+        assert_eq!(state.doc_start_locks.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_active_task_matched_by_normalized_docs_path() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let docs_path = dir.path().join(".docs");
+        let mut task = DocTask::new("running".to_string(), dir.path().to_path_buf(), docs_path.clone());
+        task.start();
+        let (tx, _) = broadcast::channel(1);
+        let state = AppState::new();
+        state
+            .doc_tasks
+            .insert("running".to_string(), Arc::new(TaskState::new(Arc::new(tokio::sync::RwLock::new(task)), tx)));
+
+        // 文档目录创建前后、带尾部分隔符或 `.` 组件的写法都指向同一任务
+        for created in [false, true] {
+            if created {
+                std::fs::create_dir(&docs_path).unwrap();
+            }
+            for spelling in [
+                docs_path.clone(),
+                PathBuf::from(format!("{}/", docs_path.display())),
+                dir.path().join(".").join(".docs"),
+            ] {
+                assert_eq!(state.active_task_for_docs_path(&spelling).await.as_deref(), Some("running"));
+            }
+        }
+        assert!(state.active_task_for_docs_path(&dir.path().join("other")).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unused_docs_path_locks_pruned() {
+        let state = AppState::new();
+        let held = state.docs_path_lock(Path::new("held/.docs"));
+        let _guard = held.lock().await;
+        drop(state.docs_path_lock(Path::new("released/.docs")));
+        assert_eq!(state.doc_start_locks.len(), 2);
+
+        state.evict_expired_tasks();
+        assert_eq!(state.doc_start_locks.len(), 1);
+        assert!(Arc::ptr_eq(&held, &state.docs_path_lock(Path::new("held/.docs"))));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_task_slots_capped_across_docs_paths() {
+        let max_tasks = 3;
+        let state = Arc::new(AppState::new());
+
+        // 与 generate_docs 相同的顺序：各自的文档目录锁 -> 占用名额 -> 较慢的启动 -> 注册任务
+        let starts = (0..=max_tasks).map(|i| {
+            let state = state.clone();
+            tokio::spawn(async move {
+                let docs_path = PathBuf::from(format!("project-{}/.docs", i));
+                let lock = state.docs_path_lock(&docs_path);
+                let _guard = lock.lock().await;
+                let Ok(_slot) = state.reserve_task_slot(max_tasks).await else {
+                    return false;
+                };
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                let mut task = DocTask::new(format!("task-{}", i), PathBuf::from("src"), docs_path);
+                task.start();
+                let (tx, _) = broadcast::channel(1);
+                state
+                    .doc_tasks
+                    .insert(format!("task-{}", i), Arc::new(TaskState::new(Arc::new(tokio::sync::RwLock::new(task)), tx)));
+                true
+            })
+        });
+        let started = futures::future::join_all(starts)
+            .await
+            .into_iter()
+            .filter(|r| *r.as_ref().unwrap())
+            .count();
+
+        assert_eq!(started, max_tasks);
+        assert_eq!(state.active_task_count().await, max_tasks);
+        assert_eq!(state.starting_tasks.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,183 @@
+//! 测试辅助工具
+//!
+//! 提供一个本地 OpenAI 兼容的 SSE 模拟服务，供依赖 LLM 的测试使用。
+
+use axum::{
+    extract::State,
+    routing::{get, post},
+    Json, Router,
+};
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 模拟 LLM 服务
+pub struct MockLlm {
+    /// 服务基础 URL（可直接作为 `base_url` 传给 `LlmClient`）
+    pub base_url: String,
+    /// 收到的请求体（按到达顺序）
+    pub requests: Arc<Mutex<Vec<serde_json::Value>>>,
+    in_flight: Arc<InFlight>,
+}
+
+impl MockLlm {
+    /// 已收到的请求数
+    pub fn request_count(&self) -> usize {
+        self.requests.lock().len()
+    }
+
+    /// 同时处理中的请求数的峰值
+    pub fn peak_in_flight(&self) -> usize {
+        self.in_flight.peak.load(Ordering::SeqCst)
+    }
+}
+
+/// 处理中的请求计数
+#[derive(Default)]
+struct InFlight {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+/// 请求处理期间持有，结束（包括连接中断导致处理被取消）时计数减一
+struct InFlightGuard(Arc<InFlight>);
+
+impl InFlightGuard {
+    fn enter(in_flight: &Arc<InFlight>) -> Self {
+        let current = in_flight.current.fetch_add(1, Ordering::SeqCst) + 1;
+        in_flight.peak.fetch_max(current, Ordering::SeqCst);
+        Self(in_flight.clone())
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.current.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Clone)]
+struct MockState {
+    responses: Arc<Vec<String>>,
+    requests: Arc<Mutex<Vec<serde_json::Value>>>,
+    in_flight: Arc<InFlight>,
+    delay: Duration,
+    /// 响应已是完整的 SSE 响应体，无需再包装
+    raw: bool,
+}
+
+/// 启动模拟 LLM 服务
+///
+/// 第 N 个请求返回 `responses[N]`，超出后重复最后一个响应。
+pub async fn spawn_mock_llm(responses: Vec<String>) -> MockLlm {
+    spawn_mock_llm_with_delay(responses, Duration::ZERO).await
+}
+
+/// 启动模拟 LLM 服务，每个请求在响应前等待 `delay`
+pub async fn spawn_mock_llm_with_delay(responses: Vec<String>, delay: Duration) -> MockLlm {
+    spawn_mock(responses, delay, false).await
+}
+
+/// 启动模拟 LLM 服务，按原样返回预设的 SSE 响应体（可自定义 delta，如推理内容）
+pub async fn spawn_mock_sse(bodies: Vec<String>) -> MockLlm {
+    spawn_mock(bodies, Duration::ZERO, true).await
+}
+
+async fn spawn_mock(responses: Vec<String>, delay: Duration, raw: bool) -> MockLlm {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let in_flight = Arc::new(InFlight::default());
+    let state = MockState {
+        responses: Arc::new(responses),
+        requests: requests.clone(),
+        in_flight: in_flight.clone(),
+        delay,
+        raw,
+    };
+
+    let app = Router::new()
+        .route("/v1/chat/completions", post(mock_chat_completions))
+        .route("/v1/models", get(mock_models))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+
+    MockLlm {
+        base_url: format!("http://{}", addr),
+        requests,
+        in_flight,
+    }
+}
+
+/// 模拟服务在 `/v1/models` 返回的模型列表
+pub const MOCK_MODELS: &[&str] = &["gpt-4o-mini", "gpt-4o"];
+
+/// 以 OpenAI 格式返回模型列表
+async fn mock_models() -> Json<serde_json::Value> {
+    let data: Vec<_> = MOCK_MODELS
+        .iter()
+        .map(|id| serde_json::json!({ "id": id, "object": "model" }))
+        .collect();
+    Json(serde_json::json!({ "object": "list", "data": data }))
+}
+
+/// 以 OpenAI SSE 格式返回预设响应
+async fn mock_chat_completions(
+    State(state): State<MockState>,
+    Json(body): Json<serde_json::Value>,
+) -> impl axum::response::IntoResponse {
+    let _in_flight = InFlightGuard::enter(&state.in_flight);
+    let index = {
+        let mut requests = state.requests.lock();
+        requests.push(body);
+        requests.len() - 1
+    };
+    let content = state
+        .responses
+        .get(index)
+        .or_else(|| state.responses.last())
+        .cloned()
+        .unwrap_or_default();
+
+    if !state.delay.is_zero() {
+        tokio::time::sleep(state.delay).await;
+    }
+
+    let body = if state.raw { content } else { sse_body(&content, "stop") };
+    ([("content-type", "text/event-stream")], body)
+}
+
+/// 构造单个 OpenAI 流式 delta 事件
+pub fn sse_delta(delta: serde_json::Value) -> String {
+    let chunk = serde_json::json!({
+        "choices": [{ "delta": delta, "finish_reason": null }]
+    });
+    format!("data: {}\n\n", chunk)
+}
+
+/// 模拟服务在每个响应末尾报告的 token 用量
+pub const MOCK_PROMPT_TOKENS: u64 = 10;
+pub const MOCK_COMPLETION_TOKENS: u64 = 20;
+
+/// 构造 OpenAI 流式响应体（末尾附带 `include_usage` 格式的用量块）
+pub fn sse_body(content: &str, finish_reason: &str) -> String {
+    let chunk = serde_json::json!({
+        "choices": [{ "delta": { "content": content }, "finish_reason": null }]
+    });
+    let done = serde_json::json!({
+        "choices": [{ "delta": {}, "finish_reason": finish_reason }]
+    });
+    let usage = serde_json::json!({
+        "choices": [],
+        "usage": {
+            "prompt_tokens": MOCK_PROMPT_TOKENS,
+            "completion_tokens": MOCK_COMPLETION_TOKENS,
+            "total_tokens": MOCK_PROMPT_TOKENS + MOCK_COMPLETION_TOKENS,
+        }
+    });
+    format!("data: {}\n\ndata: {}\n\ndata: {}\n\ndata: [DONE]\n\n", chunk, done, usage)
+}
@@ -0,0 +1,74 @@
+//! 控制台编码检查
+//!
+//! 日志中含有中文，终端 locale 不是 UTF-8 时会显示为乱码。Windows 通过设置控制台代码页解决，
+//! 其他平台无法在进程内修正终端编码，只在启动时给出提示。
+
+use tracing::warn;
+
+/// 按 POSIX 优先级（`LC_ALL` > `LC_CTYPE` > `LANG`）取生效的字符集 locale，均未设置时返回 `None`
+pub fn effective_locale<F>(lookup: F) -> Option<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .filter_map(lookup)
+        .find(|value| !value.trim().is_empty())
+}
+
+/// locale 的字符集是否为 UTF-8（如 `zh_CN.UTF-8`、`en_US.utf8`、`C.UTF-8@euro`）
+pub fn is_utf8_locale(locale: &str) -> bool {
+    let Some((_, codeset)) = locale.split_once('.') else {
+        return false;
+    };
+    let codeset = codeset.split('@').next().unwrap_or_default();
+    codeset.eq_ignore_ascii_case("utf-8") || codeset.eq_ignore_ascii_case("utf8")
+}
+
+/// 终端 locale 明确设置为非 UTF-8 时输出警告（需在日志初始化之后调用）
+///
+/// 未设置任何 locale 变量时不提示，避免在容器等环境中误报。警告使用 ASCII 文本，保证在任何编码下可读。
+pub fn warn_if_non_utf8_locale() {
+    if cfg!(windows) {
+        return;
+    }
+    if let Some(locale) = effective_locale(|key| std::env::var(key).ok()) {
+        if !is_utf8_locale(&locale) {
+            warn!(
+                "Terminal locale '{}' is not UTF-8; Chinese log output may be garbled. \
+                 Set LANG/LC_ALL to a UTF-8 locale such as C.UTF-8 or zh_CN.UTF-8.",
+                locale
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_locale_detection() {
+        assert!(is_utf8_locale("zh_CN.UTF-8"));
+        assert!(is_utf8_locale("en_US.utf8"));
+        assert!(is_utf8_locale("C.UTF-8@euro"));
+        assert!(!is_utf8_locale("zh_CN.GBK"));
+        assert!(!is_utf8_locale("C"));
+        assert!(!is_utf8_locale("POSIX"));
+
+        let env = |pairs: &[(&str, &str)]| {
+            let map: HashMap<String, String> = pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            move |key: &str| map.get(key).cloned()
+        };
+        assert_eq!(
+            effective_locale(env(&[("LANG", "en_US.UTF-8"), ("LC_ALL", "zh_CN.GBK")])),
+            Some("zh_CN.GBK".to_string())
+        );
+        assert_eq!(
+            effective_locale(env(&[("LANG", "en_US.UTF-8"), ("LC_ALL", "")])),
+            Some("en_US.UTF-8".to_string())
+        );
+        assert_eq!(effective_locale(env(&[])), None);
+    }
+}
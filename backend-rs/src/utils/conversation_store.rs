@@ -0,0 +1,298 @@
+//! 聊天会话持久化
+//!
+//! 按 `conversation_id` 保存消息历史到 JSON 文件，刷新页面后可以重新打开之前的对话。
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::warn;
+
+/// 会话中的一条消息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredMessage {
+    /// 角色：user, assistant
+    pub role: String,
+    /// 消息内容
+    pub content: String,
+    /// 时间戳
+    pub timestamp: DateTime<Utc>,
+}
+
+/// 一个会话的完整历史
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredConversation {
+    pub conversation_id: String,
+    /// 最后一次追加消息的时间
+    pub updated_at: DateTime<Utc>,
+    pub messages: Vec<StoredMessage>,
+}
+
+/// 会话存储
+///
+/// 全部会话保存在同一个 JSON 文件中，超过 `max_conversations` 时淘汰最久未更新的会话，
+/// 单个会话超过 `max_messages` 时丢弃最早的一轮对话。
+///
+/// 内存中的会话列表由同步锁保护，只在锁内修改和序列化；读写磁盘放到阻塞线程池中执行，
+/// 不在锁内、也不在异步运行时线程上做文件 I/O。
+pub struct ConversationStore {
+    store_path: PathBuf,
+    max_conversations: usize,
+    max_messages: usize,
+    /// 懒加载的会话列表（按更新时间升序）
+    conversations: Mutex<Option<Vec<StoredConversation>>>,
+    /// 每次修改后递增，用于丢弃落后的快照
+    version: AtomicU64,
+    /// 串行化写盘，内容为已写入磁盘的快照版本
+    saved_version: tokio::sync::Mutex<u64>,
+}
+
+impl ConversationStore {
+    /// 创建会话存储
+    ///
+    /// `max_conversations` 小于 1 时按 1 处理，`max_messages` 小于 2 时按 2 处理。
+    pub fn new(store_dir: Option<PathBuf>, max_conversations: usize, max_messages: usize) -> Self {
+        let store_dir = store_dir.unwrap_or_else(|| {
+            std::env::current_exe()
+                .ok()
+                .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join("storage")
+        });
+
+        // 确保目录存在
+        let _ = fs::create_dir_all(&store_dir);
+
+        Self {
+            store_path: store_dir.join("conversations.json"),
+            max_conversations: max_conversations.max(1),
+            max_messages: max_messages.max(2),
+            conversations: Mutex::new(None),
+            version: AtomicU64::new(0),
+            saved_version: tokio::sync::Mutex::new(0),
+        }
+    }
+
+    /// 追加一轮对话（用户提问 + 助手回答）并写入磁盘
+    pub async fn append_turn(&self, conversation_id: &str, user_content: &str, assistant_content: &str) {
+        self.ensure_loaded().await;
+
+        let now = Utc::now();
+        let (json, version) = {
+            let mut guard = self.conversations.lock();
+            let conversations = guard.get_or_insert_with(Vec::new);
+
+            let mut conversation = match conversations
+                .iter()
+                .position(|c| c.conversation_id == conversation_id)
+            {
+                Some(pos) => conversations.remove(pos),
+                None => StoredConversation {
+                    conversation_id: conversation_id.to_string(),
+                    updated_at: now,
+                    messages: Vec::new(),
+                },
+            };
+
+            for (role, content) in [("user", user_content), ("assistant", assistant_content)] {
+                conversation.messages.push(StoredMessage {
+                    role: role.to_string(),
+                    content: content.to_string(),
+                    timestamp: now,
+                });
+            }
+            // 按整轮丢弃最早的消息，保证历史仍以用户提问开头
+            if conversation.messages.len() > self.max_messages {
+                let excess = conversation.messages.len() - self.max_messages;
+                conversation.messages.drain(..excess.next_multiple_of(2));
+            }
+            conversation.updated_at = now;
+            conversations.push(conversation);
+
+            // 淘汰最久未更新的会话
+            if conversations.len() > self.max_conversations {
+                let excess = conversations.len() - self.max_conversations;
+                conversations.drain(..excess);
+            }
+
+            let json = match serde_json::to_string(conversations) {
+                Ok(json) => json,
+                Err(e) => {
+                    warn!("Failed to serialize conversations: {}", e);
+                    return;
+                }
+            };
+            (json, self.version.fetch_add(1, Ordering::SeqCst) + 1)
+        };
+
+        self.save(json, version).await;
+    }
+
+    /// 获取会话历史
+    pub async fn get(&self, conversation_id: &str) -> Option<StoredConversation> {
+        self.ensure_loaded().await;
+        self.conversations
+            .lock()
+            .as_ref()
+            .and_then(|conversations| conversations.iter().find(|c| c.conversation_id == conversation_id).cloned())
+    }
+
+    /// 已保存的会话数
+    #[cfg(test)]
+    async fn len(&self) -> usize {
+        self.ensure_loaded().await;
+        self.conversations.lock().as_ref().map_or(0, Vec::len)
+    }
+
+    /// 首次访问时在阻塞线程池中读取磁盘
+    async fn ensure_loaded(&self) {
+        if self.conversations.lock().is_some() {
+            return;
+        }
+        let path = self.store_path.clone();
+        let loaded = tokio::task::spawn_blocking(move || load(&path)).await.unwrap_or_default();
+        // 并发加载时保留先完成的结果
+        self.conversations.lock().get_or_insert(loaded);
+    }
+
+    /// 写入磁盘（先写临时文件再重命名，避免写到一半损坏）
+    ///
+    /// 写盘按顺序进行；已有更新的快照写入时直接丢弃当前快照。
+    async fn save(&self, json: String, version: u64) {
+        let mut saved_version = self.saved_version.lock().await;
+        if *saved_version >= version {
+            return;
+        }
+
+        let store_path = self.store_path.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let tmp_path = store_path.with_extension("json.tmp");
+            fs::write(&tmp_path, json).and_then(|_| fs::rename(&tmp_path, &store_path))
+        })
+        .await;
+        match result {
+            Ok(Ok(())) => *saved_version = version,
+            Ok(Err(e)) => warn!("Failed to save conversations to {}: {}", self.store_path.display(), e),
+            Err(e) => warn!("Conversation save task failed: {}", e),
+        }
+    }
+}
+
+/// 从磁盘读取；文件不存在或损坏时从空列表开始
+fn load(store_path: &Path) -> Vec<StoredConversation> {
+    let content = match fs::read_to_string(store_path) {
+        Ok(c) => c,
+        Err(_) => return Vec::new(),
+    };
+    match serde_json::from_str::<Vec<StoredConversation>>(&content) {
+        Ok(mut conversations) => {
+            conversations.sort_by_key(|c| c.updated_at);
+            conversations
+        }
+        Err(e) => {
+            warn!("Failed to parse conversation store {}: {}", store_path.display(), e);
+            Vec::new()
+        }
+    }
+}
+
+impl Default for ConversationStore {
+    /// 使用默认存储目录和配置中的 `max_stored_conversations` / `max_conversation_messages`
+    fn default() -> Self {
+        let config = crate::config::get_config();
+        Self::new(None, config.max_stored_conversations, config.max_conversation_messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_save_and_load_multi_turn() {
+        let dir = TempDir::new().unwrap();
+        let store = ConversationStore::new(Some(dir.path().to_path_buf()), 10, 100);
+        store.append_turn("conv-1", "What does main do?", "It starts the server.").await;
+        store.append_turn("conv-1", "And the router?", "It mounts the API routes.").await;
+        store.append_turn("conv-2", "Hi", "Hello").await;
+
+        // 新实例从磁盘读取
+        let reloaded = ConversationStore::new(Some(dir.path().to_path_buf()), 10, 100);
+        let conversation = reloaded.get("conv-1").await.unwrap();
+        let turns: Vec<(&str, &str)> = conversation
+            .messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_str()))
+            .collect();
+        assert_eq!(
+            turns,
+            vec![
+                ("user", "What does main do?"),
+                ("assistant", "It starts the server."),
+                ("user", "And the router?"),
+                ("assistant", "It mounts the API routes."),
+            ]
+        );
+        assert_eq!(reloaded.len().await, 2);
+        assert!(reloaded.get("missing").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_trims_least_recently_updated() {
+        let dir = TempDir::new().unwrap();
+        let store = ConversationStore::new(Some(dir.path().to_path_buf()), 2, 100);
+        store.append_turn("a", "q", "a").await;
+        store.append_turn("b", "q", "a").await;
+        // 更新 a 后，b 成为最久未更新的会话
+        store.append_turn("a", "q2", "a2").await;
+        store.append_turn("c", "q", "a").await;
+
+        let reloaded = ConversationStore::new(Some(dir.path().to_path_buf()), 2, 100);
+        assert_eq!(reloaded.len().await, 2);
+        assert!(reloaded.get("b").await.is_none());
+        assert_eq!(reloaded.get("a").await.unwrap().messages.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_caps_messages_per_conversation() {
+        let dir = TempDir::new().unwrap();
+        // 上限为奇数时按整轮丢弃，历史始终以用户提问开头
+        let store = ConversationStore::new(Some(dir.path().to_path_buf()), 10, 5);
+        for n in 0..4 {
+            store.append_turn("conv", &format!("q{}", n), &format!("a{}", n)).await;
+        }
+
+        let reloaded = ConversationStore::new(Some(dir.path().to_path_buf()), 10, 5);
+        let contents: Vec<String> = reloaded
+            .get("conv")
+            .await
+            .unwrap()
+            .messages
+            .into_iter()
+            .map(|m| m.content)
+            .collect();
+        assert_eq!(contents, vec!["q2", "a2", "q3", "a3"]);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_appends_keep_latest_snapshot() {
+        let dir = TempDir::new().unwrap();
+        let store = std::sync::Arc::new(ConversationStore::new(Some(dir.path().to_path_buf()), 100, 100));
+        let handles: Vec<_> = (0..20)
+            .map(|n| {
+                let store = store.clone();
+                tokio::spawn(async move { store.append_turn(&format!("conv-{}", n), "q", "a").await })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let reloaded = ConversationStore::new(Some(dir.path().to_path_buf()), 100, 100);
+        assert_eq!(reloaded.len().await, 20);
+    }
+}
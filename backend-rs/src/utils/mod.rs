@@ -1,5 +1,8 @@
 //! 工具模块
 
+pub mod console;
+mod conversation_store;
 mod request_logger;
 
-pub use request_logger::RequestLogger;
+pub use conversation_store::{ConversationStore, StoredConversation};
+pub use request_logger::{current_request_id, scope_request_id, LogEntry, RequestLogger};
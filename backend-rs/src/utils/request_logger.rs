@@ -8,8 +8,24 @@ use serde::{Deserialize, Serialize};
 use std::fs::{self, File, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use uuid::Uuid;
 
+tokio::task_local! {
+    /// 当前 HTTP 请求的 ID（由请求 ID 中间件设置）
+    static CURRENT_REQUEST_ID: String;
+}
+
+/// 在指定请求 ID 的作用域内执行 `future`，期间生成的日志条目沿用该 ID
+pub async fn scope_request_id<F: std::future::Future>(request_id: String, future: F) -> F::Output {
+    CURRENT_REQUEST_ID.scope(request_id, future).await
+}
+
+/// 当前 HTTP 请求的 ID，不在请求作用域内（如后台任务）时返回 `None`
+pub fn current_request_id() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
 /// 请求日志条目
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
@@ -74,13 +90,16 @@ pub struct MessagePreview {
 /// 请求日志记录器
 pub struct RequestLogger {
     log_path: PathBuf,
-    max_entries: usize,
+    /// 最多保留的条目数，可在运行时通过配置更新修改
+    max_entries: AtomicUsize,
     file: Mutex<Option<File>>,
 }
 
 impl RequestLogger {
     /// 创建新的日志记录器
-    pub fn new(log_dir: Option<PathBuf>) -> Self {
+    ///
+    /// `max_entries` 为文件中最多保留的条目数，小于 1 时按 1 处理。
+    pub fn new(log_dir: Option<PathBuf>, max_entries: usize) -> Self {
         let log_dir = log_dir.unwrap_or_else(|| {
             std::env::current_exe()
                 .ok()
@@ -96,14 +115,21 @@ impl RequestLogger {
 
         Self {
             log_path,
-            max_entries: 1000,
+            max_entries: AtomicUsize::new(max_entries.max(1)),
             file: Mutex::new(None),
         }
     }
 
+    /// 修改最多保留的条目数（小于 1 时按 1 处理），下次写入时生效
+    pub fn set_max_entries(&self, max_entries: usize) {
+        self.max_entries.store(max_entries.max(1), Ordering::Relaxed);
+    }
+
     /// 生成请求 ID
+    ///
+    /// 在 HTTP 请求作用域内时沿用该请求的 ID（`X-Request-Id`），便于把客户端错误与 LLM 请求日志对应起来
     pub fn generate_request_id() -> String {
-        Uuid::new_v4().to_string()[..8].to_string()
+        current_request_id().unwrap_or_else(|| Uuid::new_v4().to_string()[..8].to_string())
     }
 
     /// API 密钥脱敏
@@ -119,12 +145,12 @@ impl RequestLogger {
         }
     }
 
-    /// 截断字符串
+    /// 截断字符串（按字节上限，回退到最近的字符边界，避免切断多字节字符）
     fn truncate(s: &str, max_len: usize) -> String {
         if s.len() <= max_len {
             s.to_string()
         } else {
-            format!("{}...", &s[..max_len])
+            format!("{}...", &s[..s.floor_char_boundary(max_len)])
         }
     }
 
@@ -145,6 +171,7 @@ impl RequestLogger {
     }
 
     /// 记录请求开始
+    #[allow(clippy::too_many_arguments)]
     pub fn log_request(
         &self,
         request_id: &str,
@@ -242,14 +269,36 @@ impl RequestLogger {
         self.cleanup_if_needed();
     }
 
+    /// 读取最近的日志条目（按时间倒序）
+    ///
+    /// 可选按 `status` 过滤（如 "success"、"error"），最多返回 `limit` 条。
+    /// 无法解析的行会被跳过。
+    pub fn read_recent(&self, limit: usize, status: Option<&str>) -> Vec<LogEntry> {
+        let file = match File::open(&self.log_path) {
+            Ok(f) => f,
+            Err(_) => return Vec::new(),
+        };
+
+        let lines: Vec<String> = BufReader::new(file).lines().map_while(Result::ok).collect();
+
+        lines
+            .iter()
+            .rev()
+            .filter_map(|line| serde_json::from_str::<LogEntry>(line).ok())
+            .filter(|entry| status.is_none_or(|s| entry.status == s))
+            .take(limit)
+            .collect()
+    }
+
     /// 清理旧日志
     fn cleanup_if_needed(&self) {
         if let Ok(file) = File::open(&self.log_path) {
             let reader = BufReader::new(file);
-            let lines: Vec<String> = reader.lines().filter_map(|l| l.ok()).collect();
+            let lines: Vec<String> = reader.lines().map_while(Result::ok).collect();
 
-            if lines.len() > self.max_entries {
-                let keep_lines = &lines[lines.len() - self.max_entries..];
+            let max_entries = self.max_entries.load(Ordering::Relaxed);
+            if lines.len() > max_entries {
+                let keep_lines = &lines[lines.len() - max_entries..];
                 if let Ok(mut file) = File::create(&self.log_path) {
                     for line in keep_lines {
                         let _ = writeln!(file, "{}", line);
@@ -261,7 +310,82 @@ impl RequestLogger {
 }
 
 impl Default for RequestLogger {
+    /// 使用默认日志目录和配置中的 `log_max_entries`
     fn default() -> Self {
-        Self::new(None)
+        Self::new(None, crate::config::get_config().log_max_entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_multibyte_boundary() {
+        // 每个汉字占 3 字节，5 字节落在第二个汉字中间
+        let s = "中文提示词";
+        assert_eq!(RequestLogger::truncate(s, 5), "中...");
+        assert_eq!(RequestLogger::truncate(s, 6), "中文...");
+        assert_eq!(RequestLogger::truncate(s, 100), s);
+    }
+
+    #[test]
+    fn test_message_previews_with_chinese() {
+        let messages = vec![("user".to_string(), "请分析这个项目的架构".to_string())];
+        let previews = RequestLogger::create_message_previews(&messages, 3, 10);
+        assert_eq!(previews[0].content_preview, "请分析...");
+    }
+
+    fn seed_success(logger: &RequestLogger, request_id: &str) {
+        let entry = logger.log_request(
+            request_id, "openai", "/v1/chat/completions", "gpt-4o",
+            &[], None, None, 120, "https://api.openai.com", "sk-test",
+        );
+        logger.log_success(entry, std::time::Instant::now(), 2, 1, "ok");
+    }
+
+    #[test]
+    fn test_cleanup_respects_max_entries() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let logger = RequestLogger::new(Some(dir.path().to_path_buf()), 3);
+
+        for i in 0..5 {
+            seed_success(&logger, &format!("req-{}", i));
+        }
+
+        let entries = logger.read_recent(100, None);
+        let ids: Vec<_> = entries.iter().map(|e| e.request_id.as_str()).collect();
+        assert_eq!(ids, vec!["req-4", "req-3", "req-2"]);
+    }
+
+    #[test]
+    fn test_max_entries_updated_at_runtime() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let logger = RequestLogger::new(Some(dir.path().to_path_buf()), 10);
+
+        for i in 0..5 {
+            seed_success(&logger, &format!("req-{}", i));
+        }
+        assert_eq!(logger.read_recent(100, None).len(), 5);
+
+        logger.set_max_entries(2);
+        seed_success(&logger, "req-5");
+
+        let entries = logger.read_recent(100, None);
+        let ids: Vec<_> = entries.iter().map(|e| e.request_id.as_str()).collect();
+        assert_eq!(ids, vec!["req-5", "req-4"]);
+    }
+
+    #[test]
+    fn test_max_entries_at_least_one() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let logger = RequestLogger::new(Some(dir.path().to_path_buf()), 0);
+
+        seed_success(&logger, "a");
+        seed_success(&logger, "b");
+
+        let entries = logger.read_recent(100, None);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].request_id, "b");
     }
 }
@@ -98,6 +98,16 @@ export const useChatStore = create<ChatStore>((set, get) => ({
             }
             return { messages, isStreaming: false }
           })
+        } else if (data.type === 'chat_aborted') {
+          // Keep the partial answer and flag it as incomplete
+          set(state => {
+            const messages = [...state.messages]
+            const lastMsg = messages[messages.length - 1]
+            if (lastMsg && lastMsg.role === 'assistant') {
+              lastMsg.content += `\n\n[Response interrupted: ${data.error}]`
+            }
+            return { messages, isStreaming: false }
+          })
         } else if (data.type === 'pong') {
           // Heartbeat response
         }